@@ -0,0 +1,70 @@
+//! Compares a reclamation pass that checks each retired pointer against its own snapshot of the
+//! hazards with `RetiredSet::collect`, which takes one snapshot per pass.
+//!
+//! ```text
+//! cargo bench --bench collect
+//! ```
+//!
+//! A bag of `SLOTS` shields is checked against `RETIRED` retired pointers, none of which are
+//! protected, so every pass frees all of them.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use cs431_homework::hazard_pointer::{HazardBag, RetiredSet, Shield};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Number of shields in the bag.
+const SLOTS: usize = 64;
+
+/// Number of retired pointers in a pass.
+const RETIRED: usize = 1024;
+
+/// Number of pointers freed by `free`.
+static FREED: AtomicUsize = AtomicUsize::new(0);
+
+/// Counts the pointer as freed. The retired pointers are not real allocations.
+unsafe fn free(_: *mut ()) {
+    let _ = FREED.fetch_add(1, Ordering::Relaxed);
+}
+
+fn collect(c: &mut Criterion) {
+    let hazard_bag = HazardBag::new();
+    let shields = (0..SLOTS)
+        .map(|i| {
+            let shield = Shield::<u8>::new(&hazard_bag);
+            // Odd multiples of 8, so that no retired pointer is protected.
+            shield.set((i * 16 + 8) as *const u8);
+            shield
+        })
+        .collect::<Vec<_>>();
+    let retired = (1..=RETIRED).map(|i| i * 16).collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("collect");
+    let _ = group.throughput(Throughput::Elements(RETIRED as u64));
+    let _ = group.bench_function("per_node", |b| {
+        b.iter(|| {
+            let freed = retired
+                .iter()
+                .filter(|&&p| !hazard_bag.snapshot().contains(p))
+                .count();
+            assert_eq!(freed, RETIRED);
+        })
+    });
+    let mut set = RetiredSet::with_threshold(&hazard_bag, usize::MAX);
+    let _ = group.bench_function("per_pass", |b| {
+        b.iter(|| {
+            let freed = FREED.load(Ordering::Relaxed);
+            for &p in &retired {
+                set.retire_with(p as *mut (), free);
+            }
+            set.collect();
+            assert_eq!(FREED.load(Ordering::Relaxed) - freed, RETIRED);
+        })
+    });
+    group.finish();
+
+    drop(set);
+    drop(shields);
+}
+
+criterion_group!(benches, collect);
+criterion_main!(benches);
@@ -0,0 +1,59 @@
+//! Measures threads updating the hazards of their own shields, whose slots are allocated next to
+//! each other.
+//!
+//! ```text
+//! cargo bench --bench false_sharing
+//! ```
+//!
+//! In an iteration, each thread does a `set`/`reset` pair on its own slot, so the time per
+//! iteration should stay flat as threads are added (up to the number of cores), unless the slots
+//! share cache lines.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use crossbeam_utils::thread::scope;
+use cs431_homework::hazard_pointer::{HazardBag, Shield};
+use std::time::{Duration, Instant};
+
+/// Numbers of threads.
+const THREADS: [usize; 4] = [1, 2, 4, 8];
+
+/// A shield sent to the thread that uses it. The slots are allocated by one thread, so that they
+/// are next to each other.
+struct Sent(Shield<u8>);
+
+unsafe impl Send for Sent {}
+
+/// Runs `threads` threads that update their own shield `iters` times. Returns the elapsed time.
+fn hammer(threads: usize, iters: u64) -> Duration {
+    let hazards = HazardBag::new();
+    let shields = (0..threads)
+        .map(|_| Sent(Shield::new(&hazards)))
+        .collect::<Vec<_>>();
+    let start = Instant::now();
+    scope(|s| {
+        for (i, shield) in shields.into_iter().enumerate() {
+            let _ = s.spawn(move |_| {
+                let pointer = ((i + 1) * 8) as *const u8;
+                for _ in 0..iters {
+                    shield.0.set(pointer);
+                    shield.0.reset();
+                }
+            });
+        }
+    })
+    .unwrap();
+    start.elapsed()
+}
+
+fn false_sharing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("false_sharing");
+    for threads in THREADS {
+        let _ = group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |b, &t| {
+            b.iter_custom(|iters| hammer(t, iters))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, false_sharing);
+criterion_main!(benches);
@@ -0,0 +1,63 @@
+//! Compares the membership checks of a reclamation pass with `all_hazards` and `snapshot`.
+//!
+//! ```text
+//! cargo bench --bench hazards
+//! ```
+//!
+//! A bag of `SLOTS` shields is checked against batches of retired pointers, half of which are
+//! protected.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use cs431_homework::hazard_pointer::{HazardBag, Shield};
+
+/// Number of shields in the bag.
+const SLOTS: usize = 4096;
+
+/// Number of retired pointers checked in a pass.
+const BATCHES: [usize; 4] = [16, 64, 256, 1024];
+
+fn hazards(c: &mut Criterion) {
+    let hazard_bag = HazardBag::new();
+    let shields = (0..SLOTS)
+        .map(|i| {
+            let shield = Shield::<u8>::new(&hazard_bag);
+            shield.set(((i + 1) * 16) as *const u8);
+            shield
+        })
+        .collect::<Vec<_>>();
+
+    let mut group = c.benchmark_group("hazards");
+    for batch in BATCHES {
+        // Every other retired pointer is protected.
+        let retired = (0..batch).map(|i| (i + 1) * 8).collect::<Vec<_>>();
+        let _ = group.throughput(Throughput::Elements(batch as u64));
+        let _ = group.bench_with_input(
+            BenchmarkId::new("all_hazards", batch),
+            &retired,
+            |b, retired| {
+                b.iter(|| {
+                    let hazards = hazard_bag.all_hazards();
+                    let protected = retired.iter().filter(|p| hazards.contains(p)).count();
+                    assert_eq!(protected, batch / 2);
+                })
+            },
+        );
+        let _ = group.bench_with_input(
+            BenchmarkId::new("snapshot", batch),
+            &retired,
+            |b, retired| {
+                b.iter(|| {
+                    let hazards = hazard_bag.snapshot();
+                    let protected = retired.iter().filter(|p| hazards.contains(**p)).count();
+                    assert_eq!(protected, batch / 2);
+                })
+            },
+        );
+    }
+    group.finish();
+
+    drop(shields);
+}
+
+criterion_group!(benches, hazards);
+criterion_main!(benches);
@@ -0,0 +1,487 @@
+//! Compares `SplitOrderedList` with lock-based maps and `DashMap` on mixes of lookups, inserts and
+//! deletes.
+//!
+//! ```text
+//! cargo bench --bench maps
+//! ```
+//!
+//! Every map is driven through `NonblockingMap`, so another backend only needs an impl of the trait
+//! and a line in `maps`. The maps start with half of `KEYS`, and the threads pick keys at random
+//! among `KEYS`, so the inserts and deletes of a mix keep the map at about half full. A thread pins
+//! (or takes `()` for the maps without a guard) for each operation, so the pinning overhead of
+//! `SplitOrderedList` is measured along with the map.
+//!
+//! The trait returns references to the values, which outlive the locks of the lock-based maps. So
+//! those maps box the values and keep the deleted ones until the map is dropped, much like the
+//! epoch-based maps defer freeing them.
+//!
+//! `insert_remove` has each thread insert its own keys into a shared `SplitOrderedList` and then
+//! delete them, so every operation updates the count of the map. The count is striped, so the
+//! throughput should keep growing with the threads (up to the number of cores) instead of being
+//! capped by the cache line of a single counter.
+//!
+//! `insert_all` compares loading `LOAD_KEYS` keys into an empty `SplitOrderedList` with
+//! `insert_all` against inserting them one by one. `insert_all` sorts the keys in split order and
+//! inserts them with one cursor, while each `insert` searches from the bucket of its key.
+//!
+//! `prewarm` grows a `SplitOrderedList` from `PREWARM_BUCKETS` buckets by one insert, and then
+//! looks up every key once in a random order. `lazy` doesn't prewarm, so the first lookup in each
+//! new bucket initializes the bucket. `prewarm` does, so the insert that grows the buckets
+//! initializes them instead. `lookups` measures only the lookups, and `grow_lookups` the growing
+//! insert as well.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam_channel::{unbounded, Receiver, Sender};
+use crossbeam_epoch::{self as epoch, Guard};
+use crossbeam_utils::thread::scope;
+use cs431_homework::{MapError, NonblockingMap, SplitOrderedList};
+use dashmap::mapref::entry::Entry as DashEntry;
+use dashmap::DashMap;
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// Numbers of threads of each mix.
+const THREADS: [usize; 4] = [1, 2, 4, 8];
+
+/// Number of keys that are looked up, inserted and deleted.
+const KEYS: usize = 1 << 16;
+
+/// Number of operations in an iteration, split among the threads.
+const OPS: u64 = 1 << 12;
+
+/// Number of keys a thread of `insert_remove` inserts before deleting them. An iteration is a
+/// whole number of chunks on every thread, up to the most threads in `THREADS`.
+const CHUNK: usize = 1 << 8;
+
+/// Number of keys loaded into a map by an iteration of `insert_all`.
+const LOAD_KEYS: usize = 1 << 16;
+
+/// Numbers of buckets of `prewarm` before the growth.
+const PREWARM_BUCKETS: [usize; 3] = [1 << 12, 1 << 14, 1 << 16];
+
+/// Mix of operations, named after its percentage of lookups. The other operations are inserts and
+/// deletes, half each.
+struct Mix {
+    name: &'static str,
+    lookups: u64,
+}
+
+const MIXES: [Mix; 3] = [
+    Mix {
+        name: "read_only",
+        lookups: 100,
+    },
+    Mix {
+        name: "95_5",
+        lookups: 95,
+    },
+    Mix {
+        name: "50_50",
+        lookups: 50,
+    },
+];
+
+/// Extends the lifetime of a reference to a boxed value of one of the maps below.
+///
+/// # Safety
+///
+/// The box must not be freed during `'a`. The maps below keep the deleted boxes until they are
+/// dropped, so a value can be borrowed for the lifetime of the map.
+unsafe fn extend<'a, V>(value: &V) -> &'a V {
+    &*(value as *const V)
+}
+
+/// Deleted values of a map, freed when the map is dropped.
+#[derive(Debug)]
+struct Retired<V> {
+    sender: Sender<Box<V>>,
+    _receiver: Receiver<Box<V>>,
+}
+
+impl<V> Default for Retired<V> {
+    fn default() -> Self {
+        let (sender, _receiver) = unbounded();
+        Self { sender, _receiver }
+    }
+}
+
+impl<V> Retired<V> {
+    /// Keeps `value` until the map is dropped, and returns a reference to it.
+    fn retire(&self, value: Box<V>) -> &V {
+        let result = unsafe { extend(&*value) };
+        self.sender.send(value).unwrap();
+        result
+    }
+}
+
+/// `HashMap` behind a `Mutex`.
+#[derive(Debug)]
+struct MutexMap<V> {
+    map: Mutex<HashMap<usize, Box<V>>>,
+    retired: Retired<V>,
+}
+
+impl<V> Default for MutexMap<V> {
+    fn default() -> Self {
+        Self {
+            map: Mutex::default(),
+            retired: Retired::default(),
+        }
+    }
+}
+
+impl<V> NonblockingMap<usize, V, ()> for MutexMap<V> {
+    fn lookup<'a>(&'a self, key: &usize, _: &'a ()) -> Option<&'a V> {
+        let map = self.map.lock().unwrap();
+        map.get(key).map(|value| unsafe { extend(&**value) })
+    }
+
+    fn insert(&self, key: &usize, value: V, _: &()) -> Result<(), V> {
+        match self.map.lock().unwrap().entry(*key) {
+            Entry::Occupied(_) => Err(value),
+            Entry::Vacant(entry) => {
+                let _ = entry.insert(Box::new(value));
+                Ok(())
+            }
+        }
+    }
+
+    fn get_or_insert_with<'a, F: FnOnce() -> V>(&'a self, key: &usize, f: F, _: &'a ()) -> &'a V {
+        let mut map = self.map.lock().unwrap();
+        let value = map.entry(*key).or_insert_with(|| Box::new(f()));
+        unsafe { extend(&**value) }
+    }
+
+    fn remove<'a>(&'a self, key: &usize, _: &'a ()) -> Result<&'a V, MapError> {
+        let value = self.map.lock().unwrap().remove(key);
+        Ok(self.retired.retire(value.ok_or(MapError::NotFound)?))
+    }
+}
+
+/// `HashMap` behind a `RwLock`. Lookups take the read lock.
+#[derive(Debug)]
+struct RwLockMap<V> {
+    map: RwLock<HashMap<usize, Box<V>>>,
+    retired: Retired<V>,
+}
+
+impl<V> Default for RwLockMap<V> {
+    fn default() -> Self {
+        Self {
+            map: RwLock::default(),
+            retired: Retired::default(),
+        }
+    }
+}
+
+impl<V> NonblockingMap<usize, V, ()> for RwLockMap<V> {
+    fn lookup<'a>(&'a self, key: &usize, _: &'a ()) -> Option<&'a V> {
+        let map = self.map.read().unwrap();
+        map.get(key).map(|value| unsafe { extend(&**value) })
+    }
+
+    fn insert(&self, key: &usize, value: V, _: &()) -> Result<(), V> {
+        match self.map.write().unwrap().entry(*key) {
+            Entry::Occupied(_) => Err(value),
+            Entry::Vacant(entry) => {
+                let _ = entry.insert(Box::new(value));
+                Ok(())
+            }
+        }
+    }
+
+    fn get_or_insert_with<'a, F: FnOnce() -> V>(&'a self, key: &usize, f: F, _: &'a ()) -> &'a V {
+        if let Some(value) = self.lookup(key, &()) {
+            return value;
+        }
+        let mut map = self.map.write().unwrap();
+        let value = map.entry(*key).or_insert_with(|| Box::new(f()));
+        unsafe { extend(&**value) }
+    }
+
+    fn remove<'a>(&'a self, key: &usize, _: &'a ()) -> Result<&'a V, MapError> {
+        let value = self.map.write().unwrap().remove(key);
+        Ok(self.retired.retire(value.ok_or(MapError::NotFound)?))
+    }
+}
+
+/// `DashMap`, which shards the map by key into `RwLock<HashMap>`s.
+#[derive(Debug)]
+struct ShardedMap<V> {
+    map: DashMap<usize, Box<V>>,
+    retired: Retired<V>,
+}
+
+impl<V> Default for ShardedMap<V> {
+    fn default() -> Self {
+        Self {
+            map: DashMap::new(),
+            retired: Retired::default(),
+        }
+    }
+}
+
+impl<V> NonblockingMap<usize, V, ()> for ShardedMap<V> {
+    fn lookup<'a>(&'a self, key: &usize, _: &'a ()) -> Option<&'a V> {
+        let value = self.map.get(key)?;
+        Some(unsafe { extend(&**value) })
+    }
+
+    fn insert(&self, key: &usize, value: V, _: &()) -> Result<(), V> {
+        match self.map.entry(*key) {
+            DashEntry::Occupied(_) => Err(value),
+            DashEntry::Vacant(entry) => {
+                let _ = entry.insert(Box::new(value));
+                Ok(())
+            }
+        }
+    }
+
+    fn get_or_insert_with<'a, F: FnOnce() -> V>(&'a self, key: &usize, f: F, _: &'a ()) -> &'a V {
+        let value = self.map.entry(*key).or_insert_with(|| Box::new(f()));
+        unsafe { extend(&**value) }
+    }
+
+    fn remove<'a>(&'a self, key: &usize, _: &'a ()) -> Result<&'a V, MapError> {
+        let (_, value) = self.map.remove(key).ok_or(MapError::NotFound)?;
+        Ok(self.retired.retire(value))
+    }
+}
+
+/// Returns the next number of the xorshift generator with the given state.
+fn next(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+/// Runs `iters` iterations of `OPS` operations of `mix` on a new map of type `M`, split among
+/// `threads` threads. `pin` returns the guard of an operation. Returns the elapsed time, excluding
+/// the filling of the map.
+fn run<G, M: NonblockingMap<usize, usize, G> + Default + Sync>(
+    pin: fn() -> G,
+    mix: &Mix,
+    threads: usize,
+    iters: u64,
+) -> Duration {
+    let map = M::default();
+    for key in (0..KEYS).step_by(2) {
+        assert!(map.insert(&key, key, &pin()).is_ok());
+    }
+
+    let ops = iters * OPS / threads as u64;
+    let start = Instant::now();
+    scope(|s| {
+        for t in 0..threads {
+            let map = &map;
+            let _ = s.spawn(move |_| {
+                let mut state = 0x9e37_79b9_7f4a_7c15 ^ t as u64;
+                for _ in 0..ops {
+                    let r = next(&mut state);
+                    let key = (r >> 8) as usize % KEYS;
+                    let guard = pin();
+                    match r % 100 {
+                        op if op < mix.lookups => {
+                            let _ = map.lookup(&key, &guard);
+                        }
+                        op if op % 2 == 0 => {
+                            let _ = map.insert(&key, key, &guard);
+                        }
+                        _ => {
+                            let _ = map.remove(&key, &guard);
+                        }
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+    start.elapsed()
+}
+
+fn maps(c: &mut Criterion) {
+    for mix in &MIXES {
+        let mut group = c.benchmark_group(format!("maps/{}", mix.name));
+        let _ = group.throughput(Throughput::Elements(OPS));
+        for threads in THREADS {
+            let id = |name| BenchmarkId::new(name, threads);
+            let _ = group.bench_with_input(id("split_ordered_list"), &threads, |b, &t| {
+                b.iter_custom(|iters| {
+                    run::<Guard, SplitOrderedList<usize>>(epoch::pin, mix, t, iters)
+                })
+            });
+            let _ = group.bench_with_input(id("mutex_hash_map"), &threads, |b, &t| {
+                b.iter_custom(|iters| run::<(), MutexMap<usize>>(|| (), mix, t, iters))
+            });
+            let _ = group.bench_with_input(id("rwlock_hash_map"), &threads, |b, &t| {
+                b.iter_custom(|iters| run::<(), RwLockMap<usize>>(|| (), mix, t, iters))
+            });
+            let _ = group.bench_with_input(id("dashmap"), &threads, |b, &t| {
+                b.iter_custom(|iters| run::<(), ShardedMap<usize>>(|| (), mix, t, iters))
+            });
+        }
+        group.finish();
+    }
+}
+
+/// Runs `iters` iterations of `OPS` inserts and deletes on a `SplitOrderedList`, split among
+/// `threads` threads. Each thread inserts `CHUNK` keys of its own and deletes them, until it's done.
+/// Returns the elapsed time.
+fn run_insert_remove(threads: usize, iters: u64) -> Duration {
+    let list = SplitOrderedList::with_capacity(threads * CHUNK);
+    let chunks = iters * OPS / (2 * CHUNK * threads) as u64;
+    let start = Instant::now();
+    scope(|s| {
+        for t in 0..threads {
+            let list = &list;
+            let _ = s.spawn(move |_| {
+                let keys = t * CHUNK..(t + 1) * CHUNK;
+                for _ in 0..chunks {
+                    for key in keys.clone() {
+                        assert!(list.insert(&key, key, &epoch::pin()).is_ok());
+                    }
+                    for key in keys.clone() {
+                        assert!(list.remove(&key, &epoch::pin()).is_ok());
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+    start.elapsed()
+}
+
+fn insert_remove(c: &mut Criterion) {
+    let mut group = c.benchmark_group("maps/insert_remove");
+    let _ = group.throughput(Throughput::Elements(OPS));
+    for threads in THREADS {
+        let id = BenchmarkId::new("split_ordered_list", threads);
+        let _ = group.bench_with_input(id, &threads, |b, &t| {
+            b.iter_custom(|iters| run_insert_remove(t, iters))
+        });
+    }
+    group.finish();
+}
+
+/// Runs `iters` iterations of `load`, which loads `LOAD_KEYS` keys into an empty map. Returns the
+/// elapsed time, excluding the drops of the maps.
+fn run_load(load: impl Fn(&SplitOrderedList<usize>), iters: u64) -> Duration {
+    let mut elapsed = Duration::ZERO;
+    for _ in 0..iters {
+        let list = SplitOrderedList::new();
+        let start = Instant::now();
+        load(&list);
+        elapsed += start.elapsed();
+        assert_eq!(list.len(), LOAD_KEYS);
+    }
+    elapsed
+}
+
+fn insert_all(c: &mut Criterion) {
+    let mut group = c.benchmark_group("maps/insert_all");
+    let _ = group
+        .sample_size(10)
+        .throughput(Throughput::Elements(LOAD_KEYS as u64));
+    let _ = group.bench_function("insert", |b| {
+        b.iter_custom(|iters| {
+            run_load(
+                |list| {
+                    let guard = epoch::pin();
+                    for key in 0..LOAD_KEYS {
+                        assert!(list.insert(&key, key, &guard).is_ok());
+                    }
+                },
+                iters,
+            )
+        })
+    });
+    let _ = group.bench_function("insert_all", |b| {
+        b.iter_custom(|iters| {
+            run_load(
+                |list| {
+                    let entries = (0..LOAD_KEYS).map(|key| (key, key));
+                    assert_eq!(list.insert_all(entries, &epoch::pin()), LOAD_KEYS);
+                },
+                iters,
+            )
+        })
+    });
+    group.finish();
+}
+
+/// Returns a `SplitOrderedList` of `buckets` initialized buckets, which prewarms the new buckets
+/// after a growth if `prewarm` is set.
+fn prewarm_list(buckets: usize, prewarm: bool) -> SplitOrderedList<usize> {
+    let mut list =
+        SplitOrderedList::with_capacity(buckets * SplitOrderedList::<usize>::DEFAULT_LOAD_FACTOR);
+    list.set_prewarm(prewarm);
+    // The buckets before the growth are initialized either way.
+    list.prewarm_buckets(&epoch::pin());
+    list
+}
+
+/// Returns the number of keys inserted into a list of `buckets` buckets until it grows.
+fn growth_keys(buckets: usize) -> usize {
+    let list = prewarm_list(buckets, false);
+    let guard = epoch::pin();
+    let mut keys = 0;
+    while list.bucket_count() <= buckets {
+        assert!(list.insert(&keys, keys, &guard).is_ok());
+        keys += 1;
+    }
+    keys
+}
+
+/// Runs `iters` iterations of growing a list of `buckets` buckets by inserting `keys` keys, and
+/// then looking up every key in a random order. Returns the elapsed time of the lookups, and of the
+/// last insert, which grows the list, as well if `grow` is set.
+fn run_prewarm(buckets: usize, keys: usize, prewarm: bool, grow: bool, iters: u64) -> Duration {
+    let mut order = (0..keys).collect::<Vec<_>>();
+    let mut elapsed = Duration::ZERO;
+    for _ in 0..iters {
+        let list = prewarm_list(buckets, prewarm);
+        let guard = epoch::pin();
+        for key in 0..keys - 1 {
+            assert!(list.insert(&key, key, &guard).is_ok());
+        }
+        order.shuffle(&mut thread_rng());
+
+        let mut start = Instant::now();
+        assert!(list.insert(&(keys - 1), keys - 1, &guard).is_ok());
+        if !grow {
+            start = Instant::now();
+        }
+        for key in &order {
+            let _ = black_box(list.lookup(key, &guard));
+        }
+        elapsed += start.elapsed();
+        assert!(list.bucket_count() > buckets);
+    }
+    elapsed
+}
+
+fn prewarm(c: &mut Criterion) {
+    let mut group = c.benchmark_group("maps/prewarm");
+    let _ = group.sample_size(10);
+    for buckets in PREWARM_BUCKETS {
+        let keys = growth_keys(buckets);
+        let _ = group.throughput(Throughput::Elements(keys as u64));
+        for (bench, grow) in [("lookups", false), ("grow_lookups", true)] {
+            for (mode, prewarm) in [("lazy", false), ("prewarm", true)] {
+                let id = BenchmarkId::new(format!("{}/{}", bench, mode), buckets);
+                let _ = group.bench_function(id, |b| {
+                    b.iter_custom(|iters| run_prewarm(buckets, keys, prewarm, grow, iters))
+                });
+            }
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, maps, insert_remove, insert_all, prewarm);
+criterion_main!(benches);
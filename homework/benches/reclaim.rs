@@ -0,0 +1,245 @@
+//! Compares hazard pointers with crossbeam-epoch, and measures the recycling of hazard slots.
+//!
+//! ```text
+//! cargo bench --bench reclaim
+//! ```
+//!
+//! `treiber_stack` measures push/pop pairs on `TreiberStack` with each `Reclaim` scheme, split
+//! among the threads. `protect_read` measures protecting a pointer and reading its pointee, i.e.
+//! `Shield::protect` against pinning the epoch. `slot_recycling` measures threads that keep `LIVE`
+//! shields of a shared `HazardBag` and repeatedly replace the oldest one, so that the slots are
+//! released and claimed concurrently. `shield_churn` measures threads that create and drop
+//! short-lived shields: those of the global `HAZARDS` reuse the slots cached by their thread, while
+//! those of a local `HazardBag` acquire their slots from the shared list. `protect_contention`
+//! measures `READERS` readers protecting a pointer that a writer keeps swapping: `protect` backs
+//! off between the retries, while `spin` retries `try_protect` right away.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use crossbeam_epoch as epoch;
+use crossbeam_utils::thread::scope;
+use cs431_homework::hazard_pointer::{HazardBag, Shield, HAZARDS};
+use cs431_homework::reclaim::{EpochReclaim, HpReclaim};
+use cs431_homework::TreiberStack;
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicPtr;
+use std::collections::VecDeque;
+#[cfg(not(feature = "check-loom"))]
+use std::sync::atomic::AtomicPtr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Numbers of threads of `treiber_stack`.
+const THREADS: [usize; 4] = [1, 4, 8, 16];
+
+/// Number of push/pop pairs in an iteration of `treiber_stack`.
+const OPS: u64 = 1 << 12;
+
+/// Numbers of threads of `slot_recycling` and `shield_churn`.
+const RECYCLING_THREADS: [usize; 5] = [1, 2, 4, 8, 16];
+
+/// Number of shields a thread of `slot_recycling` keeps.
+const LIVE: usize = 4;
+
+/// Number of readers of `protect_contention`.
+const READERS: usize = 8;
+
+/// Runs `iters` iterations of `OPS` calls of `push_pop` on `threads` threads. Returns the elapsed
+/// time.
+fn run<S: Default + Sync>(
+    threads: usize,
+    iters: u64,
+    push_pop: impl Fn(&S, usize) + Sync,
+) -> Duration {
+    let stack = S::default();
+    let ops = iters * OPS / threads as u64;
+    let start = Instant::now();
+    scope(|s| {
+        for _ in 0..threads {
+            let _ = s.spawn(|_| {
+                for i in 0..ops {
+                    push_pop(&stack, i as usize);
+                }
+            });
+        }
+    })
+    .unwrap();
+    start.elapsed()
+}
+
+/// Returns a closure that pushes a value to `TreiberStack<usize, $reclaim>` and pops a value.
+macro_rules! push_pop {
+    ($reclaim:ty) => {
+        |stack: &TreiberStack<usize, $reclaim>, i| {
+            stack.push(i);
+            assert!(stack.pop().is_some());
+        }
+    };
+}
+
+fn treiber_stack(c: &mut Criterion) {
+    let mut group = c.benchmark_group("treiber_stack");
+    let _ = group.throughput(Throughput::Elements(OPS));
+    for threads in THREADS {
+        let _ = group.bench_with_input(BenchmarkId::new("hazard", threads), &threads, |b, &t| {
+            b.iter_custom(|iters| run(t, iters, push_pop!(HpReclaim)))
+        });
+        let _ = group.bench_with_input(BenchmarkId::new("epoch", threads), &threads, |b, &t| {
+            b.iter_custom(|iters| run(t, iters, push_pop!(EpochReclaim)))
+        });
+    }
+    group.finish();
+}
+
+fn protect_read(c: &mut Criterion) {
+    let mut group = c.benchmark_group("protect_read");
+
+    let mut value = 42usize;
+    let src = AtomicPtr::new(&mut value);
+    let shield = Shield::default();
+    let _ = group.bench_function("hazard", |b| {
+        b.iter(|| unsafe { *shield.protect(black_box(&src)) })
+    });
+    drop(shield);
+
+    let src = epoch::Atomic::new(42usize);
+    let _ = group.bench_function("epoch", |b| {
+        b.iter(|| {
+            let guard = epoch::pin();
+            unsafe { *black_box(&src).load(Ordering::Acquire, &guard).deref() }
+        })
+    });
+    unsafe { drop(src.into_owned()) };
+
+    group.finish();
+}
+
+/// Runs `threads` threads that create `iters` shields of a shared bag each, keeping the last
+/// `LIVE` of them. Returns the elapsed time.
+fn recycle(threads: usize, iters: u64) -> Duration {
+    let hazards = HazardBag::new();
+    let start = Instant::now();
+    scope(|s| {
+        for _ in 0..threads {
+            let _ = s.spawn(|_| {
+                let mut shields = VecDeque::with_capacity(LIVE);
+                for _ in 0..iters {
+                    if shields.len() == LIVE {
+                        drop(shields.pop_front());
+                    }
+                    shields.push_back(Shield::<u8>::new(&hazards));
+                }
+            });
+        }
+    })
+    .unwrap();
+    start.elapsed()
+}
+
+fn slot_recycling(c: &mut Criterion) {
+    let mut group = c.benchmark_group("slot_recycling");
+    for threads in RECYCLING_THREADS {
+        let _ = group.throughput(Throughput::Elements(threads as u64));
+        let _ = group.bench_with_input(BenchmarkId::from_parameter(threads), &threads, |b, &t| {
+            b.iter_custom(|iters| recycle(t, iters))
+        });
+    }
+    group.finish();
+}
+
+/// Runs `threads` threads that create `iters` shields of `hazards` each. Returns the elapsed time.
+fn churn(hazards: &HazardBag, threads: usize, iters: u64) -> Duration {
+    let mut value = 0;
+    let src = AtomicPtr::new(&mut value);
+    let start = Instant::now();
+    scope(|s| {
+        for _ in 0..threads {
+            let _ = s.spawn(|_| {
+                for _ in 0..iters {
+                    let shield = Shield::new(hazards);
+                    assert!(!shield.protect(&src).is_null());
+                }
+            });
+        }
+    })
+    .unwrap();
+    start.elapsed()
+}
+
+fn shield_churn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("shield_churn");
+    let local = HazardBag::new();
+    for threads in RECYCLING_THREADS {
+        let _ = group.throughput(Throughput::Elements(threads as u64));
+        let _ = group.bench_with_input(BenchmarkId::new("HAZARDS", threads), &threads, |b, &t| {
+            b.iter_custom(|iters| churn(&HAZARDS, t, iters))
+        });
+        let _ = group.bench_with_input(BenchmarkId::new("local", threads), &threads, |b, &t| {
+            b.iter_custom(|iters| churn(&local, t, iters))
+        });
+    }
+    group.finish();
+}
+
+/// Runs `READERS` readers that protect `iters` pointers each with `protect`, while a writer keeps
+/// swapping the pointer. Returns the elapsed time.
+fn contend(
+    iters: u64,
+    protect: impl Fn(&Shield<usize>, &AtomicPtr<usize>) -> *const usize + Sync,
+) -> Duration {
+    // The nodes are never freed while the writer swaps them, so they don't need to be retired.
+    let nodes = [0usize; 2];
+    let (a, b) = (
+        &nodes[0] as *const _ as usize,
+        &nodes[1] as *const _ as usize,
+    );
+    let src = AtomicPtr::new(a as *mut usize);
+    let readers = AtomicUsize::new(READERS);
+    let start = Instant::now();
+    scope(|s| {
+        for _ in 0..READERS {
+            let _ = s.spawn(|_| {
+                let shield = Shield::default();
+                for _ in 0..iters {
+                    assert!(!protect(&shield, &src).is_null());
+                }
+                let _ = readers.fetch_sub(1, Ordering::Relaxed);
+            });
+        }
+        let _ = s.spawn(|_| {
+            while readers.load(Ordering::Relaxed) > 0 {
+                src.store(b as *mut usize, Ordering::Release);
+                src.store(a as *mut usize, Ordering::Release);
+            }
+        });
+    })
+    .unwrap();
+    start.elapsed()
+}
+
+fn protect_contention(c: &mut Criterion) {
+    let mut group = c.benchmark_group("protect_contention");
+    let _ = group.throughput(Throughput::Elements(READERS as u64));
+    let _ = group.bench_function("protect", |b| {
+        b.iter_custom(|iters| contend(iters, |shield, src| shield.protect(src)))
+    });
+    let _ = group.bench_function("spin", |b| {
+        b.iter_custom(|iters| {
+            contend(iters, |shield, src| {
+                let mut pointer = src.load(Ordering::Relaxed) as *const usize;
+                while !shield.try_protect(&mut pointer, src) {}
+                pointer
+            })
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    treiber_stack,
+    protect_read,
+    slot_recycling,
+    shield_churn,
+    protect_contention
+);
+criterion_main!(benches);
@@ -0,0 +1,74 @@
+//! Compares creating a group of `N` shields with `ShieldArray::new` against `N` calls of
+//! `Shield::new`.
+//!
+//! ```text
+//! cargo bench --bench shield_groups
+//! ```
+//!
+//! `alloc` creates up to `GROUPS` groups in an empty bag, so every slot is allocated. `recycle`
+//! creates them again after they're dropped, so every slot is recycled.
+
+use criterion::measurement::WallTime;
+use criterion::{criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion};
+use cs431_homework::hazard_pointer::{HazardBag, Shield, ShieldArray};
+use std::time::{Duration, Instant};
+
+/// Maximum number of groups alive at once.
+const GROUPS: u64 = 256;
+
+/// Returns the time of creating `iters` groups with `create`, in bags whose slots are all
+/// allocated or all recycled.
+fn run<G>(create: impl Fn(&HazardBag) -> G, recycle: bool, iters: u64) -> Duration {
+    let mut elapsed = Duration::ZERO;
+    let mut left = iters;
+    while left > 0 {
+        let n = left.min(GROUPS);
+        left -= n;
+
+        let hazards = HazardBag::new();
+        let mut groups = Vec::with_capacity(n as usize);
+        if recycle {
+            for _ in 0..n {
+                groups.push(create(&hazards));
+            }
+            groups.clear();
+        }
+
+        let start = Instant::now();
+        for _ in 0..n {
+            groups.push(create(&hazards));
+        }
+        elapsed += start.elapsed();
+    }
+    elapsed
+}
+
+/// Benchmarks creating groups of `N` shields both ways.
+fn compare<const N: usize>(group: &mut BenchmarkGroup<'_, WallTime>) {
+    for (case, recycle) in [("alloc", false), ("recycle", true)] {
+        let _ = group.bench_function(BenchmarkId::new(format!("{}/new", case), N), |b| {
+            b.iter_custom(|iters| {
+                run(
+                    |hazards| [(); N].map(|_| Shield::<u8>::new(hazards)),
+                    recycle,
+                    iters,
+                )
+            })
+        });
+        let _ = group.bench_function(BenchmarkId::new(format!("{}/array", case), N), |b| {
+            b.iter_custom(|iters| run(ShieldArray::<u8, N>::new, recycle, iters))
+        });
+    }
+}
+
+fn shield_groups(c: &mut Criterion) {
+    let mut group = c.benchmark_group("shield_groups");
+    compare::<2>(&mut group);
+    compare::<3>(&mut group);
+    compare::<4>(&mut group);
+    compare::<8>(&mut group);
+    group.finish();
+}
+
+criterion_group!(benches, shield_groups);
+criterion_main!(benches);
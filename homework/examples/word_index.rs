@@ -0,0 +1,204 @@
+//! Indexes the words in the files of a directory and prints the most frequent words of each file.
+//!
+//! ```text
+//! cargo run --example word_index -- DIR [N]
+//! ```
+//!
+//! The files are tokenized in parallel by a `ThreadPool`. Each job counts the words of a chunk of
+//! lines into the file's `SplitOrderedList`, keyed by the hash of the word. The top words of a
+//! file are computed once and kept in a `Cache` until the file is re-indexed.
+
+use crossbeam_epoch::{pin, Guard};
+use cs431_homework::hello_server::{Cache, ThreadPool};
+use cs431_homework::{NonblockingMap, SplitOrderedList};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::{env, fs, io};
+
+/// Number of lines tokenized by a job.
+const CHUNK_LINES: usize = 64;
+
+/// Words sorted by decreasing count, then alphabetically.
+pub type TopWords = Arc<Vec<(String, usize)>>;
+
+#[derive(Debug)]
+struct WordCount {
+    word: String,
+    count: AtomicUsize,
+}
+
+/// Word counts of a file.
+#[derive(Debug, Default)]
+pub struct FileIndex {
+    counts: SplitOrderedList<WordCount>,
+    /// Keys of `counts`.
+    keys: Mutex<Vec<usize>>,
+}
+
+impl FileIndex {
+    /// Increments the count of `word`. Words with the same hash are counted together.
+    fn add(&self, word: &str, guard: &Guard) {
+        let key = word_key(word);
+        loop {
+            if let Some(count) = self.counts.lookup(&key, guard) {
+                let _ = count.count.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            let count = WordCount {
+                word: word.to_string(),
+                count: AtomicUsize::new(1),
+            };
+            if self.counts.insert(&key, count, guard).is_ok() {
+                self.keys.lock().unwrap().push(key);
+                return;
+            }
+            // Another job inserted the word first. Retry to increment its count.
+        }
+    }
+
+    /// Returns all words of the file sorted by decreasing count, then alphabetically.
+    fn top_words(&self) -> Vec<(String, usize)> {
+        let guard = pin();
+        let mut words = self
+            .keys
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|key| {
+                let count = self.counts.lookup(key, &guard).unwrap();
+                (count.word.clone(), count.count.load(Ordering::Relaxed))
+            })
+            .collect::<Vec<_>>();
+        words.sort_by(|(w1, c1), (w2, c2)| c2.cmp(c1).then_with(|| w1.cmp(w2)));
+        words
+    }
+}
+
+/// Key of the word in `SplitOrderedList`, i.e. its hash without the top bit.
+pub fn word_key(word: &str) -> usize {
+    let mut hasher = DefaultHasher::new();
+    word.hash(&mut hasher);
+    hasher.finish() as usize >> 1
+}
+
+/// Splits `text` into lowercase alphanumeric words.
+pub fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Word index of a set of files.
+#[derive(Debug)]
+pub struct WordIndex {
+    pool: ThreadPool,
+    files: Mutex<HashMap<PathBuf, Arc<FileIndex>>>,
+    /// Top words of a file, tagged with the index they are computed from.
+    cache: Cache<PathBuf, (Arc<FileIndex>, TopWords)>,
+    /// Number of times the top words are computed, i.e. cache misses.
+    computations: AtomicUsize,
+}
+
+impl WordIndex {
+    /// Creates an empty index that tokenizes files with `threads` threads.
+    pub fn new(threads: usize) -> Self {
+        Self {
+            pool: ThreadPool::new(threads),
+            files: Mutex::new(HashMap::new()),
+            cache: Cache::default(),
+            computations: AtomicUsize::new(0),
+        }
+    }
+
+    /// Indexes the regular files in `dir`. Returns their paths, sorted.
+    pub fn index_dir(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                paths.push(entry.path());
+            }
+        }
+        paths.sort();
+        self.index_files(&paths)?;
+        Ok(paths)
+    }
+
+    /// Indexes the files in parallel, replacing their previous indexes.
+    pub fn index_files(&self, paths: &[PathBuf]) -> io::Result<()> {
+        let mut indexes = Vec::new();
+        for path in paths {
+            let text = fs::read_to_string(path)?;
+            let index = Arc::new(FileIndex::default());
+            let lines = text.lines().map(str::to_string).collect::<Vec<_>>();
+            for chunk in lines.chunks(CHUNK_LINES) {
+                let chunk = chunk.to_vec();
+                let index = index.clone();
+                self.pool
+                    .submit(move || {
+                        let guard = pin();
+                        for line in &chunk {
+                            for word in tokenize(line) {
+                                index.add(&word, &guard);
+                            }
+                        }
+                    })
+                    .unwrap();
+            }
+            indexes.push((path.clone(), index));
+        }
+        self.pool.join();
+
+        let mut files = self.files.lock().unwrap();
+        for (path, index) in indexes {
+            let _ = self.cache.invalidate(&path);
+            let _ = files.insert(path, index);
+        }
+        Ok(())
+    }
+
+    /// Returns the `n` most frequent words of the file, or `None` if it's not indexed.
+    pub fn top_words(&self, path: &Path, n: usize) -> Option<Vec<(String, usize)>> {
+        let index = self.files.lock().unwrap().get(path)?.clone();
+        let path = path.to_path_buf();
+        loop {
+            let (cached_index, top) = self.cache.get_or_insert_with(path.clone(), |_| {
+                let _ = self.computations.fetch_add(1, Ordering::Relaxed);
+                (index.clone(), Arc::new(index.top_words()))
+            });
+            if Arc::ptr_eq(&cached_index, &index) {
+                return Some(top.iter().take(n).cloned().collect());
+            }
+            // Computed from an index replaced after our lookup.
+            let _ = self
+                .cache
+                .invalidate_if(&path, |(i, _)| Arc::ptr_eq(i, &cached_index));
+        }
+    }
+
+    /// Number of times the top words are computed.
+    pub fn computations(&self) -> usize {
+        self.computations.load(Ordering::Relaxed)
+    }
+}
+
+fn main() -> io::Result<()> {
+    let mut args = env::args().skip(1);
+    let dir = args.next().expect("usage: word_index DIR [N]");
+    let n = args
+        .next()
+        .map_or(5, |n| n.parse().expect("N is not a number"));
+
+    let index = WordIndex::new(4);
+    for path in index.index_dir(Path::new(&dir))? {
+        println!("{}:", path.display());
+        for (word, count) in index.top_words(&path, n).unwrap() {
+            println!("  {:>6} {}", count, word);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,17 @@
+//! Runs a sequence of operations on `OrderedListSet` and compares the results with `BTreeSet`.
+//!
+//! Run with `cargo fuzz run list_set fuzz/seeds/list_set`.
+
+#![no_main]
+use cs431_homework::test_util::ops::{decode, run_list_set};
+use cs431_homework::OrderedListSet;
+use libfuzzer_sys::fuzz_target;
+use std::collections::BTreeSet;
+
+fuzz_target!(|data: &[u8]| {
+    let set = OrderedListSet::new();
+    let mut oracle = BTreeSet::new();
+    for op in &decode(data) {
+        run_list_set(&set, &mut oracle, op);
+    }
+});
@@ -0,0 +1,17 @@
+//! Runs a sequence of operations on `SplitOrderedList` and compares the results with `HashMap`.
+//!
+//! Run with `cargo fuzz run split_ordered_list fuzz/seeds/split_ordered_list`.
+
+#![no_main]
+use cs431_homework::test_util::ops::{decode, run_split_ordered_list};
+use cs431_homework::SplitOrderedList;
+use libfuzzer_sys::fuzz_target;
+use std::collections::HashMap;
+
+fuzz_target!(|data: &[u8]| {
+    let list = SplitOrderedList::new();
+    let mut oracle = HashMap::new();
+    for op in &decode(data) {
+        run_split_ordered_list(&list, &mut oracle, op);
+    }
+});
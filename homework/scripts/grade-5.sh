@@ -60,6 +60,15 @@ for t in "${!TEST_NAMES[@]}"; do
     done
 done
 
+# Synchronization of growing the tree. Without it, the last test isn't counted.
+RUNNER="cargo --features loom-growable-array"
+TIMEOUT=2m
+TESTS=("--test growable_array sync")
+echo "Testing growable_array sync with $RUNNER, timeout $TIMEOUT..."
+if [ $(run_tests) -ne 0 ] && [ $growable_array_fail -eq ${#TEST_NAMES[@]} ]; then
+    growable_array_fail=$(( ${#TEST_NAMES[@]} - 1 ))
+fi
+
 SCORES=( 0 10 20 30 60 90 )
 SCORE=$(( ${SCORES[growable_array_fail]} + ${SCORES[split_ordered_list_fail]} ))
 echo "Score: $SCORE / 180"
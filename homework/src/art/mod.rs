@@ -71,9 +71,9 @@ impl<'a, V, I: 'a + Iterator<Item = u8> + DoubleEndedIterator> Entry<'a, V, I> {
 
     /// Deletes the value at the entry.
     ///
-    /// Returns `Ok(v)` if the entry contains a value, `v`; `Err(())` if the entry does not contain
-    /// a value.
-    pub fn delete(mut self) -> Result<V, ()> {
+    /// Returns `Ok(v)` if the entry contains a value, `v`; `Err(MapError::NotFound)` if the entry
+    /// does not contain a value.
+    pub fn delete(mut self) -> Result<V, MapError> {
         unimplemented!()
     }
 
@@ -133,7 +133,7 @@ impl<V> SequentialMap<str, V> for Art<V> {
         self.entry(key).or_insert(value)
     }
 
-    fn delete(&mut self, key: &str) -> Result<V, ()> {
+    fn remove(&mut self, key: &str) -> Result<V, MapError> {
         let key = Self::encode_key(key);
         self.entry(key).delete()
     }
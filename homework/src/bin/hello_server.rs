@@ -45,7 +45,7 @@ fn main() -> io::Result<()> {
 
     // Executes the listener.
     let listener_pool = pool.clone();
-    pool.execute(move || {
+    pool.submit(move || {
         // Creates the request handler.
         let handler = Handler::default();
 
@@ -54,15 +54,18 @@ fn main() -> io::Result<()> {
             // send a job to the thread pool.
             let report_sender = report_sender.clone();
             let handler = handler.clone();
-            listener_pool.execute(move || {
-                let report = handler.handle_conn(id, stream.unwrap());
-                report_sender.send(report).unwrap();
-            });
+            listener_pool
+                .submit(move || {
+                    let report = handler.handle_conn(id, stream.unwrap());
+                    report_sender.send(report).unwrap();
+                })
+                .unwrap();
         }
-    });
+    })
+    .unwrap();
 
     // Executes the reporter.
-    pool.execute(move || {
+    pool.submit(move || {
         let mut stats = Statistics::default();
         for report in report_receiver {
             println!("[report] {:?}", report);
@@ -72,7 +75,8 @@ fn main() -> io::Result<()> {
         println!("[sending stat]");
         stat_sender.send(stats).unwrap();
         println!("[sent stat]");
-    });
+    })
+    .unwrap();
 
     // Blocks until the reporter sends the statistics.
     let stat = stat_receiver.recv().unwrap();
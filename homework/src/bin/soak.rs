@@ -0,0 +1,393 @@
+//! Long-running soak test.
+//!
+//! Exercises `SplitOrderedList`, `OrderedListSet`, `Cache`, `ThreadPool`, and hazard pointers
+//! concurrently, periodically printing the memory usage, the number of live values in each
+//! structure, and the metrics (with the `metrics` feature). Exits with a non-zero status if an
+//! invariant check fails, a value leaks, or the RSS grows monotonically faster than the given
+//! slope.
+//!
+//! ```text
+//! cargo run --release --bin soak -- [--secs N] [--threads N] [--report-secs N]
+//!                                   [--max-rss-slope KIB_PER_SEC] [--seed N]
+//! ```
+//!
+//! The seed defaults to `HOMEWORK_TEST_SEED` or a random one. Rerunning with the printed seed
+//! gives each worker the same sequence of operations.
+
+use crossbeam_channel::RecvTimeoutError;
+use crossbeam_epoch::pin;
+use cs431_homework::hazard_pointer::{collect, retire, Shield};
+use cs431_homework::hello_server::{Cache, ThreadPool};
+use cs431_homework::test_util::drop_counter::{Counted, DropCounter};
+use cs431_homework::test_util::rng::{self, TestRng};
+use cs431_homework::{NonblockingMap, OrderedListSet, SplitOrderedList};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::AtomicPtr;
+use rand::prelude::*;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap};
+use std::panic::{self, AssertUnwindSafe};
+use std::process;
+use std::ptr;
+use std::str::FromStr;
+#[cfg(not(feature = "check-loom"))]
+use std::sync::atomic::AtomicPtr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{env, fs};
+
+/// Keys owned by each worker thread. Threads work on disjoint keys so that each can check the
+/// results against its own oracle.
+const KEYS_PER_THREAD: usize = 256;
+/// Keys shared by all threads in `Cache`.
+const CACHE_KEYS: usize = 1024;
+/// Number of pointers protected and retired with hazard pointers.
+const CELLS: usize = 16;
+/// Number of jobs submitted to the pool in each report period.
+const POOL_JOBS: usize = 64;
+/// Number of `pin().flush()` calls to wait for the deferred destructions at the end.
+const FLUSHES: usize = 1 << 16;
+
+static SOL_VALUES: DropCounter = DropCounter::new();
+static SET_VALUES: DropCounter = DropCounter::new();
+static HP_VALUES: DropCounter = DropCounter::new();
+
+#[derive(Debug)]
+struct Config {
+    secs: u64,
+    threads: usize,
+    report_secs: u64,
+    max_rss_slope: f64,
+    seed: u64,
+}
+
+impl Config {
+    fn from_args() -> Result<Self, String> {
+        let mut config = Self {
+            secs: 60,
+            threads: 4,
+            report_secs: 5,
+            max_rss_slope: 64.0,
+            seed: rng::seed(),
+        };
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for {}", arg))?;
+            match arg.as_str() {
+                "--secs" => config.secs = parse(&arg, &value)?,
+                "--threads" => config.threads = parse(&arg, &value)?,
+                "--report-secs" => config.report_secs = parse(&arg, &value)?,
+                "--max-rss-slope" => config.max_rss_slope = parse(&arg, &value)?,
+                "--seed" => config.seed = parse(&arg, &value)?,
+                _ => return Err(format!("unknown argument: {}", arg)),
+            }
+        }
+        if config.threads == 0 || config.report_secs == 0 {
+            return Err("--threads and --report-secs must be positive".to_string());
+        }
+        Ok(config)
+    }
+}
+
+fn parse<T: FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {}: {}", arg, value))
+}
+
+/// The structures under test.
+#[derive(Debug)]
+struct Structures {
+    list: SplitOrderedList<Counted<'static, usize>>,
+    set: OrderedListSet<Counted<'static, usize>>,
+    cache: Cache<usize, usize>,
+    /// `cells[i]` always points to a value `v` such that `v % CELLS == i`.
+    cells: Vec<AtomicPtr<Counted<'static, usize>>>,
+}
+
+impl Structures {
+    fn new() -> Self {
+        Self {
+            list: SplitOrderedList::new(),
+            set: OrderedListSet::new(),
+            cache: Cache::default(),
+            cells: (0..CELLS)
+                .map(|i| AtomicPtr::new(Box::into_raw(Box::new(HP_VALUES.track(i)))))
+                .collect(),
+        }
+    }
+
+    fn list_op(&self, rng: &mut impl Rng, key: usize, oracle: &mut HashMap<usize, usize>) {
+        let guard = pin();
+        match rng.gen_range(0..3) {
+            0 => {
+                let value = rng.gen::<usize>();
+                let result = self.list.insert(&key, SOL_VALUES.track(value), &guard);
+                match oracle.entry(key) {
+                    Entry::Occupied(_) => {
+                        assert!(result.is_err(), "list: inserted existing key {}", key)
+                    }
+                    Entry::Vacant(e) => {
+                        assert!(result.is_ok(), "list: failed to insert key {}", key);
+                        let _ = e.insert(value);
+                    }
+                }
+            }
+            1 => {
+                let result = self.list.remove(&key, &guard).ok().map(|v| **v);
+                assert_eq!(result, oracle.remove(&key), "list: delete {}", key);
+            }
+            _ => {
+                let result = self.list.lookup(&key, &guard).map(|v| **v);
+                assert_eq!(result, oracle.get(&key).cloned(), "list: lookup {}", key);
+            }
+        }
+    }
+
+    fn set_op(&self, rng: &mut impl Rng, key: usize, oracle: &mut BTreeSet<usize>) {
+        match rng.gen_range(0..3) {
+            0 => {
+                let result = self.set.insert(SET_VALUES.track(key)).is_ok();
+                assert_eq!(result, oracle.insert(key), "set: insert {}", key);
+            }
+            1 => {
+                let result = self.set.remove(&SET_VALUES.track(key)).is_ok();
+                assert_eq!(result, oracle.remove(&key), "set: remove {}", key);
+            }
+            _ => {
+                let result = self.set.contains(&SET_VALUES.track(key));
+                assert_eq!(result, oracle.contains(&key), "set: contains {}", key);
+            }
+        }
+    }
+
+    fn cache_op(&self, rng: &mut impl Rng) {
+        let key = rng.gen_range(0..CACHE_KEYS);
+        if rng.gen_ratio(1, 8) {
+            let _ = self.cache.invalidate(&key);
+        } else {
+            let value = self.cache.get_or_insert_with(key, |k| k * 2);
+            assert_eq!(value, key * 2, "cache: get {}", key);
+        }
+    }
+
+    fn hazard_op(&self, rng: &mut impl Rng) {
+        let i = rng.gen_range(0..CELLS);
+        let cell = &self.cells[i];
+        if rng.gen() {
+            let new = Box::into_raw(Box::new(
+                HP_VALUES.track(rng.gen::<usize>() / CELLS * CELLS + i),
+            ));
+            retire(cell.swap(new, Ordering::AcqRel) as *const Counted<'static, usize>);
+        } else {
+            let shield = Shield::default();
+            let value = **unsafe { shield.protect_ref(cell) }.unwrap();
+            assert_eq!(value % CELLS, i, "hazard pointer: cell {}", i);
+        }
+    }
+
+    /// Runs random operations on keys owned by thread `tid` until `stop` is set.
+    fn work(&self, tid: usize, mut rng: TestRng, stop: &AtomicBool) {
+        let keys = tid * KEYS_PER_THREAD..(tid + 1) * KEYS_PER_THREAD;
+        let mut list_oracle = HashMap::new();
+        let mut set_oracle = BTreeSet::new();
+        while !stop.load(Ordering::Relaxed) {
+            let key = rng.gen_range(keys.clone());
+            match rng.gen_range(0..4) {
+                0 => self.list_op(&mut rng, key, &mut list_oracle),
+                1 => self.set_op(&mut rng, key, &mut set_oracle),
+                2 => self.cache_op(&mut rng),
+                _ => self.hazard_op(&mut rng),
+            }
+        }
+    }
+}
+
+/// Runs a batch of jobs in the pool and checks that each of them runs exactly once.
+fn check_pool(pool: &ThreadPool) -> Result<(), String> {
+    let (sender, receiver) = crossbeam_channel::unbounded();
+    for i in 0..POOL_JOBS {
+        let sender = sender.clone();
+        pool.submit(move || sender.send(i).unwrap()).unwrap();
+    }
+    drop(sender);
+
+    let mut done = vec![false; POOL_JOBS];
+    loop {
+        match receiver.recv_timeout(Duration::from_secs(10)) {
+            Ok(i) if done[i] => return Err(format!("pool: job {} ran twice", i)),
+            Ok(i) => done[i] = true,
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => return Err("pool: jobs timed out".to_string()),
+        }
+    }
+    match done.iter().position(|d| !d) {
+        Some(i) => Err(format!("pool: job {} didn't run", i)),
+        None => Ok(()),
+    }
+}
+
+/// Resident set size in KiB, read from `/proc/self/statm`. Assumes 4 KiB pages.
+fn rss_kib() -> Option<u64> {
+    let statm = fs::read_to_string("/proc/self/statm").ok()?;
+    let pages = statm.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+    Some(pages * 4)
+}
+
+/// Least-squares slope of the samples.
+fn slope(samples: &[(f64, f64)]) -> f64 {
+    let n = samples.len() as f64;
+    let mean_x = samples.iter().map(|s| s.0).sum::<f64>() / n;
+    let mean_y = samples.iter().map(|s| s.1).sum::<f64>() / n;
+    let cov = samples
+        .iter()
+        .map(|s| (s.0 - mean_x) * (s.1 - mean_y))
+        .sum::<f64>();
+    let var = samples.iter().map(|s| (s.0 - mean_x).powi(2)).sum::<f64>();
+    if var == 0.0 {
+        0.0
+    } else {
+        cov / var
+    }
+}
+
+/// Checks that the RSS doesn't grow monotonically faster than `max_slope` KiB/s. The first sample
+/// is ignored as warm-up.
+fn check_rss(samples: &[(f64, f64)], max_slope: f64) -> Result<(), String> {
+    let samples = samples.get(1..).unwrap_or_default();
+    if samples.len() < 3 {
+        return Ok(());
+    }
+    let monotonic = samples.windows(2).all(|w| w[0].1 <= w[1].1);
+    let slope = slope(samples);
+    println!("RSS slope: {:.1} KiB/s", slope);
+    if monotonic && slope > max_slope {
+        Err(format!(
+            "RSS grew monotonically at {:.1} KiB/s (max {:.1})",
+            slope, max_slope
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn report(start: Instant) {
+    print!("[{:>6.1}s]", start.elapsed().as_secs_f64());
+    match rss_kib() {
+        Some(rss) => print!(" rss={}KiB", rss),
+        None => print!(" rss=?"),
+    }
+    println!(
+        " live: list={} set={} hazard={}",
+        SOL_VALUES.live(),
+        SET_VALUES.live(),
+        HP_VALUES.live()
+    );
+    #[cfg(feature = "metrics")]
+    println!("  {:?}", cs431_homework::metrics::snapshot());
+}
+
+/// Drops the structures and checks that all values are dropped.
+fn check_leaks(structures: Structures) -> Result<(), String> {
+    let guard = pin();
+    for cell in &structures.cells {
+        retire(cell.swap(ptr::null_mut(), Ordering::AcqRel) as *const Counted<'static, usize>);
+    }
+    collect();
+    drop(guard);
+    drop(structures);
+    for _ in 0..FLUSHES {
+        if SOL_VALUES.live() == 0 {
+            break;
+        }
+        pin().flush();
+    }
+
+    let mut leaks = Vec::new();
+    for (name, counter) in &[
+        ("list", &SOL_VALUES),
+        ("set", &SET_VALUES),
+        ("hazard", &HP_VALUES),
+    ] {
+        if counter.live() != 0 {
+            leaks.push(format!(
+                "{}: {} of {} values leaked",
+                name,
+                counter.live(),
+                counter.created()
+            ));
+        }
+    }
+    if leaks.is_empty() {
+        Ok(())
+    } else {
+        Err(leaks.join(", "))
+    }
+}
+
+fn run(config: &Config) -> Result<(), String> {
+    let structures = Arc::new(Structures::new());
+    // Set when the time is up or a worker fails.
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut rng = TestRng::new(config.seed);
+    let mut workers = Vec::new();
+    for tid in 0..config.threads {
+        let structures = structures.clone();
+        let rng = rng.fork();
+        let stop = stop.clone();
+        workers.push(thread::spawn(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| structures.work(tid, rng, &stop)));
+            if result.is_err() {
+                stop.store(true, Ordering::Relaxed);
+            }
+            result.is_ok()
+        }));
+    }
+
+    let pool = ThreadPool::new(config.threads);
+    let start = Instant::now();
+    let end = start + Duration::from_secs(config.secs);
+    let mut samples = Vec::new();
+    let mut result = Ok(());
+    while result.is_ok() && !stop.load(Ordering::Relaxed) && Instant::now() < end {
+        thread::sleep(Duration::from_secs(config.report_secs).min(end - Instant::now()));
+        result = check_pool(&pool);
+        report(start);
+        if let Some(rss) = rss_kib() {
+            samples.push((start.elapsed().as_secs_f64(), rss as f64));
+        }
+    }
+
+    stop.store(true, Ordering::Relaxed);
+    for (tid, worker) in workers.into_iter().enumerate() {
+        if !worker.join().unwrap() && result.is_ok() {
+            result = Err(format!("worker {} failed an invariant check", tid));
+        }
+    }
+    drop(pool);
+    result?;
+    check_rss(&samples, config.max_rss_slope)?;
+
+    let structures = Arc::try_unwrap(structures).unwrap();
+    check_leaks(structures)
+}
+
+fn main() {
+    let config = Config::from_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+    println!("{:?}", config);
+
+    match run(&config) {
+        Ok(()) => println!("soak test passed"),
+        Err(e) => {
+            eprintln!("soak test failed: {}", e);
+            process::exit(1);
+        }
+    }
+}
@@ -13,7 +13,7 @@ use lock::seqlock::{ReadGuard, SeqLock};
 
 mod base;
 
-use crate::map::ConcurrentMap;
+use crate::map::{ConcurrentMap, MapError};
 pub use base::Bst;
 use base::{AtomicRW, Cursor, Dir, Node, NodeInner};
 
@@ -69,8 +69,8 @@ where
     /// Deletes the given `key`.
     ///
     /// - Returns `Ok(value)` if `value` was deleted from `key`.
-    /// - Returns `Err(())` if `key` was vacant.
-    fn delete(&self, key: &K, guard: &Guard) -> Result<V, ()> {
+    /// - Returns `Err(MapError::NotFound)` if `key` was vacant.
+    fn remove(&self, key: &K, guard: &Guard) -> Result<V, MapError> {
         unimplemented!()
     }
 
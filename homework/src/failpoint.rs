@@ -0,0 +1,102 @@
+//! Fail points for deterministic interleaving tests.
+//!
+//! `failpoint!("name")` marks a window in the implementation where an interesting race can
+//! happen, e.g. between publishing a hazard pointer and validating it. A test can register an
+//! `Action` for the name to pause the thread that reaches the point, run some other operations,
+//! and then resume it.
+//!
+//! Fail points are compiled only with the `failpoints` feature. Without it, `failpoint!` expands
+//! to nothing.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let scenario = Scenario::setup();
+//! let barrier = Arc::new(Barrier::new(2));
+//! scenario.cfg("cache::get_or_insert_with::compute", Action::Pause(barrier.clone()));
+//!
+//! // spawn a thread that reaches the point
+//!
+//! barrier.wait(); // the thread is paused at the point
+//! // interfere
+//! barrier.wait(); // resume the thread
+//! ```
+
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::{Arc, Barrier, Mutex, MutexGuard};
+use std::thread;
+
+/// Action performed when a thread reaches a fail point.
+#[derive(Debug, Clone)]
+pub enum Action {
+    /// Do nothing.
+    Off,
+    /// Yield the thread the given number of times.
+    Yield(usize),
+    /// Wait on the barrier twice: once to notify that the point is reached, and once more to
+    /// resume. Only the first thread that reaches the point pauses. Then the action is turned
+    /// off.
+    Pause(Arc<Barrier>),
+}
+
+lazy_static! {
+    static ref ACTIONS: Mutex<HashMap<&'static str, Action>> = Mutex::new(HashMap::new());
+    static ref SCENARIO: Mutex<()> = Mutex::new(());
+}
+
+/// Configuration of fail points for a test.
+///
+/// Fail points are global, so tests that use them are serialized by `Scenario::setup`. All fail
+/// points are turned off when the scenario is dropped.
+#[derive(Debug)]
+pub struct Scenario {
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl Scenario {
+    /// Waits for the other scenarios to finish and starts a new one.
+    pub fn setup() -> Self {
+        // A failed test poisons the lock, but that doesn't affect the other tests.
+        let guard = SCENARIO.lock().unwrap_or_else(|e| e.into_inner());
+        ACTIONS.lock().unwrap().clear();
+        Self { _guard: guard }
+    }
+
+    /// Registers the action for the fail point `name`.
+    pub fn cfg(&self, name: &'static str, action: Action) {
+        let _ = ACTIONS.lock().unwrap().insert(name, action);
+    }
+}
+
+impl Drop for Scenario {
+    fn drop(&mut self) {
+        ACTIONS.lock().unwrap().clear();
+    }
+}
+
+/// Performs the action registered for `name`. Use `failpoint!` instead.
+#[doc(hidden)]
+pub fn eval(name: &'static str) {
+    let action = {
+        let mut actions = ACTIONS.lock().unwrap();
+        match actions.get(name) {
+            None => return,
+            Some(Action::Pause(_)) => actions.insert(name, Action::Off).unwrap(),
+            Some(action) => action.clone(),
+        }
+    };
+
+    match action {
+        Action::Off => (),
+        Action::Yield(n) => {
+            for _ in 0..n {
+                thread::yield_now();
+            }
+        }
+        Action::Pause(barrier) => {
+            let _ = barrier.wait();
+            let _ = barrier.wait();
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Striped counter.
+
+use core::sync::atomic::{AtomicIsize, AtomicUsize, Ordering};
+use crossbeam_utils::CachePadded;
+
+/// Number of cells of a `StripedCounter`.
+const STRIPES: usize = 16;
+
+/// The stripe of the next thread that uses a counter.
+static NEXT_STRIPE: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// The stripe of the current thread, assigned round robin on first use.
+    static STRIPE: usize = NEXT_STRIPE.fetch_add(1, Ordering::Relaxed) % STRIPES;
+}
+
+/// Counter split into cache-padded cells, so that threads updating it don't contend on one cache
+/// line. A thread updates the cell of its stripe, and a read sums all cells.
+///
+/// A cell may be negative if a thread takes away what another thread added. The sum is exact
+/// when there are no concurrent updates.
+#[derive(Debug, Default)]
+pub(crate) struct StripedCounter {
+    cells: [CachePadded<AtomicIsize>; STRIPES],
+}
+
+impl StripedCounter {
+    /// Number of cells.
+    pub(crate) const STRIPES: usize = STRIPES;
+
+    /// Adds `delta` to the cell of the current thread, and returns the new value of the cell.
+    pub(crate) fn add(&self, delta: isize) -> isize {
+        // The thread-local is gone while the thread exits. Any cell will do then.
+        let stripe = STRIPE.try_with(|stripe| *stripe).unwrap_or(0);
+        self.cells[stripe].fetch_add(delta, Ordering::AcqRel) + delta
+    }
+
+    /// Returns the sum of the cells.
+    pub(crate) fn sum(&self) -> isize {
+        self.cells
+            .iter()
+            .map(|cell| cell.load(Ordering::Acquire))
+            .sum()
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use super::*;
+    use crossbeam_utils::thread::scope;
+
+    // More threads than stripes add and take away, so that cells are shared and some go negative.
+    #[test]
+    fn sum_at_quiescence() {
+        const THREADS: usize = 2 * STRIPES + 1;
+        const STEPS: isize = 1000;
+
+        let counter = StripedCounter::default();
+        scope(|s| {
+            for t in 0..THREADS {
+                let counter = &counter;
+                let _ = s.spawn(move |_| {
+                    for _ in 0..STEPS {
+                        let _ = counter.add(if t % 2 == 0 { 2 } else { -1 });
+                    }
+                });
+            }
+        })
+        .unwrap();
+        let (adders, takers) = ((THREADS + 1) / 2, THREADS / 2);
+        assert_eq!(
+            counter.sum(),
+            STEPS * (2 * adders as isize - takers as isize)
+        );
+    }
+}
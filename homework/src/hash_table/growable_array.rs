@@ -2,11 +2,18 @@
 
 use core::fmt::Debug;
 use core::marker::PhantomData;
-use core::mem;
+use alloc::alloc::{alloc_zeroed, handle_alloc_error, Layout};
+use alloc::boxed::Box;
 use core::ops::{Deref, DerefMut};
-use core::sync::atomic::{AtomicUsize, Ordering};
-use std::ptr::null;
-use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Pointer, Shared, pin};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use crossbeam_epoch::{Atomic, Guard, Owned, Pointer, Shared};
+
+// The root is a loom atomic only with `loom-growable-array`, not with `check-loom` alone, so that
+// the other tests can use `GrowableArray` outside of a loom model.
+#[cfg(not(feature = "loom-growable-array"))]
+use core::sync::atomic::AtomicUsize as RootAtomic;
+#[cfg(feature = "loom-growable-array")]
+use loom::sync::atomic::AtomicUsize as RootAtomic;
 
 /// Growable array of `Atomic<T>`.
 ///
@@ -19,7 +26,7 @@ use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Pointer, Shared, pin};
 ///
 /// Suppose `SEGMENT_LOGSIZE = 3` (segment size 8).
 ///
-/// When a new `GrowableArray` is created, `root` is initialized with `Atomic::null()`.
+/// When a new `GrowableArray` is created, `root` is initialized with a null pointer.
 ///
 /// ```text
 ///                          +----+
@@ -133,7 +140,11 @@ use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Pointer, Shared, pin};
 ///
 #[derive(Debug)]
 pub struct GrowableArray<T> {
-    root: Atomic<Segment>,
+    /// The root segment, tagged with the height of the tree. It's a loom atomic under
+    /// `loom-growable-array`, so that loom explores the races on growing and lowering the tree.
+    root: RootAtomic,
+    /// Whether a thread is in `prune`. Only one thread prunes at a time.
+    pruning: AtomicBool,
     _marker: PhantomData<T>,
 }
 
@@ -145,9 +156,16 @@ struct Segment {
 }
 
 impl Segment {
-    fn new() -> Self {
-        Self {
-            inner: unsafe { mem::zeroed() },
+    /// Allocates a segment of null pointers. It's zeroed on the heap, since a segment is too big
+    /// to build on the small stacks of loom threads.
+    fn new() -> Owned<Self> {
+        let layout = Layout::new::<Self>();
+        unsafe {
+            let segment = alloc_zeroed(layout) as *mut Self;
+            if segment.is_null() {
+                handle_alloc_error(layout);
+            }
+            Owned::from(Box::from_raw(segment))
         }
     }
 }
@@ -188,8 +206,7 @@ impl<T> Drop for GrowableArray<T> {
     /// Deallocate segments, but not the individual elements.
     fn drop(&mut self) {
         unsafe {
-            let guard = pin();
-            let root = self.root.load(Ordering::Acquire, &guard);
+            let root = Shared::<Segment>::from_usize(self.root.load(Ordering::Acquire));
             drop_segments_recursively(root, root.tag());
         }
     }
@@ -211,7 +228,8 @@ impl<T> GrowableArray<T> {
     /// Create a new growable array.
     pub fn new() -> Self {
         Self {
-            root: Atomic::null(),
+            root: RootAtomic::new(0),
+            pruning: AtomicBool::new(false),
             _marker: PhantomData,
         }
     }
@@ -221,20 +239,22 @@ impl<T> GrowableArray<T> {
     pub fn get(&self, mut index: usize, guard: &Guard) -> &Atomic<T> {
         let (root, root_height) =
             loop {
-                let root = self.root.load(Ordering::Acquire, guard);
+                let root = self.load_root(guard);
                 let root_height = root.tag();
 
                 if !root.is_null() && index >> (root_height * SEGMENT_LOGSIZE) == 0 {
                     break (root, root_height);
                 }
 
-                let new_top_segment = Owned::new(Segment::new());
+                let new_top_segment = Segment::new();
                 (*new_top_segment).inner[0].store(root.into_usize(), Ordering::Release);
 
                 let new_root = new_top_segment;
-                let new_root = new_root.with_tag(root_height + 1);
+                let new_root = new_root.with_tag(root_height + 1).into_shared(guard);
 
-                let _ = self.root.compare_exchange(root, new_root, Ordering::Release, Ordering::Relaxed, guard);
+                if !self.cas_root(root, new_root) {
+                    drop(unsafe { new_root.into_owned() });
+                }
             };
 
         let ret = unsafe {
@@ -258,7 +278,7 @@ impl<T> GrowableArray<T> {
                         break Shared::<Segment>::from_usize(ns);
                     }
 
-                    let new_seg = Owned::new(Segment::new()).into_usize();
+                    let new_seg = Segment::new().into_usize();
                     match next_atomic.compare_exchange(0, new_seg, Ordering::Release, Ordering::Relaxed) {
                         Ok(_) => {}
                         Err(_) => {
@@ -272,4 +292,267 @@ impl<T> GrowableArray<T> {
 
         ret
     }
+
+    /// Returns the reference to the `Atomic` pointer at `index`, or `None` if a segment on the way
+    /// is not allocated. Unlike `get`, it never allocates.
+    pub fn try_get<'g>(&'g self, index: usize, guard: &'g Guard) -> Option<&'g Atomic<T>> {
+        let root = self.load_root(guard);
+        let height = root.tag();
+        if height * SEGMENT_LOGSIZE < usize::BITS as usize
+            && index >> (height * SEGMENT_LOGSIZE) != 0
+        {
+            return None;
+        }
+
+        let mut segment = unsafe { root.as_ref() }?;
+        for level in (1..height).rev() {
+            let child = segment[index_nth_part(index, level)].load(Ordering::Acquire);
+            segment = unsafe { Shared::<Segment>::from_usize(child).as_ref() }?;
+        }
+        let slot = &segment[index_nth_part(index, 0)];
+        Some(unsafe { &*(slot as *const AtomicUsize as *const Atomic<T>) })
+    }
+
+    /// Returns the height of the tree of segments. It is 0 before the first `get`.
+    pub fn height(&self) -> usize {
+        unsafe { Shared::<Segment>::from_usize(self.root.load(Ordering::Acquire)) }.tag()
+    }
+
+    /// Returns the number of slots addressable without growing the tree, saturating at
+    /// `usize::MAX`.
+    pub fn capacity(&self) -> usize {
+        match self.height() {
+            0 => 0,
+            height => 1usize
+                .checked_shl((height * SEGMENT_LOGSIZE) as u32)
+                .unwrap_or(usize::MAX),
+        }
+    }
+
+    /// Returns the number of non-null slots. Slots stored concurrently may or may not be counted.
+    pub fn occupied(&self, guard: &Guard) -> usize {
+        let root = self.load_root(guard);
+        match unsafe { root.as_ref() } {
+            Some(root_ref) => Self::occupied_in(root_ref, root.tag()),
+            None => 0,
+        }
+    }
+
+    /// Returns an iterator over the non-null slots and their indices, in the order of the indices.
+    /// Missing segments are skipped, not allocated. Slots stored concurrently may or may not be
+    /// visited.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> impl Iterator<Item = (usize, Shared<'g, T>)> {
+        let root = self.load_root(guard);
+        Iter {
+            root,
+            height: root.tag(),
+            next: if root.is_null() { None } else { Some(0) },
+            _marker: PhantomData,
+        }
+    }
+
+    /// Frees the segments that have no non-null slot under them, and lowers the height while only
+    /// the first child of the root is left. The freed segments are destroyed after the current
+    /// guards are unpinned, so concurrent `get`s are safe. The slots under non-empty segments are
+    /// not affected.
+    ///
+    /// Only one thread prunes at a time: if another thread is in `prune`, this returns without
+    /// pruning.
+    ///
+    /// # Safety
+    ///
+    /// Concurrently with `prune`, a slot may be stored to only while it's non-null, e.g. to
+    /// replace or clear its pointer with `compare_exchange`. A null slot may be in a segment that
+    /// is detached, and then a store to it is lost and the stored pointer is never seen by the
+    /// array again.
+    pub unsafe fn prune(&self, guard: &Guard) {
+        if self.pruning.swap(true, Ordering::Acquire) {
+            return;
+        }
+        self.prune_alone(guard);
+        self.pruning.store(false, Ordering::Release);
+    }
+
+    /// Prunes like `prune`, while no other thread is pruning. Only a pruner detaches segments, so
+    /// the child segments it sees stay attached until it detaches them.
+    unsafe fn prune_alone(&self, guard: &Guard) {
+        let root = self.load_root(guard);
+        if let Some(root_ref) = root.as_ref() {
+            let _ = Self::prune_in(root_ref, root.tag(), guard);
+        }
+
+        loop {
+            let root = self.load_root(guard);
+            let root_ref = match root.as_ref() {
+                Some(root_ref) => root_ref,
+                None => return,
+            };
+            let height = root.tag();
+            let new_root = if height == 1 {
+                if !Self::prune_in(root_ref, height, guard) {
+                    return;
+                }
+                Shared::null()
+            } else {
+                let child =
+                    |slot: &AtomicUsize| Shared::<Segment>::from_usize(slot.load(Ordering::Acquire));
+                if root_ref[1..].iter().any(|slot| !child(slot).is_null()) {
+                    return;
+                }
+                let first = child(&root_ref[0]);
+                if first.is_null() {
+                    Shared::null()
+                } else {
+                    first.with_tag(height - 1)
+                }
+            };
+            failpoint!("growable_array::prune::lower");
+            if !self.cas_root(root, new_root) {
+                return;
+            }
+            // The new root is still the first child of the old root, so it's not freed with it.
+            debug_assert!(
+                new_root.is_null()
+                    || Shared::<Segment>::from_usize(root_ref[0].load(Ordering::Acquire))
+                        .with_tag(0)
+                        == new_root.with_tag(0)
+            );
+
+            // A `get` holding the old root may still allocate segments under it. They are freed
+            // with the old root, except the first child if it's the new root.
+            let root = root.into_usize();
+            let detached = if new_root.is_null() { 0 } else { 1 };
+            guard.defer_unchecked(move || {
+                let root = Shared::<Segment>::from_usize(root);
+                if height > 1 {
+                    for child in &root.deref()[detached..] {
+                        drop_segments_recursively(
+                            Shared::from_usize(child.load(Ordering::Acquire)),
+                            height - 1,
+                        );
+                    }
+                }
+                drop(root.into_owned());
+            });
+        }
+    }
+
+    /// Loads the root. It's protected by `guard`.
+    fn load_root<'g>(&self, _: &'g Guard) -> Shared<'g, Segment> {
+        unsafe { Shared::from_usize(self.root.load(Ordering::Acquire)) }
+    }
+
+    /// Replaces the root with `new` if it's `current`. Returns `true` on success.
+    fn cas_root(&self, current: Shared<'_, Segment>, new: Shared<'_, Segment>) -> bool {
+        self.root
+            .compare_exchange(
+                current.into_usize(),
+                new.into_usize(),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok()
+    }
+
+    /// Detaches and frees the empty children of `segment` of `height`. Returns `true` if no
+    /// non-null slot is left under `segment`.
+    fn prune_in(segment: &Segment, height: usize, guard: &Guard) -> bool {
+        if height == 1 {
+            return segment.iter().all(|slot| {
+                unsafe { Shared::<T>::from_usize(slot.load(Ordering::Acquire)) }.is_null()
+            });
+        }
+
+        let mut empty = true;
+        for slot in segment.iter() {
+            let child = slot.load(Ordering::Acquire);
+            let child_ref = match unsafe { Shared::<Segment>::from_usize(child).as_ref() } {
+                Some(child_ref) => child_ref,
+                None => continue,
+            };
+            if !Self::prune_in(child_ref, height - 1, guard)
+                || slot
+                    .compare_exchange(child, 0, Ordering::AcqRel, Ordering::Relaxed)
+                    .is_err()
+            {
+                empty = false;
+                continue;
+            }
+            // A `get` holding the child may still allocate segments under it.
+            unsafe {
+                guard.defer_unchecked(move || {
+                    drop_segments_recursively(Shared::from_usize(child), height - 1)
+                });
+            }
+        }
+        empty
+    }
+
+    /// Returns the number of non-null slots under `segment` of `height`.
+    fn occupied_in(segment: &Segment, height: usize) -> usize {
+        if height == 1 {
+            return segment
+                .iter()
+                .filter(|slot| {
+                    !unsafe { Shared::<T>::from_usize(slot.load(Ordering::Acquire)) }.is_null()
+                })
+                .count();
+        }
+        segment
+            .iter()
+            .filter_map(|child| unsafe {
+                Shared::<Segment>::from_usize(child.load(Ordering::Acquire)).as_ref()
+            })
+            .map(|child| Self::occupied_in(child, height - 1))
+            .sum()
+    }
+}
+
+/// Iterator over the non-null slots of a `GrowableArray`. See `GrowableArray::iter`.
+///
+/// It walks the tree depth first, from the root to the slot of `next` each time, and jumps over
+/// the indices under a null child.
+struct Iter<'g, T> {
+    root: Shared<'g, Segment>,
+    height: usize,
+    /// The index to visit next, or `None` if all slots are visited.
+    next: Option<usize>,
+    _marker: PhantomData<&'g T>,
+}
+
+impl<'g, T> Iterator for Iter<'g, T> {
+    type Item = (usize, Shared<'g, T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        'next: loop {
+            let index = self.next?;
+            if self.height * SEGMENT_LOGSIZE < usize::BITS as usize
+                && index >> (self.height * SEGMENT_LOGSIZE) != 0
+            {
+                self.next = None;
+                return None;
+            }
+
+            let mut segment = unsafe { self.root.deref() };
+            for level in (1..self.height).rev() {
+                let child = segment[index_nth_part(index, level)].load(Ordering::Acquire);
+                match unsafe { Shared::<Segment>::from_usize(child).as_ref() } {
+                    Some(child) => segment = child,
+                    None => {
+                        // Skip the indices under the child.
+                        let last = index | ((1 << (level * SEGMENT_LOGSIZE)) - 1);
+                        self.next = last.checked_add(1);
+                        continue 'next;
+                    }
+                }
+            }
+
+            self.next = index.checked_add(1);
+            let slot = segment[index_nth_part(index, 0)].load(Ordering::Acquire);
+            let slot = unsafe { Shared::<T>::from_usize(slot) };
+            if !slot.is_null() {
+                return Some((index, slot));
+            }
+        }
+    }
 }
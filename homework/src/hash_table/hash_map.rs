@@ -0,0 +1,339 @@
+//! Split-ordered hash map with generic keys.
+
+use core::hash::{BuildHasher, Hash, Hasher};
+use core::ptr;
+use core::sync::atomic::Ordering;
+use crossbeam_epoch::{unprotected, Atomic, Guard, Owned, Shared};
+use std::collections::hash_map::RandomState;
+
+use super::split_ordered_list::SplitOrderedList;
+use crate::map::{MapError, NonblockingMap};
+
+/// The mark of a logically deleted entry, in the `next` pointer of the entry.
+const MARK: usize = 1;
+
+/// The mark of a sealed chain, in the `head` of the chain. Only an empty chain is sealed.
+const SEALED: usize = 1;
+
+#[derive(Debug)]
+struct Entry<K, V> {
+    key: K,
+    value: V,
+    /// The next entry, marked if this entry is logically deleted.
+    next: Atomic<Entry<K, V>>,
+}
+
+/// Whether an entry is found, the link to it, and the entry. See `Chain::find`.
+type Position<'g, K, V> = (bool, &'g Atomic<Entry<K, V>>, Shared<'g, Entry<K, V>>);
+
+/// Entries whose keys have the same hash, in a Harris-Michael list in insertion order.
+///
+/// New entries are appended at the tail, so racing inserts of the same key meet at the same link
+/// and only one of them succeeds. An empty chain can be sealed so that it can be removed from the
+/// map: inserting to a sealed chain fails, and the insert looks up the chain again.
+#[derive(Debug)]
+struct Chain<K, V> {
+    head: Atomic<Entry<K, V>>,
+}
+
+impl<K, V> Default for Chain<K, V> {
+    fn default() -> Self {
+        Self {
+            head: Atomic::null(),
+        }
+    }
+}
+
+impl<K: Eq, V> Chain<K, V> {
+    /// Finds the entry of `key`, unlinking the deleted entries on the way. If not found, the link
+    /// is the one at the tail. Returns `None` if the chain is sealed.
+    fn find<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<Position<'g, K, V>> {
+        'retry: loop {
+            let mut prev = &self.head;
+            let mut curr = prev.load(Ordering::Acquire, guard);
+            if curr.tag() == SEALED {
+                return None;
+            }
+            loop {
+                let curr_ref = match unsafe { curr.as_ref() } {
+                    Some(curr_ref) => curr_ref,
+                    None => return Some((false, prev, curr)),
+                };
+                let next = curr_ref.next.load(Ordering::Acquire, guard);
+
+                if next.tag() == MARK {
+                    let next = next.with_tag(0);
+                    if prev
+                        .compare_exchange(curr, next, Ordering::Release, Ordering::Relaxed, guard)
+                        .is_err()
+                    {
+                        continue 'retry;
+                    }
+                    unsafe { guard.defer_destroy(curr) };
+                    curr = next;
+                    continue;
+                }
+
+                if curr_ref.key == *key {
+                    return Some((true, prev, curr));
+                }
+                prev = &curr_ref.next;
+                curr = next;
+            }
+        }
+    }
+
+    fn lookup<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g Entry<K, V>> {
+        match self.find(key, guard)? {
+            (true, _, curr) => Some(unsafe { curr.deref() }),
+            (false, _, _) => None,
+        }
+    }
+
+    /// Appends `new` if its key is not in the chain. Otherwise, returns `new` back with the entry
+    /// of the key, or with `None` if the chain is sealed.
+    #[allow(clippy::type_complexity)]
+    fn insert<'g>(
+        &'g self,
+        mut new: Owned<Entry<K, V>>,
+        guard: &'g Guard,
+    ) -> Result<&'g Entry<K, V>, (Owned<Entry<K, V>>, Option<&'g Entry<K, V>>)> {
+        loop {
+            let (found, prev, curr) = match self.find(&new.key, guard) {
+                Some(found) => found,
+                None => return Err((new, None)),
+            };
+            if found {
+                return Err((new, Some(unsafe { curr.deref() })));
+            }
+            match prev.compare_exchange(curr, new, Ordering::Release, Ordering::Relaxed, guard) {
+                Ok(new) => return Ok(unsafe { new.deref() }),
+                Err(e) => new = e.new,
+            }
+        }
+    }
+
+    fn delete<'g>(&'g self, key: &K, guard: &'g Guard) -> Option<&'g V> {
+        loop {
+            let (found, prev, curr) = self.find(key, guard)?;
+            if !found {
+                return None;
+            }
+            let curr_ref = unsafe { curr.deref() };
+            let next = curr_ref.next.fetch_or(MARK, Ordering::AcqRel, guard);
+            if next.tag() == MARK {
+                // Deleted by another thread. `find` unlinks it.
+                continue;
+            }
+            if prev
+                .compare_exchange(curr, next, Ordering::Release, Ordering::Relaxed, guard)
+                .is_ok()
+            {
+                unsafe { guard.defer_destroy(curr) };
+            } else {
+                // Let `find` unlink it.
+                let _ = self.find(key, guard);
+            }
+            return Some(&curr_ref.value);
+        }
+    }
+
+    /// Seals the chain if it's empty. Returns `true` if the chain is sealed.
+    fn seal(&self, guard: &Guard) -> bool {
+        loop {
+            let head = self.head.load(Ordering::Acquire, guard);
+            if head.tag() == SEALED {
+                return true;
+            }
+            let head_ref = match unsafe { head.as_ref() } {
+                Some(head_ref) => head_ref,
+                None => {
+                    if self
+                        .head
+                        .compare_exchange(
+                            head,
+                            head.with_tag(SEALED),
+                            Ordering::AcqRel,
+                            Ordering::Relaxed,
+                            guard,
+                        )
+                        .is_ok()
+                    {
+                        return true;
+                    }
+                    continue;
+                }
+            };
+            let next = head_ref.next.load(Ordering::Acquire, guard);
+            if next.tag() != MARK {
+                return false;
+            }
+            // Unlink the deleted head, as `find` does.
+            if self
+                .head
+                .compare_exchange(
+                    head,
+                    next.with_tag(0),
+                    Ordering::Release,
+                    Ordering::Relaxed,
+                    guard,
+                )
+                .is_ok()
+            {
+                unsafe { guard.defer_destroy(head) };
+            }
+        }
+    }
+}
+
+impl<K, V> Drop for Chain<K, V> {
+    fn drop(&mut self) {
+        unsafe {
+            let guard = unprotected();
+            let mut curr = self.head.load(Ordering::Relaxed, guard).with_tag(0);
+            while !curr.is_null() {
+                let next = curr.deref().next.load(Ordering::Relaxed, guard).with_tag(0);
+                drop(curr.into_owned());
+                curr = next;
+            }
+        }
+    }
+}
+
+/// Lock-free hash map from any `K: Hash + Eq` to `V`, on top of `SplitOrderedList`.
+///
+/// Keys are hashed with `S` into the keys of the list, and the entries of the keys of the same
+/// hash are kept in a chain, which is the value of the hash in the list. Lookups compare the keys
+/// in the chain to resolve collisions. A chain is removed from the list when its last entry is
+/// deleted.
+#[derive(Debug)]
+pub struct SplitOrderedHashMap<K, V, S = RandomState> {
+    list: SplitOrderedList<Chain<K, V>>,
+    hasher: S,
+}
+
+impl<K, V, S: Default> Default for SplitOrderedHashMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, V> SplitOrderedHashMap<K, V> {
+    /// Creates a new hash map with the default hasher.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<K, V, S> SplitOrderedHashMap<K, V, S> {
+    /// Creates a new hash map that hashes the keys with `hasher`.
+    pub fn with_hasher(hasher: S) -> Self {
+        Self {
+            list: SplitOrderedList::new(),
+            hasher,
+        }
+    }
+
+    /// Returns the hasher of the map.
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> SplitOrderedHashMap<K, V, S> {
+    /// Returns the key of the chain of `key` in the list.
+    fn hash(&self, key: &K) -> usize {
+        let mut hasher = self.hasher.build_hasher();
+        key.hash(&mut hasher);
+        hasher.finish() as usize
+    }
+
+    /// Returns the chain of `hash`, inserting an empty one if there is none.
+    fn chain<'g>(&'g self, hash: usize, guard: &'g Guard) -> &'g Chain<K, V> {
+        self.list.get_or_insert_with(&hash, Chain::default, guard)
+    }
+
+    /// Removes the sealed `chain` of `hash` from the list, unless another thread already did.
+    ///
+    /// Inserts call this too when they find the chain sealed, instead of waiting for the remover
+    /// that sealed it.
+    fn remove_chain(&self, hash: usize, chain: &Chain<K, V>, guard: &Guard) {
+        // Another thread may have removed it already and inserted a new chain.
+        let _ = self.list.delete_if(&hash, |c| ptr::eq(c, chain), guard);
+    }
+}
+
+impl<K: Hash + Eq + Clone, V, S: BuildHasher> NonblockingMap<K, V>
+    for SplitOrderedHashMap<K, V, S>
+{
+    fn lookup<'a>(&'a self, key: &K, guard: &'a Guard) -> Option<&'a V> {
+        let chain = self.list.lookup(&self.hash(key), guard)?;
+        chain.lookup(key, guard).map(|entry| &entry.value)
+    }
+
+    fn insert(&self, key: &K, value: V, guard: &Guard) -> Result<(), V> {
+        let hash = self.hash(key);
+        let mut new = Owned::new(Entry {
+            key: key.clone(),
+            value,
+            next: Atomic::null(),
+        });
+        loop {
+            let chain = self.chain(hash, guard);
+            match chain.insert(new, guard) {
+                Ok(_) => return Ok(()),
+                Err((entry, Some(_))) => return Err(entry.into_box().value),
+                // The chain is being removed. Help remove it and retry with a new one.
+                Err((entry, None)) => {
+                    self.remove_chain(hash, chain, guard);
+                    new = entry;
+                }
+            }
+        }
+    }
+
+    fn get_or_insert_with<'a, F: FnOnce() -> V>(
+        &'a self,
+        key: &K,
+        f: F,
+        guard: &'a Guard,
+    ) -> &'a V {
+        let hash = self.hash(key);
+        let mut f = Some(f);
+        // Created on the first miss, and reused if the chain is removed.
+        let mut new = None;
+        loop {
+            let chain = self.chain(hash, guard);
+            if let Some(entry) = chain.lookup(key, guard) {
+                return &entry.value;
+            }
+            let entry = new.take().unwrap_or_else(|| {
+                Owned::new(Entry {
+                    key: key.clone(),
+                    value: (f.take().unwrap())(),
+                    next: Atomic::null(),
+                })
+            });
+            match chain.insert(entry, guard) {
+                Ok(entry) => return &entry.value,
+                // Inserted by another thread. Dropping `entry` drops the value of `f`.
+                Err((_, Some(entry))) => return &entry.value,
+                Err((entry, None)) => {
+                    self.remove_chain(hash, chain, guard);
+                    new = Some(entry);
+                }
+            }
+        }
+    }
+
+    fn remove<'a>(&'a self, key: &K, guard: &'a Guard) -> Result<&'a V, MapError> {
+        let hash = self.hash(key);
+        let chain = self.list.lookup(&hash, guard).ok_or(MapError::NotFound)?;
+        let value = chain.delete(key, guard).ok_or(MapError::NotFound)?;
+        if chain.seal(guard) {
+            failpoint!("split_ordered_hash_map::remove::seal");
+            self.remove_chain(hash, chain, guard);
+        }
+        Ok(value)
+    }
+}
@@ -0,0 +1,211 @@
+//! Split-order encoding of keys and bucket indices.
+//!
+//! The nodes of `SplitOrderedList` are sorted by the bit-reversed key, so that the nodes of a
+//! bucket stay contiguous when the bucket is split. A regular key `k` is encoded as `reverse(k)`
+//! and the sentinel of bucket `i` as `reverse(i)`, each with a flag that tells them apart and sorts
+//! the sentinel right before the regular key with the same bits. Keeping the flag out of the
+//! reversed word leaves every bit to the key, so every word is a valid key.
+//!
+//! The functions are defined for any unsigned word type by `key_encoding!`, so that the tests can
+//! check the 32-bit and 64-bit math on any target.
+
+macro_rules! key_encoding {
+    ($word:ty) => {
+        /// Encoded key of a node. Sorted by `bits`, and then sentinels before regular keys.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct SplitKey {
+            /// The bit-reversed key or bucket index.
+            bits: $word,
+            /// `false` for a sentinel.
+            regular: bool,
+        }
+
+        /// Encodes a regular key.
+        #[inline]
+        pub const fn regular(key: $word) -> SplitKey {
+            SplitKey {
+                bits: key.reverse_bits(),
+                regular: true,
+            }
+        }
+
+        /// Encodes the sentinel key of the bucket.
+        #[inline]
+        pub const fn sentinel(index: $word) -> SplitKey {
+            SplitKey {
+                bits: index.reverse_bits(),
+                regular: false,
+            }
+        }
+
+        /// Returns `true` if `encoded` is a sentinel key.
+        #[allow(dead_code)]
+        #[inline]
+        pub const fn is_sentinel(encoded: SplitKey) -> bool {
+            !encoded.regular
+        }
+
+        /// Decodes a regular or sentinel key into the key or bucket index.
+        #[allow(dead_code)]
+        #[inline]
+        pub const fn decode(encoded: SplitKey) -> $word {
+            encoded.bits.reverse_bits()
+        }
+
+        /// Decodes a regular key into the key, or returns `None` for a sentinel key.
+        #[inline]
+        pub const fn decode_regular(encoded: SplitKey) -> Option<$word> {
+            if encoded.regular {
+                Some(encoded.bits.reverse_bits())
+            } else {
+                None
+            }
+        }
+
+        /// Returns the smallest encoded key after `encoded`, or `None` if it's the largest.
+        #[inline]
+        pub const fn successor(encoded: SplitKey) -> Option<SplitKey> {
+            if !encoded.regular {
+                Some(SplitKey {
+                    bits: encoded.bits,
+                    regular: true,
+                })
+            } else if encoded.bits == <$word>::MAX {
+                None
+            } else {
+                Some(SplitKey {
+                    bits: encoded.bits + 1,
+                    regular: false,
+                })
+            }
+        }
+
+        /// Returns the bucket that bucket `index` is split from, i.e. `index` without its top set
+        /// bit. The parent of bucket 0 is itself.
+        #[inline]
+        pub const fn parent(index: $word) -> $word {
+            if index == 0 {
+                0
+            } else {
+                index & !(1 << (<$word>::BITS - 1 - index.leading_zeros()))
+            }
+        }
+    };
+}
+
+key_encoding!(usize);
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    macro_rules! encoding_tests {
+        ($name:ident, $word:ident) => {
+            mod $name {
+                key_encoding!($word);
+
+                #[test]
+                fn full_range() {
+                    // Every word is a key, including those with the top bit set.
+                    let top = 1 << ($word::BITS - 1);
+                    let keys = [0, 1, top - 1, top, top + 1, $word::MAX - 1, $word::MAX];
+                    for &a in &keys {
+                        assert_eq!(decode(regular(a)), a);
+                        for &b in &keys {
+                            assert_eq!(regular(a) == regular(b), a == b);
+                        }
+                    }
+                    assert_eq!(regular($word::MAX).bits, $word::MAX);
+                    assert_eq!(successor(regular($word::MAX)), None);
+                }
+
+                #[test]
+                fn round_trip() {
+                    // Boundary keys, and keys spread over the range with every bit pattern.
+                    let top = 1 << ($word::BITS - 1);
+                    let step = $word::MAX / 4099;
+                    let keys = (0..=4099).map(|i| i * step).chain([
+                        1,
+                        2,
+                        top - 1,
+                        top,
+                        top + 1,
+                        $word::MAX - 1,
+                        $word::MAX,
+                    ]);
+                    for key in keys {
+                        assert_eq!(decode_regular(regular(key)), Some(key));
+                        assert_eq!(decode_regular(sentinel(key)), None);
+                        assert_eq!(decode(regular(key)), key);
+                        assert_eq!(decode(sentinel(key)), key);
+                    }
+                }
+
+                #[test]
+                fn sentinel_adjacency() {
+                    let top = 1 << ($word::BITS - 1);
+                    let keys = [
+                        0,
+                        1,
+                        2,
+                        3,
+                        top - 1,
+                        top,
+                        top + 1,
+                        $word::MAX - 1,
+                        $word::MAX,
+                    ];
+                    for key in keys {
+                        assert!(!is_sentinel(regular(key)));
+                        assert!(is_sentinel(sentinel(key)));
+                        assert_eq!(decode(regular(key)), key);
+                        assert_eq!(decode(sentinel(key)), key);
+                        // A key comes right after the sentinel of the bucket with the same index.
+                        assert!(sentinel(key) < regular(key));
+                        assert_eq!(successor(sentinel(key)), Some(regular(key)));
+                    }
+                    assert!(regular(0) < sentinel(1));
+                    assert_eq!(successor(regular(0)).unwrap().bits, 1);
+                    assert_eq!(sentinel(1).bits, top);
+                }
+
+                #[test]
+                fn split_order() {
+                    // Bucket `i` of a table with `size` buckets contains the keys `k` with
+                    // `k % size == i`. They come after the sentinel of `i` and before the next
+                    // sentinel of a table twice as large.
+                    let size: $word = 8;
+                    let top: $word = 1 << ($word::BITS - 1);
+                    let keys = [0, 5, 13, 21, 1 << 20, top - 1, top, top + 7, $word::MAX - 2];
+                    for key in keys.iter().copied().chain(Some($word::MAX)) {
+                        let index = key % size;
+                        assert!(sentinel(index) < regular(key));
+                        let next = index + size;
+                        if key % (2 * size) == index {
+                            assert!(regular(key) < sentinel(next));
+                        } else {
+                            assert!(sentinel(next) < regular(key));
+                        }
+                    }
+                }
+
+                #[test]
+                fn parent_bucket() {
+                    assert_eq!(parent(0), 0);
+                    assert_eq!(parent(1), 0);
+                    assert_eq!(parent(6), 2);
+                    assert_eq!(parent($word::MAX >> 1), $word::MAX >> 2);
+                    assert_eq!(parent(1 << ($word::BITS - 1)), 0);
+                    assert_eq!(parent($word::MAX), $word::MAX >> 1);
+                    for index in 1..1024 {
+                        let parent = parent(index);
+                        assert!(parent < index);
+                        assert!(sentinel(parent) < sentinel(index));
+                    }
+                }
+            }
+        };
+    }
+
+    // The encoding on 32-bit and 64-bit targets.
+    encoding_tests!(word32, u32);
+    encoding_tests!(word64, u64);
+}
@@ -1,7 +1,17 @@
 //! Lock-free hash table Based on https://dl.acm.org/doi/abs/10.1145/1147954.1147958
 
+#[cfg(feature = "std")]
+mod counter;
 mod growable_array;
+#[cfg(feature = "std")]
+mod hash_map;
+#[cfg(feature = "std")]
+mod key_encoding;
+#[cfg(feature = "std")]
 mod split_ordered_list;
 
 pub use growable_array::GrowableArray;
-pub use split_ordered_list::SplitOrderedList;
+#[cfg(feature = "std")]
+pub use hash_map::SplitOrderedHashMap;
+#[cfg(feature = "std")]
+pub use split_ordered_list::{DeleteError, SplitOrderedList};
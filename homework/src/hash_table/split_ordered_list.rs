@@ -1,27 +1,178 @@
 //! Split-ordered linked list.
 
-use core::mem;
+use core::cmp;
+use core::fmt;
+use core::iter::FromIterator;
+use core::mem::{ManuallyDrop, MaybeUninit};
+use core::ops::Range;
+use core::ptr;
 use core::sync::atomic::{AtomicUsize, Ordering};
+use std::error::Error;
 use std::ptr::null;
-use crossbeam_epoch::{Atomic, CompareExchangeError, Guard, Owned, Pointer, Shared};
+use crossbeam_epoch::{pin, CompareExchangeError, Guard, Owned, Pointer, Shared};
 use lockfree::list::{Cursor, List, Node};
 
+use super::counter::StripedCounter;
 use super::growable_array::GrowableArray;
-use crate::map::NonblockingMap;
+use super::key_encoding::{self, SplitKey};
+use crate::map::{MapEntry, MapError, NonblockingMap, RawEntry};
 
-/// Lock-free map from `usize` in range [0, 2^63-1] to `V`.
+/// Number of entries that `Extend` inserts with one guard.
+const EXTEND_CHUNK: usize = 1 << 10;
+
+/// Key of a node of `SplitOrderedList::list`, which also holds the value of the entry inline. The
+/// nodes are ordered by the split-order key only, and the value of a node is `()`.
+///
+/// A bucket sentinel has no value, and it's told apart by its split-order key. The value is kept
+/// here rather than in the value of the node so that dropping a node drops the value only for an
+/// entry, since `List` drops the key and the value of a node separately. The value of a sentinel
+/// is never read or dropped.
+///
+/// An entry is deleted when its node is marked in the list. `update` replaces the whole node.
+struct Slot<V> {
+    key: SplitKey,
+    /// Initialized if `key` is regular.
+    value: MaybeUninit<V>,
+}
+
+impl<V> Slot<V> {
+    /// Creates the node of an entry.
+    fn node(key: usize, value: V) -> Node<Self, ()> {
+        let slot = Self {
+            key: key_encoding::regular(key),
+            value: MaybeUninit::new(value),
+        };
+        Node::new(slot, ())
+    }
+
+    /// Creates the sentinel node of a bucket.
+    fn sentinel(index: usize) -> Node<Self, ()> {
+        let slot = Self {
+            key: key_encoding::sentinel(index),
+            value: MaybeUninit::uninit(),
+        };
+        Node::new(slot, ())
+    }
+
+    /// Creates a slot to search the list for `key`. It has no value even if `key` is regular, so
+    /// it's never dropped.
+    fn search(key: SplitKey) -> ManuallyDrop<Self> {
+        ManuallyDrop::new(Self {
+            key,
+            value: MaybeUninit::uninit(),
+        })
+    }
+
+    /// Returns `true` if the node is a bucket sentinel.
+    fn is_sentinel(&self) -> bool {
+        key_encoding::is_sentinel(self.key)
+    }
+
+    /// Returns the key and the value of an entry, or `None` for a sentinel.
+    fn entry(&self) -> Option<(usize, &V)> {
+        let key = key_encoding::decode_regular(self.key)?;
+        Some((key, unsafe { &*self.value.as_ptr() }))
+    }
+
+    /// Returns the value of an entry, or `None` for a sentinel.
+    fn value(&self) -> Option<&V> {
+        self.entry().map(|(_, value)| value)
+    }
+
+    /// Moves the value out of an entry, and leaves the slot without a value like a sentinel.
+    /// Returns `None` for a sentinel.
+    fn take(&mut self) -> Option<V> {
+        let key = key_encoding::decode_regular(self.key)?;
+        self.key = key_encoding::sentinel(key);
+        Some(unsafe { ptr::read(self.value.as_ptr()) })
+    }
+
+    /// Extracts the value of a slot that was never shared.
+    fn into_inner(mut self) -> Option<V> {
+        self.take()
+    }
+}
+
+impl<V> Drop for Slot<V> {
+    fn drop(&mut self) {
+        if !self.is_sentinel() {
+            unsafe { ptr::drop_in_place(self.value.as_mut_ptr()) };
+        }
+    }
+}
+
+impl<V> PartialEq for Slot<V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<V> Eq for Slot<V> {}
+
+impl<V> PartialOrd for Slot<V> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<V> Ord for Slot<V> {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for Slot<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Slot")
+            .field("key", &self.key)
+            .field("value", &self.value())
+            .finish()
+    }
+}
+
+/// Error returned by `SplitOrderedList::delete_if`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DeleteError {
+    /// The key is not in the map.
+    NotFound,
+    /// The value of the key doesn't satisfy the predicate.
+    Rejected,
+}
+
+impl fmt::Display for DeleteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeleteError::NotFound => write!(f, "key not found"),
+            DeleteError::Rejected => write!(f, "value rejected by the predicate"),
+        }
+    }
+}
+
+impl Error for DeleteError {}
+
+/// Lock-free map from `usize` to `V`.
 ///
 /// NOTE: We don't care about hashing in this homework for simplicity.
 #[derive(Debug)]
 pub struct SplitOrderedList<V> {
-    /// Lock-free list sorted by recursive-split order. Use `None` sentinel node value.
-    list: List<usize, Option<V>>,
-    /// array of pointers to the buckets
-    buckets: GrowableArray<Node<usize, Option<V>>>,
-    /// number of buckets
+    /// Lock-free list sorted by recursive-split order. Sentinel nodes have no value. It owns every
+    /// node linked in it, sentinels included, and frees them when dropped.
+    list: List<Slot<V>, ()>,
+    /// array of pointers to the buckets. The sentinels are owned by `list`, so dropping the array
+    /// frees only its segments.
+    buckets: GrowableArray<Node<Slot<V>, ()>>,
+    /// number of buckets. Halving it leaves the sentinels of the upper half in the list, but they
+    /// are no longer used as entry points, since every key maps to a bucket in the lower half.
     size: AtomicUsize,
-    /// number of items
-    count: AtomicUsize,
+    /// number of items. It's updated after the list, so it may be momentarily negative when a
+    /// delete decrements it before the insert of the entry increments it. Striped, so that
+    /// inserts and deletes of different threads don't contend on it.
+    count: StripedCounter,
+    /// `size` is grown when `count > size * load_factor`, and halved when
+    /// `count < size * load_factor / 8`.
+    load_factor: usize,
+    /// Whether the thread that grows `size` initializes the new buckets. See `set_prewarm`.
+    prewarm: bool,
 }
 
 impl<V> Default for SplitOrderedList<V> {
@@ -30,151 +181,1298 @@ impl<V> Default for SplitOrderedList<V> {
             list: List::new(),
             buckets: GrowableArray::new(),
             size: AtomicUsize::new(2),
-            count: AtomicUsize::new(0),
+            count: StripedCounter::default(),
+            load_factor: Self::DEFAULT_LOAD_FACTOR,
+            prewarm: false,
         }
     }
 }
 
-fn get_top_bit(n: usize) -> usize {
-    let mut a: usize = 1;
-    while n >= a {
-        a <<= 1;
-    }
-    a >>= 1;
-    a
-}
-
 impl<V> SplitOrderedList<V> {
-    /// `size` is doubled when `count > size * LOAD_FACTOR`.
-    const LOAD_FACTOR: usize = 2;
+    /// The load factor of `new` and `with_capacity`. See `with_load_factor`.
+    pub const DEFAULT_LOAD_FACTOR: usize = 2;
+
+    /// The largest key. Every `usize` is a valid key.
+    pub const MAX_KEY: usize = usize::MAX;
 
     /// Creates a new split ordered list.
     pub fn new() -> Self {
         Self::default()
     }
 
-    /// Creates a cursor and moves it to the bucket for the given index.  If the bucket doesn't
-    /// exist, recursively initializes the buckets.
-    fn lookup_bucket<'s>(&'s self, index: usize, guard: &'s Guard) -> Cursor<'s, usize, Option<V>> {
-        unsafe {
-            loop {
-                let sentinel = self.buckets.get(index, guard);
-                let sentinel_read = sentinel.load(Ordering::Acquire, guard);
-
-                if !sentinel_read.is_null() {
-                    return Cursor::from_raw(sentinel, sentinel_read.as_raw());
-                } else {
-                    let mut cursor = if index == 0 {
-                        self.list.head(guard)
-                    } else {
-                        let prev_bucket_ind = index - get_top_bit(index);
-                        self.lookup_bucket(prev_bucket_ind, guard)
-                    };
-
-                    let new_bucket_key = index.reverse_bits();
-                    let new_bucket = Owned::new(Node::new(new_bucket_key, None::<V>));
-
-                    cursor.find_harris(&new_bucket_key, guard);
-                    if let Err(_) = cursor.insert(new_bucket, guard) {
-                        continue;
+    /// Creates a new split ordered list with enough buckets for `capacity` entries, so that
+    /// inserting them doesn't resize it.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_load_factor(capacity, Self::DEFAULT_LOAD_FACTOR)
+    }
+
+    /// Creates a new split ordered list that keeps at most about `load_factor` entries per bucket.
+    /// The number of buckets grows when there are more entries per bucket on average, and is
+    /// halved when there are fewer than `load_factor / 8`.
+    ///
+    /// A larger load factor saves buckets, at the cost of longer searches in the buckets.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `load_factor` is 0.
+    pub fn with_load_factor(load_factor: usize) -> Self {
+        Self::with_capacity_and_load_factor(0, load_factor)
+    }
+
+    /// Creates a new split ordered list with the load factor, and enough buckets for `capacity`
+    /// entries. See `with_capacity` and `with_load_factor`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `load_factor` is 0.
+    pub fn with_capacity_and_load_factor(capacity: usize, load_factor: usize) -> Self {
+        assert!(load_factor > 0, "the load factor should be positive");
+        Self {
+            size: AtomicUsize::new(Self::buckets_for(capacity, load_factor)),
+            load_factor,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the load factor. See `with_load_factor`.
+    pub fn load_factor(&self) -> usize {
+        self.load_factor
+    }
+
+    /// Sets whether the buckets are initialized eagerly when the number of buckets grows. Off by
+    /// default.
+    ///
+    /// A bucket is usually initialized by the first operation on one of its keys, which inserts its
+    /// sentinel after searching the bucket it's split from. Right after the number of buckets
+    /// grows, the first operations in each new bucket pay for that. With prewarming, the thread
+    /// that grows the number of buckets initializes the new buckets right away instead, so the
+    /// cost moves to the insert that triggers the growth, which takes time linear in the number of
+    /// buckets.
+    pub fn set_prewarm(&mut self, prewarm: bool) {
+        self.prewarm = prewarm;
+    }
+
+    /// Initializes every bucket that is not initialized yet. Operations that initialize the same
+    /// buckets at the same time agree with this on their sentinels.
+    pub fn prewarm_buckets(&self, guard: &Guard) {
+        self.init_buckets(0..self.size.load(Ordering::Acquire), guard);
+    }
+
+    /// Returns the number of entries. It's exact when there are no concurrent inserts or deletes.
+    pub fn len(&self) -> usize {
+        self.count.sum().max(0) as usize
+    }
+
+    /// Returns `true` if the map has no entries. See `len`.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the number of buckets. It grows and shrinks with the number of entries.
+    pub fn bucket_count(&self) -> usize {
+        self.size.load(Ordering::Acquire)
+    }
+
+    /// Returns the average number of entries per bucket, i.e. `len() / bucket_count()`. It's kept
+    /// between about `load_factor() / 8` and `load_factor()`. See `len`.
+    pub fn load(&self) -> f64 {
+        self.len() as f64 / self.bucket_count() as f64
+    }
+
+    /// Returns the number of entries in each bucket, indexed by the bucket. For debugging: many
+    /// entries in a few buckets mean that the keys cluster, unlike a high `load`.
+    ///
+    /// The entries are counted in one walk over the list, so the lengths are approximate when the
+    /// map is modified or resized during the walk, like `iter`. An entry is counted in the bucket
+    /// for its key even if the bucket is not initialized yet, in which case the entry is still in
+    /// the part of the list of the bucket that will be split into it.
+    pub fn bucket_lengths(&self, guard: &Guard) -> Vec<usize> {
+        let size = self.bucket_count();
+        let mut lengths = vec![0; size];
+        for (key, _) in self.entries(guard) {
+            lengths[key % size] += 1;
+        }
+        lengths
+    }
+
+    /// Returns a clone of the value of `key`.
+    ///
+    /// Unlike `lookup`, this doesn't take a guard. The current thread is pinned only for the
+    /// lookup, so the caller doesn't hold back the reclamation of deleted entries while it uses
+    /// the value, at the cost of a clone.
+    pub fn get_owned(&self, key: &usize) -> Option<V>
+    where
+        V: Clone,
+    {
+        let guard = pin();
+        self.lookup(key, &guard).cloned()
+    }
+
+    /// Returns the smallest key, or `None` if the map is empty.
+    ///
+    /// The keys are in split order in the list, which is not the order of the keys, so this scans
+    /// the whole list without collecting it. Like `iter`, the returned key was in the map at some
+    /// point during the call, and every key that is in the map for the whole call is compared.
+    pub fn min_key(&self, guard: &Guard) -> Option<usize> {
+        self.entries(guard).map(|(key, _)| key).min()
+    }
+
+    /// Returns the largest key, or `None` if the map is empty. See `min_key`.
+    pub fn max_key(&self, guard: &Guard) -> Option<usize> {
+        self.entries(guard).map(|(key, _)| key).max()
+    }
+
+    /// Returns the key stored in the map that equals `key`, and its value.
+    ///
+    /// The key is decoded from the split-order key of the node, so it's the key as the map stored
+    /// it, which matters once keys are derived from hashes or normalized before they are stored.
+    pub fn get_key_value<'g>(&'g self, key: &usize, guard: &'g Guard) -> Option<(usize, &'g V)> {
+        let (_, found, cursor) = self.find(key, guard);
+        if !found {
+            return None;
+        }
+        // `find` never finds a sentinel, so the key is regular.
+        cursor.key().unwrap().entry()
+    }
+
+    /// Returns clones of the entries, sorted by key. The current thread is pinned once for the
+    /// whole copy.
+    ///
+    /// This is not an atomic snapshot. Like `iter`, each returned entry was in the map at some
+    /// point during the call, and an entry that is in the map for the whole call is returned
+    /// exactly once. The entries inserted or deleted during the call may or may not be returned, so
+    /// the result may not be the contents of the map at any single moment. Each key is returned at
+    /// most once.
+    pub fn snapshot(&self) -> Vec<(usize, V)>
+    where
+        V: Clone,
+    {
+        let guard = pin();
+        let mut entries = self
+            .entries(&guard)
+            .map(|(key, value)| (key, value.clone()))
+            .collect::<Vec<_>>();
+        entries.sort_unstable_by_key(|&(key, _)| key);
+        entries
+    }
+
+    /// Returns an iterator over the entries. The entries are visited in split order, i.e. in the
+    /// order of the bit-reversed keys, not in the order of the keys.
+    ///
+    /// The iteration tolerates concurrent inserts and deletes. Each visited entry was in the map at
+    /// some point during the iteration, and an entry that is in the map for the whole iteration is
+    /// visited exactly once.
+    pub fn iter<'g>(&'g self, guard: &'g Guard) -> impl Iterator<Item = (usize, &'g V)> + 'g {
+        self.entries(guard)
+    }
+
+    /// Returns an iterator over the keys, in split order. See `iter`.
+    pub fn keys<'g>(&'g self, guard: &'g Guard) -> impl Iterator<Item = usize> + 'g {
+        self.entries(guard).map(|(key, _)| key)
+    }
+
+    /// Returns an iterator over the values, in the split order of their keys. See `iter`.
+    pub fn values<'g>(&'g self, guard: &'g Guard) -> impl Iterator<Item = &'g V> + 'g {
+        self.entries(guard).map(|(_, value)| value)
+    }
+
+    /// Returns an iterator over the entries from the bucket of `key` to the end of the list, in
+    /// split order. See `iter`.
+    ///
+    /// The entries of a bucket are contiguous in split order, and a bucket index comes before the
+    /// other keys of its bucket. So workers can scan disjoint buckets by starting from the bucket
+    /// indices and taking the keys while they are in the bucket, as long as the number of buckets
+    /// doesn't change.
+    pub fn iter_from<'g>(
+        &'g self,
+        key: &usize,
+        guard: &'g Guard,
+    ) -> impl Iterator<Item = (usize, &'g V)> + 'g {
+        let index = key % self.size.load(Ordering::Acquire);
+        Entries {
+            map: self,
+            cursor: self.lookup_bucket(index, guard),
+            next_key: Some(key_encoding::sentinel(index)),
+            guard,
+        }
+    }
+
+    /// Replaces the value of `key` with `value`, and returns the old value. Returns `value` back if
+    /// the key is not in the map.
+    ///
+    /// The node of the entry is replaced atomically with a new one: a lookup returns either the
+    /// old or the new value, and of racing updates of a key, each one returns the value put in by
+    /// the one before it. The old node is reclaimed like a deleted one, so the returned reference
+    /// is valid while `guard` is alive.
+    pub fn update<'g>(&'g self, key: &usize, value: V, guard: &'g Guard) -> Result<&'g V, V> {
+        let mut new_node = Owned::new(Slot::node(*key, value));
+        loop {
+            let (_, found, mut cursor) = self.find(key, guard);
+            if !found {
+                return Err(new_node.into_box().into_key().into_inner().unwrap());
+            }
+            let old = cursor.key().unwrap().value().unwrap();
+            match cursor.replace(new_node, guard) {
+                Ok(()) => return Ok(old),
+                Err(node) => {
+                    // Deleted or replaced after `find`.
+                    metric_inc!(sol_update_retries);
+                    new_node = node;
+                }
+            }
+        }
+    }
+
+    /// Deletes `key` if its value satisfies `pred`, and returns the value.
+    ///
+    /// `pred` is evaluated on the value that would be deleted, and the delete fails if the value is
+    /// replaced by `update` in the meantime. Then `pred` is evaluated again on the new value, so it
+    /// may be called more than once.
+    pub fn delete_if<'g, F: Fn(&V) -> bool>(
+        &'g self,
+        key: &usize,
+        pred: F,
+        guard: &'g Guard,
+    ) -> Result<&'g V, DeleteError> {
+        let (size, value) = self.delete_uncounted(key, pred, guard)?;
+        // Only the delete that marked the value counts it.
+        self.count_delete(size);
+        Ok(value)
+    }
+
+    /// Deletes `key` and returns its value by value, or `None` if the key is not in the map.
+    ///
+    /// It takes the map by `&mut`, so no other thread holds a reference to the value, and the value
+    /// is moved out of its node right away. Only the node is reclaimed later, without the value.
+    pub fn remove_owned(&mut self, key: &usize) -> Option<V> {
+        let guard = &pin();
+        let (size, found, cursor) = self.find(key, guard);
+        if !found {
+            return None;
+        }
+        let mut node = cursor.curr();
+        // No other thread deletes or replaces the entry, so this marks and unlinks the node.
+        let _ = cursor.delete(guard).unwrap();
+        self.count_delete(size);
+        unsafe { node.deref_mut() }.key_mut().take()
+    }
+
+    /// Deletes every entry. The buckets are kept, so that the map can be filled again without
+    /// growing.
+    ///
+    /// Entries inserted or deleted during the clear may or may not be deleted by it. Either way,
+    /// each entry is deleted at most once, and the map stays consistent. The count goes down only
+    /// by the entries deleted by the clear, so it's 0 afterwards unless there were concurrent
+    /// inserts.
+    pub fn clear(&self, guard: &Guard) {
+        let mut cleared = 0;
+        for (key, _) in self.entries(guard) {
+            if self.delete_uncounted(&key, |_| true, guard).is_ok() {
+                cleared += 1;
+            }
+        }
+        let _ = self.count.add(-cleared);
+    }
+
+    /// Deletes the entries for which `f` returns `false`, walking the list once.
+    ///
+    /// Each entry is deleted at its position in the walk, without searching for it from its
+    /// bucket. Like `iter`, an entry that is in the map for the whole call is visited exactly once,
+    /// and the entries inserted or deleted during the call may or may not be visited. An entry is
+    /// deleted only if `f` returns `false` for the value that is deleted: if the value is replaced
+    /// by `update` in the meantime, `f` is called again on the new value.
+    pub fn retain<F: Fn(usize, &V) -> bool>(&self, f: F, guard: &Guard) {
+        let mut cursor = self.list.head(guard);
+        // The cursor at the last node that wasn't deleted by the walk.
+        let mut last = cursor.clone();
+        let mut next_key = Some(key_encoding::sentinel(0));
+        while let Some(key) = next_key {
+            if cursor.find_harris(&Slot::search(key), guard).is_err() {
+                // Failed to unlink deleted nodes. Search again from the bucket of the key, as in
+                // `Entries::next`.
+                let size = self.size.load(Ordering::Acquire);
+                let index = key_encoding::decode(key) % size;
+                cursor = self.lookup_bucket(index, guard);
+                last = cursor.clone();
+                continue;
+            }
+            let slot = match cursor.key() {
+                Some(slot) => slot,
+                None => break,
+            };
+            next_key = key_encoding::successor(slot.key);
+            if let Some((key, value)) = slot.entry() {
+                if !f(key, value) {
+                    match self.delete_at(cursor.clone(), |_| true, guard) {
+                        Ok(_) => self.count_delete(self.size.load(Ordering::Acquire)),
+                        // Deleted or replaced after it was found. Visit the key again, so that `f`
+                        // is called on the new value if it was replaced.
+                        Err(_) => next_key = Some(slot.key),
                     }
+                    // The node may be unlinked, and then the cursor would fail to unlink it again.
+                    // Continue from the node before it.
+                    cursor = last.clone();
+                    continue;
+                }
+            }
+            last = cursor.clone();
+        }
+    }
+
+    /// Unlinks the nodes of deleted entries that are still in the list, and returns how many there
+    /// were. The count is exact when there are no concurrent operations.
+    ///
+    /// A delete unlinks its node right after marking it, and so does `update` with the node it
+    /// replaces, but the unlink fails if the list changes around the node at the same time. The
+    /// node then stays in the list until a search of its bucket passes over it, and slows down the
+    /// searches until then. This walks the whole list once, unlinking those nodes and deferring
+    /// their destruction.
+    pub fn purge(&self, guard: &Guard) -> usize {
+        let mut purged = 0;
+        let mut cursor = self.list.head(guard);
+        let mut next_key = Some(key_encoding::sentinel(0));
+        // The keys of the deleted nodes from `key` up to the next node that isn't deleted.
+        let mut deleted = Vec::new();
+        while let Some(key) = next_key {
+            // `find_harris_herlihy_shavit` doesn't skip marked nodes, and finds a node only if it
+            // isn't marked.
+            deleted.clear();
+            let mut peek = cursor.clone();
+            let mut peek_key = Some(key);
+            while let Some(k) = peek_key {
+                let _ = peek.find_harris_herlihy_shavit(&Slot::search(k), guard);
+                let slot = match peek.key() {
+                    Some(slot) => slot,
+                    None => break,
+                };
+                if peek.find_harris_herlihy_shavit(slot, guard) == Ok(true) {
+                    break;
+                }
+                deleted.push(slot.key);
+                peek_key = key_encoding::successor(slot.key);
+            }
 
-                    match sentinel.compare_exchange(
-                        Shared::null(), cursor.curr(), Ordering::Release, Ordering::Relaxed, guard) {
-                        Ok(_) => {}
-                        Err(_) => { cursor.delete(guard); }
+            // Unlink them by moving the cursor past them.
+            while cursor.find_harris(&Slot::search(key), guard).is_err() {
+                // Failed to unlink deleted nodes. Search again from the bucket of the key, as in
+                // `Entries::next`.
+                let size = self.size.load(Ordering::Acquire);
+                let index = key_encoding::decode(key) % size;
+                cursor = self.lookup_bucket(index, guard);
+            }
+            // A node replaced by `update` comes right before its replacement, which has the same
+            // key, so the peek skips the replacement. The deleted nodes after the replacement are
+            // counted when the walk gets to them.
+            let end = cursor.key().map(|slot| slot.key);
+            purged += match end {
+                Some(end) => deleted.iter().filter(|&&k| k <= end).count(),
+                None => deleted.len(),
+            };
+            next_key = end.and_then(key_encoding::successor);
+        }
+        purged
+    }
+
+    /// Inserts the entries of `iter`, and returns the number of inserted entries. The keys already
+    /// in the map are skipped, and so are the repeated keys in `iter` after their first entry.
+    ///
+    /// The number of buckets is first grown for all the entries, and the entries are sorted in
+    /// split order and inserted with one cursor that moves forward through the list, instead of
+    /// searching from the bucket of each key. The buckets of the entries are initialized on the
+    /// way. When the list changes around the cursor, the entry is inserted like `insert`, and the
+    /// next one is searched from its bucket.
+    pub fn insert_all<I: IntoIterator<Item = (usize, V)>>(&self, iter: I, guard: &Guard) -> usize {
+        let mut batch = iter
+            .into_iter()
+            .map(|(key, value)| (key_encoding::regular(key), key, value))
+            .collect::<Vec<_>>();
+        // Stable, so that the first of the entries of a key is inserted.
+        batch.sort_by_key(|&(split_key, _, _)| split_key);
+
+        // Grow the buckets up front. Otherwise they would double during the walk, and the buckets
+        // of the final size would be initialized by later lookups, which search from the bucket of
+        // the parent over the entries inserted here.
+        self.grow(
+            Self::buckets_for(self.len() + batch.len(), self.load_factor),
+            self.size.load(Ordering::Acquire),
+        );
+
+        let mut inserted = 0;
+        // The cursor is at the split key of the previous entry, if any.
+        let mut cursor: Option<(SplitKey, Cursor<'_, _, _>)> = None;
+        let mut bucket = None;
+        for (split_key, key, value) in batch {
+            let mut new_node = Owned::new(Slot::node(key, value));
+            let index = key % self.size.load(Ordering::Acquire);
+            if bucket != Some(index) {
+                bucket = Some(index);
+                // Move the cursor to the bucket of the key. If the bucket is uninitialized, its
+                // sentinel is inserted from the cursor, when the cursor is before the sentinel.
+                let sentinel = key_encoding::sentinel(index);
+                cursor = match (self.bucket(index, guard), cursor.take()) {
+                    (Some(c), _) => Some(c),
+                    (None, Some((prev, c))) if prev < sentinel => {
+                        self.init_bucket(index, c, guard);
+                        self.bucket(index, guard)
                     }
+                    (None, _) => None,
+                }
+                .map(|c| (sentinel, c));
+            }
+            // The previous key comes before this one, so the cursor only moves forward. If it
+            // fails to unlink deleted nodes, it may be stale.
+            let found = match &mut cursor {
+                Some((_, c)) => c.find_harris(&Slot::search(split_key), guard).ok(),
+                None => None,
+            };
+            let found = match found {
+                Some(found) => found,
+                None => {
+                    let (_, found, c) = self.find(&key, guard);
+                    cursor = Some((split_key, c));
+                    found
                 }
+            };
+            let (prev, c) = cursor.as_mut().unwrap();
+            *prev = split_key;
+            if found {
+                continue;
+            }
+            match c.insert(new_node, guard) {
+                Ok(()) => {
+                    self.count_insert(self.size.load(Ordering::Acquire));
+                    inserted += 1;
+                    continue;
+                }
+                Err(node) => new_node = node,
             }
+            if self.insert_node(&key, new_node, guard).is_ok() {
+                inserted += 1;
+            }
+            cursor = None;
         }
+        inserted
     }
 
-    /// Moves the bucket cursor returned from `lookup_bucket` to the position of the given key.
-    /// Returns `(size, found, cursor)`
-    fn find<'s>(
-        &'s self,
+    /// Deletes `key` like `delete_if`, but doesn't count the delete. Returns the number of buckets
+    /// returned by `find` with the value.
+    fn delete_uncounted<'g, F: Fn(&V) -> bool>(
+        &'g self,
         key: &usize,
+        pred: F,
+        guard: &'g Guard,
+    ) -> Result<(usize, &'g V), DeleteError> {
+        loop {
+            let (size, found, cursor) = self.find(key, guard);
+            if !found {
+                return Err(DeleteError::NotFound);
+            }
+            match self.delete_at(cursor, &pred, guard) {
+                // Deleted or replaced after `find`. Search again, for the new value if it was
+                // replaced.
+                Err(DeleteError::NotFound) => continue,
+                result => return result.map(|value| (size, value)),
+            }
+        }
+    }
+
+    /// Deletes the entry at `cursor`, which was found by `find`, if its value satisfies `pred`.
+    /// Returns `Err(DeleteError::NotFound)` if the entry was deleted or replaced after it was
+    /// found.
+    fn delete_at<'g, F: Fn(&V) -> bool>(
+        &'g self,
+        cursor: Cursor<'g, Slot<V>, ()>,
+        pred: F,
+        guard: &'g Guard,
+    ) -> Result<&'g V, DeleteError> {
+        let value = cursor.key().unwrap().value().unwrap();
+        if !pred(value) {
+            return Err(DeleteError::Rejected);
+        }
+        match cursor.delete(guard) {
+            Ok(_) => Ok(value),
+            Err(()) => Err(DeleteError::NotFound),
+        }
+    }
+
+    /// Returns an iterator over the entries in split order. See `iter`.
+    pub(crate) fn entries<'g>(&'g self, guard: &'g Guard) -> Entries<'g, V> {
+        Entries {
+            map: self,
+            cursor: self.list.head(guard),
+            next_key: Some(key_encoding::sentinel(0)),
+            guard,
+        }
+    }
+
+    /// Creates a cursor and moves it to the bucket for the given index. If the bucket doesn't
+    /// exist, initializes it and its uninitialized ancestors first.
+    fn lookup_bucket<'s>(&'s self, index: usize, guard: &'s Guard) -> Cursor<'s, Slot<V>, ()> {
+        'retry: loop {
+            // Walk down the parent chain to the closest initialized bucket. `None` if even bucket
+            // 0 is uninitialized, and then the buckets are initialized from the head.
+            let mut ancestor = index;
+            let (mut bucket, mut cursor) = loop {
+                if let Some(cursor) = self.bucket(ancestor, guard) {
+                    break (Some(ancestor), cursor);
+                }
+                if ancestor == 0 {
+                    break (None, self.list.head(guard));
+                }
+                ancestor = key_encoding::parent(ancestor);
+            };
+
+            // Initialize the buckets back up to `index`. The ancestors of `index` are `index`
+            // with some of its top set bits cleared, so the child of `bucket` on the way sets the
+            // lowest of the bits of `index` that `bucket` doesn't have.
+            loop {
+                let child = match bucket {
+                    None => 0,
+                    Some(bucket) if bucket == index => return cursor,
+                    Some(bucket) => {
+                        let missing = index & !bucket;
+                        bucket | (missing & missing.wrapping_neg())
+                    }
+                };
+                self.init_bucket(child, cursor, guard);
+                cursor = match self.bucket(child, guard) {
+                    Some(cursor) => cursor,
+                    None => continue 'retry,
+                };
+                bucket = Some(child);
+            }
+        }
+    }
+
+    /// Creates a cursor at the sentinel of the bucket for the given index, if it's initialized.
+    /// Doesn't allocate the segments of the bucket array on the way, which only `init_bucket` does.
+    fn bucket<'s>(&'s self, index: usize, guard: &'s Guard) -> Option<Cursor<'s, Slot<V>, ()>> {
+        let sentinel = self.buckets.try_get(index, guard)?;
+        let sentinel_read = sentinel.load(Ordering::Acquire, guard);
+        if sentinel_read.is_null() {
+            None
+        } else {
+            Some(unsafe { Cursor::from_raw(sentinel, sentinel_read.as_raw()) })
+        }
+    }
+
+    /// Inserts the sentinel of the bucket for the given index after `cursor`, the sentinel of its
+    /// parent bucket, and publishes it in the bucket array. The bucket may still be uninitialized
+    /// if the insert fails.
+    ///
+    /// If another initializer already linked a sentinel but hasn't published it yet, that sentinel
+    /// is published instead of linking another one. So racing initializers agree on one sentinel
+    /// per bucket.
+    fn init_bucket<'s>(
+        &'s self,
+        index: usize,
+        mut cursor: Cursor<'s, Slot<V>, ()>,
         guard: &'s Guard,
-    ) -> (usize, bool, Cursor<'s, usize, Option<V>>) {
+    ) {
+        let sentinel = self.buckets.get(index, guard);
+        let new_bucket_key = key_encoding::sentinel(index);
+
+        let found = match cursor.find_harris(&Slot::search(new_bucket_key), guard) {
+            Ok(found) => found,
+            // Failed to unlink deleted nodes.
+            Err(()) => return,
+        };
+        if !found {
+            let new_bucket = Owned::new(Slot::sentinel(index));
+            if cursor.insert(new_bucket, guard).is_err() {
+                metric_inc!(sol_bucket_init_races);
+                return;
+            }
+        }
+
+        failpoint!("split_ordered_list::lookup_bucket::publish");
+        if let Err(e) = sentinel.compare_exchange(
+            Shared::null(),
+            cursor.curr(),
+            Ordering::Release,
+            Ordering::Relaxed,
+            guard,
+        ) {
+            metric_inc!(sol_bucket_init_races);
+            // Usually the winner published the same node. If it published another one, the node
+            // linked here is an extra sentinel that no bucket points to, so unlink it. Never unlink
+            // a node found in the list, which may be the published one.
+            if !found && e.current != cursor.curr() {
+                let _ = cursor.delete(guard);
+            }
+        }
+    }
+
+    /// Moves the bucket cursor returned from `lookup_bucket` to the position of the given key.
+    /// Returns `(size, found, cursor)`. If found, the entry was not deleted when it was found.
+    fn find<'s>(&'s self, key: &usize, guard: &'s Guard) -> (usize, bool, Cursor<'s, Slot<V>, ()>) {
         let size = self.size.load(Ordering::Acquire);
         let index = key % size;
-        let mut cursor = self.lookup_bucket(index, guard);
+        let key = Slot::search(key_encoding::regular(*key));
 
-        let found =
-            match cursor.find_harris(&(key.reverse_bits() + 1), guard) {
-                Ok(b) => { b }
-                Err(_) => { false }
-            };
+        loop {
+            let mut cursor = self.lookup_bucket(index, guard);
+            match cursor.find_harris(&key, guard) {
+                Ok(found) => return (size, found, cursor),
+                // Failed to unlink deleted nodes, so the cursor may be stale. Search again from
+                // the bucket.
+                Err(()) => continue,
+            }
+        }
+    }
+
+    /// Inserts `new_node` for `key` if the key is not in the map, and counts it. Returns the slot of
+    /// the inserted node, or the node back if the key is in the map.
+    fn insert_node<'g>(
+        &'g self,
+        key: &usize,
+        mut new_node: Owned<Node<Slot<V>, ()>>,
+        guard: &'g Guard,
+    ) -> Result<&'g Slot<V>, Owned<Node<Slot<V>, ()>>> {
+        loop {
+            let (size, found, mut cursor) = self.find(key, guard);
+            if found {
+                return Err(new_node);
+            }
+            failpoint!("split_ordered_list::insert");
+            match cursor.insert(new_node, guard) {
+                Ok(_) => {
+                    self.count_insert(size);
+                    return Ok(cursor.key().unwrap());
+                }
+                Err(node) => {
+                    // The list changed around the cursor, which doesn't mean that the key is in
+                    // the list now. Search again.
+                    metric_inc!(sol_insert_retries);
+                    new_node = node;
+                }
+            }
+        }
+    }
+
+    /// Counts an inserted entry, and grows the number of buckets if the load factor is exceeded.
+    /// `size` is the number of buckets returned by `find` for the insert.
+    fn count_insert(&self, size: usize) {
+        let threshold = size.saturating_mul(self.load_factor);
+        let cell = self.count.add(1);
+        if !Self::at_checkpoint(cell, threshold) {
+            return;
+        }
+        let count = self.count.sum();
+        if count > threshold as isize {
+            // Grow straight to the size for the count, which may be several doublings away after a
+            // burst of inserts.
+            self.grow(Self::buckets_for(count as usize, self.load_factor), size);
+        }
+    }
+
+    /// Returns the number of buckets for `count` entries: the smallest power of two (and at least
+    /// 2) that keeps the load factor.
+    fn buckets_for(count: usize, load_factor: usize) -> usize {
+        let buckets = count / load_factor + usize::from(count % load_factor != 0);
+        buckets.clamp(2, 1 << (usize::BITS - 1)).next_power_of_two()
+    }
+
+    /// Grows the number of buckets to at least `target`. `size` is the number of buckets last seen
+    /// by the caller. Retries while another thread changes the size but leaves it below `target`,
+    /// so the thread that sees the largest count decides the size. With `prewarm`, the thread that
+    /// grows the size initializes the new buckets.
+    fn grow(&self, target: usize, mut size: usize) {
+        while size < target {
+            match self
+                .size
+                .compare_exchange(size, target, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => {
+                    if self.prewarm {
+                        self.init_buckets(size..target, &pin());
+                    }
+                    return;
+                }
+                Err(current) => size = current,
+            }
+        }
+    }
+
+    /// Initializes the buckets in `indices` that are not initialized yet. In increasing order, so
+    /// that the parent of each bucket is initialized before it, unless it's below the range.
+    fn init_buckets(&self, indices: Range<usize>, guard: &Guard) {
+        for index in indices {
+            if self.bucket(index, guard).is_none() {
+                let _ = self.lookup_bucket(index, guard);
+            }
+        }
+    }
 
-        (size, found, cursor)
+    /// Uncounts a deleted entry, and halves the number of buckets if the map is sparse enough.
+    /// `size` is the number of buckets returned by `find` for the delete.
+    fn count_delete(&self, size: usize) {
+        let threshold = size.saturating_mul(self.load_factor) / 8;
+        let cell = self.count.add(-1);
+        if size > 2 && Self::at_checkpoint(cell, threshold) && self.count.sum() < threshold as isize
+        {
+            let _ =
+                self.size
+                    .compare_exchange(size, size / 2, Ordering::Release, Ordering::Relaxed);
+        }
     }
 
-    fn assert_valid_key(key: usize) {
-        assert!(key.leading_zeros() != 0);
+    /// Returns `true` if the count should be checked against `threshold` after the cell of the
+    /// current thread is updated to `cell`. Summing the count reads every cell, so it's done only
+    /// when the cell hits a multiple of its share of the threshold. Then the resize is late by at
+    /// most one share per cell.
+    fn at_checkpoint(cell: isize, threshold: usize) -> bool {
+        let share = (threshold / StripedCounter::STRIPES).max(1) as isize;
+        cell.rem_euclid(share) == 0
+    }
+}
+
+impl<V> Extend<(usize, V)> for SplitOrderedList<V> {
+    /// Inserts the entries like `insert_all`, so the keys already in the map are skipped and a
+    /// repeated key keeps its first entry. The buckets are first grown for the lower bound of the
+    /// size hint. The entries are inserted in chunks of `EXTEND_CHUNK`, pinning the thread for each
+    /// chunk, so that a long iterator doesn't hold back the reclamation of deleted entries.
+    fn extend<I: IntoIterator<Item = (usize, V)>>(&mut self, iter: I) {
+        let mut iter = iter.into_iter();
+        let (lower, _) = iter.size_hint();
+        self.grow(
+            Self::buckets_for(self.len().saturating_add(lower), self.load_factor),
+            self.size.load(Ordering::Acquire),
+        );
+        loop {
+            let chunk = iter.by_ref().take(EXTEND_CHUNK).collect::<Vec<_>>();
+            if chunk.is_empty() {
+                return;
+            }
+            let _ = self.insert_all(chunk, &pin());
+        }
+    }
+}
+
+impl<V> FromIterator<(usize, V)> for SplitOrderedList<V> {
+    /// Creates a map of the entries. See `Extend`.
+    fn from_iter<I: IntoIterator<Item = (usize, V)>>(iter: I) -> Self {
+        let mut map = Self::new();
+        map.extend(iter);
+        map
     }
 }
 
 impl<V> NonblockingMap<usize, V> for SplitOrderedList<V> {
     fn lookup<'a>(&'a self, key: &usize, guard: &'a Guard) -> Option<&'a V> {
-        Self::assert_valid_key(*key);
         let (_, found, cursor) = self.find(key, guard);
         if found {
-            let a = cursor.lookup();
-            match a {
-                None => { None }
-                Some(dd) => {
-                    match dd {
-                        None => { None }
-                        Some(dd) => { Some(dd) }
-                    }
-                }
-            }
+            cursor.key().unwrap().value()
         } else {
             None
         }
     }
 
+    fn contains_key(&self, key: &usize, guard: &Guard) -> bool {
+        // `find` searches for the regular encoding of the key, so it never finds a sentinel.
+        let (_, found, _) = self.find(key, guard);
+        found
+    }
+
     fn insert(&self, key: &usize, value: V, guard: &Guard) -> Result<(), V> {
-        Self::assert_valid_key(*key);
-        let (size, found, mut cursor) = self.find(key, guard);
-        if found {
-            Err(value)
-        } else {
-            let new_node = Owned::new(Node::new(key.reverse_bits() + 1, Some(value)));
-            match cursor.insert(new_node, guard) {
+        let new_node = Owned::new(Slot::node(*key, value));
+        self.insert_node(key, new_node, guard)
+            .map(|_| ())
+            .map_err(|node| node.into_box().into_key().into_inner().unwrap())
+    }
+
+    fn get_or_insert_with<'a, F: FnOnce() -> V>(
+        &'a self,
+        key: &usize,
+        f: F,
+        guard: &'a Guard,
+    ) -> &'a V {
+        let mut f = Some(f);
+        // Created on the first miss, and reused if the insert loses.
+        let mut new_node = None;
+        loop {
+            let (size, found, mut cursor) = self.find(key, guard);
+            if found {
+                // Dropping `new_node` drops the value of `f`, if any.
+                return cursor.key().unwrap().value().unwrap();
+            }
+
+            let node = new_node
+                .take()
+                .unwrap_or_else(|| Owned::new(Slot::node(*key, (f.take().unwrap())())));
+            match cursor.insert(node, guard) {
                 Ok(_) => {
-                    let prev_count = self.count.fetch_add(1, Ordering::AcqRel);
-                    if prev_count + 1 > size * Self::LOAD_FACTOR {
-                        self.size.compare_exchange(size, size * 2, Ordering::Release, Ordering::Relaxed);
-                    }
-                    Ok(())
+                    self.count_insert(size);
+                    // The node may be replaced right away, but it's reclaimed only after `guard`.
+                    return cursor.key().unwrap().value().unwrap();
                 }
-                Err(new_node) => {
-                    Err((*new_node.into_box()).into_value().unwrap())
+                Err(node) => {
+                    metric_inc!(sol_insert_retries);
+                    new_node = Some(node);
                 }
             }
         }
     }
 
-    fn delete<'a>(&'a self, key: &usize, guard: &'a Guard) -> Result<&'a V, ()> {
-        Self::assert_valid_key(*key);
+    fn remove<'a>(&'a self, key: &usize, guard: &'a Guard) -> Result<&'a V, MapError> {
+        self.delete_if(key, |_| true, guard)
+            .map_err(|_| MapError::NotFound)
+    }
 
-        let (_, found, mut cursor) = self.find(key, guard);
-        if found {
-            self.count.fetch_sub(1, Ordering::AcqRel);
-            let ret = cursor.delete(guard);
-
-            match ret {
-                Ok(op) => {
-                    match op {
-                        None => { Err(()) }
-                        Some(v) => { Ok(v) }
+    fn entry<'a>(&'a self, key: usize, guard: &'a Guard) -> MapEntry<'a, usize, V>
+    where
+        usize: 'a,
+    {
+        let (size, found, cursor) = self.find(&key, guard);
+        let value = if found {
+            cursor.key().unwrap().value()
+        } else {
+            None
+        };
+        let raw = CursorEntry {
+            map: self,
+            key,
+            size,
+            cursor,
+            guard,
+        };
+        MapEntry::from_raw(value, Box::new(raw))
+    }
+}
+
+/// Entry of `SplitOrderedList`, at the cursor returned by `find` for the key.
+struct CursorEntry<'g, V> {
+    map: &'g SplitOrderedList<V>,
+    key: usize,
+    /// Number of buckets returned by `find`.
+    size: usize,
+    cursor: Cursor<'g, Slot<V>, ()>,
+    guard: &'g Guard,
+}
+
+impl<'g, V> RawEntry<'g, usize, V> for CursorEntry<'g, V> {
+    fn key(&self) -> &usize {
+        &self.key
+    }
+
+    fn delete(self: Box<Self>) -> Result<&'g V, MapError> {
+        let CursorEntry {
+            map,
+            key,
+            size,
+            cursor,
+            guard,
+        } = *self;
+        match map.delete_at(cursor, |_| true, guard) {
+            Ok(value) => {
+                map.count_delete(size);
+                Ok(value)
+            }
+            // The entry at the cursor was deleted, but the key may have been inserted again.
+            Err(_) => map.remove(&key, guard),
+        }
+    }
+
+    fn insert(self: Box<Self>, value: V) -> Result<&'g V, V> {
+        let CursorEntry {
+            map,
+            key,
+            size,
+            mut cursor,
+            guard,
+        } = *self;
+        let new_node = Owned::new(Slot::node(key, value));
+        // If the list changed around the cursor, the key may have been inserted. Search again.
+        let result = match cursor.insert(new_node, guard) {
+            Ok(()) => {
+                map.count_insert(size);
+                Ok(cursor.key().unwrap())
+            }
+            Err(node) => map.insert_node(&key, node, guard),
+        };
+        // The node may be replaced right away, but it's reclaimed only after `guard`.
+        match result {
+            Ok(slot) => Ok(slot.value().unwrap()),
+            Err(node) => Err(node.into_box().into_key().into_inner().unwrap()),
+        }
+    }
+}
+
+/// Iterator over the entries of `SplitOrderedList` in split order. Entries inserted or deleted
+/// during the iteration may or may not be visited.
+#[derive(Debug)]
+pub(crate) struct Entries<'g, V> {
+    map: &'g SplitOrderedList<V>,
+    cursor: Cursor<'g, Slot<V>, ()>,
+    /// Split-order key to search for next. `None` if the iteration is done.
+    next_key: Option<SplitKey>,
+    guard: &'g Guard,
+}
+
+impl<'g, V> Iterator for Entries<'g, V> {
+    type Item = (usize, &'g V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let key = self.next_key.take()?;
+            if self
+                .cursor
+                .find_harris(&Slot::search(key), self.guard)
+                .is_err()
+            {
+                // Failed to unlink deleted nodes. Search again from the bucket of the key, whose
+                // sentinel comes before the key.
+                let size = self.map.size.load(Ordering::Acquire);
+                let index = key_encoding::decode(key) % size;
+                self.cursor = self.map.lookup_bucket(index, self.guard);
+                self.next_key = Some(key);
+                continue;
+            }
+            let slot = self.cursor.key()?;
+            self.next_key = key_encoding::successor(slot.key);
+            if let Some(entry) = slot.entry() {
+                return Some(entry);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use super::*;
+    use crate::test_util::drop_counter::DropCounter;
+    use core::mem;
+    use crossbeam_epoch::{pin, Atomic};
+    use crossbeam_utils::thread::scope;
+    use std::sync::Barrier;
+
+    /// Unlinks the sentinels of the bucket for the given index, and returns how many there were.
+    /// The bucket array still points to one of them, so the list should only be dropped after this.
+    fn take_sentinels<V>(list: &SplitOrderedList<V>, index: usize, guard: &Guard) -> usize {
+        let key = key_encoding::sentinel(index);
+        let mut count = 0;
+        loop {
+            let mut cursor = list.list.head(guard);
+            match cursor.find_harris(&Slot::search(key), guard) {
+                Ok(true) => {
+                    if cursor.delete(guard).is_ok() {
+                        count += 1;
                     }
                 }
-                Err(_) => { Err(()) }
+                Ok(false) => return count,
+                Err(()) => {}
             }
-        } else {
-            Err(())
         }
     }
+
+    /// Returns the number of nodes in the list, including the sentinels and the marked nodes.
+    fn node_count<V>(list: &SplitOrderedList<V>, guard: &Guard) -> usize {
+        let mut cursor = list.list.head(guard);
+        let mut count = 0;
+        let mut next_key = Some(key_encoding::sentinel(0));
+        while let Some(key) = next_key {
+            let _ = cursor.find_harris_herlihy_shavit(&Slot::search(key), guard);
+            let slot = match cursor.key() {
+                Some(slot) => slot,
+                None => break,
+            };
+            count += 1;
+            next_key = key_encoding::successor(slot.key);
+        }
+        count
+    }
+
+    /// Returns the indices of the buckets whose sentinels are in the list, in split order. Skips
+    /// the marked sentinels. The list should be quiescent.
+    fn sentinels<V>(list: &SplitOrderedList<V>, guard: &Guard) -> Vec<usize> {
+        let mut cursor = list.list.head(guard);
+        let mut sentinels = Vec::new();
+        let mut next_key = Some(key_encoding::sentinel(0));
+        while let Some(key) = next_key {
+            let _ = cursor.find_harris(&Slot::search(key), guard).unwrap();
+            let slot = match cursor.key() {
+                Some(slot) => slot,
+                None => break,
+            };
+            if slot.is_sentinel() {
+                sentinels.push(key_encoding::decode(slot.key));
+            }
+            next_key = key_encoding::successor(slot.key);
+        }
+        sentinels
+    }
+
+    /// Deletes `key` like `remove`, but leaves its node in the list, as if the unlink had failed.
+    fn delete_linked<V>(list: &SplitOrderedList<V>, key: usize, guard: &Guard) {
+        let (size, found, cursor) = list.find(&key, guard);
+        assert!(found);
+        // The unlink fails, since `prev` doesn't point to the node.
+        let prev = Atomic::null();
+        let cursor = unsafe { Cursor::from_raw(&prev, cursor.curr().as_raw()) };
+        assert!(list.delete_at(cursor, |_| true, guard).is_ok());
+        list.count_delete(size);
+    }
+
+    // A sentinel and an entry with the same word are told apart by their split-order keys. An
+    // entry whose value was taken has no value, like a sentinel.
+    #[test]
+    fn sentinel_split_key() {
+        let top = 1 << (usize::BITS - 1);
+        for &word in &[0, 1, top - 1, top, usize::MAX] {
+            let sentinel = Slot::<usize>::sentinel(word).into_key();
+            assert!(sentinel.is_sentinel());
+            assert_eq!(sentinel.key, key_encoding::sentinel(word));
+            assert!(sentinel.entry().is_none());
+
+            let mut entry = Slot::node(word, word).into_key();
+            assert!(!entry.is_sentinel());
+            assert_eq!(entry.key, key_encoding::regular(word));
+            assert_eq!(entry.entry(), Some((word, &word)));
+
+            assert_eq!(entry.take(), Some(word));
+            assert!(entry.is_sentinel());
+            assert!(entry.entry().is_none());
+            assert_eq!(entry.take(), None);
+        }
+    }
+
+    // The value is inline, and a sentinel has none, so a node is as large as a node of a list of
+    // `Option<V>` keyed by the word.
+    #[test]
+    fn node_size() {
+        let word = mem::size_of::<usize>();
+        assert_eq!(mem::size_of::<SplitKey>(), 2 * word);
+        assert_eq!(mem::size_of::<Slot<usize>>(), 3 * word);
+        assert_eq!(
+            mem::size_of::<Node<Slot<usize>, ()>>(),
+            mem::size_of::<Node<usize, Option<usize>>>()
+        );
+    }
+
+    // Every value is dropped exactly once, however many sentinels the list has, and the sentinels
+    // are never visited as entries.
+    #[test]
+    fn sentinels_drop_no_value() {
+        const KEYS: usize = 1 << 12;
+
+        let counter = DropCounter::new();
+        let list = SplitOrderedList::new();
+        let guard = pin();
+        for key in 0..KEYS {
+            assert!(list.insert(&key, counter.track(key), &guard).is_ok());
+        }
+        for key in (0..KEYS).step_by(2) {
+            assert!(list.update(&key, counter.track(key), &guard).is_ok());
+        }
+        list.prewarm_buckets(&guard);
+        assert_eq!(list.iter(&guard).count(), KEYS);
+        assert!(list.iter(&guard).all(|(key, value)| **value == key));
+        assert_eq!(sentinels(&list, &guard).len(), list.bucket_count());
+        drop(guard);
+        drop(list);
+        for _ in 0..1 << 16 {
+            if counter.live() == 0 {
+                break;
+            }
+            pin().flush();
+        }
+        assert_eq!(counter.created(), KEYS + KEYS / 2);
+        assert_eq!(counter.dropped(), KEYS + KEYS / 2);
+    }
+
+    #[test]
+    fn purge() {
+        const KEYS: usize = 1000;
+
+        let list = SplitOrderedList::new();
+        let guard = pin();
+        for key in 0..KEYS {
+            assert_eq!(list.insert(&key, key, &guard), Ok(()));
+        }
+
+        // Delete 90% of the keys in reverse split order, so that the searches of the deletes don't
+        // pass over, and unlink, the nodes left by the earlier ones.
+        let mut deleted = (0..KEYS).filter(|key| key % 10 != 0).collect::<Vec<_>>();
+        deleted.sort_by_key(|&key| std::cmp::Reverse(key_encoding::regular(key)));
+        for &key in &deleted {
+            delete_linked(&list, key, &guard);
+        }
+        assert_eq!(list.len(), KEYS / 10);
+        // The searches of the deletes may have initialized buckets, so count the nodes afterwards.
+        let nodes = node_count(&list, &guard);
+
+        assert_eq!(list.purge(&guard), deleted.len());
+        assert_eq!(node_count(&list, &guard), nodes - deleted.len());
+        assert_eq!(list.purge(&guard), 0);
+        for key in 0..KEYS {
+            let expected = if key % 10 == 0 { Some(&key) } else { None };
+            assert_eq!(list.lookup(&key, &guard), expected);
+        }
+    }
+
+    // With prewarming, every bucket is initialized as soon as the number of buckets grows.
+    #[test]
+    fn prewarm() {
+        const KEYS: usize = 1 << 12;
+
+        let mut list = SplitOrderedList::new();
+        list.set_prewarm(true);
+        let guard = pin();
+        for key in 0..KEYS {
+            assert_eq!(list.insert(&key, key, &guard), Ok(()));
+            // The initial buckets are initialized with the first growth, as the parents of the new
+            // ones.
+            let size = list.bucket_count();
+            if size > 2 {
+                assert!((0..size).all(|index| list.bucket(index, &guard).is_some()));
+            }
+        }
+        assert_eq!(sentinels(&list, &guard).len(), list.bucket_count());
+
+        let list = SplitOrderedList::<usize>::with_capacity(KEYS);
+        assert!(sentinels(&list, &guard).is_empty());
+        list.prewarm_buckets(&guard);
+        let mut indices = sentinels(&list, &guard);
+        indices.sort_unstable();
+        assert_eq!(indices, (0..list.bucket_count()).collect::<Vec<_>>());
+    }
+
+    // Threads prewarm the buckets while others insert and look up keys, which initializes the same
+    // buckets. Each bucket should end up with exactly one sentinel, the one in the bucket array.
+    #[test]
+    fn prewarm_race() {
+        const THREADS: usize = 4;
+        const PREWARMERS: usize = 2;
+        const SIZE: usize = 1 << 10;
+        const ROUNDS: usize = 16;
+
+        for _ in 0..ROUNDS {
+            let list = SplitOrderedList::with_capacity(
+                SIZE * SplitOrderedList::<usize>::DEFAULT_LOAD_FACTOR,
+            );
+            let barrier = Barrier::new(THREADS + PREWARMERS);
+            scope(|s| {
+                for t in 0..THREADS {
+                    let (list, barrier) = (&list, &barrier);
+                    let _ = s.spawn(move |_| {
+                        let guard = pin();
+                        barrier.wait();
+                        for key in (t..SIZE).step_by(THREADS).rev() {
+                            assert_eq!(list.lookup(&key, &guard), None);
+                            assert_eq!(list.insert(&key, key, &guard), Ok(()));
+                        }
+                    });
+                }
+                for _ in 0..PREWARMERS {
+                    let (list, barrier) = (&list, &barrier);
+                    let _ = s.spawn(move |_| {
+                        let guard = pin();
+                        barrier.wait();
+                        list.prewarm_buckets(&guard);
+                    });
+                }
+            })
+            .unwrap();
+
+            let guard = pin();
+            let mut indices = sentinels(&list, &guard);
+            indices.sort_unstable();
+            assert_eq!(indices, (0..SIZE).collect::<Vec<_>>());
+            for index in 0..SIZE {
+                let cursor = list.bucket(index, &guard).unwrap();
+                let slot = cursor.key().unwrap();
+                assert!(slot.is_sentinel());
+                assert_eq!(slot.key, key_encoding::sentinel(index));
+                assert_eq!(list.lookup(&index, &guard), Some(&index));
+            }
+        }
+    }
+
+    // Threads initialize the same bucket and its ancestors at once. They should all get the same
+    // sentinel, and each bucket should have exactly one sentinel in the list.
+    #[test]
+    fn bucket_init_race() {
+        const THREADS: usize = 8;
+        const ROUNDS: usize = 256;
+        const SIZE: usize = 8;
+        const INDEX: usize = SIZE - 1;
+
+        for _ in 0..ROUNDS {
+            let list = SplitOrderedList::with_capacity(
+                SIZE * SplitOrderedList::<usize>::DEFAULT_LOAD_FACTOR,
+            );
+            let barrier = Barrier::new(THREADS);
+            let mut sentinels = vec![0; THREADS];
+            scope(|s| {
+                for sentinel in &mut sentinels {
+                    let (list, barrier) = (&list, &barrier);
+                    let _ = s.spawn(move |_| {
+                        let guard = pin();
+                        barrier.wait();
+                        *sentinel = list.lookup_bucket(INDEX, &guard).curr().as_raw() as usize;
+                    });
+                }
+            })
+            .unwrap();
+            assert!(sentinels.iter().all(|&s| s == sentinels[0]));
+
+            let guard = pin();
+            for key in (INDEX..4 * SIZE).step_by(SIZE) {
+                assert_eq!(list.insert(&key, key, &guard), Ok(()));
+                assert_eq!(list.lookup(&key, &guard), Some(&key));
+            }
+            for index in [0, 1, 3, 7] {
+                assert_eq!(take_sentinels(&list, index, &guard), 1, "bucket {}", index);
+            }
+        }
+    }
+
+    #[test]
+    fn grow() {
+        type List = SplitOrderedList<usize>;
+        assert_eq!(List::buckets_for(0, 2), 2);
+        assert_eq!(List::buckets_for(4, 2), 2);
+        assert_eq!(List::buckets_for(5, 2), 4);
+        assert_eq!(List::buckets_for(1000, 2), 512);
+        assert_eq!(List::buckets_for(usize::MAX, 2), 1 << (usize::BITS - 1));
+        assert_eq!(List::buckets_for(1000, 8), 128);
+        assert_eq!(List::buckets_for(usize::MAX, 1), 1 << (usize::BITS - 1));
+
+        // Straight to the target, even from a stale size, and never down.
+        let list = List::new();
+        list.grow(512, 2);
+        assert_eq!(list.bucket_count(), 512);
+        list.grow(1024, 2);
+        assert_eq!(list.bucket_count(), 1024);
+        list.grow(64, 2);
+        assert_eq!(list.bucket_count(), 1024);
+    }
+
+    // A burst of inserts from many threads. The number of buckets should keep up with the count,
+    // up to the lag of the checkpoints.
+    #[test]
+    fn grow_concurrent() {
+        const THREADS: usize = 8;
+        const SIZE: usize = 1 << 10;
+        const KEYS: usize = 10 * SplitOrderedList::<usize>::DEFAULT_LOAD_FACTOR * SIZE;
+
+        let list =
+            SplitOrderedList::with_capacity(SIZE * SplitOrderedList::<usize>::DEFAULT_LOAD_FACTOR);
+        assert_eq!(list.bucket_count(), SIZE);
+        scope(|s| {
+            for t in 0..THREADS {
+                let list = &list;
+                let _ = s.spawn(move |_| {
+                    for key in (t..KEYS).step_by(THREADS) {
+                        assert_eq!(list.insert(&key, key, &pin()), Ok(()));
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        let target = list.len() / SplitOrderedList::<usize>::DEFAULT_LOAD_FACTOR;
+        let size = list.bucket_count();
+        assert!(target / 2 <= size && size <= 2 * target, "{} buckets", size);
+    }
 }
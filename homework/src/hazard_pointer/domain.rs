@@ -0,0 +1,170 @@
+use core::ops::Deref;
+
+use super::{HazardBag, RetiredSet, Shield};
+
+/// Hazard pointer domain, i.e. a bag of hazard pointers and the pointers retired against it.
+///
+/// Reclamation in a domain only scans the hazards of the domain, so the data structures in
+/// different domains don't slow each other down. A pointer should be protected and retired in the
+/// same domain.
+///
+/// The retired sets of a domain borrow the domain, so their pointers are freed before the domain
+/// is dropped, or when it's dropped if they're still protected when the sets are dropped. The
+/// shields of a domain should be dropped before the domain.
+#[derive(Debug)]
+pub struct Domain {
+    hazards: HazardBag,
+    /// Threshold of the retired sets. See `RetiredSet::with_threshold`.
+    collect_threshold: usize,
+}
+
+impl Domain {
+    /// Creates a new domain.
+    #[cfg(not(feature = "check-loom"))]
+    pub const fn new() -> Self {
+        Self::with_collect_threshold(RetiredSet::THRESHOLD)
+    }
+
+    /// Creates a new domain.
+    #[cfg(feature = "check-loom")]
+    pub fn new() -> Self {
+        Self::with_collect_threshold(RetiredSet::THRESHOLD)
+    }
+
+    /// Creates a new domain whose retired sets are collected when `threshold` pointers are
+    /// retired.
+    #[cfg(not(feature = "check-loom"))]
+    pub const fn with_collect_threshold(threshold: usize) -> Self {
+        Self {
+            hazards: HazardBag::new(),
+            collect_threshold: threshold,
+        }
+    }
+
+    /// Creates a new domain whose retired sets are collected when `threshold` pointers are
+    /// retired.
+    #[cfg(feature = "check-loom")]
+    pub fn with_collect_threshold(threshold: usize) -> Self {
+        Self {
+            hazards: HazardBag::new(),
+            collect_threshold: threshold,
+        }
+    }
+
+    /// Returns the hazard pointers of the domain.
+    pub fn hazards(&self) -> &HazardBag {
+        &self.hazards
+    }
+
+    /// Creates a new shield in the domain.
+    pub fn shield<T>(&self) -> Shield<T> {
+        Shield::new(&self.hazards)
+    }
+
+    /// Creates a new retired pointer list of the domain.
+    pub fn retired_set(&self) -> RetiredSet<'_> {
+        RetiredSet::with_threshold(&self.hazards, self.collect_threshold)
+    }
+}
+
+impl Default for Domain {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A domain can be used as its `HazardBag`, e.g. `Shield::new(&HAZARDS)`.
+impl Deref for Domain {
+    type Target = HazardBag;
+
+    fn deref(&self) -> &HazardBag {
+        &self.hazards
+    }
+}
+
+impl Drop for Domain {
+    fn drop(&mut self) {
+        debug_assert_eq!(
+            self.hazards.active_count(),
+            0,
+            "a shield outlives its domain"
+        );
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use super::Domain;
+    use std::cell::RefCell;
+    use std::collections::HashSet;
+    use std::rc::Rc;
+
+    struct Tester(Rc<RefCell<HashSet<usize>>>, usize);
+
+    impl Drop for Tester {
+        fn drop(&mut self) {
+            let _ = self.0.borrow_mut().insert(self.1);
+        }
+    }
+
+    // The hazards and retired pointers of a domain should not leak into another domain.
+    #[test]
+    fn separate_domains() {
+        let domains = [Domain::new(), Domain::new()];
+        let freed = Rc::new(RefCell::new(HashSet::new()));
+        let nodes = (0..2)
+            .map(|i| Box::into_raw(Box::new(Tester(freed.clone(), i))))
+            .collect::<Vec<_>>();
+
+        let shields = [domains[0].shield(), domains[1].shield()];
+        shields[0].set(nodes[0]);
+        shields[1].set(nodes[1]);
+        for i in 0..2 {
+            assert_eq!(
+                domains[i].all_hazards(),
+                [nodes[i] as usize].iter().cloned().collect()
+            );
+        }
+
+        let mut retired = domains[0].retired_set();
+        for node in &nodes {
+            retired.retire(*node);
+        }
+        retired.collect();
+        // `nodes[1]` is protected only in the other domain.
+        assert_eq!(*freed.borrow(), [1].iter().cloned().collect());
+
+        shields[0].reset();
+        drop(retired);
+        assert_eq!(*freed.borrow(), [0, 1].iter().cloned().collect());
+
+        drop(shields);
+        drop(domains);
+    }
+
+    // With threshold 1, a retired pointer should be freed right away.
+    #[test]
+    fn collect_threshold_one() {
+        let domain = Domain::with_collect_threshold(1);
+        let freed = Rc::new(RefCell::new(HashSet::new()));
+        let mut retired = domain.retired_set();
+        for i in 0..16 {
+            retired.retire(Box::into_raw(Box::new(Tester(freed.clone(), i))));
+            assert_eq!(*freed.borrow(), (0..=i).collect());
+        }
+    }
+
+    // With a large threshold, nothing should be freed until `collect`.
+    #[test]
+    fn collect_threshold_large() {
+        let domain = Domain::with_collect_threshold(1024);
+        let freed = Rc::new(RefCell::new(HashSet::new()));
+        let mut retired = domain.retired_set();
+        for i in 0..1000 {
+            retired.retire(Box::into_raw(Box::new(Tester(freed.clone(), i))));
+        }
+        assert!(freed.borrow().is_empty());
+        retired.collect();
+        assert_eq!(*freed.borrow(), (0..1000).collect());
+    }
+}
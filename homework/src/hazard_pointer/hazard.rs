@@ -1,15 +1,34 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+#[cfg(feature = "debug-hazard-leaks")]
+use core::cell::Cell;
+use core::fmt;
 use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Deref, Index};
+#[cfg(feature = "debug-hazard-leaks")]
+use core::panic::Location;
 use core::ptr::{self, NonNull};
+use crossbeam_epoch::{Atomic, Guard, Shared};
+#[cfg(all(feature = "std", not(feature = "check-loom")))]
+use std::cell::RefCell;
+#[cfg(feature = "std")]
 use std::collections::HashSet;
-use std::fmt;
+#[cfg(feature = "debug-hazard-leaks")]
+use std::string::{String, ToString};
+#[cfg(feature = "debug-hazard-leaks")]
+use std::sync::Mutex;
+#[cfg(feature = "debug-hazard-leaks")]
+use std::thread::{self, Thread};
 
 #[cfg(not(feature = "check-loom"))]
-use core::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize, Ordering};
-use std::ops::Deref;
-use std::ptr::null;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
 #[cfg(feature = "check-loom")]
-use loom::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+use loom::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
 
+use super::membarrier;
+use super::retire::Retired;
+#[cfg(feature = "global-hazards")]
 use super::HAZARDS;
 
 /// Represents the ownership of a hazard pointer slot.
@@ -20,8 +39,11 @@ pub struct Shield<T> {
 
 impl<T> Shield<T> {
     /// Creates a new shield for hazard pointer.
+    #[track_caller]
     pub fn new(hazards: &HazardBag) -> Self {
         let slot = hazards.acquire_slot();
+        #[cfg(feature = "debug-hazard-leaks")]
+        slot.set_origin(Some(Origin::caller()));
         Self {
             slot: slot.into(),
             _marker: PhantomData,
@@ -34,68 +56,355 @@ impl<T> Shield<T> {
     ///    latest value.
     /// 3. If validated, return true. Otherwise, clear the slot (store 0) and return false.
     pub fn try_protect(&self, pointer: &mut *const T, src: &AtomicPtr<T>) -> bool {
-        unsafe {
-            fence(Ordering::SeqCst);
-
-            let ptr = (*pointer);
-            fence(Ordering::SeqCst);
-
-            let slot = self.slot.as_ref();
-            fence(Ordering::SeqCst);
-
-            slot.hazard.store(ptr as usize, Ordering::Release);
-            fence(Ordering::SeqCst);
+        self.try_protect_tagged(pointer, src, 0)
+    }
 
+    /// Like `try_protect`, but for a pointer tagged with the bits in `mask`, e.g. the mark bit of
+    /// a Harris list. The tag is removed from the hazard and ignored in the validation, and
+    /// `pointer` is updated to the latest tagged value.
+    pub fn try_protect_tagged(
+        &self,
+        pointer: &mut *const T,
+        src: &AtomicPtr<T>,
+        mask: usize,
+    ) -> bool {
+        let untagged = *pointer as usize & !mask;
+        self.try_protect_validate(untagged, || {
             let loaded = src.load(Ordering::Acquire) as *const T;
-            fence(Ordering::SeqCst);
+            *pointer = loaded;
+            loaded as usize & !mask == untagged
+        })
+    }
 
-            if loaded == ptr {
-                fence(Ordering::SeqCst);
-                true
-            } else {
-                fence(Ordering::SeqCst);
+    /// Like `try_protect`, but for a pointer in a crossbeam-epoch `Atomic`. The tag is removed
+    /// from the hazard and ignored in the validation, and `pointer` is updated to the latest tagged
+    /// value.
+    ///
+    /// This lets a data structure that publishes its nodes through `Atomic`s protect them with
+    /// hazard pointers, e.g. while it migrates from epoch-based reclamation. `guard` is only used
+    /// to load from `src`. If the nodes are retired with hazard pointers instead of
+    /// `Guard::defer_destroy`, it can be `epoch::unprotected()`.
+    pub fn try_protect_epoch<'g>(
+        &self,
+        pointer: &mut Shared<'g, T>,
+        src: &Atomic<T>,
+        guard: &'g Guard,
+    ) -> bool {
+        let untagged = pointer.as_raw() as usize;
+        self.try_protect_validate(untagged, || {
+            let loaded = src.load(Ordering::Acquire, guard);
+            *pointer = loaded;
+            loaded.as_raw() as usize == untagged
+        })
+    }
 
-                *pointer = loaded;
+    /// Like `protect`, but for a pointer in a crossbeam-epoch `Atomic`. See `try_protect_epoch`.
+    pub fn protect_epoch<'g>(&self, src: &Atomic<T>, guard: &'g Guard) -> Shared<'g, T> {
+        let mut pointer = src.load(Ordering::Acquire, guard);
+        let backoff = Backoff::default();
+        while !self.try_protect_epoch(&mut pointer, src, guard) {
+            metric_inc!(hp_protect_retries);
+            backoff.snooze();
+        }
+        pointer
+    }
+
+    /// Like `try_protect`, but validates `pointer` with `validate` instead of a source. The
+    /// validation succeeds if `validate` returns `pointer`, e.g. if the predecessor of a node
+    /// still links to it.
+    pub fn try_protect_with<F: Fn() -> *const T>(&self, pointer: *const T, validate: F) -> bool {
+        self.try_protect_validate(pointer as usize, || validate() == pointer)
+    }
 
-                fence(Ordering::SeqCst);
+    /// Stores `hazard` to the slot, and clears it if `validate` fails.
+    fn try_protect_validate(&self, hazard: usize, validate: impl FnOnce() -> bool) -> bool {
+        let slot = unsafe { self.slot.as_ref() };
+        slot.hazard.store(hazard, Ordering::Release);
+        // Order the store before the validation. See the module documentation.
+        membarrier::light();
 
-                slot.hazard.store(0, Ordering::Release);
+        failpoint!("hazard_pointer::try_protect::validate");
 
-                fence(Ordering::SeqCst);
+        if validate() {
+            true
+        } else {
+            slot.hazard.store(0, Ordering::Release);
+            false
+        }
+    }
 
-                false
+    /// Like `try_protect`, but for a pointer that may be null. A null pointer is not stored to the
+    /// slot. Instead, the slot is cleared and the validation checks that `src` is still null.
+    pub fn try_protect_nonnull(
+        &self,
+        pointer: &mut Option<NonNull<T>>,
+        src: &AtomicPtr<T>,
+    ) -> bool {
+        let mut raw = match pointer {
+            Some(pointer) => pointer.as_ptr() as *const T,
+            None => {
+                self.reset();
+                *pointer = NonNull::new(src.load(Ordering::Acquire));
+                return pointer.is_none();
             }
+        };
+        let validated = self.try_protect(&mut raw, src);
+        *pointer = NonNull::new(raw as *mut T);
+        validated
+    }
+
+    /// Like `protect`, but returns `None` for a null pointer instead of protecting it.
+    pub fn protect_nonnull(&self, src: &AtomicPtr<T>) -> Option<NonNull<T>> {
+        let mut pointer = NonNull::new(src.load(Ordering::Acquire));
+        let backoff = Backoff::default();
+        while !self.try_protect_nonnull(&mut pointer, src) {
+            metric_inc!(hp_protect_retries);
+            backoff.snooze();
         }
+        pointer
+    }
+
+    /// Protects `pointer` without validation, replacing the pointer protected by the shield.
+    ///
+    /// This is for a pointer known to be valid, e.g. a node that is not yet published. Unlike
+    /// `try_protect`, this doesn't check that the pointer is still reachable, so it doesn't stop
+    /// a `collect` that already saw the pointer retired.
+    pub fn set(&self, pointer: *const T) {
+        let slot = unsafe { self.slot.as_ref() };
+        slot.hazard.store(pointer as usize, Ordering::Release);
+        // Order the store before the later loads, as in `try_protect`.
+        membarrier::light();
+    }
+
+    /// Clears the pointer protected by the shield, keeping the slot for later use.
+    pub fn reset(&self) {
+        let slot = unsafe { self.slot.as_ref() };
+        slot.hazard.store(0, Ordering::Release);
+    }
+
+    /// Protects the pointer protected by `other`, replacing the pointer protected by the shield.
+    ///
+    /// This hands a protection over to this shield, e.g. from `curr` to `prev` in a list
+    /// traversal. The pointer stays protected as long as `other` keeps protecting it until this
+    /// returns.
+    pub fn copy_from(&self, other: &Shield<T>) {
+        let other = unsafe { other.slot.as_ref() };
+        self.set(other.hazard.load(Ordering::Relaxed) as *const T);
+    }
+
+    /// Converts the shield to a shield of another type, keeping the slot. The protected pointer
+    /// stays protected until it's replaced.
+    pub fn into_retyped<U>(self) -> Shield<U> {
+        let shield = Shield {
+            slot: self.slot,
+            _marker: PhantomData,
+        };
+        mem::forget(self);
+        shield
     }
 
     /// Get a protected pointer from `src`.
     pub fn protect(&self, src: &AtomicPtr<T>) -> *const T {
-        let mut pointer = src.load(Ordering::Relaxed) as *const T;
+        // Acquire, so the pointee is visible without relying on the validation in `try_protect`.
+        let mut pointer = src.load(Ordering::Acquire) as *const T;
+        let backoff = Backoff::default();
         while !self.try_protect(&mut pointer, src) {
-            #[cfg(feature = "check-loom")]
-                loom::sync::atomic::spin_loop_hint();
+            metric_inc!(hp_protect_retries);
+            backoff.snooze();
         }
         pointer
     }
+
+    /// Protects the pointer in `src` and returns a reference to its pointee, or `None` if it's
+    /// null. The reference borrows the shield, so it can't be used after the shield is dropped:
+    ///
+    /// ```compile_fail
+    /// # use core::sync::atomic::AtomicPtr;
+    /// # use cs431_homework::hazard_pointer::{HazardBag, Shield};
+    /// let hazards = HazardBag::new();
+    /// let src = AtomicPtr::new(Box::into_raw(Box::new(1)));
+    /// let shield = Shield::new(&hazards);
+    /// let value = unsafe { shield.protect_ref(&src) }.unwrap();
+    /// drop(shield);
+    /// assert_eq!(*value, 1);
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// The pointer in `src`, if not null, should point to a live `T` as long as it's protected by
+    /// a shield, e.g. it's retired only after it's unlinked from `src`. The shield should not be
+    /// re-pointed (by `protect`, `set`, `reset` etc.) while the reference is used. They take
+    /// `&self`, so the borrow doesn't prevent that.
+    pub unsafe fn protect_ref<'s>(&'s self, src: &AtomicPtr<T>) -> Option<&'s T> {
+        self.protect(src).as_ref()
+    }
+
+    /// Returns `true` if `src` still points to `expected`, which is protected by the shield.
+    ///
+    /// This rechecks a protection without protecting again, e.g. after a long computation on the
+    /// pointee, before acting on the assumption that `expected` is still reachable from `src`.
+    pub fn validate(&self, expected: *const T, src: &AtomicPtr<T>) -> bool {
+        debug_assert_eq!(
+            unsafe { self.slot.as_ref() }.hazard.load(Ordering::Relaxed),
+            expected as usize,
+            "validating a pointer that is not protected by the shield"
+        );
+        src.load(Ordering::Acquire) as *const T == expected
+    }
+
+    /// Protects the pointer in `src` and runs `f` on it. If `src` no longer points to it after `f`
+    /// returns, protects the new pointer and runs `f` again. Returns the pointer and the result of
+    /// `f` on it, which is computed while `src` pointed to it.
+    pub fn protect_and<R, F: FnMut(*const T) -> R>(
+        &self,
+        src: &AtomicPtr<T>,
+        mut f: F,
+    ) -> (*const T, R) {
+        loop {
+            let pointer = self.protect(src);
+            let result = f(pointer);
+            if self.validate(pointer, src) {
+                return (pointer, result);
+            }
+        }
+    }
 }
 
+/// A shield of the global `HAZARDS`.
+#[cfg(feature = "global-hazards")]
 impl<T> Default for Shield<T> {
+    #[track_caller]
     fn default() -> Self {
         Self::new(&HAZARDS)
     }
 }
 
 impl<T> Drop for Shield<T> {
-    /// Clear and release the ownership of the hazard slot.
+    /// Clear and release the ownership of the hazard slot. See `HazardSlot` for the orderings.
     fn drop(&mut self) {
         unsafe {
             let slot = self.slot.as_ref();
+            // Clear before deactivating, so that a claimer of the slot doesn't see the hazard.
             slot.hazard.store(0, Ordering::Release);
+            #[cfg(feature = "debug-hazard-leaks")]
+            slot.set_origin(None);
+            #[cfg(all(feature = "std", not(feature = "check-loom")))]
+            if slot.global && SlotCache::push(self.slot) {
+                return;
+            }
             slot.active.store(false, Ordering::Release);
         }
     }
 }
 
+/// Backoff between the retries of `try_protect` in `protect`.
+#[derive(Debug, Default)]
+struct Backoff {
+    #[cfg(all(feature = "std", not(feature = "check-loom")))]
+    inner: crossbeam_utils::Backoff,
+}
+
+impl Backoff {
+    /// Spins for exponentially longer, then yields to other threads as crossbeam's `Backoff` does.
+    /// Under loom, this is the model's spin hint. Without `std`, this only spins.
+    fn snooze(&self) {
+        #[cfg(all(feature = "std", not(feature = "check-loom")))]
+        self.inner.snooze();
+        #[cfg(feature = "check-loom")]
+        loom::sync::atomic::spin_loop_hint();
+        #[cfg(not(feature = "std"))]
+        core::hint::spin_loop();
+    }
+}
+
+/// `N` shields whose slots are acquired at once.
+pub struct ShieldArray<T, const N: usize> {
+    shields: [Shield<T>; N],
+}
+
+impl<T, const N: usize> ShieldArray<T, N> {
+    /// Creates `N` shields, acquiring their slots in one pass over the bag.
+    #[track_caller]
+    pub fn new(hazards: &HazardBag) -> Self {
+        let mut slots = [NonNull::dangling(); N];
+        hazards.acquire_slots(&mut slots);
+        #[cfg(feature = "debug-hazard-leaks")]
+        for slot in &slots {
+            unsafe { slot.as_ref() }.set_origin(Some(Origin::caller()));
+        }
+        Self {
+            shields: slots.map(|slot| Shield {
+                slot,
+                _marker: PhantomData,
+            }),
+        }
+    }
+
+    /// Try protecting the pointer `*pointer` with the `i`-th shield. See `Shield::try_protect`.
+    pub fn try_protect(&self, i: usize, pointer: &mut *const T, src: &AtomicPtr<T>) -> bool {
+        self.shields[i].try_protect(pointer, src)
+    }
+
+    /// Get a protected pointer from `src` with the `i`-th shield. See `Shield::protect`.
+    pub fn protect(&self, i: usize, src: &AtomicPtr<T>) -> *const T {
+        self.shields[i].protect(src)
+    }
+
+    /// Try protecting the pointer `*pointer` with the `i`-th shield. See
+    /// `Shield::try_protect_nonnull`.
+    pub fn try_protect_nonnull(
+        &self,
+        i: usize,
+        pointer: &mut Option<NonNull<T>>,
+        src: &AtomicPtr<T>,
+    ) -> bool {
+        self.shields[i].try_protect_nonnull(pointer, src)
+    }
+
+    /// Get a protected pointer from `src` with the `i`-th shield. See `Shield::protect_nonnull`.
+    pub fn protect_nonnull(&self, i: usize, src: &AtomicPtr<T>) -> Option<NonNull<T>> {
+        self.shields[i].protect_nonnull(src)
+    }
+
+    /// Protects `pointer` with the `i`-th shield without validation. See `Shield::set`.
+    pub fn set(&self, i: usize, pointer: *const T) {
+        self.shields[i].set(pointer)
+    }
+
+    /// Clears the pointer protected by the `i`-th shield. See `Shield::reset`.
+    pub fn reset(&self, i: usize) {
+        self.shields[i].reset()
+    }
+
+    /// Returns the shields, e.g. to move them separately.
+    pub fn into_inner(self) -> [Shield<T>; N] {
+        self.shields
+    }
+}
+
+impl<T, const N: usize> Index<usize> for ShieldArray<T, N> {
+    type Output = Shield<T>;
+
+    fn index(&self, i: usize) -> &Shield<T> {
+        &self.shields[i]
+    }
+}
+
+/// Shields of the global `HAZARDS`.
+#[cfg(feature = "global-hazards")]
+impl<T, const N: usize> Default for ShieldArray<T, N> {
+    #[track_caller]
+    fn default() -> Self {
+        Self::new(&HAZARDS)
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for ShieldArray<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.shields.iter()).finish()
+    }
+}
+
 impl<T> fmt::Debug for Shield<T> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Shield")
@@ -105,31 +414,253 @@ impl<T> fmt::Debug for Shield<T> {
     }
 }
 
+/// A protected pointer with its own slot. Unlike `Shield`, this can be sent to another thread,
+/// e.g. to finish the work on the pointer in a thread pool.
+pub struct ProtectedPtr<T> {
+    shield: Shield<T>,
+    pointer: *const T,
+}
+
+// The slot is released by any thread, and the pointer is shared with the thread.
+unsafe impl<T: Sync> Send for ProtectedPtr<T> {}
+unsafe impl<T: Sync> Sync for ProtectedPtr<T> {}
+
+impl<T> ProtectedPtr<T> {
+    /// Protects the pointer protected by `shield` with a new slot of `hazards`. The pointer is
+    /// protected by the new slot before this returns, so it stays protected when `shield` is
+    /// dropped or reused.
+    ///
+    /// # Safety
+    ///
+    /// `shield` must have been created from `hazards`. Otherwise the pointer may be freed by a
+    /// reclaimer of the bag of `shield`, which doesn't scan the new slot.
+    pub unsafe fn new(hazards: &HazardBag, shield: &Shield<T>) -> Self {
+        let new = Shield::new(hazards);
+        new.copy_from(shield);
+        let pointer = new.slot.as_ref().hazard.load(Ordering::Relaxed) as *const T;
+        Self {
+            shield: new,
+            pointer,
+        }
+    }
+
+    /// Returns the protected pointer.
+    pub fn as_ptr(&self) -> *const T {
+        self.pointer
+    }
+}
+
+impl<T> fmt::Debug for ProtectedPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProtectedPtr")
+            .field("shield", &self.shield)
+            .field("pointer", &self.pointer)
+            .finish()
+    }
+}
+
+/// A reference to a protected pointee. See `HazardBag::protect`.
+pub struct Protected<'s, T> {
+    shield: Shield<T>,
+    pointer: NonNull<T>,
+    _marker: PhantomData<&'s HazardBag>,
+}
+
+impl<T> Protected<'_, T> {
+    /// Returns the protected pointer.
+    pub fn as_ptr(&self) -> *const T {
+        self.pointer.as_ptr()
+    }
+}
+
+impl<T> Deref for Protected<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // The pointee is not freed while the slot announces it, i.e. until `self` is dropped.
+        unsafe { self.pointer.as_ref() }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Protected<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Protected")
+            .field("shield", &self.shield)
+            .field("value", &**self)
+            .finish()
+    }
+}
+
 /// Global bag (multiset) of hazards pointers.
 /// `HazardBag.head` and `HazardSlot.next` form a grow-only list of all hazard slots. Slots are
 /// never removed from this list. Instead, it gets deactivated and recycled for other `Shield`s.
+///
+/// The bag also keeps the pointers that dropped `RetiredSet`s couldn't free. See
+/// `HazardBag::orphan`.
 #[derive(Debug)]
 pub struct HazardBag {
     head: AtomicPtr<HazardSlot>,
+    /// Where `try_acquire_inactive` starts scanning: the slot after the last recycled slot, or
+    /// null for the head.
+    hint: AtomicPtr<HazardSlot>,
+    orphans: AtomicPtr<Orphans>,
 }
 
-/// See `HazardBag`
+/// Retired pointers left by a dropped `RetiredSet`. `HazardBag.orphans` and `Orphans.next` form a
+/// stack.
 #[derive(Debug)]
-struct HazardSlot {
+struct Orphans {
+    retired: Vec<Retired>,
+    next: *mut Orphans,
+}
+
+/// See `HazardBag`. The slots are opaque, and exposed only to identify them in debug queries.
+///
+/// A slot is released and claimed as follows:
+///
+/// - The owner clears `hazard` with Release, and then deactivates the slot with Release
+///   (`Shield::drop`). So a hazard is never published in an inactive slot, and the accesses of the
+///   owner to the protected pointer happen before a reclaimer that reads the cleared hazard or the
+///   deactivated slot with Acquire.
+/// - A claimer activates the slot with an Acquire CAS (`HazardBag::try_acquire_inactive`). So the
+///   previous owner's clear happens before the claimer's hazards, and can't erase them.
+///
+/// Each slot takes its own cache line (two on the architectures whose prefetcher pulls pairs of
+/// lines, as in crossbeam's `CachePadded`), so that the shields of different threads don't
+/// contend on the line when they update their hazards.
+#[cfg_attr(
+    any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "powerpc64"),
+    repr(align(128))
+)]
+#[cfg_attr(
+    not(any(target_arch = "x86_64", target_arch = "aarch64", target_arch = "powerpc64")),
+    repr(align(64))
+)]
+#[derive(Debug)]
+pub struct HazardSlot {
     // Whether this slot is occupied by a `Shield`.
     active: AtomicBool,
     // Machine representation of the hazard pointer.
     hazard: AtomicUsize,
     // Immutable pointer to the next slot in the bag.
     next: *const HazardSlot,
+    // Whether this slot belongs to the global `HAZARDS`, so that it's cached by `SlotCache`.
+    #[cfg(all(feature = "std", not(feature = "check-loom")))]
+    global: bool,
+    // Where the shield holding this slot was created.
+    #[cfg(feature = "debug-hazard-leaks")]
+    origin: Mutex<Option<Origin>>,
 }
 
 impl HazardSlot {
-    fn new(next: *const HazardSlot) -> Self {
+    fn new(next: *const HazardSlot, _global: bool) -> Self {
         Self {
             active: AtomicBool::new(true),
             hazard: AtomicUsize::new(0),
             next,
+            #[cfg(all(feature = "std", not(feature = "check-loom")))]
+            global: _global,
+            #[cfg(feature = "debug-hazard-leaks")]
+            origin: Mutex::new(None),
+        }
+    }
+
+    #[cfg(feature = "debug-hazard-leaks")]
+    fn set_origin(&self, origin: Option<Origin>) {
+        *self.origin.lock().unwrap() = origin;
+    }
+}
+
+/// Where a shield was created. See `HazardBag::assert_no_active_slots`.
+#[cfg(feature = "debug-hazard-leaks")]
+#[derive(Debug)]
+struct Origin {
+    location: &'static Location<'static>,
+    thread: Thread,
+    /// The number of shields created before this one by the thread.
+    serial: usize,
+}
+
+#[cfg(feature = "debug-hazard-leaks")]
+impl Origin {
+    #[track_caller]
+    fn caller() -> Self {
+        thread_local! {
+            static CREATED: Cell<usize> = Cell::new(0);
+        }
+        let serial = CREATED
+            .try_with(|created| created.replace(created.get() + 1))
+            .unwrap_or(0);
+        Self {
+            location: Location::caller(),
+            thread: thread::current(),
+            serial,
+        }
+    }
+}
+
+#[cfg(feature = "debug-hazard-leaks")]
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "shield #{} created at {} on ", self.serial, self.location)?;
+        match self.thread.name() {
+            Some(name) => write!(f, "thread '{}'", name),
+            None => write!(f, "{:?}", self.thread.id()),
+        }
+    }
+}
+
+/// Slots of `HAZARDS` released by the current thread.
+///
+/// The cached slots stay active, so that the thread's next shields reacquire them without walking
+/// or growing the shared list. They are released to the bag when the thread exits.
+#[cfg(all(feature = "std", not(feature = "check-loom")))]
+#[derive(Debug, Default)]
+struct SlotCache {
+    slots: Vec<NonNull<HazardSlot>>,
+}
+
+#[cfg(all(feature = "std", not(feature = "check-loom")))]
+thread_local! {
+    static SLOT_CACHE: RefCell<SlotCache> = RefCell::new(SlotCache::default());
+}
+
+#[cfg(all(feature = "std", not(feature = "check-loom")))]
+impl SlotCache {
+    /// The max number of slots cached by a thread.
+    const CAPACITY: usize = 8;
+
+    /// Takes a slot from the current thread's cache.
+    fn pop() -> Option<NonNull<HazardSlot>> {
+        SLOT_CACHE
+            .try_with(|cache| cache.borrow_mut().slots.pop())
+            .ok()
+            .flatten()
+    }
+
+    /// Puts an active slot of `HAZARDS` to the current thread's cache. Returns `false` if the
+    /// cache is full or already destroyed.
+    fn push(slot: NonNull<HazardSlot>) -> bool {
+        SLOT_CACHE
+            .try_with(|cache| {
+                let slots = &mut cache.borrow_mut().slots;
+                if slots.len() >= Self::CAPACITY {
+                    return false;
+                }
+                slots.push(slot);
+                true
+            })
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "check-loom")))]
+impl Drop for SlotCache {
+    fn drop(&mut self) {
+        for slot in self.slots.drain(..) {
+            unsafe { slot.as_ref() }
+                .active
+                .store(false, Ordering::Release);
         }
     }
 }
@@ -140,6 +671,8 @@ impl HazardBag {
     pub const fn new() -> Self {
         Self {
             head: AtomicPtr::new(ptr::null_mut()),
+            hint: AtomicPtr::new(ptr::null_mut()),
+            orphans: AtomicPtr::new(ptr::null_mut()),
         }
     }
 
@@ -148,72 +681,431 @@ impl HazardBag {
     pub fn new() -> Self {
         Self {
             head: AtomicPtr::new(ptr::null_mut()),
+            hint: AtomicPtr::new(ptr::null_mut()),
+            orphans: AtomicPtr::new(ptr::null_mut()),
         }
     }
 
     /// Acquires a slot in the hazard set, either by recycling an inactive slot or allocating a new
     /// slot.
     fn acquire_slot(&self) -> &HazardSlot {
-        unsafe {
-            // try recycling an inactive slot
-            if let Some(slot) = self.try_acquire_inactive() {
-                return slot;
+        // try reusing a slot released by this thread
+        #[cfg(all(feature = "std", not(feature = "check-loom")))]
+        if self.is_global() {
+            if let Some(slot) = SlotCache::pop() {
+                return unsafe { slot.as_ref() };
             }
+        }
 
-            // allocate a new slot and return
-            loop {
-                let head = self.head.load(Ordering::Acquire);
-                let new_slot = Box::new(HazardSlot::new(head));
+        // try recycling an inactive slot
+        if let Some(slot) = self.try_acquire_inactive() {
+            return slot;
+        }
 
-                let new_slot_raw = Box::into_raw(new_slot);
+        self.allocate_slot()
+    }
 
-                match self.head.compare_exchange(head, new_slot_raw, Ordering::Release, Ordering::Relaxed) {
-                    Ok(_) => { return &*new_slot_raw; }
-                    Err(_) => { drop(Box::from_raw(new_slot_raw)) }
+    /// Acquires `slots.len()` slots, like `acquire_slot`. Inactive slots are recycled in one pass
+    /// over the list, and the rest are allocated and pushed to the list at once.
+    fn acquire_slots(&self, slots: &mut [NonNull<HazardSlot>]) {
+        let mut acquired = 0;
+
+        // try reusing the slots released by this thread
+        #[cfg(all(feature = "std", not(feature = "check-loom")))]
+        if self.is_global() {
+            while acquired < slots.len() {
+                slots[acquired] = some_or!(SlotCache::pop(), break);
+                acquired += 1;
+            }
+        }
+
+        acquired += self.try_acquire_inactive_slots(&mut slots[acquired..]);
+        self.allocate_slots(&mut slots[acquired..]);
+    }
+
+    /// Allocates a new active slot and pushes it to the list.
+    fn allocate_slot(&self) -> &HazardSlot {
+        let mut slot = [NonNull::dangling()];
+        self.allocate_slots(&mut slot);
+        unsafe { &*slot[0].as_ptr() }
+    }
+
+    /// Allocates `slots.len()` new active slots, and pushes them to the list with one CAS. The
+    /// slots are linked in their order in `slots`.
+    fn allocate_slots(&self, slots: &mut [NonNull<HazardSlot>]) {
+        if slots.is_empty() {
+            return;
+        }
+        let global = self.is_global();
+        let mut head = self.head.load(Ordering::Acquire);
+        let mut next: *const HazardSlot = head;
+        for slot in slots.iter_mut().rev() {
+            let new_slot = Box::into_raw(Box::new(HazardSlot::new(next, global)));
+            *slot = unsafe { NonNull::new_unchecked(new_slot) };
+            next = new_slot;
+        }
+        let first = slots[0].as_ptr();
+        let last = slots[slots.len() - 1].as_ptr();
+        loop {
+            match self
+                .head
+                // Release publishes the new slots. Acquire on failure, so that the slots after the
+                // new slots are initialized when they're published. (The success ordering can't be
+                // weaker than the failure ordering.)
+                .compare_exchange(head, first, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => {
+                    metric_add!(hp_slot_allocs, slots.len());
+                    return;
+                }
+                // The slots are not published yet, so reuse them with the new head.
+                Err(current) => {
+                    head = current;
+                    unsafe { (*last).next = head };
                 }
             }
         }
     }
 
-    /// Find an inactive slot and activate it.
-    fn try_acquire_inactive(&self) -> Option<&HazardSlot> {
-        unsafe {
+    /// Returns the number of slots in the bag. Slots are never freed while the bag is alive, so this
+    /// stays at the high-water mark of the shields alive at once.
+    pub fn slot_count(&self) -> usize {
+        let mut count = 0;
+        let mut curr_p: *const HazardSlot = self.head.load(Ordering::Acquire);
+        while let Some(curr) = unsafe { curr_p.as_ref() } {
+            count += 1;
+            curr_p = curr.next;
+        }
+        count
+    }
+
+    /// Returns the number of active slots, i.e. the slots held by shields. The slots of `HAZARDS`
+    /// cached by threads are also active.
+    pub fn active_count(&self) -> usize {
+        let mut count = 0;
+        let mut curr_p: *const HazardSlot = self.head.load(Ordering::Acquire);
+        while let Some(curr) = unsafe { curr_p.as_ref() } {
+            if curr.active.load(Ordering::Acquire) {
+                count += 1;
+            }
+            curr_p = curr.next;
+        }
+        count
+    }
+
+    /// Panics if a slot of the bag is held by a shield, e.g. a shield that is forgotten with
+    /// `mem::forget` instead of dropped. With the `debug-hazard-leaks` feature, the panic message
+    /// says where the shields holding the slots were created.
+    ///
+    /// Without the feature, the slots of `HAZARDS` cached by threads count as held. See
+    /// `active_count`.
+    pub fn assert_no_active_slots(&self) {
+        #[cfg(not(feature = "debug-hazard-leaks"))]
+        {
+            let active = self.active_count();
+            assert!(
+                active == 0,
+                "{} hazard slots are still held by shields (enable the `debug-hazard-leaks` \
+                 feature to see where they were created)",
+                active
+            );
+        }
+        #[cfg(feature = "debug-hazard-leaks")]
+        {
+            // The number of origins in the message.
+            const SHOWN: usize = 16;
+
+            let mut leaks = Vec::<String>::new();
             let mut curr_p: *const HazardSlot = self.head.load(Ordering::Acquire);
-            while !curr_p.is_null() {
-                let curr = &*curr_p;
-                match curr.active.compare_exchange(false, true, Ordering::Release, Ordering::Relaxed) {
-                    Ok(_) => {
-                        return Some(curr);
-                    }
-                    Err(_) => {}
+            while let Some(curr) = unsafe { curr_p.as_ref() } {
+                if let Some(origin) = &*curr.origin.lock().unwrap() {
+                    leaks.push(origin.to_string());
                 }
                 curr_p = curr.next;
+            }
+            let count = leaks.len();
+            if count > SHOWN {
+                leaks.truncate(SHOWN);
+                leaks.push(format!("... and {} more", count - SHOWN));
+            }
+            assert!(
+                count == 0,
+                "{} hazard slots are still held by shields:\n{}",
+                count,
+                leaks.join("\n")
+            );
+        }
+    }
+
+    /// Returns `true` if this is the bag of the global `HAZARDS`.
+    #[cfg(feature = "global-hazards")]
+    fn is_global(&self) -> bool {
+        ptr::eq(self, HAZARDS.hazards())
+    }
+
+    /// Returns `true` if this is the bag of the global `HAZARDS`.
+    #[cfg(not(feature = "global-hazards"))]
+    fn is_global(&self) -> bool {
+        false
+    }
+
+    /// Find an inactive slot and activate it.
+    fn try_acquire_inactive(&self) -> Option<&HazardSlot> {
+        let mut slot = [NonNull::dangling()];
+        if self.try_acquire_inactive_slots(&mut slot) == 0 {
+            return None;
+        }
+        Some(unsafe { &*slot[0].as_ptr() })
+    }
+
+    /// Finds up to `slots.len()` inactive slots in one pass and activates them. Returns the number
+    /// of slots found, which are at the front of `slots`.
+    ///
+    /// The scan starts after the last slot recycled in the bag and wraps around to the head, so
+    /// that the slots are recycled in turn instead of contending on the ones near the head.
+    fn try_acquire_inactive_slots(&self, slots: &mut [NonNull<HazardSlot>]) -> usize {
+        if slots.is_empty() {
+            return 0;
+        }
+        // Acquire, so that the slot is initialized. It's published with Release below.
+        let start: *const HazardSlot = self.hint.load(Ordering::Acquire);
+        let head: *const HazardSlot = self.head.load(Ordering::Acquire);
+        let mut curr_p = if start.is_null() { head } else { start };
+        let mut wrapped = start.is_null();
+        let mut acquired = 0;
+        loop {
+            let curr = match unsafe { curr_p.as_ref() } {
+                Some(curr) => curr,
+                None if wrapped => return acquired,
+                None => {
+                    wrapped = true;
+                    curr_p = head;
+                    continue;
+                }
             };
-            None
+            if wrapped && curr_p == start {
+                return acquired;
+            }
+            // Check before the CAS, so that the scan doesn't write to the active slots.
+            if !curr.active.load(Ordering::Relaxed) {
+                // Acquire, so that the previous owner's clear of the hazard (`Shield::drop`)
+                // happens before the new owner's accesses. Otherwise, the clear may be ordered
+                // after the new owner's hazard and erase it.
+                if curr
+                    .active
+                    .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    slots[acquired] = curr.into();
+                    acquired += 1;
+                    if acquired == slots.len() {
+                        self.hint.store(curr.next as *mut _, Ordering::Release);
+                        return acquired;
+                    }
+                } else {
+                    metric_inc!(hp_slot_claim_failures);
+                }
+            }
+            curr_p = curr.next;
+        }
+    }
+
+    /// Protects the pointer in `src` with a new slot. Returns `None` if it's null.
+    ///
+    /// The slot is released when the returned `Protected` is dropped. The pointers stored in `src`
+    /// should be valid until they're unlinked and retired to this bag, like the pointers protected
+    /// by `Shield::protect`.
+    #[track_caller]
+    pub fn protect<T>(&self, src: &AtomicPtr<T>) -> Option<Protected<'_, T>> {
+        let shield = Shield::new(self);
+        let pointer = shield.protect_nonnull(src)?;
+        Some(Protected {
+            shield,
+            pointer,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Returns `true` if a slot of the set protects `ptr`.
+    pub fn is_protecting(&self, ptr: usize) -> bool {
+        self.hazards().any(|hazard| hazard == ptr)
+    }
+
+    /// Returns the active slots protecting `ptr`, e.g. to find out which shield keeps a retired
+    /// pointer from being freed.
+    #[cfg(debug_assertions)]
+    pub fn find_protectors(&self, ptr: usize) -> Vec<*const HazardSlot> {
+        let mut protectors = Vec::new();
+        let mut curr_p: *const HazardSlot = self.head.load(Ordering::Acquire);
+        while let Some(curr) = unsafe { curr_p.as_ref() } {
+            if curr.active.load(Ordering::Acquire) && curr.hazard.load(Ordering::Acquire) == ptr {
+                protectors.push(curr_p);
+            }
+            curr_p = curr.next;
         }
+        protectors
     }
 
-    /// Returns all the hazards in the set.
+    /// Returns all the hazards in the set. The null hazard is omitted, as in `hazards`.
+    #[cfg(feature = "std")]
     pub fn all_hazards(&self) -> HashSet<usize> {
-        unsafe {
-            let mut ret = HashSet::<usize>::new();
+        self.hazards().collect()
+    }
 
-            let mut curr_p: *const HazardSlot = self.head.load(Ordering::Acquire);
-            while !curr_p.is_null() {
-                let curr = &*curr_p;
-                if curr.active.load(Ordering::Acquire) == true {
-                    let hazard = curr.hazard.load(Ordering::Acquire);  // Todo:
-                    ret.insert(hazard);
+    /// Returns all the hazards in the set, sorted and deduplicated.
+    pub fn all_hazards_sorted(&self) -> Vec<usize> {
+        let mut hazards = self.hazards().collect::<Vec<_>>();
+        hazards.sort_unstable();
+        hazards.dedup();
+        hazards
+    }
+
+    /// Returns a snapshot of the hazards in the set. Unlike `all_hazards`, this doesn't hash the
+    /// hazards, so it's cheaper for a few membership checks.
+    pub fn snapshot(&self) -> HazardSnapshot {
+        HazardSnapshot {
+            hazards: self.all_hazards_sorted(),
+        }
+    }
+
+    /// Removes the candidates that are not protected by any slot, and returns them. The protected
+    /// candidates are left in `candidates`. The slots are traversed once.
+    ///
+    /// The candidates should be unlinked before this is called. Then the returned pointers can be
+    /// freed, as in `RetiredSet::collect`.
+    pub fn reclaimable(&self, candidates: &mut Vec<usize>) -> Vec<usize> {
+        self.reclaimable_by(candidates, |&candidate| candidate)
+    }
+
+    /// Like `reclaimable`, but the candidates are arbitrary values with a pointer.
+    pub(crate) fn reclaimable_by<T>(
+        &self,
+        candidates: &mut Vec<T>,
+        pointer: impl Fn(&T) -> usize,
+    ) -> Vec<T> {
+        // Order the unlinks of the candidates before the loads of the hazards. See the module
+        // documentation.
+        membarrier::heavy();
+        self.snapshot().reclaimable_by(candidates, pointer)
+    }
+
+    /// Hands over the retired pointers that a dropped `RetiredSet` couldn't free, so that they are
+    /// freed by the next `RetiredSet::collect` of another set.
+    #[cfg(not(feature = "check-loom"))]
+    pub(crate) fn orphan(&self, retired: Vec<Retired>) {
+        let orphans = Box::into_raw(Box::new(Orphans {
+            retired,
+            next: ptr::null_mut(),
+        }));
+        let mut head = self.orphans.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*orphans).next = head };
+            // Release the unlinks of the pointers to the adopter.
+            match self.orphans.compare_exchange_weak(
+                head,
+                orphans,
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(current) => head = current,
+            }
+        }
+    }
+
+    /// Takes all orphaned retired pointers.
+    pub(crate) fn adopt(&self) -> Vec<Retired> {
+        // Don't contend on the stack in the common case where there are no orphans.
+        if self.orphans.load(Ordering::Relaxed).is_null() {
+            return Vec::new();
+        }
+        let mut curr = self.orphans.swap(ptr::null_mut(), Ordering::Acquire);
+        let mut retired = Vec::new();
+        while !curr.is_null() {
+            let orphans = unsafe { Box::from_raw(curr) };
+            retired.extend(orphans.retired);
+            curr = orphans.next;
+        }
+        retired
+    }
+
+    /// Returns an iterator over the hazards in the set. The same hazard may be yielded more than
+    /// once. Unlike `all_hazards`, this doesn't require `std`.
+    ///
+    /// The null hazard of an active slot that protects nothing is omitted. It's never a valid
+    /// hazard, since a null pointer is never dereferenced or retired.
+    pub fn hazards(&self) -> Hazards<'_> {
+        Hazards {
+            curr: self.head.load(Ordering::Acquire),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Sorted hazards of a `HazardBag`. See `HazardBag::snapshot`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HazardSnapshot {
+    hazards: Vec<usize>,
+}
+
+impl HazardSnapshot {
+    /// Returns `true` if `hazard` was protected when the snapshot was taken.
+    pub fn contains(&self, hazard: usize) -> bool {
+        self.hazards.binary_search(&hazard).is_ok()
+    }
+
+    /// Returns the hazards, sorted.
+    pub fn as_slice(&self) -> &[usize] {
+        &self.hazards
+    }
+
+    /// Removes the candidates that are not in the snapshot, and returns them. The protected
+    /// candidates are left in `candidates`. See `HazardBag::reclaimable`.
+    pub(crate) fn reclaimable_by<T>(
+        &self,
+        candidates: &mut Vec<T>,
+        pointer: impl Fn(&T) -> usize,
+    ) -> Vec<T> {
+        let (protected, reclaimable) = mem::take(candidates)
+            .into_iter()
+            .partition(|candidate| self.contains(pointer(candidate)));
+        *candidates = protected;
+        reclaimable
+    }
+}
+
+/// Iterator over the hazards in a `HazardBag`. See `HazardBag::hazards`.
+#[derive(Debug)]
+pub struct Hazards<'s> {
+    curr: *const HazardSlot,
+    _marker: PhantomData<&'s HazardBag>,
+}
+
+impl Iterator for Hazards<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        // Slots are never freed while the bag is alive.
+        while let Some(curr) = unsafe { self.curr.as_ref() } {
+            self.curr = curr.next;
+            if curr.active.load(Ordering::Acquire) {
+                let hazard = curr.hazard.load(Ordering::Acquire);
+                if hazard != 0 {
+                    return Some(hazard);
                 }
-                curr_p = curr.next;
-            };
-            ret
+            }
         }
+        None
     }
 }
 
 impl Drop for HazardBag {
     fn drop(&mut self) {
+        #[cfg(feature = "debug-hazard-leaks")]
+        if !thread::panicking() {
+            self.assert_no_active_slots();
+        }
+
         unsafe {
             let mut curr_p: *const HazardSlot = self.head.load(Ordering::Acquire);
 
@@ -224,6 +1116,11 @@ impl Drop for HazardBag {
                 curr_p = next_p;
             };
         }
+
+        // No pointer is protected anymore.
+        for (pointer, deleter) in self.adopt() {
+            unsafe { deleter(pointer as *mut ()) };
+        }
     }
 }
 
@@ -233,18 +1130,27 @@ unsafe impl Sync for HazardSlot {}
 
 #[cfg(all(test, not(feature = "check-loom")))]
 mod tests {
-    use super::{HazardBag, Shield};
+    #[cfg(feature = "global-hazards")]
+    use super::HAZARDS;
+    use super::{HazardBag, HazardSlot, Shield, ShieldArray};
+    use crossbeam_utils::thread::scope;
     use std::collections::HashSet;
     use std::mem;
     use std::ops::Range;
-    use std::sync::{atomic::AtomicPtr, Arc};
+    use std::panic;
+    use std::ptr::{self, NonNull};
+    use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+    use std::sync::{mpsc, Arc, Barrier};
     use std::thread;
+    use std::time::Duration;
 
     const THREADS: usize = 8;
     const VALUES: Range<usize> = 1..1024;
 
-    // `all_hazards` should return hazards protected by shield(s).
+    // `all_hazards` should return hazards protected by shield(s). The shields are leaked on
+    // purpose, so this is skipped when the leaks are checked.
     #[test]
+    #[cfg_attr(feature = "debug-hazard-leaks", ignore)]
     fn all_hazards_protected() {
         let hazard_bag = Arc::new(HazardBag::new());
         let _ = (0..THREADS)
@@ -254,7 +1160,7 @@ mod tests {
                     for data in VALUES {
                         let src = AtomicPtr::new(data as *mut ());
                         let shield = Shield::new(&hazard_bag);
-                        shield.protect(&src);
+                        assert!(shield.protect_nonnull(&src).is_some());
                         // leak the shield so that
                         mem::forget(shield);
                     }
@@ -280,7 +1186,7 @@ mod tests {
                     for data in VALUES {
                         let src = AtomicPtr::new(data as *mut ());
                         let shield = Shield::new(&hazard_bag);
-                        shield.protect(&src);
+                        assert!(shield.protect_nonnull(&src).is_some());
                     }
                 })
             })
@@ -294,7 +1200,522 @@ mod tests {
         assert!(intersection.is_empty())
     }
 
-    // `acquire_slot` should recycle existing slots.
+    // `all_hazards` should return exactly the hazards of live shields of several threads, in
+    // bounded time.
+    #[test]
+    fn all_hazards_exact() {
+        let hazard_bag = Arc::new(HazardBag::new());
+        let protected = Arc::new(Barrier::new(THREADS + 1));
+        let checked = Arc::new(Barrier::new(THREADS + 1));
+        let handles = (0..THREADS)
+            .map(|t| {
+                let hazard_bag = hazard_bag.clone();
+                let protected = protected.clone();
+                let checked = checked.clone();
+                thread::spawn(move || {
+                    let shields = VALUES
+                        .filter(|data| data % THREADS == t)
+                        .map(|data| {
+                            let src = AtomicPtr::new(data as *mut ());
+                            let shield = Shield::new(&hazard_bag);
+                            assert!(shield.protect_nonnull(&src).is_some());
+                            shield
+                        })
+                        .collect::<Vec<_>>();
+                    let _ = protected.wait();
+                    let _ = checked.wait();
+                    drop(shields);
+                })
+            })
+            .collect::<Vec<_>>();
+        let _ = protected.wait();
+
+        let (sender, receiver) = mpsc::channel();
+        let _ = thread::spawn(move || sender.send(hazard_bag.all_hazards()).unwrap());
+        let all = receiver
+            .recv_timeout(Duration::from_secs(3))
+            .expect("all_hazards should return");
+        assert_eq!(all, VALUES.collect());
+
+        let _ = checked.wait();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    // `hazards`, `all_hazards_sorted` and `snapshot` should have the same hazards as
+    // `all_hazards`.
+    #[test]
+    fn hazards_iter() {
+        let hazard_bag = HazardBag::new();
+        let shields = VALUES
+            .map(|data| {
+                let src = AtomicPtr::new(data as *mut ());
+                let shield = Shield::new(&hazard_bag);
+                assert!(shield.protect_nonnull(&src).is_some());
+                shield
+            })
+            .collect::<Vec<_>>();
+        let mut hazards = hazard_bag.hazards().collect::<Vec<_>>();
+        hazards.sort_unstable();
+        assert_eq!(hazards, VALUES.collect::<Vec<_>>());
+        assert_eq!(hazard_bag.all_hazards_sorted(), hazards);
+        let snapshot = hazard_bag.snapshot();
+        assert_eq!(snapshot.as_slice(), &hazards[..]);
+        for data in VALUES {
+            assert!(snapshot.contains(data));
+        }
+        assert!(!snapshot.contains(0));
+        assert!(!snapshot.contains(VALUES.end));
+        assert_eq!(hazards.into_iter().collect::<HashSet<_>>(), hazard_bag.all_hazards());
+        drop(shields);
+        assert_eq!(hazard_bag.hazards().next(), None);
+    }
+
+    // `protect` should protect the pointee while the `Protected` is alive.
+    #[test]
+    fn protect_guard() {
+        let hazard_bag = HazardBag::new();
+        let src = AtomicPtr::new(ptr::null_mut());
+        assert!(hazard_bag.protect(&src).is_none());
+        assert_eq!(hazard_bag.hazards().next(), None);
+        assert_eq!(hazard_bag.active_count(), 0);
+
+        let mut value = 42usize;
+        src.store(&mut value, Ordering::Relaxed);
+        let protected = hazard_bag.protect(&src).unwrap();
+        assert_eq!(*protected, 42);
+        assert_eq!(protected.as_ptr(), &value as *const _);
+        assert!(hazard_bag.is_protecting(&value as *const _ as usize));
+        assert_eq!(hazard_bag.active_count(), 1);
+
+        drop(protected);
+        assert_eq!(hazard_bag.hazards().next(), None);
+        assert_eq!(hazard_bag.active_count(), 0);
+        assert_eq!(hazard_bag.slot_count(), 1);
+    }
+
+    // `find_protectors` should report every slot protecting the pointer.
+    #[cfg(debug_assertions)]
+    #[test]
+    fn find_protectors() {
+        let hazard_bag = HazardBag::new();
+        let src = AtomicPtr::new(VALUES.start as *mut ());
+        let shields = [Shield::new(&hazard_bag), Shield::new(&hazard_bag)];
+        let other = Shield::new(&hazard_bag);
+        other.set((VALUES.start + 1) as *const ());
+        for shield in &shields {
+            assert!(shield.protect_nonnull(&src).is_some());
+        }
+        assert!(hazard_bag.is_protecting(VALUES.start));
+        let mut protectors = hazard_bag.find_protectors(VALUES.start);
+        protectors.sort_unstable();
+        let mut slots = shields
+            .iter()
+            .map(|shield| shield.slot.as_ptr() as *const _)
+            .collect::<Vec<_>>();
+        slots.sort_unstable();
+        assert_eq!(protectors, slots);
+
+        let [first, second] = shields;
+        drop(first);
+        assert!(hazard_bag.is_protecting(VALUES.start));
+        assert_eq!(
+            hazard_bag.find_protectors(VALUES.start),
+            [second.slot.as_ptr() as *const _]
+        );
+        drop(second);
+        assert!(!hazard_bag.is_protecting(VALUES.start));
+        assert!(hazard_bag.find_protectors(VALUES.start).is_empty());
+    }
+
+    // Dropping a shield should clear its hazard and release its slot for the next shield.
+    #[test]
+    fn drop_releases_slot() {
+        let hazard_bag = HazardBag::new();
+        let src = AtomicPtr::new(1 as *mut ());
+        let slots = (0..1024)
+            .map(|_| {
+                let shield = Shield::<()>::new(&hazard_bag);
+                assert!(shield.protect_nonnull(&src).is_some());
+                let slot = shield.slot.as_ptr() as usize;
+                drop(shield);
+                assert_eq!(hazard_bag.hazards().next(), None);
+                slot
+            })
+            .collect::<HashSet<_>>();
+        assert_eq!(slots.len(), 1);
+    }
+
+    // Dropping the bag should free each slot once. Run under Miri or a sanitizer to check for
+    // leaks and double frees. The shields are leaked on purpose.
+    #[test]
+    #[cfg_attr(feature = "debug-hazard-leaks", ignore)]
+    fn drop_frees_slots() {
+        let hazard_bag = Arc::new(HazardBag::new());
+        let handles = (0..4)
+            .map(|_| {
+                let hazard_bag = hazard_bag.clone();
+                thread::spawn(move || {
+                    for _ in 0..128 {
+                        let shields = (0..8)
+                            .map(|_| Shield::<()>::new(&hazard_bag))
+                            .collect::<Vec<_>>();
+                        drop(shields);
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        // Leave some slots active.
+        let shields = (0..16)
+            .map(|_| Shield::<()>::new(&hazard_bag))
+            .collect::<Vec<_>>();
+        mem::forget(shields);
+        drop(Arc::try_unwrap(hazard_bag).unwrap());
+    }
+
+    // Slots allocated by contending threads should all be pushed to the list once. Run under a
+    // sanitizer to check for leaks and double frees. The shields are leaked on purpose.
+    #[test]
+    #[cfg_attr(feature = "debug-hazard-leaks", ignore)]
+    fn allocate_contended() {
+        const SHIELDS: usize = 256;
+        let hazard_bag = Arc::new(HazardBag::new());
+        let barrier = Arc::new(Barrier::new(THREADS));
+        let handles = (0..THREADS)
+            .map(|t| {
+                let hazard_bag = hazard_bag.clone();
+                let barrier = barrier.clone();
+                thread::spawn(move || {
+                    let _ = barrier.wait();
+                    let shields = (0..SHIELDS)
+                        .map(|i| {
+                            let shield = Shield::<()>::new(&hazard_bag);
+                            shield.set((t * SHIELDS + i + 1) as *const ());
+                            shield
+                        })
+                        .collect::<Vec<_>>();
+                    let slots = shields
+                        .iter()
+                        .map(|s| s.slot.as_ptr() as usize)
+                        .collect::<Vec<_>>();
+                    mem::forget(shields);
+                    slots
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut slots = HashSet::new();
+        for handle in handles {
+            slots.extend(handle.join().unwrap());
+        }
+
+        assert_eq!(slots.len(), THREADS * SHIELDS);
+        assert_eq!(hazard_bag.slot_count(), THREADS * SHIELDS);
+        assert_eq!(hazard_bag.all_hazards(), (1..=THREADS * SHIELDS).collect());
+        drop(Arc::try_unwrap(hazard_bag).unwrap());
+    }
+
+    // `reclaimable` should return exactly the unprotected candidates.
+    #[test]
+    fn reclaimable() {
+        let hazard_bag = HazardBag::new();
+        let shields = [8, 24, 40]
+            .iter()
+            .map(|&hazard| {
+                let shield = Shield::<()>::new(&hazard_bag);
+                shield.set(hazard as *const ());
+                shield
+            })
+            .collect::<Vec<_>>();
+        let mut candidates = (1..=6).map(|i| i * 8).collect::<Vec<_>>();
+        let reclaimable = hazard_bag.reclaimable(&mut candidates);
+        assert_eq!(candidates, [8, 24, 40]);
+        assert_eq!(reclaimable, [16, 32, 48]);
+
+        // The protected candidates are reclaimable once the shields are dropped.
+        drop(shields);
+        assert_eq!(hazard_bag.reclaimable(&mut candidates), [8, 24, 40]);
+        assert!(candidates.is_empty());
+        assert!(hazard_bag.reclaimable(&mut candidates).is_empty());
+    }
+
+    // Shields that protect nothing should not add the null hazard.
+    #[test]
+    fn idle_shields() {
+        let hazard_bag = HazardBag::new();
+        let shields = (0..64)
+            .map(|_| Shield::<()>::new(&hazard_bag))
+            .collect::<Vec<_>>();
+        let array = ShieldArray::<(), 4>::new(&hazard_bag);
+        let src = AtomicPtr::new(ptr::null_mut());
+        assert_eq!(shields[0].protect_nonnull(&src), None);
+        assert_eq!(array.protect(1, &src), ptr::null());
+        assert_eq!(hazard_bag.active_count(), 68);
+        assert_eq!(hazard_bag.all_hazards(), HashSet::new());
+        assert!(hazard_bag.all_hazards_sorted().is_empty());
+        assert!(hazard_bag.snapshot().as_slice().is_empty());
+        assert!(!hazard_bag.is_protecting(0));
+
+        shields[1].set(8 as *const ());
+        assert_eq!(hazard_bag.all_hazards_sorted(), [8]);
+        drop((shields, array));
+    }
+
+    // `set` and `reset` should be visible to `all_hazards` right away.
+    #[test]
+    fn set_reset() {
+        let hazard_bag = HazardBag::new();
+        let shield = Shield::new(&hazard_bag);
+        for data in VALUES {
+            shield.set(data as *const ());
+            assert_eq!(hazard_bag.all_hazards(), [data].iter().cloned().collect());
+            if data % 2 == 0 {
+                shield.reset();
+                assert_eq!(hazard_bag.all_hazards(), HashSet::new());
+            }
+        }
+        shield.reset();
+        // The slot is still held by the shield.
+        assert_eq!(hazard_bag.hazards().next(), None);
+        assert_eq!(hazard_bag.active_count(), 1);
+        drop(shield);
+        assert_eq!(hazard_bag.active_count(), 0);
+    }
+
+    // `ShieldArray` should protect a pointer per shield and release all its slots on drop.
+    #[test]
+    fn shield_array() {
+        let hazard_bag = HazardBag::new();
+        let shields = ShieldArray::<(), 4>::new(&hazard_bag);
+        assert_eq!(hazard_bag.hazards().next(), None);
+        assert_eq!(hazard_bag.active_count(), 4);
+        let src = AtomicPtr::new(1 as *mut ());
+        assert_eq!(shields.protect_nonnull(0, &src), NonNull::new(1 as *mut ()));
+        shields.set(1, 2 as *const ());
+        shields[2].set(3 as *const ());
+        assert_eq!(hazard_bag.all_hazards(), [1, 2, 3].iter().cloned().collect());
+        shields.reset(1);
+        assert_eq!(hazard_bag.all_hazards(), [1, 3].iter().cloned().collect());
+        drop(shields);
+        assert_eq!(hazard_bag.active_count(), 0);
+    }
+
+    // `try_protect_tagged` should protect the node even if its tag changes concurrently.
+    #[test]
+    fn try_protect_tagged() {
+        let hazard_bag = HazardBag::new();
+        let node = Box::into_raw(Box::new(0usize)) as usize;
+        let src = AtomicPtr::new(node as *mut usize);
+        let done = AtomicBool::new(false);
+        scope(|s| {
+            let _ = s.spawn(|_| {
+                while !done.load(Ordering::Relaxed) {
+                    src.store((node | 1) as *mut usize, Ordering::Relaxed);
+                    src.store(node as *mut usize, Ordering::Relaxed);
+                }
+            });
+            let shield = Shield::new(&hazard_bag);
+            for _ in VALUES {
+                let mut pointer = src.load(Ordering::Relaxed) as *const usize;
+                assert!(shield.try_protect_tagged(&mut pointer, &src, 1));
+                assert_eq!(pointer as usize & !1, node);
+                assert_eq!(hazard_bag.all_hazards(), [node].iter().cloned().collect());
+            }
+            done.store(true, Ordering::Relaxed);
+        })
+        .unwrap();
+        drop(unsafe { Box::from_raw(node as *mut usize) });
+    }
+
+    // `try_protect_with` should validate with the closure, not the source of the pointer.
+    #[test]
+    fn try_protect_with() {
+        let hazard_bag = HazardBag::new();
+        let shield = Shield::new(&hazard_bag);
+        // The pointer is loaded from `src`, and validated by its predecessor `pred_next`.
+        let src = AtomicPtr::new(1 as *mut ());
+        let pred_next = AtomicPtr::new(1 as *mut ());
+        let pointer = src.load(Ordering::Relaxed);
+
+        assert!(shield.try_protect_with(pointer, || pred_next.load(Ordering::Acquire)));
+        assert_eq!(hazard_bag.all_hazards(), [1].iter().cloned().collect());
+
+        // Changing `src` doesn't matter, but unlinking the node from the predecessor does.
+        src.store(2 as *mut (), Ordering::Relaxed);
+        assert!(shield.try_protect_with(pointer, || pred_next.load(Ordering::Acquire)));
+        pred_next.store(3 as *mut (), Ordering::Relaxed);
+        assert!(!shield.try_protect_with(pointer, || pred_next.load(Ordering::Acquire)));
+        assert_eq!(hazard_bag.all_hazards(), HashSet::new());
+    }
+
+    // `protect_nonnull` should not protect a null pointer.
+    #[test]
+    fn protect_nonnull() {
+        let hazard_bag = HazardBag::new();
+        let shield = Shield::new(&hazard_bag);
+        let src = AtomicPtr::new(1 as *mut ());
+        assert_eq!(shield.protect_nonnull(&src), NonNull::new(1 as *mut ()));
+        assert_eq!(hazard_bag.all_hazards(), [1].iter().cloned().collect());
+
+        src.store(ptr::null_mut(), Ordering::Relaxed);
+        assert_eq!(shield.protect_nonnull(&src), None);
+        // The previous pointer is no longer protected.
+        assert_eq!(hazard_bag.all_hazards(), HashSet::new());
+
+        // The validation fails if `src` has changed.
+        let mut pointer = None;
+        src.store(2 as *mut (), Ordering::Relaxed);
+        assert!(!shield.try_protect_nonnull(&mut pointer, &src));
+        assert_eq!(pointer, NonNull::new(2 as *mut ()));
+        assert!(shield.try_protect_nonnull(&mut pointer, &src));
+        assert_eq!(hazard_bag.all_hazards(), [2].iter().cloned().collect());
+        src.store(ptr::null_mut(), Ordering::Relaxed);
+        assert!(!shield.try_protect_nonnull(&mut pointer, &src));
+        assert_eq!(pointer, None);
+        assert!(shield.try_protect_nonnull(&mut pointer, &src));
+        assert_eq!(hazard_bag.all_hazards(), HashSet::new());
+    }
+
+    // `into_retyped` should keep the slot and its protection.
+    #[test]
+    fn into_retyped() {
+        let hazard_bag = HazardBag::new();
+        let shield = Shield::<u8>::new(&hazard_bag);
+        let slot = shield.slot;
+        let src = AtomicPtr::new(1 as *mut u8);
+        assert_eq!(shield.protect_nonnull(&src), NonNull::new(1 as *mut u8));
+
+        let shield = shield.into_retyped::<u16>();
+        assert_eq!(shield.slot, slot);
+        assert_eq!(hazard_bag.all_hazards(), [1].iter().cloned().collect());
+        let src = AtomicPtr::new(2 as *mut u16);
+        assert_eq!(shield.protect_nonnull(&src), NonNull::new(2 as *mut u16));
+        assert_eq!(hazard_bag.all_hazards(), [2].iter().cloned().collect());
+        assert_eq!((hazard_bag.slot_count(), hazard_bag.active_count()), (1, 1));
+
+        drop(shield);
+        assert_eq!(hazard_bag.active_count(), 0);
+    }
+
+    // `copy_from` should hand over a protection without a window where the node is unprotected.
+    #[test]
+    fn copy_from() {
+        struct Node {
+            next: AtomicPtr<Node>,
+        }
+
+        let hazard_bag = HazardBag::new();
+        let mut next = std::ptr::null_mut();
+        let mut nodes = Vec::new();
+        for _ in 0..3 {
+            next = Box::into_raw(Box::new(Node {
+                next: AtomicPtr::new(next),
+            }));
+            nodes.push(next as usize);
+        }
+        nodes.reverse();
+        let head = AtomicPtr::new(next);
+
+        let prev = Shield::<Node>::new(&hazard_bag);
+        let curr = Shield::<Node>::new(&hazard_bag);
+        let mut src = &head;
+        let mut prev_node = 0;
+        for node in nodes.iter().cloned() {
+            let p = curr.protect_nonnull(src).unwrap();
+            assert_eq!(p.as_ptr() as usize, node);
+            assert_eq!(
+                hazard_bag.all_hazards(),
+                [prev_node, node].iter().cloned().filter(|&n| n != 0).collect()
+            );
+
+            // Hand over the node. It's protected by both shields until `curr` moves on.
+            prev.copy_from(&curr);
+            assert_eq!(hazard_bag.all_hazards(), [node].iter().cloned().collect());
+            curr.reset();
+            assert_eq!(hazard_bag.all_hazards(), [node].iter().cloned().collect());
+            prev_node = node;
+            src = unsafe { &p.as_ref().next };
+        }
+        assert_eq!(curr.protect_nonnull(src), None);
+
+        drop((prev, curr));
+        for node in nodes {
+            drop(unsafe { Box::from_raw(node as *mut Node) });
+        }
+    }
+
+    // A thread should reuse the slots of `HAZARDS` it released, and release them on exit.
+    #[test]
+    #[cfg(feature = "global-hazards")]
+    fn slot_cache() {
+        let slot = thread::spawn(|| {
+            let slot = Shield::<()>::default().slot;
+            for _ in VALUES {
+                let shield = Shield::<()>::default();
+                assert_eq!(shield.slot, slot);
+            }
+            // `ShieldArray` also takes the cached slot, and allocates the other.
+            let shields = ShieldArray::<(), 2>::default();
+            assert_eq!(shields[0].slot, slot);
+            drop(shields);
+            // The cached slots are still active.
+            assert_eq!(HAZARDS.active_count(), 2);
+            slot.as_ptr() as usize
+        })
+        .join()
+        .unwrap();
+        let slot = unsafe { &*(slot as *const HazardSlot) };
+        assert!(!slot.active.load(Ordering::Relaxed));
+        assert_eq!(HAZARDS.active_count(), 0);
+        assert_eq!(HAZARDS.slot_count(), 2);
+    }
+
+    // `active_count` should count the live shields, and `slot_count` should stay at its
+    // high-water mark as the slots are never freed.
+    #[test]
+    fn slot_counts() {
+        let hazard_bag = HazardBag::new();
+        assert_eq!((hazard_bag.slot_count(), hazard_bag.active_count()), (0, 0));
+        let mut shields = (0..16)
+            .map(|_| Shield::<()>::new(&hazard_bag))
+            .collect::<Vec<_>>();
+        assert_eq!((hazard_bag.slot_count(), hazard_bag.active_count()), (16, 16));
+        shields.truncate(8);
+        assert_eq!((hazard_bag.slot_count(), hazard_bag.active_count()), (16, 8));
+        drop(shields);
+        assert_eq!((hazard_bag.slot_count(), hazard_bag.active_count()), (16, 0));
+
+        let shields = ShieldArray::<(), 4>::new(&hazard_bag);
+        assert_eq!((hazard_bag.slot_count(), hazard_bag.active_count()), (16, 4));
+        drop(shields);
+        assert_eq!((hazard_bag.slot_count(), hazard_bag.active_count()), (16, 0));
+    }
+
+    // Recycling should start after the last recycled slot, instead of reusing the head.
+    #[test]
+    fn recycle_in_turn() {
+        let hazard_bag = HazardBag::new();
+        let shields = (0..4)
+            .map(|_| Shield::<()>::new(&hazard_bag))
+            .collect::<Vec<_>>();
+        let mut slots = shields
+            .iter()
+            .map(|s| s.slot.as_ptr() as usize)
+            .collect::<Vec<_>>();
+        drop(shields);
+
+        // The slots are pushed to the head, so the list is in the reverse order of allocation.
+        slots.reverse();
+        for &slot in slots.iter().chain(&slots) {
+            let shield = Shield::<()>::new(&hazard_bag);
+            assert_eq!(shield.slot.as_ptr() as usize, slot);
+        }
+    }
+
+    // `acquire_slot` and `acquire_slots` should recycle existing slots.
     #[test]
     fn recycle_slots() {
         let hazard_bag = HazardBag::new();
@@ -320,5 +1741,125 @@ mod tests {
 
         // no new slots should've been created
         assert!(new_slots.is_subset(&old_slots));
+
+        let arrays = (0..128)
+            .map(|_| ShieldArray::<(), 4>::new(&hazard_bag))
+            .collect::<Vec<_>>();
+        let array_slots = arrays
+            .iter()
+            .flat_map(|a| (0..4).map(move |i| a[i].slot.as_ptr() as usize))
+            .collect::<HashSet<_>>();
+        assert_eq!(array_slots.len(), 512);
+        assert!(array_slots.is_subset(&old_slots));
+        assert!(array_slots.is_disjoint(&new_slots));
+    }
+
+    // `ShieldArray` should allocate the slots it can't recycle as one chain at the head.
+    #[test]
+    fn allocate_slots_chain() {
+        let hazard_bag = HazardBag::new();
+        drop(ShieldArray::<(), 2>::new(&hazard_bag));
+        let shields = ShieldArray::<(), 5>::new(&hazard_bag);
+        assert_eq!(hazard_bag.slot_count(), 5);
+
+        let mut slots = Vec::new();
+        let mut curr_p: *const HazardSlot = hazard_bag.head.load(Ordering::Acquire);
+        while let Some(curr) = unsafe { curr_p.as_ref() } {
+            slots.push(curr_p);
+            curr_p = curr.next;
+        }
+        // The 2 recycled slots are at the tail, after the 3 new ones in the order of the array.
+        let array = (0..5)
+            .map(|i| shields[i].slot.as_ptr() as *const HazardSlot)
+            .collect::<Vec<_>>();
+        assert_eq!(slots[..3], array[2..]);
+        assert_eq!(
+            slots[3..].iter().collect::<HashSet<_>>(),
+            array[..2].iter().collect()
+        );
+    }
+
+    // `validate` should report the swap of the source by another thread.
+    #[test]
+    fn validate_concurrent_swap() {
+        let hazard_bag = HazardBag::new();
+        let (mut a, mut b) = (1usize, 2usize);
+        let (pa, pb) = (&mut a as *mut usize, &mut b as *mut usize);
+        let src = AtomicPtr::new(pa);
+        let shield = Shield::new(&hazard_bag);
+        let pointer = shield.protect(&src);
+        assert!(shield.validate(pointer, &src));
+
+        let (pa, pb) = (pa as usize, pb as usize);
+        let src = &src;
+        let barrier = Barrier::new(2);
+        scope(|s| {
+            let _ = s.spawn(|_| {
+                let _ = barrier.wait();
+                src.store(pb as *mut usize, Ordering::Release);
+                let _ = barrier.wait();
+            });
+            // A long computation, during which the writer swaps the source.
+            assert_eq!(unsafe { *pointer }, 1);
+            let _ = barrier.wait();
+            let _ = barrier.wait();
+            assert!(!shield.validate(pointer, src));
+        })
+        .unwrap();
+
+        // `protect_and` reruns the closure on the new pointer.
+        let mut calls = 0;
+        let (pointer, value) = shield.protect_and(src, |pointer| {
+            calls += 1;
+            if calls == 1 {
+                src.store(pa as *mut usize, Ordering::Release);
+            }
+            unsafe { *pointer }
+        });
+        assert_eq!((pointer as usize, value, calls), (pa, 1, 2));
+    }
+
+    // `protect_ref` should protect the pointee it returns, and return `None` for null.
+    #[test]
+    fn protect_ref() {
+        let hazard_bag = HazardBag::new();
+        let mut value = 1usize;
+        let src = AtomicPtr::new(ptr::null_mut());
+        let shield = Shield::new(&hazard_bag);
+        assert_eq!(unsafe { shield.protect_ref(&src) }, None);
+
+        src.store(&mut value, Ordering::Release);
+        let pointee = unsafe { shield.protect_ref(&src) }.unwrap();
+        assert_eq!(*pointee, 1);
+        assert!(hazard_bag
+            .all_hazards()
+            .contains(&(pointee as *const usize as usize)));
+    }
+
+    // `assert_no_active_slots` should report a shield that is alive, and where it was created
+    // with `debug-hazard-leaks`.
+    #[test]
+    fn assert_no_active_slots() {
+        let hazard_bag = HazardBag::new();
+        drop(Shield::<()>::new(&hazard_bag));
+        hazard_bag.assert_no_active_slots();
+
+        let (shield, line) = (Shield::<()>::new(&hazard_bag), line!());
+        let message = *panic::catch_unwind(|| hazard_bag.assert_no_active_slots())
+            .unwrap_err()
+            .downcast::<String>()
+            .unwrap();
+        assert!(message.starts_with("1 hazard slots are still held by shields"));
+        if cfg!(feature = "debug-hazard-leaks") {
+            assert!(message.contains(&format!("{}:{}:", file!(), line)), "{}", message);
+        }
+        drop(shield);
+        hazard_bag.assert_no_active_slots();
+    }
+
+    // Adjacent slots should not share a cache line.
+    #[test]
+    fn slot_cache_line() {
+        assert!(mem::align_of::<HazardSlot>() >= 64);
     }
 }
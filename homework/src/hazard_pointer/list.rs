@@ -0,0 +1,329 @@
+//! Harris-Michael lock-free list map on hazard pointers.
+//!
+//! ```
+//! use cs431_homework::hazard_pointer::list::{HarrisList, ShieldBundle};
+//!
+//! let list = HarrisList::new();
+//! let guard = ShieldBundle::new();
+//! assert_eq!(list.insert(1, "one"), Ok(()));
+//! assert_eq!(list.insert(1, "uno"), Err("uno"));
+//! assert_eq!(list.lookup(&1, &guard), Some(&"one"));
+//! assert_eq!(list.delete(&1, &guard), Ok(&"one"));
+//! assert_eq!(list.lookup(&1, &guard), None);
+//! ```
+
+use core::cell::RefCell;
+use core::fmt;
+use core::mem;
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicPtr, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, Ordering};
+
+use super::{retire, Shield, ShieldArray};
+use crate::map::{MapError, NonblockingMap};
+
+/// The mark of a logically deleted node, in the `next` pointer of the node.
+const MARK: usize = 1;
+
+fn is_marked<T>(pointer: *mut T) -> bool {
+    pointer as usize & MARK != 0
+}
+
+fn marked<T>(pointer: *mut T) -> *mut T {
+    (pointer as usize | MARK) as *mut T
+}
+
+fn unmarked<T>(pointer: *mut T) -> *mut T {
+    (pointer as usize & !MARK) as *mut T
+}
+
+struct Node<K, V> {
+    key: K,
+    value: V,
+    /// The next node, marked if this node is logically deleted.
+    next: AtomicPtr<Node<K, V>>,
+}
+
+/// Shields of a traversal. `prev` protects the node of the link to `curr`.
+struct Shields<K, V> {
+    prev: Shield<Node<K, V>>,
+    curr: Shield<Node<K, V>>,
+}
+
+impl<K, V> Default for Shields<K, V> {
+    fn default() -> Self {
+        let [prev, curr] = ShieldArray::default().into_inner();
+        Self { prev, curr }
+    }
+}
+
+/// Shields that keep the values returned by `HarrisList` protected until the bundle is dropped.
+///
+/// Each returned reference adds a shield to the bundle, so a bundle should be dropped regularly,
+/// like an epoch `Guard`.
+#[derive(Debug, Default)]
+pub struct ShieldBundle {
+    shields: RefCell<Vec<Shield<()>>>,
+}
+
+impl ShieldBundle {
+    /// Creates a new, empty bundle.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of pointers kept protected by the bundle.
+    pub fn len(&self) -> usize {
+        self.shields.borrow().len()
+    }
+
+    /// Returns `true` if the bundle protects nothing.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Protects the pointer protected by `shield` until the bundle is dropped.
+    fn keep<T>(&self, shield: &Shield<T>) {
+        let kept = Shield::default();
+        kept.copy_from(shield);
+        self.shields.borrow_mut().push(kept.into_retyped());
+    }
+}
+
+/// Lock-free sorted list map of Harris, with the unlinking of Michael for hazard pointers.
+///
+/// A traversal unlinks the marked (logically deleted) nodes it meets instead of passing them, so
+/// it only follows the links of protected, unmarked nodes. Unlinked nodes are retired to the
+/// thread-local retired set.
+pub struct HarrisList<K, V> {
+    head: AtomicPtr<Node<K, V>>,
+}
+
+unsafe impl<K: Send, V: Send> Send for HarrisList<K, V> {}
+unsafe impl<K: Send + Sync, V: Send + Sync> Sync for HarrisList<K, V> {}
+
+impl<K, V> Default for HarrisList<K, V> {
+    fn default() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+}
+
+impl<K: Ord, V> HarrisList<K, V> {
+    /// Creates a new, empty list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Finds the first node whose key is not less than `key`. Returns whether its key is `key`,
+    /// the link to the node, and the node. The node of the link is protected by `shields.prev`
+    /// (unless the link is the head), and the node by `shields.curr`.
+    fn find<'s>(
+        &'s self,
+        key: &K,
+        shields: &'s mut Shields<K, V>,
+    ) -> (bool, &'s AtomicPtr<Node<K, V>>, *mut Node<K, V>) {
+        'retry: loop {
+            let mut prev = &self.head;
+            let mut curr = prev.load(Ordering::Acquire);
+            loop {
+                if curr.is_null() {
+                    return (false, prev, curr);
+                }
+                // Fails if `prev` is marked or no longer links to `curr`.
+                let mut pointer = curr as *const _;
+                if !shields.curr.try_protect(&mut pointer, prev) {
+                    continue 'retry;
+                }
+                let curr_ref = unsafe { &*curr };
+                let next = curr_ref.next.load(Ordering::Acquire);
+
+                if is_marked(next) {
+                    // Unlink `curr`. Its successor is protected in the next iteration.
+                    let next = unmarked(next);
+                    if prev
+                        .compare_exchange(curr, next, Ordering::Release, Ordering::Relaxed)
+                        .is_err()
+                    {
+                        continue 'retry;
+                    }
+                    retire(curr);
+                    curr = next;
+                    continue;
+                }
+
+                if curr_ref.key >= *key {
+                    return (curr_ref.key == *key, prev, curr);
+                }
+                prev = &curr_ref.next;
+                mem::swap(&mut shields.prev, &mut shields.curr);
+                curr = next;
+            }
+        }
+    }
+
+    /// Returns the value of `key`. The value is protected by `guard`.
+    pub fn lookup<'a>(&'a self, key: &K, guard: &'a ShieldBundle) -> Option<&'a V> {
+        let mut shields = Shields::default();
+        let (found, _, curr) = self.find(key, &mut shields);
+        if !found {
+            return None;
+        }
+        guard.keep(&shields.curr);
+        Some(unsafe { &(*curr).value })
+    }
+
+    /// Inserts a key-value pair. Returns the value back if the key is already in the list.
+    pub fn insert(&self, key: K, value: V) -> Result<(), V> {
+        let new = Box::into_raw(Box::new(Node {
+            key,
+            value,
+            next: AtomicPtr::new(ptr::null_mut()),
+        }));
+        let mut shields = Shields::default();
+        loop {
+            let (found, prev, curr) = self.find(unsafe { &(*new).key }, &mut shields);
+            if found {
+                let new = unsafe { Box::from_raw(new) };
+                return Err(new.value);
+            }
+            unsafe { (*new).next.store(curr, Ordering::Relaxed) };
+            if prev
+                .compare_exchange(curr, new, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Returns the value of `key`, inserting the value returned by `f` if the key is not in the
+    /// list. `f` is called at most once, and its value is dropped if another thread inserts the key
+    /// first. The value is protected by `guard`.
+    pub fn get_or_insert_with<'a, F: FnOnce() -> V>(
+        &'a self,
+        key: K,
+        f: F,
+        guard: &'a ShieldBundle,
+    ) -> &'a V {
+        let mut shields = Shields::default();
+        let (mut key, mut f) = (Some(key), Some(f));
+        // Created on the first miss, and reused if the insert loses.
+        let mut new: *mut Node<K, V> = ptr::null_mut();
+        let mut kept = None;
+        loop {
+            let key_ref = match &key {
+                Some(key) => key,
+                None => unsafe { &(*new).key },
+            };
+            let (found, prev, curr) = self.find(key_ref, &mut shields);
+            if found {
+                if !new.is_null() {
+                    drop(unsafe { Box::from_raw(new) });
+                }
+                guard.keep(&shields.curr);
+                return unsafe { &(*curr).value };
+            }
+
+            if new.is_null() {
+                new = Box::into_raw(Box::new(Node {
+                    key: key.take().unwrap(),
+                    value: (f.take().unwrap())(),
+                    next: AtomicPtr::new(ptr::null_mut()),
+                }));
+                // Protected before it's published, so that it can't be reclaimed before it's kept.
+                let shield = Shield::default();
+                shield.set(new);
+                kept = Some(shield);
+            }
+            unsafe { (*new).next.store(curr, Ordering::Relaxed) };
+            if prev
+                .compare_exchange(curr, new, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                guard.keep(kept.as_ref().unwrap());
+                return unsafe { &(*new).value };
+            }
+        }
+    }
+
+    /// Deletes `key` and returns its value. The value is protected by `guard`.
+    pub fn delete<'a>(&'a self, key: &K, guard: &'a ShieldBundle) -> Result<&'a V, MapError> {
+        let mut shields = Shields::default();
+        loop {
+            let (found, prev, curr) = self.find(key, &mut shields);
+            if !found {
+                return Err(MapError::NotFound);
+            }
+            let curr_ref = unsafe { &*curr };
+            let next = curr_ref.next.load(Ordering::Acquire);
+            if is_marked(next) {
+                // Deleted by another thread. `find` unlinks it.
+                continue;
+            }
+            if curr_ref
+                .next
+                .compare_exchange(next, marked(next), Ordering::AcqRel, Ordering::Relaxed)
+                .is_err()
+            {
+                continue;
+            }
+
+            let unlinked = prev
+                .compare_exchange(curr, next, Ordering::Release, Ordering::Relaxed)
+                .is_ok();
+            guard.keep(&shields.curr);
+            if unlinked {
+                retire(curr);
+            } else {
+                // Let `find` unlink it.
+                let _ = self.find(key, &mut shields);
+            }
+            return Ok(&curr_ref.value);
+        }
+    }
+}
+
+impl<K: Ord + Clone, V> NonblockingMap<K, V, ShieldBundle> for HarrisList<K, V> {
+    fn lookup<'a>(&'a self, key: &K, guard: &'a ShieldBundle) -> Option<&'a V> {
+        self.lookup(key, guard)
+    }
+
+    fn insert(&self, key: &K, value: V, _guard: &ShieldBundle) -> Result<(), V> {
+        self.insert(key.clone(), value)
+    }
+
+    fn get_or_insert_with<'a, F: FnOnce() -> V>(
+        &'a self,
+        key: &K,
+        f: F,
+        guard: &'a ShieldBundle,
+    ) -> &'a V {
+        self.get_or_insert_with(key.clone(), f, guard)
+    }
+
+    fn remove<'a>(&'a self, key: &K, guard: &'a ShieldBundle) -> Result<&'a V, MapError> {
+        self.delete(key, guard)
+    }
+}
+
+impl<K, V> Drop for HarrisList<K, V> {
+    fn drop(&mut self) {
+        let mut curr = self.head.load(Ordering::Relaxed);
+        while !curr.is_null() {
+            let node = unsafe { Box::from_raw(curr) };
+            curr = unmarked(node.next.load(Ordering::Relaxed));
+        }
+    }
+}
+
+impl<K, V> fmt::Debug for HarrisList<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HarrisList")
+            .field("head", &self.head)
+            .finish()
+    }
+}
@@ -0,0 +1,101 @@
+//! Fences of the hazard pointer protocol.
+//!
+//! A reader fences between storing a hazard and validating it with `light`, and a reclaimer fences
+//! between unlinking pointers and loading the hazards with `heavy`. See the module documentation of
+//! `hazard_pointer` for why the fences are needed.
+//!
+//! By default, both are `fence(SeqCst)`. With the `membarrier` feature on Linux, `light` is only a
+//! compiler fence, and `heavy` issues the `membarrier` system call, which makes every running
+//! thread of the process execute a full fence. Protecting is much more frequent than reclaiming, so
+//! this moves the cost of the fence to the rare side. If the kernel doesn't support expedited
+//! private barriers (before Linux 4.14), both fall back to `fence(SeqCst)`.
+
+use core::sync::atomic::compiler_fence;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{fence, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{fence, Ordering};
+
+#[cfg(all(
+    feature = "membarrier",
+    target_os = "linux",
+    not(feature = "check-loom")
+))]
+mod imp {
+    use core::sync::atomic::{AtomicU8, Ordering};
+
+    const MEMBARRIER_CMD_QUERY: libc::c_int = 0;
+    const MEMBARRIER_CMD_PRIVATE_EXPEDITED: libc::c_int = 1 << 3;
+    const MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED: libc::c_int = 1 << 4;
+
+    const UNKNOWN: u8 = 0;
+    const FENCE: u8 = 1;
+    const MEMBARRIER: u8 = 2;
+
+    /// Whether `membarrier` is used. Decided by the first fence of the process.
+    static STRATEGY: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+    fn membarrier(cmd: libc::c_int) -> libc::c_long {
+        unsafe { libc::syscall(libc::SYS_membarrier, cmd, 0) }
+    }
+
+    /// Returns `true` if the fences use `membarrier`.
+    pub(super) fn enabled() -> bool {
+        match STRATEGY.load(Ordering::Relaxed) {
+            FENCE => false,
+            MEMBARRIER => true,
+            _ => {
+                // Racing threads compute the same strategy, and registering twice is harmless.
+                let supported = membarrier(MEMBARRIER_CMD_QUERY);
+                let enabled = supported >= 0
+                    && supported & MEMBARRIER_CMD_PRIVATE_EXPEDITED as libc::c_long != 0
+                    && membarrier(MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED) == 0;
+                let strategy = if enabled { MEMBARRIER } else { FENCE };
+                STRATEGY.store(strategy, Ordering::Relaxed);
+                enabled
+            }
+        }
+    }
+
+    pub(super) fn barrier() {
+        assert_eq!(
+            membarrier(MEMBARRIER_CMD_PRIVATE_EXPEDITED),
+            0,
+            "membarrier failed after registration"
+        );
+    }
+}
+
+/// Without `membarrier`, the fences are `fence(SeqCst)`.
+#[cfg(not(all(
+    feature = "membarrier",
+    target_os = "linux",
+    not(feature = "check-loom")
+)))]
+mod imp {
+    pub(super) fn enabled() -> bool {
+        false
+    }
+
+    pub(super) fn barrier() {}
+}
+
+/// The reader side fence, between storing a hazard and validating it.
+#[inline]
+pub fn light() {
+    if imp::enabled() {
+        compiler_fence(Ordering::SeqCst);
+    } else {
+        fence(Ordering::SeqCst);
+    }
+}
+
+/// The reclaimer side fence, between unlinking pointers and loading the hazards. Pairs with
+/// `light`.
+pub fn heavy() {
+    if imp::enabled() {
+        imp::barrier();
+    } else {
+        fence(Ordering::SeqCst);
+    }
+}
@@ -5,21 +5,32 @@
 //! ```
 //! use std::ptr;
 //! use std::sync::atomic::{AtomicPtr, Ordering};
-//! use cs431_homework::hazard_pointer::{collect, retire, Shield};
+//! use cs431_homework::hazard_pointer::Domain;
 //!
-//! let shield = Shield::default();
+//! let domain = Domain::new();
+//! let mut retired = domain.retired_set();
+//!
+//! let shield = domain.shield();
 //! let atomic = AtomicPtr::new(Box::leak(Box::new(1usize)));
 //! let protected = shield.protect(&atomic);
 //! assert_eq!(unsafe { *protected }, 1);
 //!
 //! // unlink the block and retire
 //! atomic.store(ptr::null_mut(), Ordering::Relaxed);
-//! retire(protected);
+//! retired.retire(protected);
 //!
 //! // manually trigger reclamation (not necessary)
-//! collect();
+//! drop(shield);
+//! retired.collect();
 //! ```
 //!
+//! With the `global-hazards` feature (on by default), the global domain `HAZARDS` can be used
+//! without naming it: `Shield::default()` creates a shield of `HAZARDS`, and `retire` and
+//! `collect` use a thread-local retired set of it.
+//!
+//! See `stack::Stack`, `queue::Queue` and `list::HarrisList` for data structures using hazard
+//! pointers.
+//!
 //! # Algorithm and Synchronization
 //!
 //! Suppose a data structure has a memory block b. T1 wants to read the value written in b and T2
@@ -68,42 +79,82 @@
 //! between `T1-1` and `T1-2`, and another between `T2-1` and `T2-2`, then either
 //! `T1's fence ⊑ T2's fence` or `T2's fence ⊑ T1's fence` holds. Therefore, `T1-1 ⊑ T2-2` or
 //! `T2-1 ⊑ T1-2`.
+//!
+//! The fences are `membarrier::light` in `T1` and `membarrier::heavy` in `T2`. Both are SC fences by
+//! default. With the `membarrier` feature on Linux, the fence of `T1` is only a compiler fence, and
+//! the fence of `T2` is a process-wide barrier that executes an SC fence on every running thread,
+//! including `T1`. So the argument above still holds, while protecting is cheaper.
+//!
+//! # `no_std`
+//!
+//! Without the `std` feature, there is no thread-local retired set. Use `RetiredSet` directly
+//! instead of `retire` and `collect`. `HazardBag::all_hazards` returns a `HashSet`, so it's
+//! replaced by `all_hazards_sorted` and `snapshot`. `tests/no_std.rs` checks the build without
+//! `std`.
 
+#[cfg(all(feature = "std", feature = "global-hazards"))]
 use core::cell::RefCell;
 
 #[cfg(feature = "check-loom")]
 use loom::thread_local;
-#[cfg(not(feature = "check-loom"))]
+#[cfg(all(
+    feature = "std",
+    feature = "global-hazards",
+    not(feature = "check-loom")
+))]
 use std::thread_local;
 
+mod domain;
 mod hazard;
+#[cfg(all(feature = "std", feature = "global-hazards"))]
+pub mod list;
+pub mod membarrier;
+#[cfg(all(feature = "std", feature = "global-hazards"))]
+pub mod queue;
 mod retire;
+#[cfg(all(feature = "std", feature = "global-hazards"))]
+pub mod stack;
 
-pub use hazard::{HazardBag, Shield};
+pub use domain::Domain;
+pub use hazard::{
+    HazardBag, HazardSlot, HazardSnapshot, Hazards, Protected, ProtectedPtr, Shield, ShieldArray,
+};
 pub use retire::RetiredSet;
 
-#[cfg(not(feature = "check-loom"))]
-/// Default global bag of all hazard pointers.
-pub static HAZARDS: HazardBag = HazardBag::new();
+#[cfg(all(feature = "global-hazards", not(feature = "check-loom")))]
+/// Default global domain of hazard pointers.
+pub static HAZARDS: Domain = Domain::new();
 
-#[cfg(feature = "check-loom")]
+#[cfg(all(feature = "global-hazards", feature = "check-loom"))]
 loom::lazy_static! {
-    /// Default global bag of all hazard pointers.
-    pub static ref HAZARDS: HazardBag = HazardBag::new();
+    /// Default global domain of hazard pointers.
+    ///
+    /// `Domain::new` is not `const` under loom, so this is initialized lazily. Each execution of a
+    /// loom model gets a fresh domain.
+    pub static ref HAZARDS: Domain = Domain::new();
 }
 
+#[cfg(all(feature = "std", feature = "global-hazards"))]
 thread_local! {
     /// Default thread-local retired pointer list.
     static RETIRED: RefCell<RetiredSet<'static>> = RefCell::new(RetiredSet::default());
 }
 
 /// Retires a pointer.
+#[cfg(all(feature = "std", feature = "global-hazards"))]
 pub fn retire<T>(pointer: *const T) {
     RETIRED.with(|r| r.borrow_mut().retire(pointer));
 }
 
+/// Retires a pointer that is freed with `deleter`. See `RetiredSet::retire_with`.
+#[cfg(all(feature = "std", feature = "global-hazards"))]
+pub fn retire_with(pointer: *mut (), deleter: unsafe fn(*mut ())) {
+    RETIRED.with(|r| r.borrow_mut().retire_with(pointer, deleter));
+}
+
 /// Frees the pointers that are `retire`d by the current thread and not `protect`ed by any other
 /// threads.
+#[cfg(all(feature = "std", feature = "global-hazards"))]
 pub fn collect() {
     RETIRED.with(|r| r.borrow_mut().collect());
 }
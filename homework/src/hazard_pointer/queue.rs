@@ -0,0 +1,165 @@
+//! Michael-Scott queue on hazard pointers.
+//!
+//! ```
+//! use cs431_homework::hazard_pointer::queue::Queue;
+//!
+//! let queue = Queue::new();
+//! queue.push(1);
+//! queue.push(2);
+//! assert_eq!(queue.try_pop(), Some(1));
+//! assert_eq!(queue.try_pop(), Some(2));
+//! assert_eq!(queue.try_pop(), None);
+//! ```
+
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicPtr, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, Ordering};
+
+use super::{retire, Shield, ShieldArray};
+
+struct Node<T> {
+    /// Uninitialized in the sentinel node, i.e. the node pointed to by `head`.
+    data: MaybeUninit<T>,
+    next: AtomicPtr<Node<T>>,
+}
+
+impl<T> Node<T> {
+    fn new(data: MaybeUninit<T>) -> *mut Self {
+        Box::into_raw(Box::new(Self {
+            data,
+            next: AtomicPtr::new(ptr::null_mut()),
+        }))
+    }
+}
+
+/// Michael-Scott lock-free queue.
+///
+/// `push` protects the tail with a `Shield` of `HAZARDS`, and `try_pop` protects the head and its
+/// next node with two shields. Dequeued nodes are retired to the thread-local retired set.
+pub struct Queue<T> {
+    /// The sentinel node, followed by the nodes of the values.
+    head: AtomicPtr<Node<T>>,
+    /// The last node, or a node before it.
+    tail: AtomicPtr<Node<T>>,
+}
+
+unsafe impl<T: Send> Send for Queue<T> {}
+unsafe impl<T: Send> Sync for Queue<T> {}
+
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        let sentinel = Node::new(MaybeUninit::uninit());
+        Self {
+            head: AtomicPtr::new(sentinel),
+            tail: AtomicPtr::new(sentinel),
+        }
+    }
+}
+
+impl<T> Queue<T> {
+    /// Creates a new, empty queue.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a value to the back of the queue.
+    pub fn push(&self, t: T) {
+        let new = Node::new(MaybeUninit::new(t));
+        let shield = Shield::default();
+        loop {
+            // The tail is never null, and it's retired only after it's unlinked.
+            let tail_ref = unsafe { shield.protect_ref(&self.tail) }.unwrap();
+            let tail = tail_ref as *const _ as *mut Node<T>;
+            let next = tail_ref.next.load(Ordering::Acquire);
+            if !next.is_null() {
+                // The tail is lagging behind. Help advance it.
+                let _ =
+                    self.tail
+                        .compare_exchange(tail, next, Ordering::Release, Ordering::Relaxed);
+                continue;
+            }
+
+            if tail_ref
+                .next
+                .compare_exchange(ptr::null_mut(), new, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                let _ = self
+                    .tail
+                    .compare_exchange(tail, new, Ordering::Release, Ordering::Relaxed);
+                return;
+            }
+        }
+    }
+
+    /// Removes the value at the front of the queue. Returns `None` if the queue is empty.
+    pub fn try_pop(&self) -> Option<T> {
+        let shields = ShieldArray::<Node<T>, 2>::default();
+        loop {
+            let head = shields.protect(0, &self.head) as *mut Node<T>;
+            // The head is never null, and it's protected.
+            let next = shields.protect(1, unsafe { &(*head).next }) as *mut Node<T>;
+            // `next` may have been dequeued and retired after `head` is dequeued. It's not if
+            // `head` is still the head.
+            if self.head.load(Ordering::Acquire) != head {
+                continue;
+            }
+            if next.is_null() {
+                return None;
+            }
+
+            let tail = self.tail.load(Ordering::Acquire);
+            if head == tail {
+                // Don't let the head pass the tail. Help advance it.
+                let _ =
+                    self.tail
+                        .compare_exchange(tail, next, Ordering::Release, Ordering::Relaxed);
+                continue;
+            }
+
+            if self
+                .head
+                .compare_exchange(head, next, Ordering::Release, Ordering::Relaxed)
+                .is_ok()
+            {
+                // `next` is the new sentinel, so its value is moved out only by us.
+                let data = unsafe { ptr::read((*next).data.as_ptr()) };
+                retire(head);
+                return Some(data);
+            }
+        }
+    }
+
+    /// Returns `true` if the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        let shield = Shield::default();
+        // The head is never null, and it's retired only after it's unlinked.
+        let head = unsafe { shield.protect_ref(&self.head) }.unwrap();
+        head.next.load(Ordering::Acquire).is_null()
+    }
+}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        let sentinel = unsafe { Box::from_raw(self.head.load(Ordering::Relaxed)) };
+        let mut curr = sentinel.next.load(Ordering::Relaxed);
+        while !curr.is_null() {
+            let mut node = unsafe { Box::from_raw(curr) };
+            unsafe { ptr::drop_in_place(node.data.as_mut_ptr()) };
+            curr = node.next.load(Ordering::Relaxed);
+        }
+    }
+}
+
+impl<T> fmt::Debug for Queue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Queue")
+            .field("head", &self.head)
+            .field("tail", &self.tail)
+            .finish()
+    }
+}
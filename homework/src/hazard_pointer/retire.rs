@@ -1,70 +1,112 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
 use core::marker::PhantomData;
 #[cfg(not(feature = "check-loom"))]
+use core::mem;
+#[cfg(not(feature = "check-loom"))]
 use core::sync::atomic::{fence, Ordering};
 #[cfg(feature = "check-loom")]
 use loom::sync::atomic::{fence, Ordering};
 
-use super::{HazardBag, HAZARDS};
+#[cfg(feature = "global-hazards")]
+use super::HAZARDS;
+use super::{HazardBag, HazardSnapshot};
+
+/// A retired pointer. The first element of the pair is the machine representation of the pointer
+/// and the second is the function that frees it, e.g. `free::<T>` for a `Box<T>`.
+pub(crate) type Retired = (usize, unsafe fn(*mut ()));
 
 /// Thread-local list of retired pointers.
+///
+/// The pointers that are still protected when the set is dropped are handed over to its
+/// `HazardBag`, and freed by the `collect` of another set of the bag.
 #[derive(Debug)]
 pub struct RetiredSet<'s> {
     hazards: &'s HazardBag,
-    /// The first element of the pair is the machine representation of the pointer and the second
-    /// is the function pointer to `free::<T>` where `T` is the type of the object.
-    inner: Vec<(usize, unsafe fn(usize))>,
+    inner: Vec<Retired>,
+    /// `collect` is triggered when `threshold` pointers are retired.
+    threshold: usize,
     _marker: PhantomData<*const ()>, // !Send + !Sync
 }
 
 impl<'s> RetiredSet<'s> {
-    /// The max length of retired pointer list. `collect` is triggered when `THRESHOLD` pointers
-    /// are retired.
-    const THRESHOLD: usize = 64;
+    /// The default max length of retired pointer list. `collect` is triggered when `THRESHOLD`
+    /// pointers are retired.
+    pub const THRESHOLD: usize = 64;
 
     /// Create a new retired pointer list protected by the given `HazardBag`.
     pub fn new(hazards: &'s HazardBag) -> Self {
+        Self::with_threshold(hazards, Self::THRESHOLD)
+    }
+
+    /// Create a new retired pointer list that is collected when `threshold` pointers are retired.
+    /// A small threshold frees the pointers sooner, and a large one amortizes the scan of the
+    /// hazards.
+    pub fn with_threshold(hazards: &'s HazardBag, threshold: usize) -> Self {
         Self {
             hazards,
             inner: Vec::new(),
+            threshold,
             _marker: PhantomData,
         }
     }
 
     /// Retire a pointer.
     pub fn retire<T>(&mut self, pointer: *const T) {
-        unsafe fn free<T>(data: usize) {
+        unsafe fn free<T>(data: *mut ()) {
             drop(Box::from_raw(data as *mut T))
         }
 
-        self.inner.push((pointer as usize, free::<T>));
-        if self.inner.len() >= Self::THRESHOLD {
+        self.retire_with(pointer as *mut (), free::<T>);
+    }
+
+    /// Retire a pointer that is freed with `deleter`, e.g. a node allocated in an arena.
+    pub fn retire_with(&mut self, pointer: *mut (), deleter: unsafe fn(*mut ())) {
+        self.inner.push((pointer as usize, deleter));
+        if self.inner.len() >= self.threshold {
             self.collect();
         }
     }
+
     /// Free the pointers that are `retire`d by the current thread and not `protect`ed by any other
-    /// threads.
+    /// threads. The pointers left by dropped sets of the same `HazardBag` are freed as well. It
+    /// runs however few pointers are retired, e.g. to free them before the threshold is reached.
+    ///
+    /// The hazards are loaded once per call, into a `HazardSnapshot` that all retired pointers are
+    /// checked against.
     pub fn collect(&mut self) {
-        fence(Ordering::SeqCst);
-
-        let hazards = self.hazards.all_hazards();
+        self.inner.extend(self.hazards.adopt());
+        let reclaimable = self
+            .hazards
+            .reclaimable_by(&mut self.inner, |&(ptr, _)| ptr);
+        Self::free(reclaimable);
+    }
 
-        let asdf = &mut self.inner;
-        let mut new_inner = Vec::<(usize, unsafe fn(usize))>::new();
-        for (ptr, free) in asdf {
-            if hazards.contains(&ptr) {
-                new_inner.push((*ptr, *free));
-                continue;
-            }
+    /// Like `collect`, but checks the retired pointers against `snapshot` instead of taking a new
+    /// snapshot, e.g. to share one snapshot among several retired sets of the same bag. The
+    /// pointers left by dropped sets are not adopted.
+    ///
+    /// # Safety
+    ///
+    /// `snapshot` should be taken with `HazardBag::snapshot` from the bag of this set, after a
+    /// `membarrier::heavy` that follows the retirement of all pointers in this set. Otherwise, a
+    /// pointer protected after the snapshot was taken may be freed.
+    pub unsafe fn collect_with_snapshot(&mut self, snapshot: &HazardSnapshot) {
+        let reclaimable = snapshot.reclaimable_by(&mut self.inner, |&(ptr, _)| ptr);
+        Self::free(reclaimable);
+    }
 
-            unsafe { free(*ptr); }
+    /// Frees the reclaimable pointers.
+    fn free(reclaimable: Vec<Retired>) {
+        for (ptr, free) in reclaimable {
+            unsafe { free(ptr as *mut ()) };
         }
-
-        self.inner = new_inner;
         fence(Ordering::SeqCst);
-
     }
 }
 
+/// A retired set of the global `HAZARDS`.
+#[cfg(feature = "global-hazards")]
 impl Default for RetiredSet<'static> {
     fn default() -> Self {
         Self::new(&HAZARDS)
@@ -75,12 +117,12 @@ impl Default for RetiredSet<'static> {
 #[cfg(not(feature = "check-loom"))]
 impl Drop for RetiredSet<'_> {
     fn drop(&mut self) {
-        // In a production-quality implementation of hazard pointers, the remaining local retired
-        // pointers will be moved to a global list of retired pointers, which are then reclaimed by
-        // the other threads. For pedagogical purposes, here we simply wait for all retired pointers
-        // are no longer protected.
-        while !self.inner.is_empty() {
-            self.collect();
+        // Free what we can, and leave the protected pointers to the other threads instead of
+        // waiting for them. Otherwise, a thread that exits while its retired pointers are protected
+        // would block until the protection ends.
+        self.collect();
+        if !self.inner.is_empty() {
+            self.hazards.orphan(mem::take(&mut self.inner));
         }
     }
 }
@@ -88,9 +130,11 @@ impl Drop for RetiredSet<'_> {
 #[cfg(all(test, not(feature = "check-loom")))]
 mod tests {
     use super::{HazardBag, RetiredSet};
-    use std::cell::RefCell;
+    use crate::hazard_pointer::{membarrier, Shield};
+    use std::cell::{Cell, RefCell};
     use std::collections::HashSet;
     use std::rc::Rc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     // retire `THRESHOLD` pointers to trigger collection
     #[test]
@@ -111,4 +155,97 @@ mod tests {
 
         assert_eq!(freed, (0..RetiredSet::THRESHOLD).collect())
     }
+
+    // `retire_with` should free the pointers with the deleter.
+    #[test]
+    fn retire_with_arena() {
+        const NODES: usize = 16;
+
+        struct Arena {
+            freed: Cell<usize>,
+        }
+        struct Node {
+            arena: *const Arena,
+        }
+        // Returns the node to its arena.
+        unsafe fn free_node(node: *mut ()) {
+            let arena = &*(*(node as *mut Node)).arena;
+            arena.freed.set(arena.freed.get() + 1);
+        }
+
+        let arena = Arena {
+            freed: Cell::new(0),
+        };
+        let mut nodes = (0..NODES)
+            .map(|_| Node { arena: &arena })
+            .collect::<Vec<_>>();
+        let hazards = HazardBag::new();
+        let shield = Shield::new(&hazards);
+        shield.set(&nodes[0]);
+
+        let mut retires = RetiredSet::new(&hazards);
+        for node in &mut nodes {
+            retires.retire_with(node as *mut Node as *mut (), free_node);
+        }
+        retires.collect();
+        assert_eq!(arena.freed.get(), NODES - 1);
+
+        drop(shield);
+        retires.collect();
+        assert_eq!(arena.freed.get(), NODES);
+    }
+
+    // The protected pointers of a dropped set should be freed by another set.
+    #[test]
+    fn drop_orphans() {
+        let hazards = HazardBag::new();
+        let freed = Cell::new(0usize);
+        unsafe fn free_node(node: *mut ()) {
+            let freed = &*(node as *const Cell<usize>);
+            freed.set(freed.get() + 1);
+        }
+        let shield = Shield::new(&hazards);
+        shield.set(&freed);
+
+        let mut retires = RetiredSet::new(&hazards);
+        retires.retire_with(&freed as *const _ as *mut (), free_node);
+        drop(retires);
+        assert_eq!(freed.get(), 0);
+
+        let mut retires = RetiredSet::new(&hazards);
+        retires.collect();
+        assert_eq!(freed.get(), 0);
+        drop(shield);
+        retires.collect();
+        assert_eq!(freed.get(), 1);
+    }
+
+    // A snapshot should be reusable across collections, and keep protecting what it contains.
+    #[test]
+    fn collect_with_snapshot() {
+        static FREED: AtomicUsize = AtomicUsize::new(0);
+        unsafe fn free_node(_: *mut ()) {
+            let _ = FREED.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let hazards = HazardBag::new();
+        let shield = Shield::new(&hazards);
+        shield.set(8 as *const u8);
+
+        let mut retires = RetiredSet::new(&hazards);
+        for i in 1..=4 {
+            retires.retire_with((i * 8) as *mut (), free_node);
+        }
+        membarrier::heavy();
+        let snapshot = hazards.snapshot();
+        unsafe { retires.collect_with_snapshot(&snapshot) };
+        assert_eq!(FREED.load(Ordering::Relaxed), 3);
+
+        // The stale snapshot still protects the pointer.
+        drop(shield);
+        unsafe { retires.collect_with_snapshot(&snapshot) };
+        assert_eq!(FREED.load(Ordering::Relaxed), 3);
+        retires.collect();
+        assert_eq!(FREED.load(Ordering::Relaxed), 4);
+    }
 }
@@ -0,0 +1,17 @@
+//! Treiber's stack on hazard pointers.
+//!
+//! ```
+//! use cs431_homework::hazard_pointer::stack::Stack;
+//!
+//! let stack = Stack::new();
+//! stack.push(1);
+//! assert_eq!(stack.pop(), Some(1));
+//! assert_eq!(stack.pop(), None);
+//! ```
+
+use crate::reclaim::HpReclaim;
+use crate::TreiberStack;
+
+/// Treiber's lock-free stack on hazard pointers. `pop` protects the head with a `Shield` of
+/// `HAZARDS`, and retires the popped node to the thread-local retired set.
+pub type Stack<T> = TreiberStack<T, HpReclaim>;
@@ -1,15 +1,111 @@
 //! Thread-safe key/value cache.
 
 use std::collections::hash_map::{Entry, HashMap};
+use std::error::Error;
+use std::fmt;
 use std::hash::Hash;
-use std::sync::{Arc, Mutex, RwLock, LockResult};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Error returned when `Cache` can't return a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheError {
+    /// The thread computing the value panicked. The entry is removed, so the next call computes
+    /// the value again.
+    Panicked,
+    /// The value was still being computed by another thread when the timeout elapsed.
+    TimedOut,
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheError::Panicked => write!(f, "computation of the cached value panicked"),
+            CacheError::TimedOut => write!(f, "timed out waiting for the cached value"),
+        }
+    }
+}
+
+impl Error for CacheError {}
+
+#[derive(Debug)]
+enum State<V> {
+    Computing,
+    Ready(V),
+    Panicked,
+}
+
+/// Value of an entry, which is published by the thread that computes it.
+#[derive(Debug)]
+struct Slot<V> {
+    state: Mutex<State<V>>,
+    published: Condvar,
+}
+
+impl<V: Clone> Slot<V> {
+    /// Waits for the value to be published, until `deadline` if given.
+    fn wait(&self, deadline: Option<Instant>) -> Result<V, CacheError> {
+        let mut state = self.state.lock().unwrap();
+        if let State::Computing = *state {
+            metric_inc!(cache_waits);
+        }
+        loop {
+            match &*state {
+                State::Ready(value) => return Ok(value.clone()),
+                State::Panicked => return Err(CacheError::Panicked),
+                State::Computing => {}
+            }
+            state = match deadline {
+                None => self.published.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(CacheError::TimedOut);
+                    }
+                    self.published
+                        .wait_timeout(state, deadline - now)
+                        .unwrap()
+                        .0
+                }
+            };
+        }
+    }
+
+    fn publish(&self, state: State<V>) {
+        *self.state.lock().unwrap() = state;
+        self.published.notify_all();
+    }
+}
 
 /// Cache that remembers the result for each key.
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct Cache<K, V> {
-    // todo! This is an example cache type. Build your own cache type that satisfies the
-    // specification for `get_or_insert_with`.
-    inner: Mutex<HashMap<K, Arc<RwLock<Option<V>>>>>,
+    inner: Mutex<HashMap<K, Arc<Slot<V>>>>,
+}
+
+impl<K, V> Default for Cache<K, V> {
+    fn default() -> Self {
+        Self {
+            inner: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+/// Marks the slot as panicked if the computation unwinds before publishing the value.
+struct Computing<'c, K: Eq + Hash + Clone, V: Clone> {
+    cache: &'c Cache<K, V>,
+    key: K,
+    slot: Arc<Slot<V>>,
+    published: bool,
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> Drop for Computing<'_, K, V> {
+    fn drop(&mut self) {
+        if !self.published {
+            self.slot.publish(State::Panicked);
+            let _ = self.cache.remove_slot(&self.key, &self.slot);
+        }
+    }
 }
 
 impl<K: Eq + Hash + Clone, V: Clone> Cache<K, V> {
@@ -23,30 +119,95 @@ impl<K: Eq + Hash + Clone, V: Clone> Cache<K, V> {
     /// On the other hand, since `f` may consume a lot of resource (= money), it's desirable not to
     /// duplicate the work. That is, `f` should be run only once for each key. Specifically, even
     /// for the concurrent invocations of `get_or_insert_with(key, f)`, `f` is called only once.
+    ///
+    /// Panics if the thread computing the value panicked. See `try_get_or_insert_with`.
     pub fn get_or_insert_with<F: FnOnce(K) -> V>(&self, key: K, f: F) -> V {
-        use std::collections::hash_map::Entry;
+        self.try_get_or_insert_with(key, f)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
 
-        let mut map = self.inner.lock().unwrap();
+    /// Like `get_or_insert_with`, but returns `Err(CacheError::Panicked)` if another thread
+    /// computing the value panicked.
+    pub fn try_get_or_insert_with<F: FnOnce(K) -> V>(&self, key: K, f: F) -> Result<V, CacheError> {
+        self.get_or_compute(key, f, None)
+    }
+
+    /// Like `try_get_or_insert_with`, but waits at most `timeout` for another thread computing
+    /// the value, and returns `Err(CacheError::TimedOut)` if it's not done by then. `f` itself is
+    /// not interrupted.
+    pub fn get_or_insert_with_timeout<F: FnOnce(K) -> V>(
+        &self,
+        key: K,
+        timeout: Duration,
+        f: F,
+    ) -> Result<V, CacheError> {
+        self.get_or_compute(key, f, Some(Instant::now() + timeout))
+    }
 
-        let (found, has_inserted) = match map.entry(key.clone()) {
-            Entry::Occupied(o) => (o.into_mut().clone(), false),
-            Entry::Vacant(v) => {
-                let placeholder = Arc::new(RwLock::new(None));
-                let p = v.insert(placeholder);
-                (p.clone(), true)
+    fn get_or_compute<F: FnOnce(K) -> V>(
+        &self,
+        key: K,
+        f: F,
+        deadline: Option<Instant>,
+    ) -> Result<V, CacheError> {
+        let mut map = self.inner.lock().unwrap();
+        let slot = match map.entry(key.clone()) {
+            Entry::Occupied(o) => {
+                let slot = o.get().clone();
+                drop(map);
+                return slot.wait(deadline);
             }
+            Entry::Vacant(v) => v
+                .insert(Arc::new(Slot {
+                    state: Mutex::new(State::Computing),
+                    published: Condvar::new(),
+                }))
+                .clone(),
         };
-        let first_write_lock = if has_inserted { Some(found.write().unwrap()) } else { None };
         drop(map);
 
-        match first_write_lock {
-            Some(mut write_guard) => *write_guard = Some(f(key)),
-            None => (),
+        let mut computing = Computing {
+            cache: self,
+            key: key.clone(),
+            slot,
+            published: false,
+        };
+        failpoint!("cache::get_or_insert_with::compute");
+        let value = f(key);
+        computing.slot.publish(State::Ready(value.clone()));
+        computing.published = true;
+        Ok(value)
+    }
+
+    /// Removes the entry for `key` if it's `slot`.
+    fn remove_slot(&self, key: &K, slot: &Arc<Slot<V>>) -> bool {
+        match self.inner.lock().unwrap().entry(key.clone()) {
+            Entry::Occupied(o) if Arc::ptr_eq(o.get(), slot) => {
+                let _ = o.remove();
+                true
+            }
+            // The entry is already replaced by another one.
+            _ => false,
         }
-        // first_write_lock moved out.
+    }
 
-        let ret = found.read().unwrap().clone().unwrap();
+    /// Forget the value for `key`, so that the next `get_or_insert_with(key, f)` calls `f` again.
+    /// Returns `true` if there was an entry for `key`.
+    ///
+    /// An invocation of `get_or_insert_with(key, f)` that is already running is not affected: it
+    /// still returns the value it computed or waited for.
+    pub fn invalidate(&self, key: &K) -> bool {
+        self.inner.lock().unwrap().remove(key).is_some()
+    }
+
+    /// Forget the value for `key` if it satisfies `pred`. Returns `true` if the entry is removed.
+    ///
+    /// If the value for `key` is being computed, this waits for the computation to finish.
+    pub fn invalidate_if<P: FnOnce(&V) -> bool>(&self, key: &K, pred: P) -> bool {
+        let slot = some_or!(self.inner.lock().unwrap().get(key).cloned(), return false);
 
-        ret
+        // Don't hold the map lock while waiting for the computation.
+        let value = ok_or!(slot.wait(None), return false);
+        pred(&value) && self.remove_slot(key, &slot)
     }
 }
@@ -6,8 +6,8 @@ mod statistics;
 mod tcp;
 mod thread_pool;
 
-pub use cache::Cache;
+pub use cache::{Cache, CacheError};
 pub use handler::Handler;
 pub use statistics::{Report, Statistics};
 pub use tcp::CancellableTcpListener;
-pub use thread_pool::ThreadPool;
+pub use thread_pool::{PoolError, ThreadPool};
@@ -4,12 +4,75 @@
 
 // NOTE: Crossbeam channels are MPMC, which means that you don't need to wrap the receiver in
 // Arc<Mutex<..>>. Just clone the receiver and give it to each worker thread.
-use crossbeam_channel::{unbounded, Sender, RecvError};
+use crossbeam_channel::{bounded, unbounded, Receiver, Sender, RecvError, SendError, TrySendError};
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
 use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
 use itertools::{join, Itertools};
 
-struct Job(Box<dyn FnOnce() + Send + 'static>);
+/// Error returned by `ThreadPool::submit` and `ThreadPool::try_execute`. It holds the rejected job.
+pub enum PoolError<F> {
+    /// All workers have exited because jobs panicked, so no job can run anymore.
+    ShuttingDown(F),
+    /// The queue of a pool created with `with_capacity` is full.
+    QueueFull(F),
+}
+
+impl<F> PoolError<F> {
+    /// Returns the rejected job.
+    pub fn into_inner(self) -> F {
+        match self {
+            PoolError::ShuttingDown(f) | PoolError::QueueFull(f) => f,
+        }
+    }
+}
+
+impl<F> fmt::Debug for PoolError<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::ShuttingDown(_) => write!(f, "ShuttingDown(..)"),
+            PoolError::QueueFull(_) => write!(f, "QueueFull(..)"),
+        }
+    }
+}
+
+impl<F> fmt::Display for PoolError<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::ShuttingDown(_) => write!(f, "thread pool is shutting down"),
+            PoolError::QueueFull(_) => write!(f, "thread pool queue is full"),
+        }
+    }
+}
+
+impl<F> Error for PoolError<F> {}
+
+/// Job closure whose concrete type can be recovered when it's rejected.
+trait JobFn: Send {
+    fn run(self: Box<Self>);
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<F: FnOnce() + Send + 'static> JobFn for F {
+    fn run(self: Box<Self>) {
+        (*self)()
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+struct Job(Box<dyn JobFn>);
+
+impl Job {
+    /// Recovers the closure of the job created from `F`.
+    fn into_inner<F: 'static>(self) -> F {
+        *self.0.into_any().downcast().ok().unwrap()
+    }
+}
 
 #[derive(Debug)]
 struct Worker {
@@ -66,9 +129,17 @@ pub struct ThreadPool {
 impl ThreadPool {
     /// Create a new ThreadPool with `size` threads. Panics if the size is 0.
     pub fn new(size: usize) -> Self {
-        assert!(size > 0);
+        Self::with_channel(size, unbounded())
+    }
 
-        let (sender, receiver) = crossbeam_channel::unbounded::<Job>();
+    /// Create a new ThreadPool with `size` threads whose queue holds at most `capacity` jobs that
+    /// are not yet taken by a worker. Panics if the size is 0.
+    pub fn with_capacity(size: usize, capacity: usize) -> Self {
+        Self::with_channel(size, bounded(capacity))
+    }
+
+    fn with_channel(size: usize, (sender, receiver): (Sender<Job>, Receiver<Job>)) -> Self {
+        assert!(size > 0);
 
         let inner_pool = Arc::new(ThreadPoolInner::default());
         ThreadPool {
@@ -82,10 +153,8 @@ impl ThreadPool {
 
                         match job {
                             Ok(f) => {
-                                inner_pool.start_job();
-
                                 println!("Worker {} got a job; executing.", id);
-                                (f.0)();
+                                f.0.run();
                                 inner_pool.finish_job()
                             }
                             Err(_) => {
@@ -101,14 +170,62 @@ impl ThreadPool {
         }
     }
 
-    /// Execute a new job in the thread pool.
+    /// Execute a new job in the thread pool. If the queue is full, blocks until a worker takes a
+    /// job from it. Returns the job in `Err` if all workers have exited.
+    pub fn submit<F>(&self, f: F) -> Result<(), PoolError<F>>
+        where
+            F: FnOnce() + Send + 'static,
+    {
+        let job = Job{0: Box::new(f)};
+
+        // Counted before it's sent so that `join` waits for the jobs not yet received.
+        self.pool_inner.start_job();
+        let job_sender = self.job_sender.as_ref().unwrap();
+        if let Err(SendError(job)) = job_sender.send(job) {
+            self.pool_inner.finish_job();
+            return Err(PoolError::ShuttingDown(job.into_inner()));
+        }
+        metric_max!(pool_queue_depth_max, job_sender.len());
+        Ok(())
+    }
+
+    /// Execute a new job in the thread pool. If the queue is full, blocks until a worker takes a
+    /// job from it.
+    ///
+    /// Panics if all workers have exited.
+    #[deprecated(note = "use `submit`, which returns a `PoolError`")]
     pub fn execute<F>(&self, f: F)
         where
             F: FnOnce() + Send + 'static,
+    {
+        if let Err(e) = self.submit(f) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Execute a new job in the thread pool without blocking. Returns the job in `Err` if the
+    /// queue is full or all workers have exited.
+    pub fn try_execute<F>(&self, f: F) -> Result<(), PoolError<F>>
+        where
+            F: FnOnce() + Send + 'static,
     {
         let job = Job{0: Box::new(f)};
 
-        self.job_sender.as_ref().unwrap().send(job).unwrap()
+        self.pool_inner.start_job();
+        let job_sender = self.job_sender.as_ref().unwrap();
+        match job_sender.try_send(job) {
+            Ok(()) => {
+                metric_max!(pool_queue_depth_max, job_sender.len());
+                Ok(())
+            }
+            Err(e) => {
+                self.pool_inner.finish_job();
+                Err(match e {
+                    TrySendError::Full(job) => PoolError::QueueFull(job.into_inner()),
+                    TrySendError::Disconnected(job) => PoolError::ShuttingDown(job.into_inner()),
+                })
+            }
+        }
     }
 
     /// Block the current thread until all jobs in the pool have been executed.  NOTE: This method
@@ -1,30 +1,72 @@
 //! Homeworks
+//!
+//! Without the default `std` feature, only `hazard_pointer` and `GrowableArray` are available.
+//! They require the `alloc` feature.
 
+#![cfg_attr(not(feature = "std"), no_std)]
 #![warn(missing_docs)]
 #![warn(missing_debug_implementations)]
 #![allow(clippy::result_unit_err)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
 #[macro_use]
 mod utils;
 
+#[cfg(feature = "std")]
 mod arc;
+#[cfg(feature = "std")]
 mod art;
+#[cfg(feature = "std")]
 mod bst;
+#[cfg(feature = "std")]
 mod elim_stack;
+#[cfg(feature = "failpoints")]
+pub mod failpoint;
+#[cfg(feature = "alloc")]
 mod hash_table;
+#[cfg(feature = "alloc")]
 pub mod hazard_pointer;
+#[cfg(feature = "std")]
 pub mod hello_server;
+#[cfg(feature = "std")]
 mod linked_list;
+#[cfg(feature = "std")]
 mod list_set;
+#[cfg(feature = "std")]
 mod map;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "parallel")]
+mod parallel;
+#[cfg(feature = "std")]
+pub mod reclaim;
+#[cfg(feature = "std")]
+pub mod test_util;
+#[cfg(feature = "std")]
+mod treiber_stack;
 
+#[cfg(feature = "std")]
 pub use arc::Arc;
+#[cfg(feature = "std")]
 pub use art::{Art, Entry};
+#[cfg(feature = "std")]
 pub use bst::Bst;
+#[cfg(feature = "std")]
 pub use elim_stack::ElimStack;
-pub use hash_table::{GrowableArray, SplitOrderedList};
+#[cfg(feature = "alloc")]
+pub use hash_table::GrowableArray;
+#[cfg(feature = "std")]
+pub use hash_table::{DeleteError, SplitOrderedHashMap, SplitOrderedList};
+#[cfg(feature = "std")]
 pub use linked_list::LinkedList;
+#[cfg(feature = "std")]
 pub use list_set::OrderedListSet;
+#[cfg(feature = "std")]
 pub use map::{
-    ConcurrentMap, NonblockingConcurrentMap, NonblockingMap, RandGen, SequentialMap, StrStringMap,
+    CachedMap, ConcurrentMap, MapEntry, MapError, NonblockingConcurrentMap, NonblockingMap,
+    OccupiedEntry, RandGen, SequentialMap, StrStringMap, VacantEntry,
 };
+#[cfg(feature = "std")]
+pub use treiber_stack::TreiberStack;
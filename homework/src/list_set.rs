@@ -3,6 +3,8 @@ use std::cmp;
 use std::ptr;
 use std::sync::{Mutex, MutexGuard};
 
+use crate::map::MapError;
+
 #[derive(Debug)]
 struct Node<T> {
     data: T,
@@ -62,6 +64,18 @@ impl<T> OrderedListSet<T> {
             head: Mutex::new(ptr::null_mut()),
         }
     }
+
+    /// Creates a list from values sorted in increasing order without duplicates.
+    #[cfg_attr(not(feature = "parallel"), allow(dead_code))]
+    pub(crate) fn from_sorted(values: Vec<T>) -> Self {
+        let mut head = ptr::null_mut();
+        for data in values.into_iter().rev() {
+            head = Node::new(data, head);
+        }
+        Self {
+            head: Mutex::new(head),
+        }
+    }
 }
 
 impl<T: Ord> OrderedListSet<T> {
@@ -89,8 +103,9 @@ impl<T: Ord> OrderedListSet<T> {
         }
     }
 
-    /// Remove the key from the set and return it.
-    pub fn remove(&self, key: &T) -> Result<T, ()> {
+    /// Remove the key from the set and return it. Returns `Err(MapError::NotFound)` if the set
+    /// doesn't have the key.
+    pub fn remove(&self, key: &T) -> Result<T, MapError> {
         match self.find(&key) {
             (true, Cursor(mut guard)) => {
                 assert!(!(*guard).is_null());
@@ -100,9 +115,15 @@ impl<T: Ord> OrderedListSet<T> {
                 *guard = next_node;
                 Ok(data)
             }
-            (false, _) => Err(())
+            (false, _) => Err(MapError::NotFound)
         }
     }
+
+    /// Remove the key from the set and return it.
+    #[deprecated(note = "use `remove`, which returns a `MapError`")]
+    pub fn delete(&self, key: &T) -> Result<T, ()> {
+        self.remove(key).map_err(|_| ())
+    }
 }
 
 #[derive(Debug)]
@@ -0,0 +1,146 @@
+//! Nonblocking map with a cache of values derived from its entries.
+
+use core::marker::PhantomData;
+use crossbeam_epoch::{pin, Guard};
+
+use super::{MapError, NonblockingMap};
+use crate::hello_server::Cache;
+
+/// `NonblockingMap` from `usize` that memoizes values derived from its entries.
+///
+/// The backing map `M` is the source of truth. `get_derived` caches the derivation of the current
+/// value of a key, and `insert` and `remove` write through to `M` and invalidate the cached
+/// derivation.
+///
+/// # Guarantee
+///
+/// If `get_derived(key, derive)` returns `Some(d)`, then `d` was derived from a value that was
+/// the value of `key` in the map at some point during the call. In particular, once
+/// `remove(key)` returns, no `get_derived(key, ..)` that starts afterwards returns a derivation
+/// of the removed value.
+///
+/// To this end, each cached derivation remembers the address of the value it was derived from.
+/// An address identifies a value as long as the value is not reclaimed, and a value is not
+/// reclaimed until every thread that may have cached a derivation of it has removed it:
+///
+/// - A writer invalidates the entry after unlinking the value, while still pinned.
+/// - A reader that cached a derivation after the writer's invalidation rechecks the map and
+///   removes its own entry, while still pinned.
+#[derive(Debug)]
+pub struct CachedMap<V, D, M: NonblockingMap<usize, V>> {
+    map: M,
+    /// Derivations, tagged with the address of the value they were derived from.
+    cache: Cache<usize, (usize, D)>,
+    _marker: PhantomData<V>,
+}
+
+impl<V, D, M: NonblockingMap<usize, V> + Default> Default for CachedMap<V, D, M> {
+    fn default() -> Self {
+        Self {
+            map: M::default(),
+            cache: Cache::default(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<V, D: Clone, M: NonblockingMap<usize, V>> CachedMap<V, D, M> {
+    /// Creates a new cached map.
+    pub fn new() -> Self
+    where
+        M: Default,
+    {
+        Self::default()
+    }
+
+    /// Returns the value derived from the value of `key`, or `None` if there is no such key.
+    ///
+    /// `derive` is called only if no derivation of the current value of `key` is cached. See
+    /// `CachedMap` for the guarantee on the returned derivation.
+    pub fn get_derived<F>(&self, key: usize, derive: F) -> Option<D>
+    where
+        F: FnOnce(&V) -> D,
+    {
+        let guard = pin();
+        let mut derive = Some(derive);
+        loop {
+            let value = self.map.lookup(&key, &guard)?;
+            let addr = value as *const V as usize;
+
+            let mut derived_here = false;
+            let (derived_from, derived) = self.cache.get_or_insert_with(key, |_| {
+                derived_here = true;
+                (addr, (derive.take().unwrap())(value))
+            });
+
+            if derived_here {
+                // A writer may have unlinked `value` and invalidated the cache before our entry
+                // was published. Then nobody else will remove the entry, so do it ourselves.
+                if self.lookup_addr(key, &guard) != Some(addr) {
+                    let _ = self.cache.invalidate_if(&key, |(from, _)| *from == addr);
+                }
+                return Some(derived);
+            }
+
+            if derived_from == addr {
+                return Some(derived);
+            }
+
+            // The cached derivation is for a value that is no longer the value of `key`.
+            let _ = self
+                .cache
+                .invalidate_if(&key, |(from, _)| *from == derived_from);
+        }
+    }
+
+    fn lookup_addr(&self, key: usize, guard: &Guard) -> Option<usize> {
+        self.map
+            .lookup(&key, guard)
+            .map(|value| value as *const V as usize)
+    }
+}
+
+impl<V, D: Clone, M: NonblockingMap<usize, V>> NonblockingMap<usize, V> for CachedMap<V, D, M> {
+    fn lookup<'a>(&'a self, key: &usize, guard: &'a Guard) -> Option<&'a V> {
+        self.map.lookup(key, guard)
+    }
+
+    fn contains_key(&self, key: &usize, guard: &Guard) -> bool {
+        self.map.contains_key(key, guard)
+    }
+
+    fn insert(&self, key: &usize, value: V, guard: &Guard) -> Result<(), V> {
+        self.map.insert(key, value, guard)?;
+        let _ = self.cache.invalidate(key);
+        Ok(())
+    }
+
+    fn get_or_insert_with<'a, F: FnOnce() -> V>(
+        &'a self,
+        key: &usize,
+        f: F,
+        guard: &'a Guard,
+    ) -> &'a V {
+        // `f` is called only when the key is missing, which is when `insert` would invalidate. An
+        // extra invalidation if another thread inserted first is harmless.
+        let mut called = false;
+        let value = self.map.get_or_insert_with(
+            key,
+            || {
+                called = true;
+                f()
+            },
+            guard,
+        );
+        if called {
+            let _ = self.cache.invalidate(key);
+        }
+        value
+    }
+
+    fn remove<'a>(&'a self, key: &usize, guard: &'a Guard) -> Result<&'a V, MapError> {
+        let value = self.map.remove(key, guard)?;
+        let _ = self.cache.invalidate(key);
+        Ok(value)
+    }
+}
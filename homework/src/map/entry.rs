@@ -0,0 +1,143 @@
+//! Entry API of `NonblockingMap`.
+
+use core::fmt;
+
+use super::{MapError, NonblockingMap};
+
+/// Position of a key in a map, found by `NonblockingMap::entry`. The follow-up operation of an
+/// entry goes through it, so that a map can reuse the position instead of searching again.
+pub(crate) trait RawEntry<'g, K, V> {
+    /// Returns the key.
+    fn key(&self) -> &K;
+
+    /// Deletes the key. See `OccupiedEntry::delete`.
+    fn delete(self: Box<Self>) -> Result<&'g V, MapError>;
+
+    /// Inserts `value` for the key. See `VacantEntry::insert`.
+    fn insert(self: Box<Self>, value: V) -> Result<&'g V, V>;
+}
+
+/// Entry of a key in a `NonblockingMap`, returned by `NonblockingMap::entry`.
+///
+/// The entry reflects the map when it was created. Other threads may insert or delete the key
+/// before the follow-up operation, which then acts on the current state of the map.
+#[derive(Debug)]
+pub enum MapEntry<'g, K, V> {
+    /// The key was in the map.
+    Occupied(OccupiedEntry<'g, K, V>),
+    /// The key was not in the map.
+    Vacant(VacantEntry<'g, K, V>),
+}
+
+impl<'g, K, V> MapEntry<'g, K, V> {
+    /// Creates an entry at `raw`, occupied if `value` is `Some`.
+    pub(crate) fn from_raw(value: Option<&'g V>, raw: Box<dyn RawEntry<'g, K, V> + 'g>) -> Self {
+        match value {
+            Some(value) => MapEntry::Occupied(OccupiedEntry { value, raw }),
+            None => MapEntry::Vacant(VacantEntry { raw }),
+        }
+    }
+
+    /// Returns the key.
+    pub fn key(&self) -> &K {
+        match self {
+            MapEntry::Occupied(entry) => entry.key(),
+            MapEntry::Vacant(entry) => entry.key(),
+        }
+    }
+}
+
+/// Entry of a key that was in the map. See `MapEntry`.
+pub struct OccupiedEntry<'g, K, V> {
+    value: &'g V,
+    raw: Box<dyn RawEntry<'g, K, V> + 'g>,
+}
+
+impl<'g, K, V> OccupiedEntry<'g, K, V> {
+    /// Returns the key.
+    pub fn key(&self) -> &K {
+        self.raw.key()
+    }
+
+    /// Returns the value of the key when the entry was created.
+    pub fn get(&self) -> &'g V {
+        self.value
+    }
+
+    /// Deletes the key, and returns its value.
+    ///
+    /// If the key was deleted after the entry was created, it's deleted from the current state of
+    /// the map instead: this returns `Err(MapError::NotFound)` if the key is no longer in the map,
+    /// and otherwise the value inserted in the meantime, which may differ from `get`.
+    pub fn delete(self) -> Result<&'g V, MapError> {
+        self.raw.delete()
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for OccupiedEntry<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OccupiedEntry")
+            .field("key", self.key())
+            .field("value", self.value)
+            .finish()
+    }
+}
+
+/// Entry of a key that was not in the map. See `MapEntry`.
+pub struct VacantEntry<'g, K, V> {
+    raw: Box<dyn RawEntry<'g, K, V> + 'g>,
+}
+
+impl<'g, K, V> VacantEntry<'g, K, V> {
+    /// Returns the key.
+    pub fn key(&self) -> &K {
+        self.raw.key()
+    }
+
+    /// Inserts `value` for the key, and returns a reference to it.
+    ///
+    /// If another thread inserted the key after the entry was created, returns `value` back.
+    pub fn insert(self, value: V) -> Result<&'g V, V> {
+        self.raw.insert(value)
+    }
+}
+
+impl<K: fmt::Debug, V> fmt::Debug for VacantEntry<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VacantEntry")
+            .field("key", self.key())
+            .finish()
+    }
+}
+
+/// Entry of the default `NonblockingMap::entry`, which searches the map again for the follow-up
+/// operation.
+pub(crate) struct SearchEntry<'g, K, G, M: ?Sized> {
+    pub(crate) map: &'g M,
+    pub(crate) key: K,
+    pub(crate) guard: &'g G,
+}
+
+impl<'g, K, V, G, M: ?Sized + NonblockingMap<K, V, G>> RawEntry<'g, K, V>
+    for SearchEntry<'g, K, G, M>
+{
+    fn key(&self) -> &K {
+        &self.key
+    }
+
+    fn delete(self: Box<Self>) -> Result<&'g V, MapError> {
+        self.map.remove(&self.key, self.guard)
+    }
+
+    fn insert(self: Box<Self>, value: V) -> Result<&'g V, V> {
+        // `get_or_insert_with` takes the value only if it inserts it.
+        let mut value = Some(value);
+        let inserted = self
+            .map
+            .get_or_insert_with(&self.key, || value.take().unwrap(), self.guard);
+        match value {
+            None => Ok(inserted),
+            Some(value) => Err(value),
+        }
+    }
+}
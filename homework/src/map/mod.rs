@@ -0,0 +1,312 @@
+mod cached;
+mod entry;
+
+pub use cached::CachedMap;
+pub(crate) use entry::RawEntry;
+pub use entry::{MapEntry, OccupiedEntry, VacantEntry};
+
+use core::fmt;
+use core::marker::PhantomData;
+use crossbeam_epoch::Guard;
+use lock::{Lock, RawLock};
+use rand::{distributions::Alphanumeric, Rng};
+use std::error::Error;
+use std::sync::Arc;
+
+/// Types that has random generator
+pub trait RandGen {
+    /// Randomly generates a value.
+    fn rand_gen<R: Rng>(rng: &mut R) -> Self;
+}
+
+const KEY_MAX_LENGTH: usize = 4;
+
+impl RandGen for String {
+    fn rand_gen<R: Rng>(rng: &mut R) -> Self {
+        let length = rng.gen::<usize>() % KEY_MAX_LENGTH;
+        rng.sample_iter(&Alphanumeric)
+            .take(length)
+            .map(|x| x as char)
+            .collect()
+    }
+}
+
+impl RandGen for usize {
+    /// pick only 16 bits, MSB=0
+    fn rand_gen<R: Rng>(rng: &mut R) -> Self {
+        const MASK: usize = 0x4004004004007777usize;
+        rng.gen::<usize>() & MASK
+    }
+}
+
+impl RandGen for u32 {
+    /// pick only 16 bits
+    fn rand_gen<R: Rng>(rng: &mut R) -> Self {
+        const MASK: u32 = 0x66666666u32;
+        rng.gen::<u32>() & MASK
+    }
+}
+
+/// Error returned by a map operation that fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MapError {
+    /// The key is not in the map.
+    NotFound,
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::NotFound => write!(f, "key not found"),
+        }
+    }
+}
+
+impl Error for MapError {}
+
+/// Trait for a sequential key-value map.
+pub trait SequentialMap<K: ?Sized, V> {
+    /// Lookups a key.
+    fn lookup<'a>(&'a self, key: &'a K) -> Option<&'a V>;
+
+    /// Inserts a key-value pair.
+    fn insert<'a>(&'a mut self, key: &'a K, value: V) -> Result<&'a mut V, (&'a mut V, V)>;
+
+    /// Deletes a key. Returns `Err(MapError::NotFound)` if the key is not in the map.
+    fn remove(&mut self, key: &K) -> Result<V, MapError>;
+
+    /// Deletes a key.
+    #[deprecated(note = "use `remove`, which returns a `MapError`")]
+    fn delete(&mut self, key: &K) -> Result<V, ()> {
+        self.remove(key).map_err(|_| ())
+    }
+}
+
+/// Trait for a concurrent key-value map.
+pub trait ConcurrentMap<K: ?Sized, V> {
+    /// Lookups a key.
+    fn lookup<'a, F, R>(&'a self, key: &'a K, guard: &'a Guard, f: F) -> R
+    where
+        F: FnOnce(Option<&V>) -> R;
+
+    /// Inserts a key-value pair.
+    fn insert<'a>(&'a self, key: &'a K, value: V, guard: &'a Guard) -> Result<(), V>;
+
+    /// Deletes a key. Returns `Err(MapError::NotFound)` if the key is not in the map.
+    fn remove(&self, key: &K, guard: &Guard) -> Result<V, MapError>;
+
+    /// Deletes a key.
+    #[deprecated(note = "use `remove`, which returns a `MapError`")]
+    fn delete(&self, key: &K, guard: &Guard) -> Result<V, ()> {
+        self.remove(key, guard).map_err(|_| ())
+    }
+}
+
+/// Trait for a nonblocking key-value map.
+///
+/// The guard `G` keeps the returned references valid. It's an epoch `Guard` by default, and e.g.
+/// a `ShieldBundle` for the maps on hazard pointers.
+pub trait NonblockingMap<K: ?Sized, V, G = Guard> {
+    /// Lookups the given key to get the reference to its value.
+    fn lookup<'a>(&'a self, key: &K, guard: &'a G) -> Option<&'a V>;
+
+    /// Returns `true` if the given key is in the map.
+    fn contains_key(&self, key: &K, guard: &G) -> bool {
+        self.lookup(key, guard).is_some()
+    }
+
+    /// Inserts a key-value pair.
+    fn insert(&self, key: &K, value: V, guard: &G) -> Result<(), V>;
+
+    /// Returns the value of the given key, inserting the value returned by `f` if the key is not in
+    /// the map.
+    ///
+    /// `f` is called at most once. If another thread inserts the key first, the value returned by
+    /// `f` is dropped and the value of the other thread is returned.
+    fn get_or_insert_with<'a, F: FnOnce() -> V>(&'a self, key: &K, f: F, guard: &'a G) -> &'a V;
+
+    /// Deletes the given key and its value. Returns `Err(MapError::NotFound)` if the key is not in
+    /// the map.
+    fn remove<'a>(&'a self, key: &K, guard: &'a G) -> Result<&'a V, MapError>;
+
+    /// Deletes the given key and its value.
+    #[deprecated(note = "use `remove`, which returns a `MapError`")]
+    fn delete<'a>(&'a self, key: &K, guard: &'a G) -> Result<&'a V, ()> {
+        self.remove(key, guard).map_err(|_| ())
+    }
+
+    /// Returns the entry of the given key, to inspect it and then delete it or insert a value for
+    /// it. See `MapEntry`.
+    ///
+    /// The default implementation searches the map again for the follow-up operation. A map that
+    /// can keep the position of the key should override it.
+    fn entry<'a>(&'a self, key: K, guard: &'a G) -> MapEntry<'a, K, V>
+    where
+        K: Sized + 'a,
+    {
+        let value = self.lookup(&key, guard);
+        let raw = entry::SearchEntry {
+            map: self,
+            key,
+            guard,
+        };
+        MapEntry::from_raw(value, Box::new(raw))
+    }
+}
+
+impl<K: ?Sized, V, G, M: ?Sized + NonblockingMap<K, V, G>> NonblockingMap<K, V, G> for &M {
+    fn lookup<'a>(&'a self, key: &K, guard: &'a G) -> Option<&'a V> {
+        (**self).lookup(key, guard)
+    }
+
+    fn contains_key(&self, key: &K, guard: &G) -> bool {
+        (**self).contains_key(key, guard)
+    }
+
+    fn insert(&self, key: &K, value: V, guard: &G) -> Result<(), V> {
+        (**self).insert(key, value, guard)
+    }
+
+    fn get_or_insert_with<'a, F: FnOnce() -> V>(&'a self, key: &K, f: F, guard: &'a G) -> &'a V {
+        (**self).get_or_insert_with(key, f, guard)
+    }
+
+    fn remove<'a>(&'a self, key: &K, guard: &'a G) -> Result<&'a V, MapError> {
+        (**self).remove(key, guard)
+    }
+
+    fn entry<'a>(&'a self, key: K, guard: &'a G) -> MapEntry<'a, K, V>
+    where
+        K: Sized + 'a,
+    {
+        (**self).entry(key, guard)
+    }
+}
+
+impl<K: ?Sized, V, G, M: ?Sized + NonblockingMap<K, V, G>> NonblockingMap<K, V, G> for Arc<M> {
+    fn lookup<'a>(&'a self, key: &K, guard: &'a G) -> Option<&'a V> {
+        (**self).lookup(key, guard)
+    }
+
+    fn contains_key(&self, key: &K, guard: &G) -> bool {
+        (**self).contains_key(key, guard)
+    }
+
+    fn insert(&self, key: &K, value: V, guard: &G) -> Result<(), V> {
+        (**self).insert(key, value, guard)
+    }
+
+    fn get_or_insert_with<'a, F: FnOnce() -> V>(&'a self, key: &K, f: F, guard: &'a G) -> &'a V {
+        (**self).get_or_insert_with(key, f, guard)
+    }
+
+    fn remove<'a>(&'a self, key: &K, guard: &'a G) -> Result<&'a V, MapError> {
+        (**self).remove(key, guard)
+    }
+
+    fn entry<'a>(&'a self, key: K, guard: &'a G) -> MapEntry<'a, K, V>
+    where
+        K: Sized + 'a,
+    {
+        (**self).entry(key, guard)
+    }
+}
+
+impl<K: ?Sized, V, G, M: ?Sized + NonblockingMap<K, V, G>> NonblockingMap<K, V, G> for Box<M> {
+    fn lookup<'a>(&'a self, key: &K, guard: &'a G) -> Option<&'a V> {
+        (**self).lookup(key, guard)
+    }
+
+    fn contains_key(&self, key: &K, guard: &G) -> bool {
+        (**self).contains_key(key, guard)
+    }
+
+    fn insert(&self, key: &K, value: V, guard: &G) -> Result<(), V> {
+        (**self).insert(key, value, guard)
+    }
+
+    fn get_or_insert_with<'a, F: FnOnce() -> V>(&'a self, key: &K, f: F, guard: &'a G) -> &'a V {
+        (**self).get_or_insert_with(key, f, guard)
+    }
+
+    fn remove<'a>(&'a self, key: &K, guard: &'a G) -> Result<&'a V, MapError> {
+        (**self).remove(key, guard)
+    }
+
+    fn entry<'a>(&'a self, key: K, guard: &'a G) -> MapEntry<'a, K, V>
+    where
+        K: Sized + 'a,
+    {
+        (**self).entry(key, guard)
+    }
+}
+
+/// Converts str sequential map into string sequential map
+#[derive(Default, Debug)]
+pub struct StrStringMap<V, M: SequentialMap<str, V>> {
+    inner: M,
+    _marker: PhantomData<V>,
+}
+
+impl<V, M: SequentialMap<str, V>> SequentialMap<String, V> for StrStringMap<V, M> {
+    fn lookup<'a>(&'a self, key: &'a String) -> Option<&'a V> {
+        self.inner.lookup(key)
+    }
+
+    fn insert<'a>(&'a mut self, key: &'a String, value: V) -> Result<&'a mut V, (&'a mut V, V)> {
+        self.inner.insert(key, value)
+    }
+
+    fn remove(&mut self, key: &String) -> Result<V, MapError> {
+        self.inner.remove(key)
+    }
+}
+
+impl<K: ?Sized, V, L: RawLock, M> ConcurrentMap<K, V> for Lock<L, M>
+where
+    M: SequentialMap<K, V>,
+{
+    fn lookup<'a, F, R>(&'a self, key: &'a K, _guard: &'a Guard, f: F) -> R
+    where
+        F: FnOnce(Option<&V>) -> R,
+    {
+        f(self.lock().lookup(key))
+    }
+
+    fn insert<'a>(&'a self, key: &'a K, value: V, _guard: &'a Guard) -> Result<(), V> {
+        self.lock()
+            .insert(key, value)
+            .map(|_| ())
+            .map_err(|(_, v)| v)
+    }
+
+    fn remove(&self, key: &K, _guard: &Guard) -> Result<V, MapError> {
+        self.lock().remove(key)
+    }
+}
+
+/// Converts nonblocking map into concurrent map
+#[derive(Default, Debug)]
+pub struct NonblockingConcurrentMap<K: ?Sized, V: Clone, M: NonblockingMap<K, V>> {
+    inner: M,
+    _marker: PhantomData<(Box<K>, V)>,
+}
+
+impl<K: ?Sized, V: Clone, M: NonblockingMap<K, V>> ConcurrentMap<K, V>
+    for NonblockingConcurrentMap<K, V, M>
+{
+    fn lookup<'a, F, R>(&'a self, key: &'a K, guard: &'a Guard, f: F) -> R
+    where
+        F: FnOnce(Option<&V>) -> R,
+    {
+        f(self.inner.lookup(key, guard))
+    }
+
+    fn insert<'a>(&'a self, key: &'a K, value: V, guard: &'a Guard) -> Result<(), V> {
+        self.inner.insert(key, value, guard)
+    }
+
+    fn remove(&self, key: &K, guard: &Guard) -> Result<V, MapError> {
+        self.inner.remove(key, guard).map(|v| v.clone())
+    }
+}
@@ -0,0 +1,59 @@
+//! Counters for CAS retries, allocations, and contention.
+//!
+//! The counters are compiled only with the `metrics` feature. Without it, `metric_inc!` and
+//! `metric_max!` expand to nothing. All counters are updated with relaxed orderings, so a
+//! snapshot taken while other threads are running is not necessarily consistent.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+macro_rules! counters {
+    ($($(#[$doc:meta])* $name:ident,)*) => {
+        #[doc(hidden)]
+        #[derive(Debug)]
+        pub struct Counters {
+            $(pub $name: AtomicUsize,)*
+        }
+
+        #[doc(hidden)]
+        pub static COUNTERS: Counters = Counters {
+            $($name: AtomicUsize::new(0),)*
+        };
+
+        /// Values of the counters at some point.
+        #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+        pub struct MetricsSnapshot {
+            $($(#[$doc])* pub $name: usize,)*
+        }
+
+        /// Reads all counters.
+        pub fn snapshot() -> MetricsSnapshot {
+            MetricsSnapshot {
+                $($name: COUNTERS.$name.load(Ordering::Relaxed),)*
+            }
+        }
+
+        /// Resets all counters to 0.
+        pub fn reset() {
+            $(COUNTERS.$name.store(0, Ordering::Relaxed);)*
+        }
+    };
+}
+
+counters! {
+    /// Failed validations in `Shield::protect`.
+    hp_protect_retries,
+    /// Hazard slots allocated because no inactive slot could be recycled.
+    hp_slot_allocs,
+    /// Failed CASes when claiming an inactive hazard slot that another thread claimed first.
+    hp_slot_claim_failures,
+    /// Failed CASes when linking a new node in `SplitOrderedList::insert`.
+    sol_insert_retries,
+    /// Failed CASes when replacing a value in `SplitOrderedList::update`.
+    sol_update_retries,
+    /// Bucket initializations in `SplitOrderedList` that lost the race to another thread.
+    sol_bucket_init_races,
+    /// `Cache::get_or_insert_with` calls that waited for another thread's computation.
+    cache_waits,
+    /// Maximum number of jobs waiting in a `ThreadPool` queue.
+    pool_queue_depth_max,
+}
@@ -0,0 +1,103 @@
+//! Rayon interop for the concurrent structures, enabled by the `parallel` feature.
+
+use core::cmp::Ordering;
+use crossbeam_epoch::pin;
+use rayon::iter::{FromParallelIterator, IntoParallelIterator, ParallelExtend, ParallelIterator};
+use rayon::vec::IntoIter;
+
+use crate::map::NonblockingMap;
+use crate::{OrderedListSet, SplitOrderedList};
+
+impl<V: Send + Sync> FromParallelIterator<(usize, V)> for SplitOrderedList<V> {
+    /// Builds the list in parallel. If a key is repeated, one of its values is kept.
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = (usize, V)>,
+    {
+        let par_iter = par_iter.into_par_iter();
+        let mut list = match par_iter.opt_len() {
+            Some(len) => Self::with_capacity(len),
+            None => Self::new(),
+        };
+        list.par_extend(par_iter);
+        list
+    }
+}
+
+impl<V: Send + Sync> ParallelExtend<(usize, V)> for SplitOrderedList<V> {
+    /// Inserts the entries in parallel. Keys already in the list keep their values. If a new key
+    /// is repeated, one of its values is kept.
+    fn par_extend<I>(&mut self, par_iter: I)
+    where
+        I: IntoParallelIterator<Item = (usize, V)>,
+    {
+        let list = &*self;
+        par_iter
+            .into_par_iter()
+            .for_each_init(pin, |guard, (key, value)| {
+                let _ = list.insert(&key, value, guard);
+            });
+    }
+}
+
+impl<V: Clone + Send> SplitOrderedList<V> {
+    /// Returns a parallel iterator over clones of the entries. The entries are collected in split
+    /// order with one guard.
+    pub fn par_snapshot(&self) -> IntoIter<(usize, V)> {
+        let guard = pin();
+        self.entries(&guard)
+            .map(|(key, value)| (key, value.clone()))
+            .collect::<Vec<_>>()
+            .into_par_iter()
+    }
+}
+
+/// Merges two sorted vectors without duplicates into one.
+fn merge<T: Ord>(left: Vec<T>, right: Vec<T>) -> Vec<T> {
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        match l.cmp(r) {
+            Ordering::Less => merged.push(left.next().unwrap()),
+            Ordering::Greater => merged.push(right.next().unwrap()),
+            Ordering::Equal => {
+                merged.push(left.next().unwrap());
+                let _ = right.next();
+            }
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    merged
+}
+
+impl<T: Ord + Send> FromParallelIterator<T> for OrderedListSet<T> {
+    /// Builds the set by sorting a run of values on each worker and merging the runs. The nodes
+    /// are linked at once at the end.
+    fn from_par_iter<I>(par_iter: I) -> Self
+    where
+        I: IntoParallelIterator<Item = T>,
+    {
+        let sorted = par_iter
+            .into_par_iter()
+            .fold(Vec::new, |mut run, value| {
+                run.push(value);
+                run
+            })
+            .map(|mut run| {
+                run.sort_unstable();
+                run.dedup();
+                run
+            })
+            .reduce(Vec::new, merge);
+        Self::from_sorted(sorted)
+    }
+}
+
+impl<T: Clone + Send> OrderedListSet<T> {
+    /// Returns a parallel iterator over clones of the values, collected in order.
+    pub fn par_snapshot(&self) -> IntoIter<T> {
+        self.iter().cloned().collect::<Vec<_>>().into_par_iter()
+    }
+}
@@ -0,0 +1,79 @@
+//! Memory reclamation schemes behind a common interface.
+//!
+//! A lock-free structure generic over `Reclaim` can run on either epoch-based reclamation
+//! (`EpochReclaim`) or hazard pointers (`HpReclaim`). See `TreiberStack` for an example.
+
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicPtr, Ordering};
+use crossbeam_epoch::{Guard, Shared};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, Ordering};
+
+#[cfg(feature = "global-hazards")]
+use crate::hazard_pointer::{retire, Shield};
+
+/// Memory reclamation scheme for nodes of type `T`.
+///
+/// Nodes must be allocated with `Box`.
+pub trait Reclaim<T> {
+    /// State of an operation that protects the pointers loaded with `protect`.
+    type Guard;
+
+    /// Starts an operation.
+    fn pin() -> Self::Guard;
+
+    /// Loads a pointer from `src` and protects it. The pointer is valid until `guard` is dropped
+    /// or `protect` is called again with it.
+    fn protect(guard: &mut Self::Guard, src: &AtomicPtr<T>) -> *mut T;
+
+    /// Frees `pointer` once it is no longer protected by any thread.
+    ///
+    /// # Safety
+    ///
+    /// `pointer` should be created by `Box::into_raw`, unreachable from the structure, and retired
+    /// only once.
+    unsafe fn retire(guard: &Self::Guard, pointer: *mut T);
+}
+
+/// Epoch-based reclamation with `crossbeam_epoch`. `protect` protects all loaded pointers until
+/// the guard is dropped.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct EpochReclaim;
+
+impl<T> Reclaim<T> for EpochReclaim {
+    type Guard = Guard;
+
+    fn pin() -> Guard {
+        crossbeam_epoch::pin()
+    }
+
+    fn protect(_guard: &mut Guard, src: &AtomicPtr<T>) -> *mut T {
+        src.load(Ordering::Acquire)
+    }
+
+    unsafe fn retire(guard: &Guard, pointer: *mut T) {
+        guard.defer_destroy(Shared::from(pointer as *const T));
+    }
+}
+
+/// Hazard pointers with the global `HAZARDS`. `protect` protects one pointer at a time.
+#[cfg(feature = "global-hazards")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HpReclaim;
+
+#[cfg(feature = "global-hazards")]
+impl<T> Reclaim<T> for HpReclaim {
+    type Guard = Shield<T>;
+
+    fn pin() -> Shield<T> {
+        Shield::default()
+    }
+
+    fn protect(guard: &mut Shield<T>, src: &AtomicPtr<T>) -> *mut T {
+        guard.protect(src) as *mut T
+    }
+
+    unsafe fn retire(_guard: &Shield<T>, pointer: *mut T) {
+        retire(pointer);
+    }
+}
@@ -0,0 +1,119 @@
+//! Counting created and dropped values to detect leaks and double frees.
+
+use core::cmp::Ordering as CmpOrdering;
+use core::fmt;
+use core::ops::Deref;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts the values created by `track` and how many of them are dropped.
+#[derive(Debug, Default)]
+pub struct DropCounter {
+    created: AtomicUsize,
+    dropped: AtomicUsize,
+}
+
+impl DropCounter {
+    /// Creates a new counter.
+    pub const fn new() -> Self {
+        Self {
+            created: AtomicUsize::new(0),
+            dropped: AtomicUsize::new(0),
+        }
+    }
+
+    /// Wraps `value` so that it is counted.
+    pub fn track<T>(&self, value: T) -> Counted<'_, T> {
+        let _ = self.created.fetch_add(1, Ordering::Relaxed);
+        Counted {
+            counter: self,
+            value,
+        }
+    }
+
+    /// Number of values created so far.
+    pub fn created(&self) -> usize {
+        self.created.load(Ordering::Relaxed)
+    }
+
+    /// Number of values dropped so far.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Number of values that are not dropped yet. Panics if more values are dropped than created.
+    pub fn live(&self) -> usize {
+        // Read `dropped` first so that it doesn't exceed `created` due to concurrent updates.
+        let dropped = self.dropped();
+        let created = self.created();
+        created
+            .checked_sub(dropped)
+            .expect("more values are dropped than created")
+    }
+}
+
+/// A value counted by a `DropCounter`. Comparisons only look at the value.
+pub struct Counted<'c, T> {
+    counter: &'c DropCounter,
+    value: T,
+}
+
+impl<T> Deref for Counted<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> Drop for Counted<'_, T> {
+    fn drop(&mut self) {
+        let _ = self.counter.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Counted<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for Counted<'_, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Eq> Eq for Counted<'_, T> {}
+
+impl<T: PartialOrd> PartialOrd for Counted<'_, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        self.value.partial_cmp(&other.value)
+    }
+}
+
+impl<T: Ord> Ord for Counted<'_, T> {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.value.cmp(&other.value)
+    }
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use super::DropCounter;
+
+    #[test]
+    fn live() {
+        let counter = DropCounter::new();
+        let a = counter.track(1);
+        let b = counter.track(2);
+        assert_eq!(counter.live(), 2);
+        assert!(a < b);
+        drop(a);
+        assert_eq!(
+            (counter.created(), counter.dropped(), counter.live()),
+            (2, 1, 1)
+        );
+        drop(b);
+        assert_eq!(counter.live(), 0);
+    }
+}
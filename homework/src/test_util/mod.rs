@@ -0,0 +1,5 @@
+//! Utilities shared by the tests, the fuzz targets, and the soak test.
+
+pub mod drop_counter;
+pub mod ops;
+pub mod rng;
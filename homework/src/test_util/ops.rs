@@ -0,0 +1,121 @@
+//! Operation sequences checked against sequential oracles.
+//!
+//! The fuzz targets decode arbitrary bytes into `Op`s with `decode`, and the proptest generators
+//! build `Op`s from bytes with `Op::decode` as well, so both explore the same operations. The
+//! `run_*` functions perform an operation on the structure and on the oracle and panic if the
+//! results differ.
+
+use crossbeam_epoch::pin;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::{MapError, NonblockingMap, OrderedListSet, SplitOrderedList};
+
+/// Number of keys. The domain is small so that sequences hit existing keys often.
+pub const KEYS: usize = 16;
+
+/// Number of bytes `Op::decode` consumes at most.
+pub const OP_BYTES: usize = 3;
+
+/// An operation on a map or a set. Sets ignore the value of `Insert`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Inserts the key with the value.
+    Insert(usize, usize),
+    /// Deletes the key.
+    Delete(usize),
+    /// Looks up the key.
+    Lookup(usize),
+    /// Visits all keys.
+    Iterate,
+}
+
+impl Op {
+    /// Decodes an operation from the front of `bytes` and advances it. Returns `None` if `bytes`
+    /// is too short.
+    pub fn decode(bytes: &mut &[u8]) -> Option<Self> {
+        let (&tag, rest) = bytes.split_first()?;
+        *bytes = rest;
+        if tag % 4 == 3 {
+            return Some(Op::Iterate);
+        }
+
+        let (&key, rest) = bytes.split_first()?;
+        *bytes = rest;
+        let key = key as usize % KEYS;
+        match tag % 4 {
+            0 => {
+                let (&value, rest) = bytes.split_first()?;
+                *bytes = rest;
+                Some(Op::Insert(key, value as usize))
+            }
+            1 => Some(Op::Delete(key)),
+            _ => Some(Op::Lookup(key)),
+        }
+    }
+}
+
+/// Decodes all operations in `bytes`. Trailing bytes that don't form an operation are ignored.
+pub fn decode(mut bytes: &[u8]) -> Vec<Op> {
+    let mut ops = Vec::new();
+    while let Some(op) = Op::decode(&mut bytes) {
+        ops.push(op);
+    }
+    ops
+}
+
+/// Performs `op` on `list` and `oracle`, and checks that the results agree.
+pub fn run_split_ordered_list(
+    list: &SplitOrderedList<usize>,
+    oracle: &mut HashMap<usize, usize>,
+    op: &Op,
+) {
+    let guard = pin();
+    match *op {
+        Op::Insert(k, v) => {
+            let expected = match oracle.entry(k) {
+                Entry::Occupied(_) => Err(v),
+                Entry::Vacant(e) => {
+                    let _ = e.insert(v);
+                    Ok(())
+                }
+            };
+            assert_eq!(list.insert(&k, v, &guard), expected);
+        }
+        Op::Delete(k) => {
+            assert_eq!(list.remove(&k, &guard).ok(), oracle.remove(&k).as_ref());
+        }
+        Op::Lookup(k) => {
+            assert_eq!(list.lookup(&k, &guard), oracle.get(&k));
+        }
+        Op::Iterate => {
+            for k in 0..KEYS {
+                assert_eq!(list.lookup(&k, &guard), oracle.get(&k));
+            }
+        }
+    }
+}
+
+/// Performs `op` on `set` and `oracle`, and checks that the results agree.
+pub fn run_list_set(set: &OrderedListSet<usize>, oracle: &mut BTreeSet<usize>, op: &Op) {
+    match *op {
+        Op::Insert(k, _) => {
+            let expected = if oracle.insert(k) { Ok(()) } else { Err(k) };
+            assert_eq!(set.insert(k), expected);
+        }
+        Op::Delete(k) => {
+            let expected = if oracle.remove(&k) {
+                Ok(k)
+            } else {
+                Err(MapError::NotFound)
+            };
+            assert_eq!(set.remove(&k), expected);
+        }
+        Op::Lookup(k) => {
+            assert_eq!(set.contains(&k), oracle.contains(&k));
+        }
+        Op::Iterate => {
+            assert!(set.iter().eq(oracle.iter()));
+        }
+    }
+}
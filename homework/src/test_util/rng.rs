@@ -0,0 +1,105 @@
+//! Seeded random number generator for reproducible randomized tests.
+//!
+//! `rng()` seeds the generator with `HOMEWORK_TEST_SEED` if it is set, or a random seed
+//! otherwise, and prints the seed. A failed test can be rerun with the printed seed to get the
+//! same random choices:
+//!
+//! ```text
+//! HOMEWORK_TEST_SEED=1234 cargo test --test list_set
+//! ```
+//!
+//! Multi-threaded tests should give each thread its own generator with `TestRng::fork`, called in
+//! a fixed order.
+
+use rand::{Error, RngCore};
+use std::env;
+
+/// Name of the environment variable for the seed.
+pub const SEED_VAR: &str = "HOMEWORK_TEST_SEED";
+
+/// Small xorshift64* generator.
+#[derive(Debug, Clone)]
+pub struct TestRng {
+    state: u64,
+}
+
+impl TestRng {
+    /// Creates a generator from the seed.
+    pub fn new(seed: u64) -> Self {
+        // Scramble the seed with SplitMix64 so that similar seeds give unrelated sequences and the
+        // state is never 0.
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        Self {
+            state: if z == 0 { 1 } else { z },
+        }
+    }
+
+    /// Creates a new generator seeded by this one.
+    pub fn fork(&mut self) -> Self {
+        Self::new(self.next_u64())
+    }
+}
+
+impl RngCore for TestRng {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Returns the seed from `HOMEWORK_TEST_SEED`, or a random seed if it's not set. Panics if the
+/// variable is not a `u64`.
+pub fn seed() -> u64 {
+    match env::var(SEED_VAR) {
+        Ok(seed) => seed
+            .parse()
+            .unwrap_or_else(|_| panic!("{} is not a u64: {}", SEED_VAR, seed)),
+        Err(_) => rand::random(),
+    }
+}
+
+/// Creates a generator with `seed()` and prints the seed.
+pub fn rng() -> TestRng {
+    let seed = seed();
+    println!("{}={}", SEED_VAR, seed);
+    TestRng::new(seed)
+}
+
+#[cfg(all(test, not(feature = "check-loom")))]
+mod tests {
+    use super::TestRng;
+    use rand::Rng;
+
+    #[test]
+    fn deterministic() {
+        let mut rng1 = TestRng::new(42);
+        let mut rng2 = TestRng::new(42);
+        let (mut fork1, mut fork2) = (rng1.fork(), rng2.fork());
+        for _ in 0..1024 {
+            assert_eq!(rng1.gen::<u64>(), rng2.gen::<u64>());
+            assert_eq!(fork1.gen_range(0..100), fork2.gen_range(0..100));
+        }
+        assert_ne!(TestRng::new(0).gen::<u64>(), TestRng::new(1).gen::<u64>());
+    }
+}
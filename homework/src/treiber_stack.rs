@@ -0,0 +1,108 @@
+//! Treiber's lock-free stack, generic over the reclamation scheme.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::ptr;
+#[cfg(not(feature = "check-loom"))]
+use core::sync::atomic::{AtomicPtr, Ordering};
+#[cfg(feature = "check-loom")]
+use loom::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::reclaim::{EpochReclaim, Reclaim};
+
+/// Node of `TreiberStack`.
+#[derive(Debug)]
+pub struct Node<T> {
+    data: ManuallyDrop<T>,
+    next: *mut Node<T>,
+}
+
+/// Treiber's lock-free stack whose popped nodes are reclaimed with `R`.
+pub struct TreiberStack<T, R: Reclaim<Node<T>> = EpochReclaim> {
+    head: AtomicPtr<Node<T>>,
+    _marker: PhantomData<(*const T, R)>,
+}
+
+unsafe impl<T: Send, R: Reclaim<Node<T>>> Send for TreiberStack<T, R> {}
+unsafe impl<T: Send, R: Reclaim<Node<T>>> Sync for TreiberStack<T, R> {}
+
+impl<T, R: Reclaim<Node<T>>> Default for TreiberStack<T, R> {
+    fn default() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, R: Reclaim<Node<T>>> TreiberStack<T, R> {
+    /// Creates a new, empty stack.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Pushes a value on top of the stack.
+    pub fn push(&self, t: T) {
+        let node = Box::into_raw(Box::new(Node {
+            data: ManuallyDrop::new(t),
+            next: ptr::null_mut(),
+        }));
+
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            unsafe { (*node).next = head };
+            match self
+                .head
+                .compare_exchange(head, node, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(h) => head = h,
+            }
+        }
+    }
+
+    /// Pops the top element from the stack. Returns `None` if the stack is empty.
+    pub fn pop(&self) -> Option<T> {
+        let mut guard = R::pin();
+        loop {
+            let head = R::protect(&mut guard, &self.head);
+            let head_ref = unsafe { head.as_ref() }?;
+
+            if self
+                .head
+                .compare_exchange(head, head_ref.next, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+            {
+                // The node is unlinked, so no other thread takes its data.
+                let data = unsafe { ManuallyDrop::into_inner(ptr::read(&head_ref.data)) };
+                unsafe { R::retire(&guard, head) };
+                return Some(data);
+            }
+        }
+    }
+
+    /// Returns `true` if the stack is empty.
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Acquire).is_null()
+    }
+}
+
+impl<T, R: Reclaim<Node<T>>> Drop for TreiberStack<T, R> {
+    fn drop(&mut self) {
+        let mut curr = self.head.load(Ordering::Relaxed);
+        while !curr.is_null() {
+            let mut node = unsafe { Box::from_raw(curr) };
+            unsafe { ManuallyDrop::drop(&mut node.data) };
+            curr = node.next;
+        }
+    }
+}
+
+impl<T, R: Reclaim<Node<T>>> fmt::Debug for TreiberStack<T, R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TreiberStack")
+            .field("head", &self.head)
+            .finish()
+    }
+}
@@ -19,3 +19,67 @@ macro_rules! some_or {
         }
     }};
 }
+
+#[cfg(feature = "failpoints")]
+/// Performs the action registered for the fail point. See `failpoint`.
+macro_rules! failpoint {
+    ($name:expr) => {
+        $crate::failpoint::eval($name)
+    };
+}
+
+#[cfg(not(feature = "failpoints"))]
+/// Performs the action registered for the fail point. See `failpoint`.
+macro_rules! failpoint {
+    ($name:expr) => {};
+}
+
+#[cfg(feature = "metrics")]
+/// Increments the counter. See `metrics`.
+macro_rules! metric_inc {
+    ($name:ident) => {
+        $crate::metrics::COUNTERS
+            .$name
+            .fetch_add(1, core::sync::atomic::Ordering::Relaxed)
+    };
+}
+
+#[cfg(not(feature = "metrics"))]
+/// Increments the counter. See `metrics`.
+macro_rules! metric_inc {
+    ($name:ident) => {};
+}
+
+#[cfg(feature = "metrics")]
+/// Adds the given value to the counter. See `metrics`.
+macro_rules! metric_add {
+    ($name:ident, $value:expr) => {
+        $crate::metrics::COUNTERS
+            .$name
+            .fetch_add($value, core::sync::atomic::Ordering::Relaxed)
+    };
+}
+
+#[cfg(not(feature = "metrics"))]
+/// Adds the given value to the counter. See `metrics`.
+macro_rules! metric_add {
+    ($name:ident, $value:expr) => {};
+}
+
+#[cfg(feature = "metrics")]
+/// Updates the counter to the maximum of its value and the given value. See `metrics`.
+#[allow(unused_macros)]
+macro_rules! metric_max {
+    ($name:ident, $value:expr) => {
+        $crate::metrics::COUNTERS
+            .$name
+            .fetch_max($value, core::sync::atomic::Ordering::Relaxed)
+    };
+}
+
+#[cfg(not(feature = "metrics"))]
+/// Updates the counter to the maximum of its value and the given value. See `metrics`.
+#[allow(unused_macros)]
+macro_rules! metric_max {
+    ($name:ident, $value:expr) => {};
+}
@@ -1,3 +1,5 @@
+#![allow(deprecated)]
+
 use cs431_homework::{Art, SequentialMap, StrStringMap};
 
 pub mod map;
@@ -1,3 +1,5 @@
+#![allow(deprecated)]
+
 use cs431_homework::{Bst, SequentialMap};
 
 pub mod map;
@@ -1,8 +1,9 @@
 use crossbeam_channel::bounded;
 use crossbeam_utils::thread::scope;
-use cs431_homework::hello_server::Cache;
+use cs431_homework::hello_server::{Cache, CacheError};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Barrier;
+use std::thread::sleep;
 use std::time::Duration;
 
 const NUM_THREADS: usize = 8;
@@ -113,3 +114,68 @@ fn cache_no_reader_block() {
     })
     .unwrap();
 }
+
+#[test]
+fn cache_panicked() {
+    let cache = &Cache::default();
+
+    scope(|s| {
+        let (t1_panic_sender, t1_panic_receiver) = bounded(0);
+        // T1 panics while inserting 1.
+        let t1 = s.spawn(move |_| {
+            cache.get_or_insert_with(1, |_| {
+                t1_panic_receiver.recv().unwrap();
+                panic!("T1 panics");
+            })
+        });
+
+        // T2 waits for T1 and gets the error.
+        let t2 = s.spawn(move |_| cache.try_get_or_insert_with(1, |_| panic!()));
+        sleep(Duration::from_millis(100));
+        t1_panic_sender.send(()).unwrap();
+
+        assert!(t1.join().is_err());
+        assert_eq!(t2.join().unwrap(), Err(CacheError::Panicked));
+    })
+    .unwrap();
+
+    // The entry is removed, so the value is computed again.
+    assert_eq!(cache.try_get_or_insert_with(1, |k| k), Ok(1));
+    assert_eq!(cache.get_or_insert_with(1, |_| panic!()), 1);
+}
+
+#[test]
+fn cache_timeout() {
+    let cache = &Cache::default();
+
+    scope(|s| {
+        let (t1_quit_sender, t1_quit_receiver) = bounded(0);
+        // T1 blocks while inserting 1.
+        let t1 = s.spawn(move |_| {
+            cache.get_or_insert_with_timeout(1, Duration::from_secs(0), |k| {
+                t1_quit_receiver.recv().unwrap();
+                k
+            })
+        });
+        sleep(Duration::from_millis(100));
+
+        // The timeout only applies to waiting for another thread.
+        assert_eq!(
+            cache.get_or_insert_with_timeout(1, Duration::from_millis(10), |_| panic!()),
+            Err(CacheError::TimedOut)
+        );
+        assert_eq!(
+            cache.get_or_insert_with_timeout(2, Duration::from_secs(0), |k| k),
+            Ok(2)
+        );
+
+        t1_quit_sender.send(()).unwrap();
+        assert_eq!(t1.join().unwrap(), Ok(1));
+    })
+    .unwrap();
+
+    assert_eq!(
+        cache.get_or_insert_with_timeout(1, Duration::from_secs(0), |_| panic!()),
+        Ok(1)
+    );
+}
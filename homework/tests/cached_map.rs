@@ -0,0 +1,197 @@
+use crossbeam_epoch::pin;
+use crossbeam_utils::thread::scope;
+use cs431_homework::{CachedMap, MapEntry, NonblockingMap, SplitOrderedList};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Barrier;
+
+type Map = CachedMap<usize, usize, SplitOrderedList<usize>>;
+
+#[test]
+fn smoke() {
+    let map = Map::new();
+    let guard = pin();
+
+    assert_eq!(map.get_derived(1, |_| panic!()), None);
+    assert_eq!(map.insert(&1, 10, &guard), Ok(()));
+    assert_eq!(map.get_derived(1, |v| v + 1), Some(11));
+    // cached
+    assert_eq!(map.get_derived(1, |_| panic!()), Some(11));
+
+    assert_eq!(map.remove(&1, &guard), Ok(&10));
+    assert_eq!(map.get_derived(1, |_| panic!()), None);
+
+    assert_eq!(map.insert(&1, 20, &guard), Ok(()));
+    assert_eq!(map.get_derived(1, |v| v + 1), Some(21));
+}
+
+// `CachedMap` uses the default `entry`, which goes through its own operations and so keeps the
+// cache coherent.
+#[test]
+fn entry() {
+    let map = Map::new();
+    let guard = pin();
+
+    match map.entry(1, &guard) {
+        MapEntry::Vacant(entry) => assert_eq!(entry.insert(10), Ok(&10)),
+        MapEntry::Occupied(_) => panic!("occupied"),
+    }
+    assert_eq!(map.get_derived(1, |v| v + 1), Some(11));
+
+    let entry = match map.entry(1, &guard) {
+        MapEntry::Occupied(entry) => entry,
+        MapEntry::Vacant(_) => panic!("vacant"),
+    };
+    assert_eq!(entry.get(), &10);
+    assert_eq!(entry.delete(), Ok(&10));
+    assert_eq!(map.get_derived(1, |_| panic!()), None);
+
+    let entry = match map.entry(1, &guard) {
+        MapEntry::Vacant(entry) => entry,
+        MapEntry::Occupied(_) => panic!("occupied"),
+    };
+    assert_eq!(map.insert(&1, 20, &guard), Ok(()));
+    assert_eq!(entry.insert(10), Err(10));
+    assert_eq!(map.get_derived(1, |v| v + 1), Some(21));
+}
+
+#[test]
+fn derive_once_concurrent() {
+    const THREADS: usize = 8;
+    const KEYS: usize = 128;
+
+    let map = Map::new();
+    for key in 0..KEYS {
+        map.insert(&key, key, &pin()).unwrap();
+    }
+    let barrier = Barrier::new(THREADS);
+    let num_derive = AtomicUsize::new(0);
+    scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|_| {
+                barrier.wait();
+                for key in 0..KEYS {
+                    let derived = map.get_derived(key, |v| {
+                        num_derive.fetch_add(1, Ordering::Relaxed);
+                        v * 2
+                    });
+                    assert_eq!(derived, Some(key * 2));
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert_eq!(num_derive.load(Ordering::Relaxed), KEYS);
+}
+
+// A derivation that started before `remove` must not be cached as the derivation of the key.
+#[test]
+fn derive_races_delete() {
+    let map = Map::new();
+    map.insert(&1, 1, &pin()).unwrap();
+    let barrier = Barrier::new(2);
+
+    scope(|s| {
+        let reader = s.spawn(|_| {
+            map.get_derived(1, |v| {
+                // the writer runs between these barriers
+                barrier.wait();
+                barrier.wait();
+                v * 10
+            })
+        });
+
+        barrier.wait();
+        assert_eq!(map.remove(&1, &pin()), Ok(&1));
+        barrier.wait();
+
+        // linearized before `remove`
+        assert_eq!(reader.join().unwrap(), Some(10));
+    })
+    .unwrap();
+
+    assert_eq!(map.get_derived(1, |_| panic!()), None);
+    map.insert(&1, 2, &pin()).unwrap();
+    assert_eq!(map.get_derived(1, |v| v * 10), Some(20));
+}
+
+// Like `derive_races_delete`, but a new value is inserted while the old one is being derived.
+#[test]
+fn derive_races_replace() {
+    let map = Map::new();
+    map.insert(&1, 1, &pin()).unwrap();
+    let barrier = Barrier::new(2);
+
+    scope(|s| {
+        let reader = s.spawn(|_| {
+            map.get_derived(1, |v| {
+                barrier.wait();
+                barrier.wait();
+                v * 10
+            })
+        });
+
+        barrier.wait();
+        let guard = pin();
+        assert_eq!(map.remove(&1, &guard), Ok(&1));
+        assert_eq!(map.insert(&1, 2, &guard), Ok(()));
+        drop(guard);
+        barrier.wait();
+
+        assert_eq!(reader.join().unwrap(), Some(10));
+    })
+    .unwrap();
+
+    assert_eq!(map.get_derived(1, |v| v * 10), Some(20));
+}
+
+// Each key is owned by a writer that replaces its value with increasing numbers. Readers must
+// never observe a value going backwards, which would happen if a stale derivation is returned.
+#[test]
+fn derive_monotonic_concurrent() {
+    const WRITERS: usize = 4;
+    const READERS: usize = 4;
+    const KEYS_PER_WRITER: usize = 4;
+    const STEPS: usize = 4096;
+
+    let map = Map::new();
+    for key in 0..WRITERS * KEYS_PER_WRITER {
+        map.insert(&key, 0, &pin()).unwrap();
+    }
+    let barrier = Barrier::new(WRITERS + READERS);
+
+    scope(|s| {
+        for w in 0..WRITERS {
+            let map = &map;
+            let barrier = &barrier;
+            s.spawn(move |_| {
+                barrier.wait();
+                for step in 1..=STEPS {
+                    let key = w * KEYS_PER_WRITER + step % KEYS_PER_WRITER;
+                    let guard = pin();
+                    map.remove(&key, &guard).unwrap();
+                    map.insert(&key, step, &guard).unwrap();
+                }
+            });
+        }
+        for _ in 0..READERS {
+            s.spawn(|_| {
+                let mut last = vec![0; WRITERS * KEYS_PER_WRITER];
+                barrier.wait();
+                for step in 0..STEPS {
+                    let key = step % last.len();
+                    if let Some(value) = map.get_derived(key, |v| *v) {
+                        assert!(value >= last[key], "stale derivation for key {}", key);
+                        last[key] = value;
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = pin();
+    for key in 0..WRITERS * KEYS_PER_WRITER {
+        let value = *map.lookup(&key, &guard).unwrap();
+        assert_eq!(map.get_derived(key, |v| *v), Some(value));
+    }
+}
@@ -0,0 +1,283 @@
+//! Interleaving tests using fail points. Run with `cargo test --features failpoints`.
+
+#![cfg(feature = "failpoints")]
+
+#[cfg(not(feature = "check-loom"))]
+use core::ptr;
+use crossbeam_epoch::{pin, Owned, Shared};
+use crossbeam_utils::thread::scope;
+use cs431_homework::failpoint::{Action, Scenario};
+#[cfg(not(feature = "check-loom"))]
+use cs431_homework::hazard_pointer::{HazardBag, Shield};
+use cs431_homework::hello_server::Cache;
+use cs431_homework::{GrowableArray, NonblockingMap, SplitOrderedHashMap, SplitOrderedList};
+#[cfg(not(feature = "check-loom"))]
+use std::sync::atomic::AtomicPtr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier};
+
+// The source is changed after the hazard is published but before it is validated. Under
+// `check-loom`, the hazards are loom atomics, which can't be used outside of a loom model.
+#[cfg(not(feature = "check-loom"))]
+#[test]
+fn try_protect_validation_fails() {
+    let scenario = Scenario::setup();
+    let barrier = Arc::new(Barrier::new(2));
+    scenario.cfg(
+        "hazard_pointer::try_protect::validate",
+        Action::Pause(barrier.clone()),
+    );
+
+    let old = Box::into_raw(Box::new(1usize));
+    let new = Box::into_raw(Box::new(2usize));
+    let src = AtomicPtr::new(old);
+    let (old, new) = (old as usize, new as usize);
+    let hazards = HazardBag::new();
+
+    scope(|s| {
+        let th = s.spawn(|_| {
+            let shield = Shield::new(&hazards);
+            let mut pointer = old as *const usize;
+            let validated = shield.try_protect(&mut pointer, &src);
+            (validated, pointer as usize)
+        });
+
+        barrier.wait();
+        // published before validation
+        assert!(hazards.all_hazards().contains(&old));
+        src.store(new as *mut usize, Ordering::SeqCst);
+        barrier.wait();
+
+        assert_eq!(th.join().unwrap(), (false, new));
+    })
+    .unwrap();
+    assert!(!hazards.all_hazards().contains(&old));
+
+    src.store(ptr::null_mut(), Ordering::Relaxed);
+    unsafe {
+        drop(Box::from_raw(old as *mut usize));
+        drop(Box::from_raw(new as *mut usize));
+    }
+}
+
+// Two threads insert the same key, and the first one is paused right before linking its node.
+#[test]
+fn split_ordered_list_insert_same_key() {
+    let scenario = Scenario::setup();
+    let barrier = Arc::new(Barrier::new(2));
+    scenario.cfg("split_ordered_list::insert", Action::Pause(barrier.clone()));
+
+    let list = SplitOrderedList::new();
+    scope(|s| {
+        let th = s.spawn(|_| list.insert(&1, 1, &pin()));
+
+        barrier.wait();
+        assert_eq!(list.insert(&1, 2, &pin()), Ok(()));
+        barrier.wait();
+
+        assert_eq!(th.join().unwrap(), Err(1));
+    })
+    .unwrap();
+
+    let guard = pin();
+    assert_eq!(list.lookup(&1, &guard), Some(&2));
+    assert_eq!(list.remove(&1, &guard), Ok(&2));
+    assert_eq!(list.lookup(&1, &guard), None);
+}
+
+// The first thread is paused right before linking its node, and another key is linked at the same
+// place in the meantime. The insert should search again instead of failing.
+#[test]
+fn split_ordered_list_insert_retry() {
+    let scenario = Scenario::setup();
+    let barrier = Arc::new(Barrier::new(2));
+    scenario.cfg("split_ordered_list::insert", Action::Pause(barrier.clone()));
+
+    let list = SplitOrderedList::new();
+    // initialize the bucket of 1 and 3
+    assert_eq!(list.lookup(&1, &pin()), None);
+    scope(|s| {
+        let th = s.spawn(|_| list.insert(&1, 1, &pin()));
+
+        barrier.wait();
+        assert_eq!(list.insert(&3, 3, &pin()), Ok(()));
+        barrier.wait();
+
+        assert_eq!(th.join().unwrap(), Ok(()));
+    })
+    .unwrap();
+
+    let guard = pin();
+    assert_eq!(list.lookup(&1, &guard), Some(&1));
+    assert_eq!(list.lookup(&3, &guard), Some(&3));
+    assert_eq!(list.len(), 2);
+}
+
+// Two threads initialize the same bucket, and the first one is paused right before publishing the
+// sentinel in the bucket array.
+#[test]
+fn split_ordered_list_bucket_init_race() {
+    let scenario = Scenario::setup();
+    let barrier = Arc::new(Barrier::new(2));
+    scenario.cfg(
+        "split_ordered_list::lookup_bucket::publish",
+        Action::Pause(barrier.clone()),
+    );
+
+    let list = SplitOrderedList::new();
+    scope(|s| {
+        let th = s.spawn(|_| list.lookup(&1, &pin()).cloned());
+
+        barrier.wait();
+        assert_eq!(list.insert(&1, 1, &pin()), Ok(()));
+        assert_eq!(list.insert(&3, 3, &pin()), Ok(()));
+        barrier.wait();
+
+        let found = th.join().unwrap();
+        assert!(found == None || found == Some(1));
+    })
+    .unwrap();
+
+    let guard = pin();
+    assert_eq!(list.lookup(&1, &guard), Some(&1));
+    assert_eq!(list.lookup(&3, &guard), Some(&3));
+    assert_eq!(list.remove(&1, &guard), Ok(&1));
+    assert_eq!(list.remove(&3, &guard), Ok(&3));
+    assert_eq!(list.lookup(&1, &guard), None);
+    assert_eq!(list.lookup(&3, &guard), None);
+    // The other thread published the sentinel linked by the paused one, and the paused one left it
+    // in the list when its publish failed.
+    assert_eq!(list.insert(&5, 5, &guard), Ok(()));
+    assert_eq!(list.lookup(&5, &guard), Some(&5));
+}
+
+// The remover of the last entry of a chain is paused after sealing the chain but before removing
+// it from the list. Inserts of the same hash should remove the chain themselves instead of waiting.
+#[test]
+fn split_ordered_hash_map_insert_to_sealed_chain() {
+    let scenario = Scenario::setup();
+    let barrier = Arc::new(Barrier::new(2));
+    scenario.cfg(
+        "split_ordered_hash_map::remove::seal",
+        Action::Pause(barrier.clone()),
+    );
+
+    let map = SplitOrderedHashMap::<usize, usize>::new();
+    assert_eq!(map.insert(&1, 1, &pin()), Ok(()));
+    scope(|s| {
+        let th = s.spawn(|_| map.remove(&1, &pin()).map(|v| *v));
+
+        barrier.wait();
+        let guard = pin();
+        assert_eq!(map.lookup(&1, &guard), None);
+        assert_eq!(map.insert(&1, 2, &guard), Ok(()));
+        assert_eq!(map.remove(&1, &guard), Ok(&2));
+        assert_eq!(map.get_or_insert_with(&1, || 3, &guard), &3);
+        drop(guard);
+        barrier.wait();
+
+        assert_eq!(th.join().unwrap(), Ok(1));
+    })
+    .unwrap();
+
+    let guard = pin();
+    assert_eq!(map.lookup(&1, &guard), Some(&3));
+}
+
+// A pruner is paused before lowering the root to its first child, and the child becomes empty.
+// Another prune should leave the child alone instead of freeing it under the first pruner.
+#[test]
+fn growable_array_prune_while_lowering() {
+    let scenario = Scenario::setup();
+    let barrier = Arc::new(Barrier::new(2));
+    scenario.cfg(
+        "growable_array::prune::lower",
+        Action::Pause(barrier.clone()),
+    );
+
+    let array = GrowableArray::<usize>::new();
+    let guard = pin();
+    array.get(0, &guard).store(Owned::new(0), Ordering::Release);
+    let _ = array.get(1 << 10, &guard);
+    assert_eq!(array.height(), 2);
+    scope(|s| {
+        let th = s.spawn(|_| unsafe { array.prune(&pin()) });
+
+        barrier.wait();
+        let element = array
+            .get(0, &guard)
+            .swap(Shared::null(), Ordering::AcqRel, &guard);
+        drop(unsafe { element.into_owned() });
+        unsafe { array.prune(&guard) };
+        let height = array.height();
+        barrier.wait();
+
+        th.join().unwrap();
+        assert_eq!(height, 2);
+    })
+    .unwrap();
+
+    // The first pruner lowers the root to the child, and then frees it.
+    assert_eq!(array.height(), 0);
+}
+
+// Another thread asks for the key while the first one is computing its value.
+#[test]
+fn cache_get_while_computing() {
+    let scenario = Scenario::setup();
+    let barrier = Arc::new(Barrier::new(2));
+    scenario.cfg(
+        "cache::get_or_insert_with::compute",
+        Action::Pause(barrier.clone()),
+    );
+
+    let cache = Cache::default();
+    let num_compute = AtomicUsize::new(0);
+    scope(|s| {
+        let th1 = s.spawn(|_| {
+            cache.get_or_insert_with(1, |k| {
+                let _ = num_compute.fetch_add(1, Ordering::Relaxed);
+                k + 1
+            })
+        });
+
+        barrier.wait();
+        let th2 = s.spawn(|_| {
+            cache.get_or_insert_with(1, |k| {
+                let _ = num_compute.fetch_add(1, Ordering::Relaxed);
+                k + 1
+            })
+        });
+        barrier.wait();
+
+        assert_eq!(th1.join().unwrap(), 2);
+        assert_eq!(th2.join().unwrap(), 2);
+    })
+    .unwrap();
+    assert_eq!(num_compute.load(Ordering::Relaxed), 1);
+}
+
+// The key is invalidated while its value is being computed.
+#[test]
+fn cache_invalidate_while_computing() {
+    let scenario = Scenario::setup();
+    let barrier = Arc::new(Barrier::new(2));
+    scenario.cfg(
+        "cache::get_or_insert_with::compute",
+        Action::Pause(barrier.clone()),
+    );
+
+    let cache = Cache::default();
+    scope(|s| {
+        let th = s.spawn(|_| cache.get_or_insert_with(1, |_| 1));
+
+        barrier.wait();
+        assert!(cache.invalidate(&1));
+        barrier.wait();
+
+        // the running computation is not affected
+        assert_eq!(th.join().unwrap(), 1);
+    })
+    .unwrap();
+    assert_eq!(cache.get_or_insert_with(1, |_| 2), 2);
+}
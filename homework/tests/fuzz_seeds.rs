@@ -0,0 +1,41 @@
+//! Replays the inputs in `fuzz/seeds` so that they are checked without `cargo fuzz`.
+
+use cs431_homework::test_util::ops::{decode, run_list_set, run_split_ordered_list};
+use cs431_homework::{OrderedListSet, SplitOrderedList};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+
+fn seeds(target: &str) -> Vec<Vec<u8>> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fuzz/seeds")
+        .join(target);
+    let seeds = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| fs::read(entry.unwrap().path()).unwrap())
+        .collect::<Vec<_>>();
+    assert!(!seeds.is_empty());
+    seeds
+}
+
+#[test]
+fn split_ordered_list() {
+    for seed in seeds("split_ordered_list") {
+        let list = SplitOrderedList::new();
+        let mut oracle = HashMap::new();
+        for op in &decode(&seed) {
+            run_split_ordered_list(&list, &mut oracle, op);
+        }
+    }
+}
+
+#[test]
+fn list_set() {
+    for seed in seeds("list_set") {
+        let set = OrderedListSet::new();
+        let mut oracle = BTreeSet::new();
+        for op in &decode(&seed) {
+            run_list_set(&set, &mut oracle, op);
+        }
+    }
+}
@@ -1,7 +1,10 @@
+#![allow(deprecated)]
+
 use core::mem::{replace, ManuallyDrop};
-use core::sync::atomic::Ordering;
+use core::sync::atomic::{AtomicBool, Ordering};
 use crossbeam_epoch::{pin, unprotected, Atomic, Guard, Owned, Shared};
-use cs431_homework::{GrowableArray, NonblockingConcurrentMap, NonblockingMap};
+use crossbeam_utils::thread::scope;
+use cs431_homework::{GrowableArray, MapError, NonblockingConcurrentMap, NonblockingMap};
 
 mod map;
 
@@ -42,12 +45,52 @@ impl<V> NonblockingMap<u32, V> for ArrayMap<V> {
         }
     }
 
-    fn delete<'g>(&self, key: &u32, guard: &'g Guard) -> Result<&'g V, ()> {
+    fn get_or_insert_with<'g, F: FnOnce() -> V>(
+        &'g self,
+        key: &u32,
+        f: F,
+        guard: &'g Guard,
+    ) -> &'g V {
+        let slot = self.array.get(*key as usize, guard);
+        let mut f = Some(f);
+        let mut new: Option<Owned<Node<V>>> = None;
+        loop {
+            let curr = slot.load(Ordering::Acquire, guard);
+            if let Some(n) = unsafe { curr.as_ref() } {
+                if let Some(new) = new {
+                    drop(ManuallyDrop::into_inner(new.into_box().data));
+                }
+                return &*n.data;
+            }
+            let node = new.take().unwrap_or_else(|| {
+                Owned::new(Node {
+                    data: ManuallyDrop::new((f.take().unwrap())()),
+                    next: Atomic::null(),
+                })
+            });
+            match slot.compare_exchange(
+                Shared::null(),
+                node,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(n) => {
+                    let value = unsafe { &*n.deref().data };
+                    self.storage.push_node(unsafe { n.into_owned() });
+                    return value;
+                }
+                Err(e) => new = Some(e.new),
+            }
+        }
+    }
+
+    fn remove<'g>(&self, key: &u32, guard: &'g Guard) -> Result<&'g V, MapError> {
         let slot = self.array.get(*key as usize, guard);
         let curr = slot.load(Ordering::Relaxed, guard);
         // no entry
         if curr.is_null() {
-            return Err(());
+            return Err(MapError::NotFound);
         }
         match slot.compare_exchange(
             curr,
@@ -57,7 +100,7 @@ impl<V> NonblockingMap<u32, V> for ArrayMap<V> {
             guard,
         ) {
             Ok(_) => Ok(unsafe { &*curr.as_ref().unwrap().data }),
-            Err(_) => Err(()), // already removed
+            Err(_) => Err(MapError::NotFound), // already removed
         }
     }
 }
@@ -136,6 +179,247 @@ fn smoke() {
     assert_eq!(list.lookup(&37, &guard), None);
 }
 
+/// Stores a new element at each of `indices`, and returns the elements.
+fn store_all<'g>(
+    array: &'g GrowableArray<usize>,
+    indices: &[usize],
+    guard: &'g Guard,
+) -> Vec<Shared<'g, usize>> {
+    indices
+        .iter()
+        .map(|&index| {
+            let slot = array.get(index, guard);
+            slot.compare_exchange(
+                Shared::null(),
+                Owned::new(index),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            )
+            .unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn introspection() {
+    let array = GrowableArray::<usize>::new();
+    let guard = pin();
+    assert_eq!((array.height(), array.capacity()), (0, 0));
+    assert_eq!(array.occupied(&guard), 0);
+
+    // `get` allocates the segments but doesn't store anything.
+    let _ = array.get(0, &guard);
+    assert_eq!((array.height(), array.capacity()), (1, 1 << 10));
+    assert_eq!(array.occupied(&guard), 0);
+
+    // Each index needs one more level of segments than the previous one.
+    let mut elements = Vec::new();
+    for (height, &index) in [0, 1 << 10, 1 << 20, 1 << 30].iter().enumerate() {
+        let indices = [index, index + 1, index * 2 + 3];
+        elements.extend(store_all(&array, &indices, &guard));
+        assert_eq!(array.height(), height + 1);
+        assert_eq!(array.capacity(), 1 << (10 * (height + 1)));
+        assert_eq!(array.occupied(&guard), elements.len());
+    }
+
+    // Indices that fit don't grow the array.
+    elements.extend(store_all(&array, &[(1 << 40) - 1, 12345], &guard));
+    assert_eq!(array.height(), 4);
+    assert_eq!(array.occupied(&guard), elements.len());
+
+    for element in elements {
+        drop(unsafe { element.into_owned() });
+    }
+}
+
+#[test]
+fn iter() {
+    let array = GrowableArray::<usize>::new();
+    let guard = pin();
+    assert_eq!(array.iter(&guard).count(), 0);
+
+    // Slots that are not stored are not visited.
+    let _ = array.get(5, &guard);
+    assert_eq!(array.iter(&guard).count(), 0);
+
+    let indices = [0, 1, 1000, 1_000_000];
+    let elements = store_all(&array, &indices, &guard);
+    let (visited, values): (Vec<_>, Vec<_>) = array
+        .iter(&guard)
+        .map(|(index, element)| (index, *unsafe { element.deref() }))
+        .unzip();
+    assert_eq!(visited, indices);
+    assert_eq!(values, indices);
+
+    for element in elements {
+        drop(unsafe { element.into_owned() });
+    }
+}
+
+/// Sets the slots of `indices` to null, and frees their elements.
+fn clear_all(array: &GrowableArray<usize>, indices: &[usize], guard: &Guard) {
+    for &index in indices {
+        let element = array
+            .get(index, guard)
+            .swap(Shared::null(), Ordering::AcqRel, guard);
+        drop(unsafe { element.into_owned() });
+    }
+}
+
+#[test]
+fn prune() {
+    let array = GrowableArray::<usize>::new();
+    let guard = pin();
+    // No other thread uses the array.
+    unsafe { array.prune(&guard) };
+    assert_eq!(array.height(), 0);
+
+    let _ = store_all(&array, &[0, 1, 1000, 1_000_000, 1 << 30], &guard);
+    assert_eq!((array.height(), array.capacity()), (4, 1 << 40));
+
+    // The segments of the cleared index go away, and the root is lowered to the one that covers
+    // the rest.
+    clear_all(&array, &[1 << 30], &guard);
+    unsafe { array.prune(&guard) };
+    assert_eq!((array.height(), array.capacity()), (2, 1 << 20));
+    let indices = array
+        .iter(&guard)
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+    assert_eq!(indices, [0, 1, 1000, 1_000_000]);
+
+    clear_all(&array, &[1_000_000], &guard);
+    unsafe { array.prune(&guard) };
+    assert_eq!((array.height(), array.capacity()), (1, 1 << 10));
+    let element = array.get(1000, &guard).load(Ordering::Acquire, &guard);
+    assert_eq!(unsafe { element.deref() }, &1000);
+
+    // Pruning again changes nothing.
+    unsafe { array.prune(&guard) };
+    assert_eq!(array.height(), 1);
+    assert_eq!(array.occupied(&guard), 3);
+
+    clear_all(&array, &[0, 1, 1000], &guard);
+    unsafe { array.prune(&guard) };
+    assert_eq!((array.height(), array.capacity()), (0, 0));
+    assert_eq!(array.iter(&guard).count(), 0);
+
+    // The array grows again.
+    let _ = store_all(&array, &[1 << 20], &guard);
+    assert_eq!(array.height(), 3);
+    assert_eq!(array.iter(&guard).count(), 1);
+    clear_all(&array, &[1 << 20], &guard);
+}
+
+// Readers `get` the `live` indices that stay stored while a thread `get`s far indices, so that the
+// array keeps growing, and `pruners` threads keep pruning it. Nothing is stored under the far
+// indices, which `prune` requires.
+fn prune_concurrent_with(live: usize, pruners: usize) {
+    const READERS: usize = 2;
+    const STEPS: usize = 4096 * 4;
+
+    let array = GrowableArray::<usize>::new();
+    let live = (0..live).map(|i| i * 7).collect::<Vec<_>>();
+    let _ = store_all(&array, &live, &pin());
+    let done = AtomicBool::new(false);
+    scope(|s| {
+        let (array, live, done) = (&array, &live, &done);
+        for _ in 0..READERS {
+            let _ = s.spawn(move |_| {
+                while !done.load(Ordering::Acquire) {
+                    for &index in live {
+                        let guard = pin();
+                        let element = array.get(index, &guard).load(Ordering::Acquire, &guard);
+                        assert_eq!(unsafe { element.as_ref() }, Some(&index));
+                    }
+                }
+            });
+        }
+        for _ in 0..pruners {
+            let _ = s.spawn(move |_| {
+                while !done.load(Ordering::Acquire) {
+                    unsafe { array.prune(&pin()) };
+                }
+            });
+        }
+        let _ = s.spawn(move |_| {
+            for step in 0..STEPS {
+                let index = (step % 3 + 1) << (10 * (step % 3 + 1));
+                let guard = pin();
+                let element = array.get(index, &guard).load(Ordering::Acquire, &guard);
+                assert!(element.is_null());
+            }
+            done.store(true, Ordering::Release);
+        });
+    })
+    .unwrap();
+
+    let guard = pin();
+    unsafe { array.prune(&guard) };
+    assert_eq!(array.height(), if live.is_empty() { 0 } else { 1 });
+    assert_eq!(array.occupied(&guard), live.len());
+    clear_all(&array, &live, &guard);
+}
+
+#[test]
+fn prune_concurrent() {
+    prune_concurrent_with(64, 1);
+}
+
+// The array is lowered to empty segments, which a concurrent prune should not free while they're
+// the root.
+#[test]
+fn prune_concurrent_pruners() {
+    prune_concurrent_with(0, 2);
+}
+
+// Threads store to their own indices, spread over several heights, while a reader checks that the
+// numbers only grow.
+#[test]
+fn introspection_concurrent() {
+    const THREADS: usize = 4;
+    const STEPS: usize = 1024;
+
+    let array = GrowableArray::<usize>::new();
+    // Two elements per leaf segment, which need up to height 3.
+    let index = |i: usize, t: usize| (i * THREADS + t) << 9;
+    scope(|s| {
+        let array = &array;
+        let _ = s.spawn(move |_| {
+            let (mut height, mut capacity, mut occupied) = (0, 0, 0);
+            while occupied < THREADS * STEPS {
+                let guard = pin();
+                let (h, c, o) = (array.height(), array.capacity(), array.occupied(&guard));
+                assert!(h >= height && c >= capacity && o >= occupied);
+                assert!(o <= THREADS * STEPS);
+                height = h;
+                capacity = c;
+                occupied = o;
+            }
+        });
+        for t in 0..THREADS {
+            let _ = s.spawn(move |_| {
+                let indices = (0..STEPS).map(|i| index(i, t)).collect::<Vec<_>>();
+                let _ = store_all(array, &indices, &pin());
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = pin();
+    assert_eq!(array.height(), 3);
+    assert_eq!(array.occupied(&guard), THREADS * STEPS);
+    for t in 0..THREADS {
+        for i in 0..STEPS {
+            let element = array
+                .get(index(i, t), &guard)
+                .load(Ordering::Relaxed, &guard);
+            drop(unsafe { element.into_owned() });
+        }
+    }
+}
+
 #[test]
 fn stress_sequential() {
     const STEPS: usize = 4096;
@@ -171,3 +455,76 @@ fn log_concurrent() {
     const STEPS: usize = 4096 * 12;
     map::log_concurrent::<u32, NonblockingConcurrentMap<_, _, ArrayMap<usize>>>(THREADS, STEPS);
 }
+
+mod mock;
+
+// Races of `get`s that grow the tree. The root is a loom atomic only with
+// `cargo test --features loom-growable-array --test growable_array sync`; with `check-loom` alone,
+// loom doesn't explore the races on it.
+mod sync {
+    use super::mock::model;
+    use super::mock::sync::Arc;
+    use super::mock::thread;
+    use core::ptr;
+    use core::sync::atomic::Ordering;
+    use crossbeam_epoch::{unprotected, Owned, Shared};
+    use cs431_homework::test_util::drop_counter::{Counted, DropCounter};
+    use cs431_homework::GrowableArray;
+
+    /// Stores to index 0, and then two threads store to `indices`, growing the tree to `height`.
+    /// The slots the threads stored to should be the ones in the final tree, and every element
+    /// should be reachable from it.
+    fn grow_race(counter: &'static DropCounter, indices: [usize; 2], height: usize) {
+        // No segment is freed while the array is alive, so the guards don't need to protect
+        // anything.
+        model(move || {
+            let guard = unsafe { unprotected() };
+            let array = Arc::new(GrowableArray::<Counted<'static, usize>>::new());
+            array
+                .get(0, guard)
+                .store(Owned::new(counter.track(0)), Ordering::Release);
+
+            let spawn = |index| {
+                let array = array.clone();
+                thread::spawn(move || {
+                    let slot = array.get(index, unsafe { unprotected() });
+                    slot.store(Owned::new(counter.track(index)), Ordering::Release);
+                    slot as *const _ as usize
+                })
+            };
+            let handles = [spawn(indices[0]), spawn(indices[1])];
+            let slots = handles.map(|handle| handle.join().unwrap());
+            for (&index, &slot) in indices.iter().zip(&slots) {
+                let found = array.try_get(index, guard).unwrap();
+                assert!(ptr::eq(found, slot as *const _), "{}", index);
+            }
+            assert_eq!(array.height(), height);
+
+            for &index in [0].iter().chain(&indices) {
+                let element = array.try_get(index, guard).unwrap().swap(
+                    Shared::null(),
+                    Ordering::AcqRel,
+                    guard,
+                );
+                assert_eq!(**unsafe { element.deref() }, index);
+                drop(unsafe { element.into_owned() });
+            }
+            drop(array);
+            assert_eq!(counter.live(), 0);
+        });
+    }
+
+    // One thread needs one more level and the other needs two.
+    #[test]
+    fn grow_one_and_two_levels_sync() {
+        static COUNTER: DropCounter = DropCounter::new();
+        grow_race(&COUNTER, [1 << 10, 1 << 20], 3);
+    }
+
+    // Both threads need the same new level, under different children of the new root.
+    #[test]
+    fn grow_same_level_sync() {
+        static COUNTER: DropCounter = DropCounter::new();
+        grow_race(&COUNTER, [1 << 10, 2 << 10], 2);
+    }
+}
@@ -0,0 +1,54 @@
+//! Checks which operations of `GrowableArray` allocate, with an allocator that counts the
+//! allocations. The allocator is global, so this binary has only one test.
+
+use crossbeam_epoch as epoch;
+use cs431_homework::GrowableArray;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Number of allocations so far.
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+struct CountingAlloc;
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATED.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+#[test]
+fn try_get_never_allocates() {
+    // The first pin registers the thread to the GC, which allocates.
+    drop(epoch::pin());
+
+    let array = GrowableArray::<usize>::new();
+    let guard = epoch::pin();
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    for &index in &[0, 1, 1 << 20, usize::MAX] {
+        assert!(array.try_get(index, &guard).is_none());
+    }
+    assert_eq!(ALLOCATED.load(Ordering::Relaxed), allocated);
+
+    // `get` allocates the segments on the way.
+    let slot = array.get(1 << 20, &guard);
+    assert!(ALLOCATED.load(Ordering::Relaxed) > allocated);
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+
+    // The allocated path is found, and the missing ones are not allocated.
+    assert!(ptr::eq(array.try_get(1 << 20, &guard).unwrap(), slot));
+    assert!(array.try_get(0, &guard).is_some());
+    for &index in &[1 << 10, 1 << 21, 1 << 30, usize::MAX] {
+        assert!(array.try_get(index, &guard).is_none());
+    }
+    assert_eq!(ALLOCATED.load(Ordering::Relaxed), allocated);
+}
@@ -1,28 +1,50 @@
-use core::mem::ManuallyDrop;
-use core::ptr;
+use std::collections::HashSet;
+use std::sync::{mpsc, Arc, Mutex};
 use std::thread::sleep;
 use std::time::Duration;
 
 #[cfg(not(feature = "check-loom"))]
-use core::sync::atomic::{AtomicPtr, Ordering::*};
+use core::sync::atomic::{AtomicBool, AtomicPtr, Ordering::*};
 #[cfg(feature = "check-loom")]
-use loom::sync::atomic::{AtomicPtr, Ordering::*};
+use loom::sync::atomic::{AtomicBool, AtomicPtr, Ordering::*};
 
+use crossbeam_epoch::{unprotected, Atomic, Owned};
 use crossbeam_utils::thread::scope;
-use cs431_homework::hazard_pointer::{collect, retire, Shield};
+use cs431_homework::hazard_pointer::{Domain, HazardBag, ProtectedPtr, Shield};
+use cs431_homework::hello_server::ThreadPool;
+use cs431_homework::test_util::drop_counter::{Counted, DropCounter};
+
+// The data structures on the global `HAZARDS`.
+#[cfg(all(feature = "global-hazards", not(feature = "check-loom")))]
+use core::sync::atomic::{AtomicIsize, AtomicUsize};
+#[cfg(all(feature = "global-hazards", feature = "check-loom"))]
+use loom::sync::atomic::{AtomicIsize, AtomicUsize};
+#[cfg(feature = "global-hazards")]
+use {
+    crossbeam_epoch::pin,
+    cs431_homework::hazard_pointer::list::{HarrisList, ShieldBundle},
+    cs431_homework::hazard_pointer::queue::Queue,
+    cs431_homework::hazard_pointer::stack::Stack,
+    cs431_homework::test_util::ops::{self, Op, KEYS},
+    cs431_homework::test_util::rng::rng,
+    cs431_homework::{NonblockingMap, SplitOrderedList},
+    rand::{Rng, RngCore},
+};
 
 #[test]
 fn counter() {
     const THREADS: usize = 4;
     const ITER: usize = 1024 * 16;
 
+    let domain = Domain::new();
     let count = AtomicPtr::new(Box::leak(Box::new(0usize)));
     scope(|s| {
         for _ in 0..THREADS {
             s.spawn(|_| {
+                let mut retired = domain.retired_set();
                 for _ in 0..ITER {
                     let mut new = Box::new(0);
-                    let shield = Shield::default();
+                    let shield = domain.shield();
                     loop {
                         let cur_ptr = shield.protect(&count);
                         let value = unsafe { *cur_ptr };
@@ -32,7 +54,7 @@ fn counter() {
                             .compare_exchange(cur_ptr as *mut _, new_ptr, AcqRel, Acquire)
                             .is_ok()
                         {
-                            retire(cur_ptr as *mut usize);
+                            retired.retire(cur_ptr as *mut usize);
                             break;
                         } else {
                             new = unsafe { Box::from_raw(new_ptr) };
@@ -46,7 +68,7 @@ fn counter() {
     let cur = count.load(Acquire);
     // exclusive access
     assert_eq!(unsafe { *cur }, THREADS * ITER);
-    retire(cur);
+    domain.retired_set().retire(cur);
 }
 
 // like `counter`, but trigger interesting interleaving using `sleep` and always call `collect`.
@@ -55,13 +77,15 @@ fn counter_sleep() {
     const THREADS: usize = 4;
     const ITER: usize = 1024 * 16;
 
+    let domain = Domain::new();
     let count = AtomicPtr::new(Box::leak(Box::new(0usize)));
     scope(|s| {
         for _ in 0..THREADS {
             s.spawn(|_| {
+                let mut retired = domain.retired_set();
                 for _ in 0..ITER {
                     let mut new = Box::new(0);
-                    let shield = Shield::default();
+                    let shield = domain.shield();
                     loop {
                         let mut cur_ptr = count.load(Relaxed) as *const _;
                         while !shield.try_protect(&mut cur_ptr, &count) {
@@ -74,8 +98,8 @@ fn counter_sleep() {
                             .compare_exchange(cur_ptr as *mut _, new_ptr, AcqRel, Acquire)
                             .is_ok()
                         {
-                            retire(cur_ptr as *mut usize);
-                            collect();
+                            retired.retire(cur_ptr as *mut usize);
+                            retired.collect();
                             break;
                         } else {
                             new = unsafe { Box::from_raw(new_ptr) };
@@ -89,10 +113,11 @@ fn counter_sleep() {
     let cur = count.load(Acquire);
     // exclusive access
     assert_eq!(unsafe { *cur }, THREADS * ITER);
-    retire(cur);
+    domain.retired_set().retire(cur);
 }
 
 #[test]
+#[cfg(feature = "global-hazards")]
 fn stack() {
     const THREADS: usize = 8;
     const ITER: usize = 1024 * 16;
@@ -113,6 +138,7 @@ fn stack() {
 }
 
 #[test]
+#[cfg(feature = "global-hazards")]
 fn two_stacks() {
     const THREADS: usize = 8;
     const ITER: usize = 1024 * 16;
@@ -135,98 +161,389 @@ fn two_stacks() {
     assert!(stack1.pop().is_none());
 }
 
-/// Treiber's lock-free stack.
-///
-/// Usable with any number of producers and consumers.
-#[derive(Debug)]
-pub struct Stack<T> {
-    head: AtomicPtr<Node<T>>,
+#[test]
+#[cfg(feature = "global-hazards")]
+fn queue() {
+    let counter = DropCounter::new();
+    let queue = Queue::new();
+    assert!(queue.is_empty());
+    for i in 0..16 {
+        queue.push(counter.track(i));
+    }
+    assert!(!queue.is_empty());
+    for i in 0..8 {
+        assert_eq!(*queue.try_pop().unwrap(), i);
+    }
+    assert_eq!(counter.live(), 8);
+    drop(queue);
+    assert_eq!(counter.live(), 0);
 }
 
-#[derive(Debug)]
-struct Node<T> {
-    data: ManuallyDrop<T>,
-    next: *const Node<T>,
+// Consumers see the values of each producer in the order they are pushed.
+#[test]
+#[cfg(feature = "global-hazards")]
+fn queue_fifo() {
+    const PRODUCERS: usize = 4;
+    const CONSUMERS: usize = 4;
+    const ITER: usize = 1024 * 16;
+
+    let queue = Queue::new();
+    let remaining = AtomicUsize::new(PRODUCERS * ITER);
+    let mut popped = HashSet::new();
+    scope(|s| {
+        for p in 0..PRODUCERS {
+            let queue = &queue;
+            s.spawn(move |_| {
+                for i in 0..ITER {
+                    queue.push((p, i));
+                }
+            });
+        }
+        let consumers = (0..CONSUMERS)
+            .map(|_| {
+                s.spawn(|_| {
+                    let mut popped = Vec::new();
+                    let mut last = [None; PRODUCERS];
+                    while remaining.load(Relaxed) > 0 {
+                        if let Some((p, i)) = queue.try_pop() {
+                            let _ = remaining.fetch_sub(1, Relaxed);
+                            assert!(last[p] < Some(i));
+                            last[p] = Some(i);
+                            popped.push((p, i));
+                        }
+                    }
+                    popped
+                })
+            })
+            .collect::<Vec<_>>();
+        for consumer in consumers {
+            for value in consumer.join().unwrap() {
+                assert!(popped.insert(value));
+            }
+        }
+    })
+    .unwrap();
+    assert!(queue.is_empty());
+    assert_eq!(popped.len(), PRODUCERS * ITER);
 }
 
-unsafe impl<T: Send> Send for Node<T> {}
-unsafe impl<T: Sync> Sync for Node<T> {}
+// A `ProtectedPtr` keeps the pointer protected while it's moved to a thread pool.
+#[test]
+fn protected_ptr_thread_pool() {
+    let hazards = Arc::new(HazardBag::new());
+    let node = Box::into_raw(Box::new(123usize));
+    let src = AtomicPtr::new(node);
+    let expected = [node as usize].iter().cloned().collect::<HashSet<_>>();
+
+    let shield = Shield::new(&hazards);
+    assert_eq!(shield.protect(&src), node);
+    // `shield` is from `hazards`.
+    let protected = unsafe { ProtectedPtr::new(&hazards, &shield) };
+    assert_eq!(protected.as_ptr(), node);
+    drop(shield);
+    assert_eq!(hazards.all_hazards(), expected);
+
+    let pool = ThreadPool::new(1);
+    let (sender, receiver) = mpsc::channel();
+    pool.submit(move || {
+        sender.send(hazards.all_hazards()).unwrap();
+        assert_eq!(unsafe { *protected.as_ptr() }, 123);
+        drop(protected);
+        sender.send(hazards.all_hazards()).unwrap();
+    })
+    .unwrap();
+    assert_eq!(receiver.recv().unwrap(), expected);
+    assert_eq!(receiver.recv().unwrap(), HashSet::new());
+    pool.join();
+    drop(unsafe { Box::from_raw(node) });
+}
 
-impl<T> Stack<T> {
-    /// Creates a new, empty stack.
-    pub fn new() -> Stack<T> {
-        Stack {
-            head: AtomicPtr::new(ptr::null_mut()),
+// Readers protect nodes while a writer replaces and retires them. No protected node is freed.
+#[test]
+fn retire_protected() {
+    const READERS: usize = 4;
+    const ITER: usize = 1024 * 16;
+
+    struct Node {
+        id: usize,
+        freed: &'static Mutex<HashSet<usize>>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            assert!(self.freed.lock().unwrap().insert(self.id));
         }
     }
 
-    /// Pushes a value on top of the stack.
-    pub fn push(&self, t: T) {
-        let new = Box::leak(Box::new(Node {
-            data: ManuallyDrop::new(t),
-            next: ptr::null(),
-        }));
-
-        loop {
-            let head = self.head.load(Relaxed);
-            new.next = head;
-
-            if self
-                .head
-                .compare_exchange(head, new, Release, Relaxed)
-                .is_ok()
-            {
-                break;
+    let freed: &'static Mutex<HashSet<usize>> = Box::leak(Box::new(Mutex::new(HashSet::new())));
+    let new_node = |id| Box::into_raw(Box::new(Node { id, freed }));
+    let domain = Domain::new();
+    let src = AtomicPtr::new(new_node(0));
+    let done = AtomicBool::new(false);
+    scope(|s| {
+        for _ in 0..READERS {
+            s.spawn(|_| {
+                let shield = domain.shield();
+                while !done.load(Acquire) {
+                    let node = unsafe { shield.protect_ref(&src) }.unwrap();
+                    assert!(!freed.lock().unwrap().contains(&node.id));
+                }
+            });
+        }
+        s.spawn(|_| {
+            let mut retired = domain.retired_set();
+            for id in 1..ITER {
+                let old = src.swap(new_node(id), AcqRel);
+                retired.retire(old);
             }
+            retired.collect();
+            done.store(true, Release);
+        });
+    })
+    .unwrap();
+
+    let mut retired = domain.retired_set();
+    retired.retire(src.load(Acquire));
+    retired.collect();
+    assert_eq!(freed.lock().unwrap().len(), ITER);
+}
+
+// Readers protect tagged nodes published through an epoch `Atomic`, while a writer replaces them
+// and retires them with hazard pointers instead of `defer_destroy`. No protected node is freed.
+#[test]
+fn protect_epoch_atomic() {
+    const READERS: usize = 4;
+    const ITER: usize = 1024 * 16;
+
+    struct Node {
+        id: usize,
+        freed: &'static Mutex<HashSet<usize>>,
+    }
+
+    impl Drop for Node {
+        fn drop(&mut self) {
+            assert!(self.freed.lock().unwrap().insert(self.id));
         }
     }
 
-    /// Attempts to pop the top element from the stack.
-    ///
-    /// Returns `None` if the stack is empty.
-    pub fn pop(&self) -> Option<T> {
-        let shield = Shield::default();
-        loop {
-            let head_ptr = shield.protect(&self.head);
-            let head_ref = unsafe { head_ptr.as_ref()? };
-
-            if self
-                .head
-                .compare_exchange(
-                    head_ptr as *mut _,
-                    head_ref.next as *mut _,
-                    Relaxed,
-                    Relaxed,
-                )
-                .is_ok()
-            {
-                let head_ptr = head_ptr as *mut Node<T>;
-                unsafe {
-                    let data = ManuallyDrop::take(&mut (*head_ptr).data);
-                    retire(head_ptr);
-                    return Some(data);
+    let freed: &'static Mutex<HashSet<usize>> = Box::leak(Box::new(Mutex::new(HashSet::new())));
+    let new_node = |id| Owned::new(Node { id, freed }).with_tag(id % 2);
+    let domain = Domain::new();
+    let src = Atomic::null();
+    src.store(new_node(0), Release);
+    let done = AtomicBool::new(false);
+    scope(|s| {
+        for _ in 0..READERS {
+            let _ = s.spawn(|_| {
+                let shield = domain.shield();
+                let guard = unsafe { unprotected() };
+                while !done.load(Acquire) {
+                    let node = shield.protect_epoch(&src, guard);
+                    let node = unsafe { node.deref() };
+                    assert!(!freed.lock().unwrap().contains(&node.id));
                 }
+            });
+        }
+        let _ = s.spawn(|_| {
+            let guard = unsafe { unprotected() };
+            let mut retired = domain.retired_set();
+            for id in 1..ITER {
+                let old = src.swap(new_node(id), AcqRel, guard);
+                assert_eq!(old.tag(), (id - 1) % 2);
+                retired.retire(old.as_raw());
             }
+            retired.collect();
+            done.store(true, Release);
+        });
+    })
+    .unwrap();
+
+    let mut retired = domain.retired_set();
+    retired.retire(src.load(Acquire, unsafe { unprotected() }).as_raw());
+    retired.collect();
+    assert_eq!(freed.lock().unwrap().len(), ITER);
+}
+
+// Short-lived threads retire nodes that are protected when they exit. The nodes are freed by a
+// later collection of another thread.
+#[test]
+fn retired_on_thread_exit() {
+    const THREADS: usize = 8;
+    const NODES: usize = 4;
+
+    let domain = Domain::new();
+    let counter = DropCounter::new();
+    let nodes = (0..THREADS * NODES)
+        .map(|i| Box::into_raw(Box::new(counter.track(i))))
+        .collect::<Vec<_>>();
+    let shields = nodes
+        .iter()
+        .map(|&node| {
+            let shield = domain.shield();
+            shield.set(node);
+            shield
+        })
+        .collect::<Vec<_>>();
+    let addrs = nodes.iter().map(|&node| node as usize).collect::<Vec<_>>();
+    scope(|s| {
+        for chunk in addrs.chunks(NODES) {
+            let domain = &domain;
+            let _ = s.spawn(move |_| {
+                let mut retired = domain.retired_set();
+                for &node in chunk {
+                    retired.retire(node as *mut Counted<'_, usize>);
+                }
+            });
         }
+    })
+    .unwrap();
+    assert_eq!(counter.live(), THREADS * NODES);
+
+    drop(shields);
+    domain.retired_set().collect();
+    assert_eq!(counter.created(), THREADS * NODES);
+    assert_eq!(counter.live(), 0);
+}
+
+/// Performs `op` on `map` with the keys shifted by `base`. Returns the observable results.
+#[cfg(feature = "global-hazards")]
+fn apply_op<G>(
+    map: &impl NonblockingMap<usize, usize, G>,
+    guard: &G,
+    base: usize,
+    op: Op,
+) -> Vec<Option<usize>> {
+    match op {
+        Op::Insert(k, v) => vec![map.insert(&(base + k), v, guard).err()],
+        Op::Delete(k) => vec![map.remove(&(base + k), guard).ok().copied()],
+        Op::Lookup(k) => vec![map.lookup(&(base + k), guard).copied()],
+        Op::Iterate => (base..base + KEYS)
+            .map(|k| map.lookup(&k, guard).copied())
+            .collect(),
     }
+}
+
+// Threads run random traces on a shared `HarrisList` and each on its own `SplitOrderedList`, in
+// disjoint key ranges. The lists should give the same results.
+#[test]
+#[cfg(feature = "global-hazards")]
+fn harris_list_differential() {
+    const THREADS: usize = 8;
+    const OPS: usize = 1024 * 4;
+
+    let list = HarrisList::new();
+    let mut rng = rng();
+    let rngs = (0..THREADS).map(|_| rng.fork()).collect::<Vec<_>>();
+    scope(|s| {
+        for (t, mut rng) in rngs.into_iter().enumerate() {
+            let list = &list;
+            let _ = s.spawn(move |_| {
+                let mut bytes = vec![0; OPS * ops::OP_BYTES];
+                rng.fill_bytes(&mut bytes);
+                let oracle = SplitOrderedList::new();
+                for op in ops::decode(&bytes) {
+                    let bundle = ShieldBundle::new();
+                    let expected = apply_op(&oracle, &pin(), t * KEYS, op);
+                    assert_eq!(apply_op(list, &bundle, t * KEYS, op), expected, "{:?}", op);
+                }
+            });
+        }
+    })
+    .unwrap();
+}
+
+// Threads insert and delete the same keys. Each key is in the list iff its successful inserts
+// outnumber its successful deletes.
+#[test]
+#[cfg(feature = "global-hazards")]
+fn harris_list_contended() {
+    const THREADS: usize = 8;
+    const OPS: usize = 1024 * 16;
 
-    /// Returns `true` if the stack is empty.
-    pub fn is_empty(&self) -> bool {
-        self.head.load(Acquire).is_null()
+    let list = HarrisList::new();
+    let mut rng = rng();
+    let rngs = (0..THREADS).map(|_| rng.fork()).collect::<Vec<_>>();
+    let balances = (0..KEYS).map(|_| AtomicIsize::new(0)).collect::<Vec<_>>();
+    scope(|s| {
+        for mut rng in rngs {
+            let (list, balances) = (&list, &balances);
+            let _ = s.spawn(move |_| {
+                for _ in 0..OPS {
+                    let key = rng.gen_range(0..KEYS);
+                    let bundle = ShieldBundle::new();
+                    if rng.gen() {
+                        if list.insert(key, key).is_ok() {
+                            let _ = balances[key].fetch_add(1, Relaxed);
+                        }
+                    } else if let Ok(&value) = list.delete(&key, &bundle) {
+                        assert_eq!(value, key);
+                        let _ = balances[key].fetch_sub(1, Relaxed);
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let bundle = ShieldBundle::new();
+    for (key, balance) in balances.iter().enumerate() {
+        let present = list.lookup(&key, &bundle).is_some();
+        assert_eq!(present as isize, balance.load(Relaxed));
     }
 }
 
-impl<T> Drop for Stack<T> {
-    fn drop(&mut self) {
-        while self.pop().is_some() {}
+// Threads get or insert the same keys, starting at different keys. They should agree on one value
+// per key, and the values of the losers should be dropped.
+#[test]
+#[cfg(feature = "global-hazards")]
+fn harris_list_get_or_insert_with() {
+    const THREADS: usize = 8;
+
+    let counter = DropCounter::new();
+    let list = HarrisList::new();
+    // The value each thread got for each key.
+    let mut observed = vec![vec![0; KEYS]; THREADS];
+    scope(|s| {
+        for (t, observed) in observed.iter_mut().enumerate() {
+            let (list, counter) = (&list, &counter);
+            let _ = s.spawn(move |_| {
+                for i in 0..KEYS {
+                    let key = (t * KEYS / THREADS + i) % KEYS;
+                    let bundle = ShieldBundle::new();
+                    let mut calls = 0;
+                    let value = list.get_or_insert_with(
+                        key,
+                        || {
+                            calls += 1;
+                            counter.track(t)
+                        },
+                        &bundle,
+                    );
+                    assert!(calls <= 1);
+                    observed[key] = **value;
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let bundle = ShieldBundle::new();
+    for key in 0..KEYS {
+        let value = **list.lookup(&key, &bundle).unwrap();
+        assert!(observed.iter().all(|o| o[key] == value));
     }
+    assert_eq!(counter.live(), KEYS);
+    drop(list);
+    assert_eq!(counter.live(), 0);
 }
 
 mod mock;
 
+// Uses the global `HAZARDS`, which `check-loom` enables.
+#[cfg(feature = "global-hazards")]
 mod sync {
     use super::mock::model;
-    use super::mock::sync::atomic::{AtomicPtr, AtomicUsize, Ordering::*};
+    use super::mock::sync::atomic::{fence, AtomicBool, AtomicPtr, AtomicUsize, Ordering::*};
     use super::mock::sync::Arc;
     use super::mock::thread;
     use core::ptr;
@@ -291,6 +608,227 @@ mod sync {
         })
     }
 
+    // A node published with release is initialized when `protect` returns it.
+    #[test]
+    fn protect_publish_sync() {
+        model(|| {
+            let atomic = Arc::new(AtomicPtr::new(ptr::null_mut::<AtomicUsize>()));
+
+            let th = {
+                let atomic = atomic.clone();
+                thread::spawn(move || {
+                    let shield = Shield::default();
+                    let local = shield.protect(&atomic);
+                    if !local.is_null() {
+                        assert_eq!(unsafe { (*local).load(Relaxed) }, 123);
+                    }
+                })
+            };
+
+            // initialize and publish
+            let local = Box::into_raw(Box::new(AtomicUsize::new(0)));
+            unsafe { (*local).store(123, Relaxed) };
+            atomic.store(local, Release);
+
+            th.join().unwrap();
+            unsafe { drop(Box::from_raw(local)) };
+        })
+    }
+
+    /// Value of a node that is "freed". A reader of a protected node should never see it.
+    const FREED: usize = usize::MAX;
+
+    /// Unlinks the node in `atomic` and "frees" it if it's not protected, as `collect` does.
+    /// Returns the node.
+    fn unlink_reclaim(hazards: &HazardBag, atomic: &AtomicPtr<AtomicUsize>) -> *mut AtomicUsize {
+        let local = atomic.swap(ptr::null_mut(), Relaxed);
+        fence(SeqCst);
+        if !hazards.all_hazards().contains(&(local as usize)) {
+            unsafe { (*local).store(FREED, Relaxed) };
+        }
+        local
+    }
+
+    // A node validated by `try_protect` is not freed by a concurrent reclaimer.
+    #[test]
+    fn try_protect_reclaim_sync() {
+        model(|| {
+            let hazards = Arc::new(HazardBag::new());
+            let node = Box::into_raw(Box::new(AtomicUsize::new(123)));
+            let atomic = Arc::new(AtomicPtr::new(node));
+
+            let th = {
+                let hazards = hazards.clone();
+                let atomic = atomic.clone();
+                thread::spawn(move || {
+                    let shield = Shield::new(&hazards);
+                    let mut local = atomic.load(Relaxed) as *const AtomicUsize;
+                    if !local.is_null() && shield.try_protect(&mut local, &atomic) {
+                        assert_eq!(unsafe { (*local).load(Relaxed) }, 123);
+                    }
+                })
+            };
+
+            let local = unlink_reclaim(&hazards, &atomic);
+            th.join().unwrap();
+            unsafe { drop(Box::from_raw(local)) };
+        })
+    }
+
+    // Like `try_protect_reclaim_sync`, but the reader retries with `protect`.
+    #[test]
+    fn protect_reclaim_sync() {
+        model(|| {
+            let hazards = Arc::new(HazardBag::new());
+            let node = Box::into_raw(Box::new(AtomicUsize::new(123)));
+            let atomic = Arc::new(AtomicPtr::new(node));
+
+            let th = {
+                let hazards = hazards.clone();
+                let atomic = atomic.clone();
+                thread::spawn(move || {
+                    let shield = Shield::new(&hazards);
+                    let local = shield.protect(&atomic);
+                    if !local.is_null() {
+                        assert_eq!(unsafe { (*local).load(Relaxed) }, 123);
+                    }
+                })
+            };
+
+            let local = unlink_reclaim(&hazards, &atomic);
+            th.join().unwrap();
+            unsafe { drop(Box::from_raw(local)) };
+        })
+    }
+
+    // The hazard of a recycled slot is not overwritten by the previous owner's clear.
+    #[test]
+    fn recycle_slot_sync() {
+        model(|| {
+            let hazards = Arc::new(HazardBag::new());
+            // Relaxed, so that it doesn't synchronize `th` with the main thread.
+            let released = Arc::new(AtomicBool::new(false));
+
+            let th = {
+                let hazards = hazards.clone();
+                let released = released.clone();
+                thread::spawn(move || {
+                    let shield = Shield::new(&hazards);
+                    shield.set(8 as *const ());
+                    drop(shield);
+                    released.store(true, Relaxed);
+                })
+            };
+
+            if released.load(Relaxed) {
+                let shield = Shield::new(&hazards);
+                shield.set(16 as *const ());
+                // A new slot would be linked before the slot of `th`. So if there is only one
+                // slot, `shield` recycled the slot of `th`.
+                if hazards.slot_count() == 1 {
+                    assert_eq!(hazards.all_hazards(), [16].iter().cloned().collect());
+                }
+            }
+            th.join().unwrap();
+        })
+    }
+
+    // While a shield is dropped, a reclaimer doesn't miss its accesses and a recycler of its slot
+    // keeps its own hazard.
+    #[test]
+    fn shield_drop_recycle_sync() {
+        model(|| {
+            let hazards = Arc::new(HazardBag::new());
+            let obj = Box::into_raw(Box::new(AtomicUsize::new(0))) as usize;
+            let shield = Shield::new(&hazards);
+            shield.set(obj as *const AtomicUsize);
+
+            let reclaimer = {
+                let hazards = hazards.clone();
+                thread::spawn(move || {
+                    if !hazards.all_hazards().contains(&obj) {
+                        let obj = obj as *const AtomicUsize;
+                        assert_eq!(unsafe { (*obj).load(Relaxed) }, 123);
+                    }
+                })
+            };
+            let recycler = {
+                let hazards = hazards.clone();
+                thread::spawn(move || {
+                    let shield = Shield::new(&hazards);
+                    shield.set(8 as *const ());
+                    assert!(hazards.all_hazards().contains(&8));
+                })
+            };
+
+            unsafe { (*(obj as *const AtomicUsize)).store(123, Relaxed) };
+            drop(shield);
+
+            reclaimer.join().unwrap();
+            recycler.join().unwrap();
+            assert_eq!(hazards.hazards().next(), None);
+            unsafe { drop(Box::from_raw(obj as *mut AtomicUsize)) };
+        })
+    }
+
+    /// Runs the hazard pointer protocol on one pointer with the given fences: a reader publishes a
+    /// hazard and validates it, while a reclaimer unlinks the pointer and checks the hazard. Panics
+    /// if the reader uses the pointer that the reclaimer frees.
+    fn protect_unlink(reader_fence: fn(), reclaimer_fence: fn()) {
+        model(move || {
+            let node = 1usize;
+            let src = Arc::new(AtomicUsize::new(node));
+            let hazard = Arc::new(AtomicUsize::new(0));
+
+            let reader = {
+                let (src, hazard) = (src.clone(), hazard.clone());
+                thread::spawn(move || {
+                    hazard.store(node, Release);
+                    reader_fence();
+                    src.load(Acquire) == node
+                })
+            };
+            src.store(0, Relaxed);
+            reclaimer_fence();
+            let freed = hazard.load(Acquire) != node;
+
+            let used = reader.join().unwrap();
+            assert!(!(used && freed), "use-after-free");
+        })
+    }
+
+    fn sc_fence() {
+        fence(SeqCst);
+    }
+
+    // Without the fences, the store of the hazard and the store of the unlink can both be missed.
+    #[test]
+    #[cfg(feature = "check-loom")]
+    #[should_panic(expected = "use-after-free")]
+    fn protect_unlink_unfenced() {
+        protect_unlink(|| {}, || {});
+    }
+
+    // A fence on only one side is not enough.
+    #[test]
+    #[cfg(feature = "check-loom")]
+    #[should_panic(expected = "use-after-free")]
+    fn protect_unlink_reader_fenced() {
+        protect_unlink(sc_fence, || {});
+    }
+
+    #[test]
+    fn protect_unlink_fenced() {
+        protect_unlink(sc_fence, sc_fence);
+    }
+
+    // The fences of `Shield::try_protect` and `collect`, which are asymmetric with the `membarrier`
+    // feature.
+    #[test]
+    fn protect_unlink_membarrier() {
+        protect_unlink(membarrier::light, membarrier::heavy);
+    }
+
     // Above tests can't detect the absence of release-acquire between `Shield::drop` and `collect`
     // for an unknown reasone. So explicitly check release-acquire between `Shield::drop` and
     // `all_hazards`.
@@ -0,0 +1,229 @@
+//! Stress test of `SplitOrderedList` that checks concurrent histories against a sequential
+//! `BTreeMap`.
+//!
+//! Threads run pre-generated random sequences of inserts, deletes and lookups on a shared list, and
+//! log the result of each operation with the span of a global clock in which it ran. Afterwards
+//! the logs are checked for a linearization: an order of the operations that respects their spans,
+//! in which a sequential map gives the logged results and ends up with the contents of the list.
+//! The operations on different keys commute, so the linearization is searched for each key
+//! separately, on the entry of the key in the sequential map.
+//!
+//! The seed is printed, and a failure can be reproduced with `HOMEWORK_TEST_SEED` (the choices are
+//! reproduced, the interleaving is not). The `slow-tests` feature runs a larger version:
+//!
+//! ```text
+//! cargo test --release --features slow-tests --test linearizability
+//! ```
+//!
+//! With the `failpoints` feature, the threads yield in the windows of the races of inserts, so
+//! that the operations interleave more often even on a single core.
+
+use crossbeam_epoch::pin;
+use crossbeam_utils::thread::scope;
+#[cfg(feature = "failpoints")]
+use cs431_homework::failpoint::{Action, Scenario};
+use cs431_homework::test_util::rng::rng;
+use cs431_homework::{NonblockingMap, SplitOrderedList};
+use rand::Rng;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+#[cfg(not(feature = "slow-tests"))]
+mod size {
+    pub const THREADS: usize = 4;
+    pub const OPS: usize = 1 << 12;
+    pub const KEYS: usize = 64;
+    pub const ROUNDS: usize = 4;
+}
+
+#[cfg(feature = "slow-tests")]
+mod size {
+    pub const THREADS: usize = 8;
+    pub const OPS: usize = 1 << 16;
+    pub const KEYS: usize = 1 << 10;
+    pub const ROUNDS: usize = 16;
+}
+
+use size::*;
+
+/// Operation on a key, with its result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    /// `insert` of the value, and whether it inserted.
+    Insert(usize, bool),
+    /// `remove`, and the deleted value.
+    Delete(Option<usize>),
+    /// `lookup`, and the found value.
+    Lookup(Option<usize>),
+}
+
+impl Op {
+    /// Applies the operation to `value`, the entry of its key in the sequential map. Returns `false`
+    /// if the sequential map gives another result than the logged one.
+    fn apply(self, value: &mut Option<usize>) -> bool {
+        match self {
+            Op::Insert(v, inserted) => {
+                if inserted != value.is_none() {
+                    return false;
+                }
+                if inserted {
+                    *value = Some(v);
+                }
+                true
+            }
+            Op::Delete(deleted) => deleted == value.take(),
+            Op::Lookup(found) => found == *value,
+        }
+    }
+}
+
+/// Logged operation. It took effect between the clock readings `start` and `end`.
+#[derive(Debug, Clone, Copy)]
+struct Event {
+    op: Op,
+    start: usize,
+    end: usize,
+}
+
+/// Returns `true` if the events of a key have a linearization that ends with `last`, the value of
+/// the key in the list after the threads finished. The key is absent before the events.
+///
+/// Searches depth first over the prefixes of the linearizations, which are identified by the
+/// linearized events and the value after them. The next event is one that started before every
+/// pending event ended.
+fn linearizable(events: &[Event], last: Option<usize>) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack = vec![(vec![false; events.len()], None)];
+    while let Some((done, value)) = stack.pop() {
+        let pending = events.iter().zip(&done).filter(|(_, &done)| !done);
+        let first_end = match pending.clone().map(|(e, _)| e.end).min() {
+            Some(first_end) => first_end,
+            None if value == last => return true,
+            None => continue,
+        };
+        for (i, event) in events.iter().enumerate() {
+            if done[i] || event.start > first_end {
+                continue;
+            }
+            let mut next = value;
+            if !event.op.apply(&mut next) {
+                continue;
+            }
+            let mut next_done = done.clone();
+            next_done[i] = true;
+            if visited.insert((next_done.clone(), next)) {
+                stack.push((next_done, next));
+            }
+        }
+    }
+    false
+}
+
+#[test]
+fn linearizable_histories() {
+    let event = |op, start, end| Event { op, start, end };
+
+    // Sequential, and an insert that overlaps a lookup.
+    let events = [
+        event(Op::Insert(1, true), 0, 1),
+        event(Op::Lookup(Some(1)), 2, 5),
+        event(Op::Delete(Some(1)), 3, 4),
+        event(Op::Insert(2, true), 6, 7),
+    ];
+    assert!(linearizable(&events, Some(2)));
+    assert!(!linearizable(&events, None));
+
+    // Lost update: both inserts succeeded, but only one delete is needed afterwards.
+    let events = [
+        event(Op::Insert(1, true), 0, 3),
+        event(Op::Insert(2, true), 1, 2),
+        event(Op::Delete(Some(1)), 4, 5),
+    ];
+    assert!(!linearizable(&events, None));
+
+    // A lookup that finds a value deleted before it started.
+    let events = [
+        event(Op::Insert(1, true), 0, 1),
+        event(Op::Delete(Some(1)), 2, 3),
+        event(Op::Lookup(Some(1)), 4, 5),
+    ];
+    assert!(!linearizable(&events, None));
+}
+
+#[test]
+fn split_ordered_list_linearizable() {
+    #[cfg(feature = "failpoints")]
+    let _scenario = {
+        let scenario = Scenario::setup();
+        scenario.cfg("split_ordered_list::insert", Action::Yield(1));
+        scenario.cfg(
+            "split_ordered_list::lookup_bucket::publish",
+            Action::Yield(1),
+        );
+        scenario
+    };
+    let mut rng = rng();
+    for _ in 0..ROUNDS {
+        let plans = (0..THREADS)
+            .map(|_| {
+                let mut rng = rng.fork();
+                (0..OPS)
+                    .map(|_| (rng.gen_range(0..KEYS), rng.gen_range(0..10)))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let list = SplitOrderedList::new();
+        let clock = AtomicUsize::new(0);
+        let histories = Mutex::new(BTreeMap::<usize, Vec<Event>>::new());
+        scope(|s| {
+            for (t, plan) in plans.iter().enumerate() {
+                let (list, clock, histories) = (&list, &clock, &histories);
+                let _ = s.spawn(move |_| {
+                    let mut log = Vec::with_capacity(OPS);
+                    for (i, &(key, choice)) in plan.iter().enumerate() {
+                        let guard = pin();
+                        let start = clock.fetch_add(1, Ordering::SeqCst);
+                        let op = match choice {
+                            0..=3 => {
+                                let value = t * OPS + i;
+                                Op::Insert(value, list.insert(&key, value, &guard).is_ok())
+                            }
+                            4..=6 => Op::Delete(list.remove(&key, &guard).ok().copied()),
+                            _ => Op::Lookup(list.lookup(&key, &guard).copied()),
+                        };
+                        let end = clock.fetch_add(1, Ordering::SeqCst);
+                        log.push((key, Event { op, start, end }));
+                    }
+
+                    let mut histories = histories.lock().unwrap();
+                    for (key, event) in log {
+                        histories.entry(key).or_default().push(event);
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        let guard = pin();
+        let contents = list
+            .iter(&guard)
+            .map(|(k, v)| (k, *v))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(list.len(), contents.len());
+        assert!(contents.keys().all(|&key| key < KEYS));
+        let histories = histories.into_inner().unwrap();
+        for key in 0..KEYS {
+            let events = histories.get(&key).map_or(&[][..], Vec::as_slice);
+            let last = contents.get(&key).copied();
+            assert!(
+                linearizable(events, last),
+                "no linearization of key {} ending with {:?}: {:#?}",
+                key,
+                last,
+                events
+            );
+        }
+    }
+}
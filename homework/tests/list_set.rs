@@ -1,4 +1,5 @@
 use crossbeam_utils::thread;
+use cs431_homework::test_util::rng::{rng, TestRng};
 use rand::distributions::Alphanumeric;
 use rand::prelude::*;
 use std::collections::{HashMap, HashSet};
@@ -61,7 +62,7 @@ fn stress_sequential() {
         Ops::RemoveNone,
         Ops::Iterate,
     ];
-    let mut rng = thread_rng();
+    let mut rng = rng();
     let set = OrderedListSet::default();
     let mut hashset = HashSet::<String>::new();
 
@@ -111,7 +112,7 @@ fn stress_sequential() {
 const THREADS: usize = 16;
 const STEPS: usize = 4096 * 8;
 
-fn generate_random_string(rng: &mut ThreadRng) -> String {
+fn generate_random_string(rng: &mut TestRng) -> String {
     rng.sample_iter(&Alphanumeric)
         .take(1)
         .map(|x| x as char)
@@ -146,12 +147,14 @@ impl Log {
 fn stress_concurrent() {
     let ops = [Ops::Contains, Ops::Insert, Ops::Remove, Ops::Remove];
 
+    let mut rng = rng();
     let set = OrderedListSet::new();
 
     thread::scope(|s| {
         for _ in 0..THREADS {
-            s.spawn(|_| {
-                let mut rng = thread_rng();
+            let mut rng = rng.fork();
+            let set = &set;
+            s.spawn(move |_| {
                 for _ in 0..STEPS {
                     let op = ops.choose(&mut rng).unwrap();
 
@@ -219,13 +222,15 @@ fn log_concurrent() {
     const THREADS: usize = 16;
     const STEPS: usize = 4096 * 12;
 
+    let mut rng = rng();
     let set = OrderedListSet::new();
 
     let logs = thread::scope(|s| {
         let mut handles = Vec::new();
         for _ in 0..THREADS {
-            let handle = s.spawn(|_| {
-                let mut rng = thread_rng();
+            let mut rng = rng.fork();
+            let set = &set;
+            let handle = s.spawn(move |_| {
                 let mut logs = Vec::new();
                 for _ in 0..STEPS {
                     let op = ops.choose(&mut rng).unwrap();
@@ -276,6 +281,7 @@ fn iter_consistent() {
     const THREADS: usize = 15;
     const STEPS: usize = 4096 * 12;
 
+    let mut rng = rng();
     let set = OrderedListSet::new();
 
     // pre-fill with even numbers
@@ -288,8 +294,9 @@ fn iter_consistent() {
     thread::scope(|s| {
         // insert or remove odd numbers
         for _ in 0..THREADS {
-            s.spawn(|_| {
-                let mut rng = thread_rng();
+            let mut rng = rng.fork();
+            let (set, done) = (&set, &done);
+            s.spawn(move |_| {
                 for _ in 0..STEPS {
                     let key = 2 * rng.gen_range(0..50) + 1;
                     if rng.gen() {
@@ -1,13 +1,17 @@
+#![allow(deprecated)]
+
 use core::fmt;
 use core::hash::Hash;
 use core::marker::PhantomData;
-use cs431_homework::{ConcurrentMap, RandGen, SequentialMap};
+use cs431_homework::test_util::rng::{rng, TestRng};
+use cs431_homework::{ConcurrentMap, MapError, RandGen, SequentialMap};
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
 
 use rand::prelude::*;
 
 use crossbeam_epoch::pin;
-use crossbeam_utils::thread;
 
 pub fn stress_sequential<
     K: fmt::Debug + Clone + Eq + Hash + RandGen,
@@ -31,7 +35,7 @@ pub fn stress_sequential<
         Ops::DeleteSome,
         Ops::DeleteNone,
     ];
-    let mut rng = thread_rng();
+    let mut rng = rng();
     let mut map = M::default();
     let mut hashmap = HashMap::<K, usize>::new();
 
@@ -58,7 +62,7 @@ pub fn stress_sequential<
                 hashmap.entry(key).or_insert(value);
             }
             Ops::DeleteSome => {
-                let key = hashmap.keys().choose(&mut rng).map(|k| k.clone());
+                let key = hashmap.keys().choose(&mut rng).cloned();
                 if let Some(key) = key {
                     println!("iteration {}: delete({:?}) (existing)", i, key);
                     assert_eq!(map.delete(&key), hashmap.remove(&key).ok_or(()));
@@ -98,8 +102,8 @@ impl<K: ?Sized, V, M: ConcurrentMap<K, V>> SequentialMap<K, V> for Sequentialize
         }
     }
 
-    fn delete(&mut self, key: &K) -> Result<V, ()> {
-        self.inner.delete(key, &pin())
+    fn remove(&mut self, key: &K) -> Result<V, MapError> {
+        self.inner.remove(key, &pin())
     }
 
     fn lookup<'a>(&'a self, key: &'a K) -> Option<&'a V> {
@@ -117,9 +121,31 @@ pub fn stress_concurrent_sequential<
     stress_sequential::<K, Sequentialize<K, usize, M>>(steps);
 }
 
+/// Runs `f` on `threads` threads, each with its own handle to `map` and a generator forked from
+/// `rng`, and returns the results.
+fn run_threads<M, R, F>(threads: usize, map: &Arc<M>, rng: &mut TestRng, f: F) -> Vec<R>
+where
+    M: Send + Sync + 'static,
+    R: Send + 'static,
+    F: Fn(&M, TestRng) -> R + Send + Sync + 'static,
+{
+    let f = Arc::new(f);
+    (0..threads)
+        .map(|_| {
+            let map = map.clone();
+            let f = f.clone();
+            let rng = rng.fork();
+            thread::spawn(move || f(&map, rng))
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|h| h.join().unwrap())
+        .collect()
+}
+
 pub fn lookup_concurrent<
-    K: fmt::Debug + Eq + Hash + RandGen + Send + Sync,
-    M: Default + Sync + ConcurrentMap<K, usize>,
+    K: fmt::Debug + Eq + Hash + RandGen + Send + Sync + 'static,
+    M: Default + Send + Sync + ConcurrentMap<K, usize> + 'static,
 >(
     threads: usize,
     steps: usize,
@@ -132,8 +158,8 @@ pub fn lookup_concurrent<
 
     let ops = [Ops::LookupSome, Ops::LookupNone];
 
-    let mut rng = thread_rng();
-    let map = M::default();
+    let mut rng = rng();
+    let map = Arc::new(M::default());
     let mut hashmap = HashMap::<K, usize>::new();
 
     for _ in 0..steps {
@@ -143,61 +169,50 @@ pub fn lookup_concurrent<
         hashmap.entry(key).or_insert(value);
     }
 
-    thread::scope(|s| {
-        for _ in 0..threads {
-            s.spawn(|_| {
-                let mut rng = thread_rng();
-                for _ in 0..steps {
-                    let op = ops.choose(&mut rng).unwrap();
-
-                    match op {
-                        Ops::LookupSome => {
-                            if let Some(key) = hashmap.keys().choose(&mut rng) {
-                                assert_eq!(
-                                    map.lookup(key, &pin(), |r| r.map(|v| *v)),
-                                    hashmap.get(key).map(|v| *v)
-                                );
-                            }
-                        }
-                        Ops::LookupNone => {
-                            let key = K::rand_gen(&mut rng);
-                            assert_eq!(
-                                map.lookup(&key, &pin(), |r| r.map(|v| *v)),
-                                hashmap.get(&key).map(|v| *v)
-                            );
-                        }
+    let _ = run_threads(threads, &map, &mut rng, move |map, mut rng| {
+        for _ in 0..steps {
+            let op = ops.choose(&mut rng).unwrap();
+
+            match op {
+                Ops::LookupSome => {
+                    if let Some(key) = hashmap.keys().choose(&mut rng) {
+                        assert_eq!(
+                            map.lookup(key, &pin(), |r| r.copied()),
+                            hashmap.get(key).copied()
+                        );
                     }
                 }
-            });
+                Ops::LookupNone => {
+                    let key = K::rand_gen(&mut rng);
+                    assert_eq!(
+                        map.lookup(&key, &pin(), |r| r.copied()),
+                        hashmap.get(&key).copied()
+                    );
+                }
+            }
         }
-    })
-    .unwrap();
+    });
 }
 
 pub fn insert_concurrent<
-    K: fmt::Debug + Eq + Hash + RandGen,
-    M: Default + Sync + ConcurrentMap<K, usize>,
+    K: fmt::Debug + Eq + Hash + RandGen + 'static,
+    M: Default + Send + Sync + ConcurrentMap<K, usize> + 'static,
 >(
     threads: usize,
     steps: usize,
 ) {
-    let map = M::default();
-
-    thread::scope(|s| {
-        for _ in 0..threads {
-            s.spawn(|_| {
-                let mut rng = thread_rng();
-                for _ in 0..steps {
-                    let key = K::rand_gen(&mut rng);
-                    let value = rng.gen::<usize>();
-                    if map.insert(&key, value, &pin()).is_ok() {
-                        assert_eq!(map.lookup(&key, &pin(), |r| *r.unwrap()), value);
-                    }
-                }
-            });
+    let mut rng = rng();
+    let map = Arc::new(M::default());
+
+    let _ = run_threads(threads, &map, &mut rng, move |map, mut rng| {
+        for _ in 0..steps {
+            let key = K::rand_gen(&mut rng);
+            let value = rng.gen::<usize>();
+            if map.insert(&key, value, &pin()).is_ok() {
+                assert_eq!(map.lookup(&key, &pin(), |r| *r.unwrap()), value);
+            }
         }
-    })
-    .unwrap();
+    });
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -225,43 +240,38 @@ impl<K, V> Log<K, V> {
 }
 
 pub fn stress_concurrent<
-    K: fmt::Debug + Eq + Hash + RandGen,
-    M: Default + Sync + ConcurrentMap<K, usize>,
+    K: fmt::Debug + Eq + Hash + RandGen + 'static,
+    M: Default + Send + Sync + ConcurrentMap<K, usize> + 'static,
 >(
     threads: usize,
     steps: usize,
 ) {
     let ops = [Ops::Lookup, Ops::Insert, Ops::Delete];
 
-    let map = M::default();
-
-    thread::scope(|s| {
-        for _ in 0..threads {
-            s.spawn(|_| {
-                let mut rng = thread_rng();
-                for _ in 0..steps {
-                    let op = ops.choose(&mut rng).unwrap();
-
-                    match op {
-                        Ops::Lookup => {
-                            let key = K::rand_gen(&mut rng);
-                            let _ = map.lookup(&key, &pin(), |_v| {});
-                        }
-                        Ops::Insert => {
-                            let key = K::rand_gen(&mut rng);
-                            let value = rng.gen::<usize>();
-                            let _ = map.insert(&key, value, &pin());
-                        }
-                        Ops::Delete => {
-                            let key = K::rand_gen(&mut rng);
-                            let _ = map.delete(&key, &pin());
-                        }
-                    }
+    let mut rng = rng();
+    let map = Arc::new(M::default());
+
+    let _ = run_threads(threads, &map, &mut rng, move |map, mut rng| {
+        for _ in 0..steps {
+            let op = ops.choose(&mut rng).unwrap();
+
+            match op {
+                Ops::Lookup => {
+                    let key = K::rand_gen(&mut rng);
+                    let _ = map.lookup(&key, &pin(), |_v| {});
                 }
-            });
+                Ops::Insert => {
+                    let key = K::rand_gen(&mut rng);
+                    let value = rng.gen::<usize>();
+                    let _ = map.insert(&key, value, &pin());
+                }
+                Ops::Delete => {
+                    let key = K::rand_gen(&mut rng);
+                    let _ = map.delete(&key, &pin());
+                }
+            }
         }
-    })
-    .unwrap();
+    });
 }
 
 fn assert_logs_consistent<K: Clone + Eq + Hash, V: Clone + Eq + Hash>(logs: &Vec<Vec<Log<K, V>>>) {
@@ -310,68 +320,57 @@ fn assert_logs_consistent<K: Clone + Eq + Hash, V: Clone + Eq + Hash>(logs: &Vec
 }
 
 pub fn log_concurrent<
-    K: fmt::Debug + Clone + Eq + Hash + Send + RandGen,
-    M: Default + Sync + ConcurrentMap<K, usize>,
+    K: fmt::Debug + Clone + Eq + Hash + Send + RandGen + 'static,
+    M: Default + Send + Sync + ConcurrentMap<K, usize> + 'static,
 >(
     threads: usize,
     steps: usize,
 ) {
     let ops = [Ops::Lookup, Ops::Insert, Ops::Delete];
 
-    let map = M::default();
-
-    let logs = thread::scope(|s| {
-        let mut handles = Vec::new();
-        for _ in 0..threads {
-            let handle = s.spawn(|_| {
-                let mut rng = thread_rng();
-                let mut logs = Vec::new();
-                for _ in 0..steps {
-                    let op = ops.choose(&mut rng).unwrap();
-
-                    match op {
-                        Ops::Lookup => {
-                            let key = K::rand_gen(&mut rng);
-                            map.lookup(&key, &pin(), |value| {
-                                logs.push(Log::Lookup {
-                                    key: key.clone(),
-                                    value: value.map(|v| *v),
-                                });
-                            });
-                        }
-                        Ops::Insert => {
-                            let key = K::rand_gen(&mut rng);
-                            let value = rng.gen::<usize>();
-                            let result = map.insert(&key, value, &pin());
-                            let value = match result {
-                                Ok(()) => Ok(value),
-                                Err(_) => Err(()),
-                            };
-                            logs.push(Log::Insert {
-                                key: key.clone(),
-                                value,
-                            });
-                        }
-                        Ops::Delete => {
-                            let key = K::rand_gen(&mut rng);
-                            let result = map.delete(&key, &pin());
-                            logs.push(Log::Delete {
-                                key: key.clone(),
-                                value: result,
-                            });
-                        }
-                    }
+    let mut rng = rng();
+    let map = Arc::new(M::default());
+
+    let logs = run_threads(threads, &map, &mut rng, move |map, mut rng| {
+        let mut logs = Vec::new();
+        for _ in 0..steps {
+            let op = ops.choose(&mut rng).unwrap();
+
+            match op {
+                Ops::Lookup => {
+                    let key = K::rand_gen(&mut rng);
+                    map.lookup(&key, &pin(), |value| {
+                        logs.push(Log::Lookup {
+                            key: key.clone(),
+                            value: value.copied(),
+                        });
+                    });
+                }
+                Ops::Insert => {
+                    let key = K::rand_gen(&mut rng);
+                    let value = rng.gen::<usize>();
+                    let result = map.insert(&key, value, &pin());
+                    let value = match result {
+                        Ok(()) => Ok(value),
+                        Err(_) => Err(()),
+                    };
+                    logs.push(Log::Insert {
+                        key: key.clone(),
+                        value,
+                    });
+                }
+                Ops::Delete => {
+                    let key = K::rand_gen(&mut rng);
+                    let result = map.delete(&key, &pin());
+                    logs.push(Log::Delete {
+                        key: key.clone(),
+                        value: result,
+                    });
                 }
-                logs
-            });
-            handles.push(handle);
+            }
         }
-        handles
-            .into_iter()
-            .map(|h| h.join().unwrap())
-            .collect::<Vec<_>>()
-    })
-    .unwrap();
+        logs
+    });
 
     assert_logs_consistent(&logs);
 }
@@ -0,0 +1,189 @@
+//! Tests for the counters. Run with `cargo test --features metrics,failpoints`.
+
+#![cfg(feature = "metrics")]
+
+use cs431_homework::hello_server::ThreadPool;
+use cs431_homework::metrics::{self, MetricsSnapshot};
+use lazy_static::lazy_static;
+use std::sync::mpsc::channel;
+use std::sync::{Mutex, MutexGuard};
+
+lazy_static! {
+    static ref METRICS: Mutex<()> = Mutex::new(());
+}
+
+/// Counters are global, so tests that read them are serialized.
+fn lock_metrics() -> MutexGuard<'static, ()> {
+    METRICS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[test]
+fn reset() {
+    let _lock = lock_metrics();
+    metrics::reset();
+    assert_eq!(metrics::snapshot(), MetricsSnapshot::default());
+}
+
+#[test]
+fn pool_queue_depth_max() {
+    const JOBS: usize = 16;
+
+    let _lock = lock_metrics();
+    metrics::reset();
+
+    let pool = ThreadPool::new(1);
+    let (sender, receiver) = channel::<()>();
+    // block the only worker
+    pool.submit(move || receiver.recv().unwrap()).unwrap();
+    for _ in 0..JOBS {
+        pool.submit(|| ()).unwrap();
+    }
+    assert!(metrics::snapshot().pool_queue_depth_max >= JOBS - 1);
+
+    sender.send(()).unwrap();
+}
+
+#[cfg(feature = "failpoints")]
+mod failpoints {
+    use super::*;
+    #[cfg(not(feature = "check-loom"))]
+    use core::ptr;
+    use crossbeam_epoch::pin;
+    use crossbeam_utils::thread::scope;
+    use cs431_homework::failpoint::{Action, Scenario};
+    #[cfg(not(feature = "check-loom"))]
+    use cs431_homework::hazard_pointer::{HazardBag, Shield};
+    use cs431_homework::hello_server::Cache;
+    use cs431_homework::{NonblockingMap, SplitOrderedList};
+    #[cfg(not(feature = "check-loom"))]
+    use std::sync::atomic::{AtomicPtr, Ordering};
+    use std::sync::{Arc, Barrier};
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    // Under `check-loom`, the hazards are loom atomics, which can't be used outside of a loom
+    // model.
+    #[cfg(not(feature = "check-loom"))]
+    #[test]
+    fn hp_protect_retries() {
+        let _lock = lock_metrics();
+        let scenario = Scenario::setup();
+        let barrier = Arc::new(Barrier::new(2));
+        scenario.cfg(
+            "hazard_pointer::try_protect::validate",
+            Action::Pause(barrier.clone()),
+        );
+        metrics::reset();
+
+        let old = Box::into_raw(Box::new(1usize));
+        let new = Box::into_raw(Box::new(2usize));
+        let src = AtomicPtr::new(old);
+        let new = new as usize;
+        let hazards = HazardBag::new();
+
+        scope(|s| {
+            let th = s.spawn(|_| {
+                let shield = Shield::new(&hazards);
+                shield.protect(&src) as usize
+            });
+
+            barrier.wait();
+            src.store(new as *mut usize, Ordering::SeqCst);
+            barrier.wait();
+
+            assert_eq!(th.join().unwrap(), new);
+        })
+        .unwrap();
+        assert_eq!(metrics::snapshot().hp_protect_retries, 1);
+
+        src.store(ptr::null_mut(), Ordering::Relaxed);
+        unsafe {
+            drop(Box::from_raw(old));
+            drop(Box::from_raw(new as *mut usize));
+        }
+    }
+
+    #[test]
+    fn sol_insert_retries() {
+        let _lock = lock_metrics();
+        let scenario = Scenario::setup();
+
+        let list = SplitOrderedList::new();
+        // initialize the bucket of 1 and 3
+        assert_eq!(list.lookup(&1, &pin()), None);
+
+        let barrier = Arc::new(Barrier::new(2));
+        scenario.cfg("split_ordered_list::insert", Action::Pause(barrier.clone()));
+        metrics::reset();
+
+        scope(|s| {
+            let th = s.spawn(|_| {
+                let _ = list.insert(&1, 1, &pin());
+            });
+
+            barrier.wait();
+            // linked right where 1 would be linked
+            assert_eq!(list.insert(&3, 3, &pin()), Ok(()));
+            barrier.wait();
+
+            th.join().unwrap();
+        })
+        .unwrap();
+        assert!(metrics::snapshot().sol_insert_retries >= 1);
+    }
+
+    #[test]
+    fn sol_bucket_init_races() {
+        let _lock = lock_metrics();
+        let scenario = Scenario::setup();
+        let barrier = Arc::new(Barrier::new(2));
+        scenario.cfg(
+            "split_ordered_list::lookup_bucket::publish",
+            Action::Pause(barrier.clone()),
+        );
+        metrics::reset();
+
+        let list = SplitOrderedList::new();
+        scope(|s| {
+            let th = s.spawn(|_| {
+                let _ = list.lookup(&1, &pin());
+            });
+
+            barrier.wait();
+            assert_eq!(list.insert(&1, 1, &pin()), Ok(()));
+            barrier.wait();
+
+            th.join().unwrap();
+        })
+        .unwrap();
+        assert!(metrics::snapshot().sol_bucket_init_races >= 1);
+    }
+
+    #[test]
+    fn cache_waits() {
+        let _lock = lock_metrics();
+        let scenario = Scenario::setup();
+        let barrier = Arc::new(Barrier::new(2));
+        scenario.cfg(
+            "cache::get_or_insert_with::compute",
+            Action::Pause(barrier.clone()),
+        );
+        metrics::reset();
+
+        let cache = Cache::default();
+        scope(|s| {
+            let th1 = s.spawn(|_| cache.get_or_insert_with(1, |k| k));
+
+            barrier.wait();
+            let th2 = s.spawn(|_| cache.get_or_insert_with(1, |_| panic!()));
+            // give `th2` time to block on the computation
+            sleep(Duration::from_millis(100));
+            barrier.wait();
+
+            assert_eq!(th1.join().unwrap(), 1);
+            assert_eq!(th2.join().unwrap(), 1);
+        })
+        .unwrap();
+        assert_eq!(metrics::snapshot().cache_waits, 1);
+    }
+}
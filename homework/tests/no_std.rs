@@ -0,0 +1,46 @@
+//! Checks that the crate builds without `std`, i.e. with only the `alloc` feature.
+//!
+//! The check is cross-compiled to `thumbv7em-none-eabihf`, which has no `std` at all, if the target
+//! is installed (`rustup target add thumbv7em-none-eabihf`). Otherwise, it's compiled to the host,
+//! which still catches the uses of `std` in a `#![no_std]` crate.
+
+#![cfg(not(feature = "check-loom"))]
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+const TARGET: &str = "thumbv7em-none-eabihf";
+
+/// Returns `true` if the standard libraries of `target` are installed.
+fn has_target(target: &str) -> bool {
+    let output = Command::new("rustc")
+        .args(&["--print", "sysroot"])
+        .output()
+        .expect("failed to run rustc");
+    let sysroot = String::from_utf8(output.stdout).unwrap();
+    Path::new(sysroot.trim())
+        .join("lib/rustlib")
+        .join(target)
+        .exists()
+}
+
+#[test]
+fn no_std_alloc() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let mut cargo = Command::new(env!("CARGO"));
+    let _ = cargo
+        .current_dir(manifest_dir)
+        .args(&["check", "--lib", "--offline"])
+        .args(&["--no-default-features", "--features", "alloc"])
+        // A separate target directory, so that this doesn't wait for the lock of the running test.
+        .arg("--target-dir")
+        .arg(Path::new(manifest_dir).join("target/no_std"));
+    if has_target(TARGET) {
+        let _ = cargo.args(&["--target", TARGET]);
+    } else {
+        eprintln!("{} is not installed, checking the host target instead", TARGET);
+    }
+    let status = cargo.status().expect("failed to run cargo");
+    assert!(status.success(), "the crate doesn't build without `std`");
+}
@@ -0,0 +1,143 @@
+//! Property-based tests comparing the structures against sequential oracles.
+//!
+//! The number of cases can be increased with the `PROPTEST_CASES` environment variable.
+
+use crossbeam_epoch::pin;
+use crossbeam_utils::thread::scope;
+use cs431_homework::hello_server::Cache;
+use cs431_homework::test_util::ops::{run_list_set, run_split_ordered_list, Op, KEYS, OP_BYTES};
+use cs431_homework::{NonblockingMap, OrderedListSet, SplitOrderedList};
+use proptest::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::thread;
+
+const MAX_OPS: usize = 64;
+
+fn op() -> impl Strategy<Value = Op> {
+    any::<[u8; OP_BYTES]>().prop_map(|bytes| Op::decode(&mut &bytes[..]).unwrap())
+}
+
+fn ops() -> impl Strategy<Value = Vec<Op>> {
+    prop::collection::vec(op(), 0..MAX_OPS)
+}
+
+/// Operations paired with whether to yield before running it.
+fn ops_with_yields() -> impl Strategy<Value = Vec<(Op, bool)>> {
+    prop::collection::vec((op(), any::<bool>()), 0..MAX_OPS)
+}
+
+fn run_cache(cache: &Cache<usize, usize>, oracle: &mut BTreeMap<usize, usize>, op: &Op) {
+    match *op {
+        Op::Insert(k, v) => {
+            let expected = *oracle.entry(k).or_insert(v);
+            assert_eq!(cache.get_or_insert_with(k, |_| v), expected);
+        }
+        Op::Delete(k) => {
+            assert_eq!(cache.invalidate(&k), oracle.remove(&k).is_some());
+        }
+        Op::Lookup(k) => {
+            if let Some(v) = oracle.get(&k) {
+                assert_eq!(cache.get_or_insert_with(k, |_| panic!()), *v);
+            }
+        }
+        Op::Iterate => {
+            for (k, v) in oracle.iter() {
+                assert_eq!(cache.get_or_insert_with(*k, |_| panic!()), *v);
+            }
+        }
+    }
+}
+
+/// Shifts the keys of `ops` by `KEYS` so that they don't overlap with the unshifted ones.
+fn shift_keys(ops: &mut [(Op, bool)]) {
+    for (op, _) in ops {
+        match op {
+            Op::Insert(k, _) | Op::Delete(k) | Op::Lookup(k) => *k += KEYS,
+            Op::Iterate => (),
+        }
+    }
+}
+
+/// Runs `ops1` and `ops2` on `s` from two threads, yielding at the given points. The keys of
+/// `ops2` are shifted so that the threads operate on disjoint keys. Then the result of each
+/// operation is still deterministic except for `Iterate`, which is skipped.
+fn run_two_threads<S: Sync, O: Default + Send>(
+    s: &S,
+    ops1: Vec<(Op, bool)>,
+    mut ops2: Vec<(Op, bool)>,
+    run: fn(&S, &mut O, &Op),
+) -> (O, O) {
+    fn run_thread<S, O: Default>(s: &S, ops: &[(Op, bool)], run: fn(&S, &mut O, &Op)) -> O {
+        let mut oracle = O::default();
+        for (op, yield_now) in ops {
+            if *yield_now {
+                thread::yield_now();
+            }
+            if let Op::Iterate = op {
+                continue;
+            }
+            run(s, &mut oracle, op);
+        }
+        oracle
+    }
+
+    shift_keys(&mut ops2);
+    scope(|scope| {
+        let th1 = scope.spawn(|_| run_thread(s, &ops1, run));
+        let th2 = scope.spawn(|_| run_thread(s, &ops2, run));
+        (th1.join().unwrap(), th2.join().unwrap())
+    })
+    .unwrap()
+}
+
+proptest! {
+    #[test]
+    fn split_ordered_list_sequential(ops in ops()) {
+        let list = SplitOrderedList::new();
+        let mut oracle = HashMap::new();
+        for op in &ops {
+            run_split_ordered_list(&list, &mut oracle, op);
+        }
+    }
+
+    #[test]
+    fn list_set_sequential(ops in ops()) {
+        let set = OrderedListSet::new();
+        let mut oracle = BTreeSet::new();
+        for op in &ops {
+            run_list_set(&set, &mut oracle, op);
+        }
+    }
+
+    #[test]
+    fn cache_sequential(ops in ops()) {
+        let cache = Cache::default();
+        let mut oracle = BTreeMap::new();
+        for op in &ops {
+            run_cache(&cache, &mut oracle, op);
+        }
+    }
+
+    #[test]
+    fn split_ordered_list_two_threads(ops1 in ops_with_yields(), ops2 in ops_with_yields()) {
+        let list = SplitOrderedList::new();
+        let (oracle1, oracle2) = run_two_threads(&list, ops1, ops2, run_split_ordered_list);
+        let guard = pin();
+        for (k, v) in oracle1.iter().chain(oracle2.iter()) {
+            prop_assert_eq!(list.lookup(k, &guard), Some(v));
+        }
+        for k in 0..2 * KEYS {
+            if !oracle1.contains_key(&k) && !oracle2.contains_key(&k) {
+                prop_assert_eq!(list.lookup(&k, &guard), None);
+            }
+        }
+    }
+
+    #[test]
+    fn list_set_two_threads(ops1 in ops_with_yields(), ops2 in ops_with_yields()) {
+        let set = OrderedListSet::new();
+        let (oracle1, oracle2) = run_two_threads(&set, ops1, ops2, run_list_set);
+        let union = oracle1.union(&oracle2).collect::<Vec<_>>();
+        prop_assert_eq!(set.iter().collect::<Vec<_>>(), union);
+    }
+}
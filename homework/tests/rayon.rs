@@ -0,0 +1,86 @@
+use crossbeam_epoch::pin;
+use cs431_homework::test_util::rng::rng;
+use cs431_homework::{NonblockingMap, OrderedListSet, SplitOrderedList};
+use rand::seq::SliceRandom;
+use rayon::prelude::*;
+
+const ELEMENTS: usize = 1_000_000;
+
+/// Keys spread over the key space, shuffled.
+fn keys() -> Vec<usize> {
+    let mut keys = (0..ELEMENTS)
+        .map(|i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+        .collect::<Vec<_>>();
+    keys.shuffle(&mut rng());
+    keys
+}
+
+#[test]
+fn split_ordered_list_from_par_iter() {
+    let keys = keys();
+    let parallel = keys
+        .par_iter()
+        .map(|&k| (k, k / 2))
+        .collect::<SplitOrderedList<_>>();
+
+    let sequential = SplitOrderedList::new();
+    let guard = pin();
+    for &k in &keys {
+        assert_eq!(sequential.insert(&k, k / 2, &guard), Ok(()));
+    }
+
+    let snapshot = parallel.par_snapshot().collect::<Vec<_>>();
+    assert_eq!(snapshot.len(), ELEMENTS);
+    assert_eq!(snapshot, sequential.par_snapshot().collect::<Vec<_>>());
+    keys.par_iter()
+        .for_each(|k| assert_eq!(parallel.lookup(k, &pin()), Some(&(k / 2))));
+}
+
+#[test]
+fn split_ordered_list_par_extend() {
+    let guard = pin();
+    let mut list = SplitOrderedList::new();
+    assert_eq!(list.insert(&1, 1, &guard), Ok(()));
+
+    // Existing keys keep their values, and one of the values of a repeated key is kept.
+    list.par_extend((0..1000).into_par_iter().map(|i| (i % 100, i)));
+    let mut snapshot = list.par_snapshot().collect::<Vec<_>>();
+    snapshot.sort_unstable();
+    assert_eq!(snapshot.len(), 100);
+    assert_eq!(snapshot[1], (1, 1));
+    for (k, v) in snapshot {
+        assert_eq!(v % 100, k);
+    }
+}
+
+#[test]
+fn ordered_list_set_from_par_iter() {
+    let mut values = keys();
+    // Repeat some values.
+    values.extend_from_slice(&values[..ELEMENTS / 10].to_vec());
+    let parallel = values.par_iter().cloned().collect::<OrderedListSet<_>>();
+
+    // Inserting in decreasing order takes linear time.
+    let sequential = OrderedListSet::new();
+    let mut sorted = values.clone();
+    sorted.sort_unstable();
+    sorted.dedup();
+    for &v in sorted.iter().rev() {
+        assert_eq!(sequential.insert(v), Ok(()));
+    }
+
+    assert!(parallel.iter().eq(sequential.iter()));
+    assert_eq!(parallel.par_snapshot().collect::<Vec<_>>(), sorted);
+}
+
+#[test]
+fn empty() {
+    let list = Vec::<(usize, usize)>::new()
+        .into_par_iter()
+        .collect::<SplitOrderedList<_>>();
+    assert_eq!(list.par_snapshot().count(), 0);
+    let set = Vec::<usize>::new()
+        .into_par_iter()
+        .collect::<OrderedListSet<_>>();
+    assert_eq!(set.par_snapshot().count(), 0);
+}
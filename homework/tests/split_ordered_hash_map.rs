@@ -0,0 +1,141 @@
+use crossbeam_epoch as epoch;
+use crossbeam_utils::thread::scope;
+use cs431_homework::{MapError, NonblockingConcurrentMap, NonblockingMap, SplitOrderedHashMap};
+use std::hash::{BuildHasherDefault, Hasher};
+
+pub mod map;
+
+/// Hashes everything to 0.
+#[derive(Debug, Default)]
+struct CollidingHasher;
+
+impl Hasher for CollidingHasher {
+    fn finish(&self) -> u64 {
+        0
+    }
+
+    fn write(&mut self, _bytes: &[u8]) {}
+}
+
+type Colliding = BuildHasherDefault<CollidingHasher>;
+
+#[test]
+fn smoke() {
+    let map = SplitOrderedHashMap::<String, usize>::new();
+    let guard = epoch::pin();
+    let (one, two) = ("one".to_string(), "two".to_string());
+
+    assert_eq!(map.insert(&one, 1, &guard), Ok(()));
+    assert_eq!(map.insert(&one, 11, &guard), Err(11));
+    assert_eq!(map.lookup(&two, &guard), None);
+    assert_eq!(map.lookup(&one, &guard), Some(&1));
+    assert!(map.contains_key(&one, &guard));
+    assert!(!map.contains_key(&two, &guard));
+    assert_eq!(map.get_or_insert_with(&two, || 2, &guard), &2);
+    assert_eq!(map.remove(&one, &guard), Ok(&1));
+    assert_eq!(map.remove(&one, &guard), Err(MapError::NotFound));
+    assert_eq!(map.lookup(&two, &guard), Some(&2));
+}
+
+// All keys have the same hash, so they're told apart only by comparing them.
+#[test]
+fn colliding_keys() {
+    const KEYS: usize = 64;
+
+    let map = SplitOrderedHashMap::<usize, usize, Colliding>::default();
+    let guard = epoch::pin();
+    for key in 0..KEYS {
+        assert_eq!(map.insert(&key, key + 1, &guard), Ok(()));
+    }
+    for key in 0..KEYS {
+        assert_eq!(map.lookup(&key, &guard), Some(&(key + 1)));
+        assert_eq!(map.insert(&key, 0, &guard), Err(0));
+    }
+
+    // Delete from the head, the middle, and the tail of the chain.
+    for key in (0..KEYS).step_by(2).chain(Some(KEYS - 1)) {
+        assert_eq!(map.remove(&key, &guard), Ok(&(key + 1)));
+    }
+    for key in 0..KEYS {
+        let present = key % 2 == 1 && key != KEYS - 1;
+        assert_eq!(map.lookup(&key, &guard).is_some(), present, "{}", key);
+    }
+
+    // Deleting every key removes the chain, and inserting again creates a new one.
+    for key in (1..KEYS - 1).step_by(2) {
+        assert_eq!(map.remove(&key, &guard), Ok(&(key + 1)));
+    }
+    assert_eq!(map.lookup(&0, &guard), None);
+    assert_eq!(map.get_or_insert_with(&0, || 42, &guard), &42);
+    assert_eq!(map.get_or_insert_with(&1, || 43, &guard), &43);
+    assert_eq!(map.get_or_insert_with(&0, || 44, &guard), &42);
+}
+
+// Threads insert and delete colliding keys, so that chains are removed and created again while
+// other threads use them. Each thread owns its keys, so it knows what the map should contain.
+#[test]
+fn colliding_keys_concurrent() {
+    const THREADS: usize = 8;
+    const KEYS: usize = 16;
+    const ROUNDS: usize = 256;
+
+    let map = SplitOrderedHashMap::<usize, usize, Colliding>::default();
+    scope(|s| {
+        for t in 0..THREADS {
+            let map = &map;
+            let _ = s.spawn(move |_| {
+                let keys = (0..KEYS).map(|i| t * KEYS + i);
+                for round in 0..ROUNDS {
+                    let guard = epoch::pin();
+                    for key in keys.clone() {
+                        assert_eq!(map.insert(&key, round, &guard), Ok(()));
+                    }
+                    for key in keys.clone() {
+                        assert_eq!(map.lookup(&key, &guard), Some(&round));
+                        assert_eq!(map.remove(&key, &guard), Ok(&round));
+                        assert_eq!(map.lookup(&key, &guard), None);
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+}
+
+#[test]
+fn stress_sequential() {
+    const STEPS: usize = 4096;
+    map::stress_concurrent_sequential::<
+        String,
+        NonblockingConcurrentMap<_, _, SplitOrderedHashMap<String, usize>>,
+    >(STEPS);
+}
+
+#[test]
+fn stress_sequential_colliding() {
+    const STEPS: usize = 4096;
+    map::stress_concurrent_sequential::<
+        String,
+        NonblockingConcurrentMap<_, _, SplitOrderedHashMap<String, usize, Colliding>>,
+    >(STEPS);
+}
+
+#[test]
+fn stress_concurrent() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096 * 64;
+    map::stress_concurrent::<
+        String,
+        NonblockingConcurrentMap<_, _, SplitOrderedHashMap<String, usize>>,
+    >(THREADS, STEPS);
+}
+
+#[test]
+fn log_concurrent_colliding() {
+    const THREADS: usize = 16;
+    const STEPS: usize = 4096;
+    map::log_concurrent::<
+        String,
+        NonblockingConcurrentMap<_, _, SplitOrderedHashMap<String, usize, Colliding>>,
+    >(THREADS, STEPS);
+}
@@ -1,5 +1,18 @@
+#![allow(deprecated)]
+
+use crossbeam_channel::unbounded;
 use crossbeam_epoch as epoch;
-use cs431_homework::{NonblockingConcurrentMap, NonblockingMap, SplitOrderedList};
+use crossbeam_utils::thread::scope;
+use cs431_homework::test_util::drop_counter::DropCounter;
+use cs431_homework::test_util::rng::rng;
+use cs431_homework::{
+    DeleteError, MapEntry, MapError, NonblockingConcurrentMap, NonblockingMap, SplitOrderedList,
+};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 pub mod map;
 
@@ -26,6 +39,1701 @@ pub fn smoke() {
     assert_eq!(list.lookup(&37, &guard), None);
 }
 
+fn smoke_generic(map: impl NonblockingMap<usize, usize>) {
+    let guard = epoch::pin();
+    assert_eq!(map.insert(&37, 37, &guard), Ok(()));
+    assert_eq!(map.insert(&37, 42, &guard), Err(42));
+    assert_eq!(map.lookup(&37, &guard), Some(&37));
+    assert_eq!(map.remove(&37, &guard), Ok(&37));
+    assert_eq!(map.lookup(&37, &guard), None);
+    assert_eq!(map.get_or_insert_with(&37, || 42, &guard), &42);
+    assert_eq!(map.get_or_insert_with(&37, || 37, &guard), &42);
+    assert_eq!(map.remove(&37, &guard), Ok(&42));
+}
+
+// `NonblockingMap` is implemented for references and smart pointers to maps.
+#[test]
+fn smoke_wrappers() {
+    let list = SplitOrderedList::new();
+    smoke_generic(&list);
+    smoke_generic(&&list);
+    smoke_generic(Arc::new(SplitOrderedList::new()));
+    smoke_generic(Box::new(SplitOrderedList::new()));
+    smoke_generic(Arc::new(Box::new(SplitOrderedList::new())));
+}
+
+#[test]
+fn max_key() {
+    const MAX_KEY: usize = SplitOrderedList::<usize>::MAX_KEY;
+    assert_eq!(MAX_KEY, usize::MAX);
+    const TOP: usize = 1 << (usize::BITS - 1);
+
+    // Keys with the top bit set, and keys whose encodings are next to each other or to bucket
+    // sentinels. With 1024 buckets, `k` and `k + 1024` are in the same bucket, and `k + 512` in the
+    // bucket split from it.
+    let mut keys = vec![0, 1, TOP - 1, TOP, TOP + 1, MAX_KEY - 1, MAX_KEY];
+    for &base in &[0, 1023, TOP - 1024, TOP, MAX_KEY - 2048] {
+        keys.extend([base, base + 1, base + 512, base + 1024].iter());
+    }
+    keys.sort_unstable();
+    keys.dedup();
+
+    for list in [
+        SplitOrderedList::new(),
+        SplitOrderedList::with_capacity(2048),
+    ] {
+        let guard = epoch::pin();
+        for &key in &keys {
+            assert_eq!(list.insert(&key, key, &guard), Ok(()));
+        }
+        for &key in &keys {
+            assert_eq!(list.insert(&key, key, &guard), Err(key));
+            assert_eq!(list.lookup(&key, &guard), Some(&key));
+        }
+        let mut entries = list.iter(&guard).map(|(k, &v)| (k, v)).collect::<Vec<_>>();
+        entries.sort_unstable();
+        assert_eq!(entries, keys.iter().map(|&k| (k, k)).collect::<Vec<_>>());
+        for &key in &keys {
+            assert_eq!(list.remove(&key, &guard), Ok(&key));
+            assert_eq!(list.lookup(&key, &guard), None);
+        }
+        assert!(list.is_empty());
+    }
+}
+
+// The first lookup in the last of 2^20 buckets initializes its 20 ancestors. It should fit in a
+// small stack.
+#[test]
+fn cold_buckets() {
+    const BUCKETS: usize = 1 << 20;
+
+    let list = SplitOrderedList::with_capacity(2 * BUCKETS);
+    scope(|s| {
+        let list = &list;
+        let _ = s
+            .builder()
+            .stack_size(64 * 1024)
+            .spawn(move |_| {
+                let guard = epoch::pin();
+                let last = BUCKETS - 1;
+                assert_eq!(list.lookup(&last, &guard), None);
+                assert_eq!(list.insert(&last, last, &guard), Ok(()));
+                assert_eq!(list.lookup(&last, &guard), Some(&last));
+
+                // The ancestors of the last bucket are initialized, and their entries come before its
+                // entries in split order.
+                let ancestors = (0..20).map(|bits| last >> bits << bits).collect::<Vec<_>>();
+                for &key in &ancestors[1..] {
+                    assert_eq!(list.insert(&key, key, &guard), Ok(()));
+                }
+                let keys = list.iter(&guard).map(|(k, _)| k).collect::<Vec<_>>();
+                let mut expected = ancestors;
+                expected.sort_unstable_by_key(|k| k.reverse_bits());
+                assert_eq!(keys, expected);
+            })
+            .unwrap();
+    })
+    .unwrap();
+}
+
+#[test]
+fn len() {
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+    assert!(list.is_empty());
+    for key in 0..100 {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    assert_eq!(list.insert(&0, 0, &guard), Err(0));
+    assert_eq!(list.len(), 100);
+    for key in 0..50 {
+        assert_eq!(list.remove(&key, &guard), Ok(&key));
+    }
+    assert_eq!(list.remove(&0, &guard), Err(MapError::NotFound));
+    assert_eq!(list.len(), 50);
+    assert!(!list.is_empty());
+}
+
+// Threads insert and delete the same few keys. Only the operations that succeed should be counted.
+#[test]
+fn len_concurrent() {
+    const THREADS: usize = 8;
+    const STEPS: usize = 4096 * 4;
+    const KEYS: usize = 16;
+
+    let list = SplitOrderedList::new();
+    let mut rng = rng();
+    let rngs = (0..THREADS).map(|_| rng.fork()).collect::<Vec<_>>();
+    // The successful inserts and deletes.
+    let (inserts, deletes) = (AtomicUsize::new(0), AtomicUsize::new(0));
+    scope(|s| {
+        for mut rng in rngs {
+            let (list, inserts, deletes) = (&list, &inserts, &deletes);
+            let _ = s.spawn(move |_| {
+                for _ in 0..STEPS {
+                    let key = rng.gen_range(0..KEYS);
+                    let guard = epoch::pin();
+                    if rng.gen() {
+                        if list.insert(&key, key, &guard).is_ok() {
+                            let _ = inserts.fetch_add(1, Ordering::Relaxed);
+                        }
+                    } else if list.remove(&key, &guard).is_ok() {
+                        let _ = deletes.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+    assert_eq!(list.len(), inserts.into_inner() - deletes.into_inner());
+    assert_eq!(list.keys(&epoch::pin()).count(), list.len());
+
+    // Deleting the remaining entries should bring the count back to 0.
+    let guard = epoch::pin();
+    for key in 0..KEYS {
+        while list.remove(&key, &guard).is_ok() {}
+    }
+    assert_eq!(list.len(), 0);
+    assert!(list.is_empty());
+}
+
+// More threads than the count has stripes insert their own keys and delete some of them, so that
+// threads share cells of the count. At quiescence, the count should be the number of entries.
+#[test]
+fn len_many_threads() {
+    const THREADS: usize = 40;
+    const KEYS: usize = 1024;
+
+    let list = SplitOrderedList::new();
+    scope(|s| {
+        for t in 0..THREADS {
+            let list = &list;
+            let _ = s.spawn(move |_| {
+                let guard = epoch::pin();
+                let keys = t * KEYS..(t + 1) * KEYS;
+                for key in keys.clone() {
+                    assert_eq!(list.insert(&key, key, &guard), Ok(()));
+                }
+                // Threads delete different numbers of keys.
+                for key in keys.step_by(t % 4 + 2) {
+                    assert_eq!(list.remove(&key, &guard), Ok(&key));
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = epoch::pin();
+    let entries = list.iter(&guard).count();
+    let expected = (0..THREADS)
+        .map(|t| KEYS - (KEYS + t % 4 + 1) / (t % 4 + 2))
+        .sum::<usize>();
+    assert_eq!(entries, expected);
+    assert_eq!(list.len(), expected);
+}
+
+// A map created with the capacity for its entries never resizes while they are inserted.
+#[test]
+fn with_capacity() {
+    for &load_factor in &[1, SplitOrderedList::<usize>::DEFAULT_LOAD_FACTOR, 8] {
+        for &capacity in &[0, 1, 5, 1000, 1 << 16] {
+            let list = SplitOrderedList::with_capacity_and_load_factor(capacity, load_factor);
+            assert_eq!(list.load_factor(), load_factor);
+            let size = list.bucket_count();
+            let guard = epoch::pin();
+            for key in 0..capacity {
+                assert_eq!(list.insert(&key, key, &guard), Ok(()));
+            }
+            assert_eq!(list.bucket_count(), size, "capacity {}", capacity);
+            assert!(capacity <= size * load_factor);
+        }
+    }
+}
+
+// With 4 times the load factor, the map has a quarter of the buckets, each 4 times as long.
+#[test]
+fn bucket_lengths() {
+    const KEYS: usize = 1 << 12;
+
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+    assert_eq!(list.load(), 0.0);
+    assert_eq!(list.bucket_lengths(&guard), vec![0; list.bucket_count()]);
+
+    // Random keys, and a cluster of keys in bucket 0.
+    let mut rng = rng();
+    let mut keys = (0..KEYS)
+        .map(|_| rng.gen::<usize>())
+        .collect::<HashSet<_>>();
+    keys.extend((0..64).map(|i| i << 20));
+    for &key in &keys {
+        let _ = list.insert(&key, key, &guard);
+    }
+    let size = list.bucket_count();
+    assert_eq!(list.load(), keys.len() as f64 / size as f64);
+
+    let mut expected = vec![0; size];
+    for &key in &keys {
+        expected[key % size] += 1;
+    }
+    let lengths = list.bucket_lengths(&guard);
+    assert_eq!(lengths, expected);
+    assert_eq!(lengths.iter().sum::<usize>(), list.len());
+    assert!(lengths[0] >= 64);
+}
+
+#[test]
+fn load_factor() {
+    const KEYS: usize = 1 << 14;
+
+    let default = SplitOrderedList::new();
+    let long = SplitOrderedList::with_load_factor(8);
+    assert_eq!(default.load_factor(), 2);
+    let guard = epoch::pin();
+    for key in 0..KEYS {
+        assert_eq!(default.insert(&key, key, &guard), Ok(()));
+        assert_eq!(long.insert(&key, key, &guard), Ok(()));
+    }
+    assert_eq!(default.bucket_count(), 4 * long.bucket_count());
+    assert!(KEYS <= long.bucket_count() * 8);
+
+    // The buckets shrink according to the load factor too.
+    for key in 0..KEYS - 16 {
+        assert_eq!(default.remove(&key, &guard), Ok(&key));
+        assert_eq!(long.remove(&key, &guard), Ok(&key));
+    }
+    assert_eq!(default.bucket_count(), 4 * long.bucket_count());
+}
+
+#[test]
+#[should_panic(expected = "load factor")]
+fn load_factor_zero() {
+    let _ = SplitOrderedList::<usize>::with_load_factor(0);
+}
+
+// A mass delete shrinks the bucket array. The keys left in the map should still be found through
+// the buckets of the smaller array, and the array should grow again with the entries.
+#[test]
+fn shrink() {
+    const KEYS: usize = 4096;
+    const KEEP: usize = 256;
+
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+    assert_eq!(list.bucket_count(), 2);
+    for key in 0..KEYS {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    let grown = list.bucket_count();
+    assert!(grown >= KEYS / 4, "{}", grown);
+
+    for key in (0..KEYS).filter(|key| key % KEEP != 0) {
+        assert_eq!(list.remove(&key, &guard), Ok(&key));
+    }
+    let shrunk = list.bucket_count();
+    assert!(shrunk <= 4 * KEYS / KEEP, "{}", shrunk);
+    for key in 0..KEYS {
+        let expected = if key % KEEP == 0 { Some(&key) } else { None };
+        assert_eq!(list.lookup(&key, &guard), expected);
+    }
+
+    for key in (0..KEYS).filter(|key| key % KEEP != 0) {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    assert_eq!(list.bucket_count(), grown);
+    for key in 0..KEYS {
+        assert_eq!(list.lookup(&key, &guard), Some(&key));
+    }
+
+    for key in 0..KEYS {
+        assert_eq!(list.remove(&key, &guard), Ok(&key));
+    }
+    assert!(list.bucket_count() <= 8, "{}", list.bucket_count());
+    assert!(list.is_empty());
+}
+
+#[test]
+fn clear() {
+    const KEYS: usize = 1024;
+
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+    list.clear(&guard);
+    assert!(list.is_empty());
+
+    for key in 0..KEYS {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    let buckets = list.bucket_count();
+    list.clear(&guard);
+    assert!(list.is_empty());
+    assert_eq!(list.bucket_count(), buckets);
+    assert_eq!(list.iter(&guard).next(), None);
+    for key in 0..KEYS {
+        assert_eq!(list.lookup(&key, &guard), None);
+    }
+
+    // The map is usable after the clear.
+    for key in 0..KEYS {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    assert_eq!(list.len(), KEYS);
+    assert_eq!(list.bucket_count(), buckets);
+    for key in 0..KEYS {
+        assert_eq!(list.lookup(&key, &guard), Some(&key));
+    }
+}
+
+// A clear races with writers that insert their own keys. Each key should end up either deleted or
+// still in the map, and the map should agree with its iterator and its count.
+#[test]
+fn clear_concurrent() {
+    const WRITERS: usize = 4;
+    const KEYS: usize = 4096;
+    const CLEARS: usize = 16;
+
+    let list = SplitOrderedList::new();
+    scope(|s| {
+        for t in 0..WRITERS {
+            let list = &list;
+            let _ = s.spawn(move |_| {
+                for key in (t * KEYS..(t + 1) * KEYS).rev() {
+                    let guard = epoch::pin();
+                    assert_eq!(list.insert(&key, key, &guard), Ok(()));
+                }
+            });
+        }
+        let _ = s.spawn(|_| {
+            for _ in 0..CLEARS {
+                list.clear(&epoch::pin());
+            }
+        });
+    })
+    .unwrap();
+
+    let guard = epoch::pin();
+    let mut survivors = HashSet::new();
+    for key in 0..WRITERS * KEYS {
+        match list.lookup(&key, &guard) {
+            Some(value) => {
+                assert_eq!(*value, key);
+                assert!(survivors.insert(key));
+            }
+            None => assert_eq!(list.insert(&key, key, &guard), Ok(())),
+        }
+    }
+    for &key in &survivors {
+        assert_eq!(list.insert(&key, key, &guard), Err(key));
+    }
+    assert_eq!(list.len(), WRITERS * KEYS);
+    assert_eq!(list.iter(&guard).count(), WRITERS * KEYS);
+}
+
+#[test]
+fn retain() {
+    const KEYS: usize = 1 << 16;
+
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+    list.retain(|_, _| false, &guard);
+    for key in 0..KEYS {
+        assert_eq!(list.insert(&key, !key, &guard), Ok(()));
+    }
+
+    let visited = AtomicUsize::new(0);
+    list.retain(
+        |key, &value| {
+            assert_eq!(value, !key);
+            let _ = visited.fetch_add(1, Ordering::Relaxed);
+            key % 3 == 0
+        },
+        &guard,
+    );
+    assert_eq!(visited.into_inner(), KEYS);
+    assert_eq!(list.len(), (KEYS + 2) / 3);
+    assert_eq!(list.iter(&guard).count(), list.len());
+    for key in 0..KEYS {
+        let expected = if key % 3 == 0 { Some(!key) } else { None };
+        assert_eq!(list.lookup(&key, &guard).copied(), expected);
+    }
+
+    list.retain(|_, _| true, &guard);
+    assert_eq!(list.len(), (KEYS + 2) / 3);
+    list.retain(|_, _| false, &guard);
+    assert!(list.is_empty());
+    assert_eq!(list.iter(&guard).next(), None);
+}
+
+// Writers insert and delete the odd keys while a retain deletes the even keys that are multiples of
+// 4. The other even keys should survive, and the count should match the entries.
+#[test]
+fn retain_concurrent() {
+    const WRITERS: usize = 4;
+    const KEYS: usize = 1 << 12;
+    const ROUNDS: usize = 16;
+
+    for _ in 0..ROUNDS {
+        let list = SplitOrderedList::new();
+        let guard = epoch::pin();
+        for key in (0..KEYS).step_by(2) {
+            assert_eq!(list.insert(&key, key, &guard), Ok(()));
+        }
+        drop(guard);
+
+        let mut rng = rng();
+        let rngs = (0..WRITERS).map(|_| rng.fork()).collect::<Vec<_>>();
+        let done = AtomicBool::new(false);
+        scope(|s| {
+            for mut rng in rngs {
+                let (list, done) = (&list, &done);
+                let _ = s.spawn(move |_| {
+                    while !done.load(Ordering::Relaxed) {
+                        let key = rng.gen_range(0..KEYS / 2) * 2 + 1;
+                        let guard = epoch::pin();
+                        if rng.gen() {
+                            let _ = list.insert(&key, key, &guard);
+                        } else {
+                            let _ = list.remove(&key, &guard);
+                        }
+                    }
+                });
+            }
+
+            list.retain(|key, _| key % 4 != 0, &epoch::pin());
+            done.store(true, Ordering::Relaxed);
+        })
+        .unwrap();
+
+        let guard = epoch::pin();
+        for key in (0..KEYS).step_by(2) {
+            let expected = if key % 4 == 0 { None } else { Some(&key) };
+            assert_eq!(list.lookup(&key, &guard), expected);
+        }
+        assert_eq!(list.iter(&guard).count(), list.len());
+    }
+}
+
+// Purges race with threads that insert and delete their own keys. The purges should neither lose
+// nor revive entries.
+#[test]
+fn purge_concurrent() {
+    const THREADS: usize = 4;
+    const KEYS: usize = 4096;
+
+    let list = SplitOrderedList::new();
+    let done = AtomicUsize::new(0);
+    scope(|s| {
+        for t in 0..THREADS {
+            let (list, done) = (&list, &done);
+            let _ = s.spawn(move |_| {
+                let keys = t * KEYS..(t + 1) * KEYS;
+                for key in keys.clone() {
+                    assert_eq!(list.insert(&key, key, &epoch::pin()), Ok(()));
+                }
+                // Keep every tenth key.
+                for key in keys.filter(|key| key % 10 != 0) {
+                    assert_eq!(list.remove(&key, &epoch::pin()), Ok(&key));
+                }
+                let _ = done.fetch_add(1, Ordering::Release);
+            });
+        }
+        let _ = s.spawn(|_| {
+            while done.load(Ordering::Acquire) < THREADS {
+                let _ = list.purge(&epoch::pin());
+            }
+        });
+    })
+    .unwrap();
+
+    let guard = epoch::pin();
+    let _ = list.purge(&guard);
+    assert_eq!(list.purge(&guard), 0);
+    for key in 0..THREADS * KEYS {
+        let expected = if key % 10 == 0 { Some(&key) } else { None };
+        assert_eq!(list.lookup(&key, &guard), expected);
+    }
+    assert_eq!(list.iter(&guard).count(), list.len());
+}
+
+// Threads insert the same few keys in different orders. Each key should be inserted by exactly one
+// thread and counted once.
+#[test]
+fn insert_same_keys_concurrent() {
+    const THREADS: usize = 16;
+    const KEYS: usize = 64;
+    const ROUNDS: usize = 64;
+
+    let mut rng = rng();
+    for _ in 0..ROUNDS {
+        let list = SplitOrderedList::new();
+        let rngs = (0..THREADS).map(|_| rng.fork()).collect::<Vec<_>>();
+        // The keys each thread inserted.
+        let mut inserted = vec![Vec::new(); THREADS];
+        scope(|s| {
+            for (mut rng, inserted) in rngs.into_iter().zip(&mut inserted) {
+                let list = &list;
+                let _ = s.spawn(move |_| {
+                    let mut keys = (0..KEYS).collect::<Vec<_>>();
+                    keys.shuffle(&mut rng);
+                    let guard = epoch::pin();
+                    for key in keys {
+                        if list.insert(&key, key, &guard).is_ok() {
+                            inserted.push(key);
+                        }
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        let mut inserted = inserted.concat();
+        inserted.sort_unstable();
+        assert_eq!(inserted, (0..KEYS).collect::<Vec<_>>());
+        assert_eq!(list.len(), KEYS);
+        let guard = epoch::pin();
+        for key in 0..KEYS {
+            assert_eq!(list.lookup(&key, &guard), Some(&key));
+        }
+    }
+}
+
+#[test]
+fn insert_all() {
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+    assert_eq!(list.insert_all(None, &guard), 0);
+    for key in (0..1000).step_by(4) {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+
+    // Unsorted, with keys already in the map and keys repeated in the batch. The first entry of a
+    // repeated key is inserted.
+    let mut keys = (0..1000).rev().collect::<Vec<_>>();
+    keys.shuffle(&mut rng());
+    let batch = keys
+        .iter()
+        .map(|&key| (key, key))
+        .chain((0..1000).step_by(2).map(|key| (key, !key)))
+        .chain(Some((SplitOrderedList::<usize>::MAX_KEY, 0)));
+    assert_eq!(list.insert_all(batch, &guard), 750 + 1);
+    assert_eq!(list.len(), 1001);
+    // The buckets are grown for the whole batch up front, repeated keys included.
+    assert_eq!(list.bucket_count(), 1024);
+    for key in 0..1000 {
+        assert_eq!(list.lookup(&key, &guard), Some(&key));
+    }
+    assert_eq!(
+        list.lookup(&SplitOrderedList::<usize>::MAX_KEY, &guard),
+        Some(&0)
+    );
+    assert_eq!(list.insert_all((0..1000).map(|key| (key, key)), &guard), 0);
+}
+
+#[test]
+fn from_iter_extend() {
+    const KEYS: usize = 5000;
+
+    // Repeated keys keep their first entry, also across the chunks of the insert.
+    let mut list = (0..KEYS)
+        .map(|key| (key, key))
+        .chain((0..KEYS).rev().map(|key| (key, !key)))
+        .collect::<SplitOrderedList<_>>();
+    assert_eq!(list.len(), KEYS);
+    // Grown for the lower bound of the size hint.
+    assert_eq!(list.bucket_count(), 8192);
+
+    list.extend((0..2 * KEYS).map(|key| (key, !key)));
+    assert_eq!(list.len(), 2 * KEYS);
+    let guard = epoch::pin();
+    for key in 0..2 * KEYS {
+        let expected = if key < KEYS { key } else { !key };
+        assert_eq!(list.lookup(&key, &guard), Some(&expected));
+    }
+
+    let list = None.into_iter().collect::<SplitOrderedList<usize>>();
+    assert!(list.is_empty());
+}
+
+// The entries come from a channel fed by several threads, so the iterator has no size hint and the
+// entries of a key come in any order. Exactly one entry of each key should be kept.
+#[test]
+fn from_iter_channel() {
+    const THREADS: usize = 4;
+    const KEYS: usize = 4096;
+
+    let (sender, receiver) = unbounded();
+    let list = scope(|s| {
+        for t in 0..THREADS {
+            let sender = sender.clone();
+            let _ = s.spawn(move |_| {
+                for key in 0..KEYS {
+                    sender.send((key, t)).unwrap();
+                }
+            });
+        }
+        drop(sender);
+        receiver.into_iter().collect::<SplitOrderedList<_>>()
+    })
+    .unwrap();
+
+    assert_eq!(list.len(), KEYS);
+    let guard = epoch::pin();
+    for key in 0..KEYS {
+        assert!(*list.lookup(&key, &guard).unwrap() < THREADS);
+    }
+}
+
+// Threads insert overlapping batches while others insert and delete the keys between them, so that
+// the cursors of the batches go stale. Each key of the batches should be inserted by exactly one
+// of them.
+#[test]
+fn insert_all_concurrent() {
+    const THREADS: usize = 4;
+    const CHURNERS: usize = 4;
+    const KEYS: usize = 4096;
+    const ROUNDS: usize = 16;
+
+    let mut rng = rng();
+    for _ in 0..ROUNDS {
+        let list = SplitOrderedList::new();
+        let (inserted, finished) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        let rngs = (0..CHURNERS).map(|_| rng.fork()).collect::<Vec<_>>();
+        scope(|s| {
+            for t in 0..THREADS {
+                let (list, inserted, finished) = (&list, &inserted, &finished);
+                let _ = s.spawn(move |_| {
+                    let batch = (t * KEYS / 8..KEYS).rev().map(|i| (i * 2, i * 2));
+                    let count = list.insert_all(batch, &epoch::pin());
+                    let _ = inserted.fetch_add(count, Ordering::Relaxed);
+                    let _ = finished.fetch_add(1, Ordering::Release);
+                });
+            }
+            for mut rng in rngs {
+                let (list, finished) = (&list, &finished);
+                let _ = s.spawn(move |_| {
+                    while finished.load(Ordering::Acquire) < THREADS {
+                        let key = rng.gen_range(0..KEYS) * 2 + 1;
+                        let guard = epoch::pin();
+                        if list.insert(&key, key, &guard).is_ok() {
+                            assert_eq!(list.remove(&key, &guard), Ok(&key));
+                        }
+                    }
+                });
+            }
+        })
+        .unwrap();
+
+        assert_eq!(inserted.into_inner(), KEYS);
+        assert_eq!(list.len(), KEYS);
+        let guard = epoch::pin();
+        for key in 0..2 * KEYS {
+            let expected = if key % 2 == 0 { Some(&key) } else { None };
+            assert_eq!(list.lookup(&key, &guard), expected);
+        }
+    }
+}
+
+#[test]
+fn entry() {
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+
+    match list.entry(1, &guard) {
+        MapEntry::Vacant(entry) => {
+            assert_eq!(entry.key(), &1);
+            assert_eq!(entry.insert(10), Ok(&10));
+        }
+        MapEntry::Occupied(_) => panic!("occupied"),
+    }
+    assert_eq!(list.lookup(&1, &guard), Some(&10));
+    assert_eq!(list.len(), 1);
+
+    match list.entry(1, &guard) {
+        MapEntry::Occupied(entry) => {
+            assert_eq!(entry.key(), &1);
+            assert_eq!(entry.get(), &10);
+            assert_eq!(entry.delete(), Ok(&10));
+        }
+        MapEntry::Vacant(_) => panic!("vacant"),
+    }
+    assert_eq!(list.lookup(&1, &guard), None);
+    assert_eq!(list.len(), 0);
+    assert!(matches!(list.entry(1, &guard), MapEntry::Vacant(_)));
+}
+
+// Another thread inserts the key between `entry` and `VacantEntry::insert`. The insert should
+// return the value back and leave the value of the other thread.
+#[test]
+fn entry_vacant_race() {
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+
+    let entry = match list.entry(1, &guard) {
+        MapEntry::Vacant(entry) => entry,
+        MapEntry::Occupied(_) => panic!("occupied"),
+    };
+    scope(|s| {
+        let _ = s.spawn(|_| assert_eq!(list.insert(&1, 20, &epoch::pin()), Ok(())));
+    })
+    .unwrap();
+    assert_eq!(entry.insert(10), Err(10));
+    assert_eq!(list.lookup(&1, &guard), Some(&20));
+    assert_eq!(list.len(), 1);
+
+    // The cursor of the entry is stale, but the key is still absent, so the insert goes through.
+    // 5 comes right before 3 in split order, so it's inserted at the position found for 3.
+    let entry = match list.entry(3, &guard) {
+        MapEntry::Vacant(entry) => entry,
+        MapEntry::Occupied(_) => panic!("occupied"),
+    };
+    assert_eq!(list.insert(&5, 50, &guard), Ok(()));
+    assert_eq!(entry.insert(30), Ok(&30));
+    assert_eq!(list.lookup(&3, &guard), Some(&30));
+    assert_eq!(list.len(), 3);
+}
+
+// The key of an occupied entry is deleted and inserted again before `OccupiedEntry::delete`. The
+// delete should delete the new value, or fail if the key is gone.
+#[test]
+fn entry_occupied_race() {
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+    assert_eq!(list.insert(&1, 10, &guard), Ok(()));
+
+    let entry = match list.entry(1, &guard) {
+        MapEntry::Occupied(entry) => entry,
+        MapEntry::Vacant(_) => panic!("vacant"),
+    };
+    assert_eq!(list.remove(&1, &guard), Ok(&10));
+    assert_eq!(list.insert(&1, 20, &guard), Ok(()));
+    assert_eq!(entry.get(), &10);
+    assert_eq!(entry.delete(), Ok(&20));
+    assert_eq!(list.len(), 0);
+
+    assert_eq!(list.insert(&1, 30, &guard), Ok(()));
+    let entry = match list.entry(1, &guard) {
+        MapEntry::Occupied(entry) => entry,
+        MapEntry::Vacant(_) => panic!("vacant"),
+    };
+    assert_eq!(list.remove(&1, &guard), Ok(&30));
+    assert_eq!(entry.delete(), Err(MapError::NotFound));
+    assert_eq!(list.len(), 0);
+}
+
+// Threads toggle the same keys through entries. Every successful insert and delete is counted, and
+// their difference should be the final number of entries.
+#[test]
+fn entry_concurrent() {
+    const THREADS: usize = 8;
+    const KEYS: usize = 64;
+    const STEPS: usize = 1 << 14;
+
+    let list = SplitOrderedList::new();
+    let (inserted, deleted) = (AtomicUsize::new(0), AtomicUsize::new(0));
+    scope(|s| {
+        for t in 0..THREADS {
+            let (list, inserted, deleted) = (&list, &inserted, &deleted);
+            let _ = s.spawn(move |_| {
+                for i in 0..STEPS {
+                    let guard = epoch::pin();
+                    let key = (i * 7 + t) % KEYS;
+                    match list.entry(key, &guard) {
+                        MapEntry::Occupied(entry) => {
+                            if entry.delete().is_ok() {
+                                let _ = deleted.fetch_add(1, Ordering::Relaxed);
+                            }
+                        }
+                        MapEntry::Vacant(entry) => {
+                            if entry.insert(i).is_ok() {
+                                let _ = inserted.fetch_add(1, Ordering::Relaxed);
+                            }
+                        }
+                    };
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let len = inserted.into_inner() - deleted.into_inner();
+    assert_eq!(list.len(), len);
+    assert_eq!(list.keys(&epoch::pin()).count(), len);
+}
+
+#[test]
+fn iter() {
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+    assert_eq!(list.iter(&guard).next(), None);
+
+    // Enough keys to resize the list a few times, including the largest key.
+    let mut expected = HashMap::new();
+    for key in (0..1000)
+        .map(|i| i * 7)
+        .chain(Some(SplitOrderedList::<usize>::MAX_KEY))
+    {
+        assert_eq!(list.insert(&key, !key, &guard), Ok(()));
+        let _ = expected.insert(key, !key);
+    }
+    for key in (0..1000).step_by(3).map(|i| i * 7) {
+        assert_eq!(list.remove(&key, &guard), Ok(&!key));
+        let _ = expected.remove(&key);
+    }
+
+    let entries = list.iter(&guard).map(|(k, v)| (k, *v)).collect::<Vec<_>>();
+    assert_eq!(entries.len(), expected.len());
+    assert_eq!(entries.into_iter().collect::<HashMap<_, _>>(), expected);
+}
+
+#[test]
+fn keys_values() {
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+    assert_eq!(list.keys(&guard).next(), None);
+    assert_eq!(list.values(&guard).next(), None);
+
+    for key in 0..1000 {
+        assert_eq!(list.insert(&key, !key, &guard), Ok(()));
+    }
+    for key in (0..1000).step_by(3) {
+        assert_eq!(list.remove(&key, &guard), Ok(&!key));
+    }
+
+    // The keys and the values are in the same order as the entries.
+    let (keys, values): (Vec<_>, Vec<&usize>) = list.iter(&guard).unzip();
+    assert_eq!(list.keys(&guard).collect::<Vec<_>>(), keys);
+    assert_eq!(list.values(&guard).collect::<Vec<_>>(), values);
+    assert_eq!(list.keys(&guard).count(), list.len());
+    assert_eq!(
+        list.keys(&guard).collect::<HashSet<_>>(),
+        (0..1000).filter(|key| key % 3 != 0).collect()
+    );
+}
+
+// Each worker scans the buckets of its own, starting from the bucket indices. Together they should
+// visit every entry once.
+#[test]
+fn iter_from() {
+    const BUCKETS: usize = 64;
+    const WORKERS: usize = 4;
+
+    let list = SplitOrderedList::with_capacity(BUCKETS * 2);
+    let guard = epoch::pin();
+    // Leave some buckets empty, including the first and the last ones.
+    let keys = (0..BUCKETS * 2)
+        .map(|i| i * 3 + 1)
+        .filter(|key| key % BUCKETS != BUCKETS - 1)
+        .collect::<HashSet<_>>();
+    for &key in &keys {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    assert_eq!(list.bucket_count(), BUCKETS);
+
+    // Starts at the bucket of the key, whichever key of the bucket it is.
+    assert_eq!(
+        list.iter_from(&(BUCKETS + 1), &guard).next(),
+        list.iter_from(&1, &guard).next()
+    );
+    assert_eq!(
+        list.iter_from(&0, &guard).collect::<Vec<_>>(),
+        list.iter(&guard).collect::<Vec<_>>()
+    );
+
+    let visited = Mutex::new(Vec::new());
+    scope(|s| {
+        for w in 0..WORKERS {
+            let (list, visited) = (&list, &visited);
+            let _ = s.spawn(move |_| {
+                let guard = epoch::pin();
+                for bucket in (w..BUCKETS).step_by(WORKERS) {
+                    let keys = list
+                        .iter_from(&bucket, &guard)
+                        .map(|(key, value)| {
+                            assert_eq!(*value, key);
+                            key
+                        })
+                        .take_while(|key| key % BUCKETS == bucket);
+                    visited.lock().unwrap().extend(keys);
+                }
+            });
+        }
+    })
+    .unwrap();
+    let visited = visited.into_inner().unwrap();
+    assert_eq!(visited.len(), keys.len());
+    assert_eq!(visited.into_iter().collect::<HashSet<_>>(), keys);
+}
+
+// Writers insert and delete the odd keys while the even keys stay in the list. An iteration should
+// visit each even key once, and only the entries that were inserted.
+#[test]
+fn iter_concurrent() {
+    const WRITERS: usize = 4;
+    const KEYS: usize = 1024;
+    const ITERATIONS: usize = 64;
+
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+    for key in (0..KEYS).step_by(2) {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    drop(guard);
+
+    let mut rng = rng();
+    let rngs = (0..WRITERS).map(|_| rng.fork()).collect::<Vec<_>>();
+    let done = AtomicBool::new(false);
+    scope(|s| {
+        for mut rng in rngs {
+            let (list, done) = (&list, &done);
+            let _ = s.spawn(move |_| {
+                while !done.load(Ordering::Relaxed) {
+                    let key = rng.gen_range(0..KEYS / 2) * 2 + 1;
+                    let guard = epoch::pin();
+                    if rng.gen() {
+                        let _ = list.insert(&key, key, &guard);
+                    } else {
+                        let _ = list.remove(&key, &guard);
+                    }
+                }
+            });
+        }
+
+        for _ in 0..ITERATIONS {
+            let guard = epoch::pin();
+            let mut visited = HashSet::new();
+            for (key, value) in list.iter(&guard) {
+                assert_eq!(*value, key);
+                assert!(key < KEYS);
+                assert!(visited.insert(key), "key {} is visited twice", key);
+            }
+            for key in (0..KEYS).step_by(2) {
+                assert!(visited.contains(&key), "key {} is not visited", key);
+            }
+        }
+        done.store(true, Ordering::Relaxed);
+    })
+    .unwrap();
+}
+
+// Writers insert and delete the odd keys while the even keys stay in the list, as in
+// `iter_concurrent`. A snapshot should be sorted by key, hold each even key, and hold intact clones
+// of the values.
+#[test]
+fn snapshot_concurrent() {
+    const WRITERS: usize = 4;
+    const KEYS: usize = 1024;
+    const SNAPSHOTS: usize = 64;
+
+    let list = SplitOrderedList::new();
+    assert!(list.snapshot().is_empty());
+    let guard = epoch::pin();
+    for key in (0..KEYS).step_by(2) {
+        assert_eq!(list.insert(&key, key.to_string(), &guard), Ok(()));
+    }
+    drop(guard);
+
+    let mut rng = rng();
+    let rngs = (0..WRITERS).map(|_| rng.fork()).collect::<Vec<_>>();
+    let done = AtomicBool::new(false);
+    scope(|s| {
+        for mut rng in rngs {
+            let (list, done) = (&list, &done);
+            let _ = s.spawn(move |_| {
+                while !done.load(Ordering::Relaxed) {
+                    let key = rng.gen_range(0..KEYS / 2) * 2 + 1;
+                    let guard = epoch::pin();
+                    if rng.gen() {
+                        let _ = list.insert(&key, key.to_string(), &guard);
+                    } else {
+                        let _ = list.remove(&key, &guard);
+                    }
+                }
+            });
+        }
+
+        for _ in 0..SNAPSHOTS {
+            let snapshot = list.snapshot();
+            assert!(snapshot.windows(2).all(|w| w[0].0 < w[1].0));
+            for (key, value) in &snapshot {
+                assert!(*key < KEYS);
+                assert_eq!(*value, key.to_string());
+            }
+            let keys = snapshot.iter().map(|(key, _)| *key).collect::<HashSet<_>>();
+            for key in (0..KEYS).step_by(2) {
+                assert!(keys.contains(&key), "key {} is not in the snapshot", key);
+            }
+        }
+        done.store(true, Ordering::Relaxed);
+    })
+    .unwrap();
+}
+
+#[test]
+fn get_or_insert_with() {
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+
+    let calls = AtomicUsize::new(0);
+    let make = |value| {
+        let calls = &calls;
+        move || {
+            let _ = calls.fetch_add(1, Ordering::Relaxed);
+            value
+        }
+    };
+    assert_eq!(list.get_or_insert_with(&37, make(1), &guard), &1);
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+    // A hit doesn't call `f`.
+    assert_eq!(list.get_or_insert_with(&37, make(2), &guard), &1);
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+    assert_eq!(list.lookup(&37, &guard), Some(&1));
+    assert_eq!(list.len(), 1);
+
+    assert_eq!(list.remove(&37, &guard), Ok(&1));
+    assert_eq!(list.get_or_insert_with(&37, make(3), &guard), &3);
+    assert_eq!(calls.load(Ordering::Relaxed), 2);
+
+    // Enough keys to resize the list.
+    for key in 100..200 {
+        assert_eq!(
+            list.get_or_insert_with(&key, || key * 2, &guard),
+            &(key * 2)
+        );
+    }
+    for key in 100..200 {
+        assert_eq!(
+            list.get_or_insert_with(&key, || unreachable!(), &guard),
+            &(key * 2)
+        );
+    }
+    assert_eq!(list.len(), 101);
+}
+
+// Racing callers should agree on one value per key, and the values of the losers should be dropped.
+#[test]
+fn get_or_insert_with_concurrent() {
+    const THREADS: usize = 8;
+    const KEYS: usize = 256;
+
+    let counter = DropCounter::new();
+    let list = SplitOrderedList::new();
+    let mut rng = rng();
+    let rngs = (0..THREADS).map(|_| rng.fork()).collect::<Vec<_>>();
+    // The value each thread got for each key.
+    let mut observed = vec![vec![0; KEYS]; THREADS];
+    scope(|s| {
+        for ((thread, mut rng), observed) in rngs.into_iter().enumerate().zip(&mut observed) {
+            let (list, counter) = (&list, &counter);
+            let _ = s.spawn(move |_| {
+                let mut keys = (0..KEYS).collect::<Vec<_>>();
+                keys.shuffle(&mut rng);
+                let guard = epoch::pin();
+                for key in keys {
+                    let mut calls = 0;
+                    let value = list.get_or_insert_with(
+                        &key,
+                        || {
+                            calls += 1;
+                            counter.track(thread)
+                        },
+                        &guard,
+                    );
+                    assert!(calls <= 1);
+                    observed[key] = **value;
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = epoch::pin();
+    for key in 0..KEYS {
+        let value = **list.lookup(&key, &guard).unwrap();
+        for thread in &observed {
+            assert_eq!(thread[key], value);
+        }
+    }
+    assert_eq!(list.len(), KEYS);
+    drop(guard);
+    // Only the winners are still alive.
+    assert_eq!(counter.live(), KEYS);
+    drop(list);
+    assert_eq!(counter.live(), 0);
+}
+
+#[test]
+fn update() {
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+
+    assert_eq!(list.update(&37, 1, &guard), Err(1));
+    assert_eq!(list.insert(&37, 1, &guard), Ok(()));
+    assert_eq!(list.update(&37, 2, &guard), Ok(&1));
+    assert_eq!(list.lookup(&37, &guard), Some(&2));
+    assert_eq!(list.insert(&37, 3, &guard), Err(3));
+    assert_eq!(list.len(), 1);
+    assert_eq!(list.remove(&37, &guard), Ok(&2));
+    assert_eq!(list.update(&37, 4, &guard), Err(4));
+    assert_eq!(list.lookup(&37, &guard), None);
+
+    // Enough keys to resize the list. Updates shouldn't disturb the buckets.
+    for key in 0..100 {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    for key in 0..100 {
+        assert_eq!(list.update(&key, key + 1, &guard), Ok(&key));
+    }
+    let mut entries = list.iter(&guard).map(|(k, v)| (k, *v)).collect::<Vec<_>>();
+    entries.sort_unstable();
+    assert_eq!(entries, (0..100).map(|k| (k, k + 1)).collect::<Vec<_>>());
+    assert_eq!(list.len(), 100);
+}
+
+// Each update of a key should return the value of the update right before it, so that every value
+// but the last one is returned exactly once.
+#[test]
+fn update_concurrent() {
+    const THREADS: usize = 8;
+    const STEPS: usize = 4096;
+
+    let list = SplitOrderedList::new();
+    assert_eq!(list.insert(&37, 0, &epoch::pin()), Ok(()));
+    // The old values each thread got.
+    let mut olds = vec![Vec::new(); THREADS];
+    scope(|s| {
+        for (t, olds) in olds.iter_mut().enumerate() {
+            let list = &list;
+            let _ = s.spawn(move |_| {
+                for i in 0..STEPS {
+                    let guard = epoch::pin();
+                    let old = list.update(&37, 1 + t * STEPS + i, &guard).unwrap();
+                    olds.push(*old);
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = epoch::pin();
+    let last = *list.lookup(&37, &guard).unwrap();
+    let mut values = olds
+        .into_iter()
+        .flatten()
+        .chain(Some(last))
+        .collect::<Vec<_>>();
+    values.sort_unstable();
+    assert_eq!(values, (0..=THREADS * STEPS).collect::<Vec<_>>());
+    assert_eq!(list.len(), 1);
+}
+
+// Values are replaced by racing updates, then removed by value and sent to another thread, or
+// left in the map. Each value should be dropped exactly once.
+#[test]
+fn remove_owned() {
+    const THREADS: usize = 4;
+    const KEYS: usize = 1024;
+    const FLUSHES: usize = 1 << 16;
+    static COUNTER: DropCounter = DropCounter::new();
+
+    let mut list = SplitOrderedList::new();
+    let guard = epoch::pin();
+    for key in 0..KEYS {
+        assert!(list.insert(&key, COUNTER.track(key), &guard).is_ok());
+    }
+    drop(guard);
+    scope(|s| {
+        for _ in 0..THREADS {
+            let list = &list;
+            let _ = s.spawn(move |_| {
+                for key in (0..KEYS).step_by(3) {
+                    assert!(list.update(&key, COUNTER.track(!key), &epoch::pin()).is_ok());
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let (sender, receiver) = unbounded();
+    for key in (0..KEYS).step_by(2) {
+        sender.send((key, list.remove_owned(&key).unwrap())).unwrap();
+    }
+    drop(sender);
+    let mut removed = scope(|s| {
+        s.spawn(|_| {
+            receiver
+                .into_iter()
+                .map(|(key, value)| {
+                    assert_eq!(*value, if key % 3 == 0 { !key } else { key });
+                    key
+                })
+                .collect::<Vec<_>>()
+        })
+        .join()
+        .unwrap()
+    })
+    .unwrap();
+    removed.sort_unstable();
+    assert_eq!(removed, (0..KEYS).step_by(2).collect::<Vec<_>>());
+
+    assert!(list.remove_owned(&0).is_none());
+    assert_eq!(list.len(), KEYS / 2);
+    let guard = epoch::pin();
+    for key in 0..KEYS {
+        let value = list.lookup(&key, &guard).map(|value| **value);
+        match key {
+            _ if key % 2 == 0 => assert_eq!(value, None),
+            _ if key % 3 == 0 => assert_eq!(value, Some(!key)),
+            _ => assert_eq!(value, Some(key)),
+        }
+    }
+    drop(guard);
+
+    // The removed values are dropped by the consumer.
+    assert!(COUNTER.dropped() >= KEYS / 2);
+    drop(list);
+    // The replaced values are dropped after the epoch advances.
+    for _ in 0..FLUSHES {
+        if COUNTER.live() == 0 {
+            break;
+        }
+        epoch::pin().flush();
+    }
+    assert_eq!(COUNTER.created(), KEYS + THREADS * ((KEYS + 2) / 3));
+    assert_eq!(COUNTER.live(), 0);
+}
+
+// With racing inserts, updates, and deletes, every value put in the map should be either returned
+// by exactly one update or delete, or left in the map.
+#[test]
+fn update_delete_concurrent() {
+    const THREADS: usize = 8;
+    const STEPS: usize = 4096 * 4;
+    const KEYS: usize = 4;
+
+    let list = SplitOrderedList::new();
+    let mut rng = rng();
+    let rngs = (0..THREADS).map(|_| rng.fork()).collect::<Vec<_>>();
+    // The values each thread put in the map, and the values it got back.
+    let mut logs = vec![(Vec::new(), Vec::new()); THREADS];
+    scope(|s| {
+        for ((t, mut rng), (put, got)) in rngs.into_iter().enumerate().zip(&mut logs) {
+            let list = &list;
+            let _ = s.spawn(move |_| {
+                for i in 0..STEPS {
+                    let key = rng.gen_range(0..KEYS);
+                    let value = t * STEPS + i;
+                    let guard = epoch::pin();
+                    match rng.gen_range(0..3) {
+                        0 => {
+                            if list.insert(&key, value, &guard).is_ok() {
+                                put.push(value);
+                            }
+                        }
+                        1 => {
+                            if let Ok(old) = list.update(&key, value, &guard) {
+                                put.push(value);
+                                got.push(*old);
+                            }
+                        }
+                        _ => {
+                            if let Ok(old) = list.remove(&key, &guard) {
+                                got.push(*old);
+                            }
+                        }
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    let guard = epoch::pin();
+    let (mut put, mut got) = (Vec::new(), Vec::new());
+    for (p, g) in logs {
+        put.extend(p);
+        got.extend(g);
+    }
+    got.extend((0..KEYS).filter_map(|key| list.lookup(&key, &guard).copied()));
+    put.sort_unstable();
+    got.sort_unstable();
+    assert_eq!(put, got);
+}
+
+#[test]
+fn get_key_value() {
+    const MAX_KEY: usize = SplitOrderedList::<usize>::MAX_KEY;
+    const TOP: usize = 1 << (usize::BITS - 1);
+
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+    let keys = [0, 1, 2, TOP - 1, TOP, TOP + 1, MAX_KEY - 1, MAX_KEY];
+    for &key in &keys {
+        assert_eq!(list.get_key_value(&key, &guard), None);
+        assert_eq!(list.insert(&key, !key, &guard), Ok(()));
+    }
+    for &key in &keys {
+        assert_eq!(list.get_key_value(&key, &guard), Some((key, &!key)));
+    }
+    assert_eq!(list.get_key_value(&3, &guard), None);
+
+    assert_eq!(list.remove(&TOP, &guard), Ok(&!TOP));
+    assert_eq!(list.get_key_value(&TOP, &guard), None);
+    assert_eq!(
+        list.get_key_value(&(TOP + 1), &guard),
+        Some((TOP + 1, &!(TOP + 1)))
+    );
+}
+
+#[test]
+fn min_max_key() {
+    const MAX_KEY: usize = SplitOrderedList::<usize>::MAX_KEY;
+
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+    assert_eq!(list.min_key(&guard), None);
+    assert_eq!(list.max_key(&guard), None);
+
+    // Dense. The smallest and largest keys are far apart in split order.
+    for key in 1..1000 {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    assert_eq!(list.min_key(&guard), Some(1));
+    assert_eq!(list.max_key(&guard), Some(999));
+    assert_eq!(list.remove(&1, &guard), Ok(&1));
+    assert_eq!(list.min_key(&guard), Some(2));
+
+    // Sparse, over the whole key range.
+    let list = SplitOrderedList::new();
+    let mut rng = rng();
+    let keys = (0..100)
+        .map(|_| rng.gen_range(1..MAX_KEY))
+        .collect::<HashSet<_>>();
+    for &key in &keys {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    assert_eq!(list.min_key(&guard), keys.iter().min().copied());
+    assert_eq!(list.max_key(&guard), keys.iter().max().copied());
+    for key in [0, MAX_KEY] {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    assert_eq!(list.min_key(&guard), Some(0));
+    assert_eq!(list.max_key(&guard), Some(MAX_KEY));
+}
+
+// Writers insert and delete the keys outside of `STABLE` while those stay in the map. The bounds
+// should be keys that were in the map, and within the stable keys.
+#[test]
+fn min_max_key_concurrent() {
+    const WRITERS: usize = 4;
+    const KEYS: usize = 1024;
+    const STABLE: std::ops::Range<usize> = 256..768;
+    const ROUNDS: usize = 64;
+
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+    for key in STABLE {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    drop(guard);
+
+    let mut rng = rng();
+    let rngs = (0..WRITERS).map(|_| rng.fork()).collect::<Vec<_>>();
+    let done = AtomicBool::new(false);
+    scope(|s| {
+        for mut rng in rngs {
+            let (list, done) = (&list, &done);
+            let _ = s.spawn(move |_| {
+                while !done.load(Ordering::Relaxed) {
+                    let key = rng.gen_range(0..KEYS - STABLE.len());
+                    let key = if key < STABLE.start {
+                        key
+                    } else {
+                        key + STABLE.len()
+                    };
+                    let guard = epoch::pin();
+                    if rng.gen() {
+                        let _ = list.insert(&key, key, &guard);
+                    } else {
+                        let _ = list.remove(&key, &guard);
+                    }
+                }
+            });
+        }
+
+        for _ in 0..ROUNDS {
+            let guard = epoch::pin();
+            let min = list.min_key(&guard).unwrap();
+            let max = list.max_key(&guard).unwrap();
+            assert!(min <= STABLE.start && STABLE.end - 1 <= max);
+            assert!(max < KEYS);
+        }
+        done.store(true, Ordering::Relaxed);
+    })
+    .unwrap();
+}
+
+#[test]
+fn get_owned() {
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+    assert_eq!(list.get_owned(&1), None);
+    assert_eq!(list.insert(&1, "one".to_string(), &guard), Ok(()));
+    assert_eq!(list.get_owned(&1), Some("one".to_string()));
+    assert!(list.update(&1, "uno".to_string(), &guard).is_ok());
+    assert_eq!(list.get_owned(&1), Some("uno".to_string()));
+    assert!(list.remove(&1, &guard).is_ok());
+    assert_eq!(list.get_owned(&1), None);
+}
+
+// Writers update their keys with increasing values while readers compare `get_owned` with a
+// following `lookup`. Both should see values of the key, and the lookup shouldn't see an older one.
+#[test]
+fn get_owned_concurrent() {
+    const WRITERS: usize = 4;
+    const READERS: usize = 4;
+    const KEYS: usize = 64;
+    const STEPS: usize = 4096;
+
+    let list = SplitOrderedList::new();
+    for key in 0..KEYS {
+        assert_eq!(list.insert(&key, key, &epoch::pin()), Ok(()));
+    }
+    scope(|s| {
+        for w in 0..WRITERS {
+            let list = &list;
+            let _ = s.spawn(move |_| {
+                for step in 1..=STEPS {
+                    for key in (w..KEYS).step_by(WRITERS) {
+                        let guard = epoch::pin();
+                        assert!(list.update(&key, step * KEYS + key, &guard).is_ok());
+                    }
+                }
+            });
+        }
+        for _ in 0..READERS {
+            let list = &list;
+            let _ = s.spawn(move |_| {
+                for step in 0..STEPS * 4 {
+                    let key = step % KEYS;
+                    let owned = list.get_owned(&key).unwrap();
+                    let guard = epoch::pin();
+                    let looked_up = *list.lookup(&key, &guard).unwrap();
+                    assert_eq!(owned % KEYS, key);
+                    assert_eq!(looked_up % KEYS, key);
+                    assert!(owned <= looked_up, "{} > {}", owned, looked_up);
+                }
+            });
+        }
+    })
+    .unwrap();
+
+    for key in 0..KEYS {
+        assert_eq!(list.get_owned(&key), Some(STEPS * KEYS + key));
+    }
+}
+
+// The keys equal to the bucket indices sit right after the sentinels of the buckets, which must
+// not be mistaken for them.
+#[test]
+fn contains_key() {
+    const BUCKETS: usize = 64;
+
+    let list = SplitOrderedList::with_capacity(BUCKETS);
+    let guard = epoch::pin();
+    // Initialize every bucket without inserting the keys of the bucket indices.
+    for key in BUCKETS..2 * BUCKETS {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    for key in 0..BUCKETS {
+        assert!(!list.contains_key(&key, &guard), "{}", key);
+        assert!(list.contains_key(&(BUCKETS + key), &guard));
+    }
+
+    for key in (0..BUCKETS).step_by(2) {
+        assert_eq!(list.insert(&key, key, &guard), Ok(()));
+    }
+    for key in 0..BUCKETS {
+        assert_eq!(list.contains_key(&key, &guard), key % 2 == 0, "{}", key);
+    }
+    for key in (0..BUCKETS).step_by(4) {
+        assert_eq!(list.remove(&key, &guard), Ok(&key));
+    }
+    for key in 0..BUCKETS {
+        assert_eq!(list.contains_key(&key, &guard), key % 4 == 2, "{}", key);
+    }
+    assert!(!list.contains_key(&usize::MAX, &guard));
+}
+
+#[test]
+fn delete_if() {
+    let list = SplitOrderedList::new();
+    let guard = epoch::pin();
+
+    let even = |v: &usize| v % 2 == 0;
+    assert_eq!(
+        list.delete_if(&37, even, &guard),
+        Err(DeleteError::NotFound)
+    );
+    assert_eq!(list.insert(&37, 1, &guard), Ok(()));
+    assert_eq!(
+        list.delete_if(&37, even, &guard),
+        Err(DeleteError::Rejected)
+    );
+    assert_eq!(list.lookup(&37, &guard), Some(&1));
+    assert_eq!(list.update(&37, 2, &guard), Ok(&1));
+    assert_eq!(list.delete_if(&37, even, &guard), Ok(&2));
+    assert_eq!(list.lookup(&37, &guard), None);
+    assert_eq!(
+        list.delete_if(&37, even, &guard),
+        Err(DeleteError::NotFound)
+    );
+    assert!(list.is_empty());
+}
+
+// An updater flips the parity of the value while a deleter deletes only even values. The deleter
+// should never delete an odd value, and every value should be returned at most once.
+#[test]
+fn delete_if_racing_update() {
+    const STEPS: usize = 4096 * 4;
+
+    let list = SplitOrderedList::new();
+    let done = AtomicBool::new(false);
+    let (mut replaced, mut deleted) = (Vec::new(), Vec::new());
+    scope(|s| {
+        let (list, done, replaced) = (&list, &done, &mut replaced);
+        let _ = s.spawn(move |_| {
+            for value in 0..STEPS {
+                let guard = epoch::pin();
+                match list.update(&37, value, &guard) {
+                    Ok(old) => replaced.push(*old),
+                    Err(value) => assert_eq!(list.insert(&37, value, &guard), Ok(())),
+                }
+            }
+            done.store(true, Ordering::Release);
+        });
+
+        while !done.load(Ordering::Acquire) {
+            let guard = epoch::pin();
+            if let Ok(value) = list.delete_if(&37, |v| v % 2 == 0, &guard) {
+                deleted.push(*value);
+            }
+        }
+    })
+    .unwrap();
+
+    assert!(deleted.iter().all(|v| v % 2 == 0), "{:?}", deleted);
+    let guard = epoch::pin();
+    let mut values = replaced
+        .into_iter()
+        .chain(deleted)
+        .chain(list.lookup(&37, &guard).copied())
+        .collect::<Vec<_>>();
+    values.sort_unstable();
+    assert_eq!(values, (0..STEPS).collect::<Vec<_>>());
+}
+
+// Owners insert, look up, and delete their own keys while the other threads insert and delete the
+// keys right next to them in split order. The failed unlinks of the neighbours shouldn't make the
+// owners miss their keys.
+#[test]
+fn delete_neighbours_concurrent() {
+    const OWNERS: usize = 4;
+    const CHURNERS: usize = 4;
+    const STEPS: usize = 4096 * 4;
+    // The keys differ only in the high bits, so they're in the same bucket and next to each other in
+    // split order.
+    const NEIGHBOURS: usize = 64;
+    fn key(i: usize) -> usize {
+        i << 48 | 1
+    }
+
+    let list = SplitOrderedList::new();
+    let mut rng = rng();
+    let rngs = (0..CHURNERS).map(|_| rng.fork()).collect::<Vec<_>>();
+    scope(|s| {
+        for owner in 0..OWNERS {
+            let list = &list;
+            let _ = s.spawn(move |_| {
+                let key = key(owner * NEIGHBOURS / OWNERS);
+                for step in 0..STEPS {
+                    let guard = epoch::pin();
+                    assert_eq!(list.insert(&key, step, &guard), Ok(()));
+                    assert_eq!(list.lookup(&key, &guard), Some(&step));
+                    assert_eq!(list.remove(&key, &guard), Ok(&step));
+                    assert_eq!(list.lookup(&key, &guard), None);
+                }
+            });
+        }
+        for mut rng in rngs {
+            let list = &list;
+            let _ = s.spawn(move |_| {
+                for _ in 0..STEPS {
+                    let i = rng.gen_range(0..NEIGHBOURS);
+                    if i % (NEIGHBOURS / OWNERS) == 0 {
+                        continue;
+                    }
+                    let guard = epoch::pin();
+                    if rng.gen() {
+                        let _ = list.insert(&key(i), i, &guard);
+                    } else {
+                        let _ = list.remove(&key(i), &guard);
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+}
+
+// Readers look up the keys that stay in the map while the other threads keep inserting and
+// deleting the keys between them in split order, a few million operations in total. The lookups
+// should see through the unlinks racing with them, and never miss a key.
+#[test]
+fn lookup_between_deletes_concurrent() {
+    const READERS: usize = 4;
+    const CHURNERS: usize = 4;
+    const STEPS: usize = 1 << 18;
+    // Even `i` stay in the map, and odd `i` come and go.
+    const KEYS: usize = 256;
+    fn key(i: usize) -> usize {
+        i << 48 | 1
+    }
+
+    let list = SplitOrderedList::new();
+    for i in (0..KEYS).step_by(2) {
+        assert_eq!(list.insert(&key(i), i, &epoch::pin()), Ok(()));
+    }
+    let mut rng = rng();
+    let rngs = (0..READERS + CHURNERS)
+        .map(|_| rng.fork())
+        .collect::<Vec<_>>();
+    scope(|s| {
+        for (t, mut rng) in rngs.into_iter().enumerate() {
+            let list = &list;
+            let _ = s.spawn(move |_| {
+                for _ in 0..STEPS {
+                    let guard = epoch::pin();
+                    if t < READERS {
+                        let i = rng.gen_range(0..KEYS / 2) * 2;
+                        assert_eq!(list.lookup(&key(i), &guard), Some(&i));
+                    } else {
+                        let i = rng.gen_range(0..KEYS / 2) * 2 + 1;
+                        if rng.gen() {
+                            let _ = list.insert(&key(i), i, &guard);
+                        } else {
+                            let _ = list.remove(&key(i), &guard);
+                        }
+                    }
+                }
+            });
+        }
+    })
+    .unwrap();
+    for i in (0..KEYS).step_by(2) {
+        assert_eq!(list.lookup(&key(i), &epoch::pin()), Some(&i));
+    }
+}
+
 #[test]
 fn stress_sequential() {
     const STEPS: usize = 4096;
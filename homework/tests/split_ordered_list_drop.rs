@@ -0,0 +1,64 @@
+//! Checks that dropping a `SplitOrderedList` frees all of its memory, with an allocator that
+//! counts the live allocations. The allocator is global, so this binary has only one test.
+
+use crossbeam_epoch as epoch;
+use cs431_homework::test_util::drop_counter::DropCounter;
+use cs431_homework::{NonblockingMap, SplitOrderedList};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicIsize, Ordering};
+
+/// Number of allocations that are not freed yet.
+static LIVE: AtomicIsize = AtomicIsize::new(0);
+
+struct CountingAlloc;
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = LIVE.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let _ = LIVE.fetch_sub(1, Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+// Fills maps of a few sizes, so that they have many sentinels and several levels of bucket
+// segments. No entry is deleted, since deleted nodes are freed by the epoch GC later.
+#[test]
+fn drop_frees_everything() {
+    // The first pin registers the thread to the GC, which allocates. So does the first fail
+    // point reached under `failpoints`, so a list is used once before counting.
+    {
+        let list = SplitOrderedList::new();
+        let guard = epoch::pin();
+        assert!(list.insert(&0, 0, &guard).is_ok());
+        assert!(list.lookup(&0, &guard).is_some());
+    }
+
+    for &keys in &[0, 1, 100, 10_000, 100_000] {
+        let counter = DropCounter::new();
+        let live = LIVE.load(Ordering::Relaxed);
+        {
+            let list = SplitOrderedList::new();
+            let guard = epoch::pin();
+            for key in 0..keys {
+                assert!(list.insert(&key, counter.track(key), &guard).is_ok());
+            }
+            for key in 0..2 * keys {
+                assert_eq!(list.lookup(&key, &guard).is_some(), key < keys);
+            }
+        }
+        assert_eq!(counter.live(), 0, "values leaked with {} keys", keys);
+        assert_eq!(
+            LIVE.load(Ordering::Relaxed),
+            live,
+            "allocations leaked with {} keys",
+            keys
+        );
+    }
+}
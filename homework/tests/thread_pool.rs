@@ -1,9 +1,11 @@
+#![allow(deprecated)]
+
 use crossbeam_channel::bounded;
-use cs431_homework::hello_server::ThreadPool;
+use cs431_homework::hello_server::{PoolError, ThreadPool};
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Barrier};
 use std::thread::sleep;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 const NUM_THREADS: usize = 4;
 const NUM_JOBS: usize = 1024;
@@ -67,3 +69,60 @@ fn thread_pool_drop_propagate_panic() {
         panic!();
     });
 }
+
+/// `try_execute` returns the job if the queue is full.
+#[test]
+fn thread_pool_queue_full() {
+    let pool = ThreadPool::with_capacity(1, 1);
+    let counter = Arc::new(AtomicUsize::new(0));
+    let (started_sender, started_receiver) = bounded(0);
+    let (quit_sender, quit_receiver) = bounded(0);
+
+    // The only worker blocks.
+    pool.try_execute(move || {
+        started_sender.send(()).unwrap();
+        quit_receiver.recv().unwrap();
+    })
+    .unwrap();
+    started_receiver.recv().unwrap();
+
+    let job = |counter: &Arc<AtomicUsize>| {
+        let counter = counter.clone();
+        move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    };
+    // Fills the queue.
+    assert!(pool.try_execute(job(&counter)).is_ok());
+    let rejected = match pool.try_execute(job(&counter)) {
+        Err(PoolError::QueueFull(job)) => job,
+        result => panic!("expected QueueFull, got {:?}", result),
+    };
+
+    quit_sender.send(()).unwrap();
+    pool.join();
+    assert_eq!(counter.load(Ordering::Relaxed), 1);
+    rejected();
+    assert_eq!(counter.load(Ordering::Relaxed), 2);
+}
+
+/// `try_execute` and `submit` return the job after all workers have exited.
+#[test]
+#[should_panic(expected = "Worker panicked")]
+fn thread_pool_shutting_down() {
+    let pool = ThreadPool::new(1);
+    pool.execute(|| panic!("job panics"));
+
+    let deadline = Instant::now() + Duration::from_secs(3);
+    let result = loop {
+        let result = pool.try_execute(|| ());
+        if result.is_err() || Instant::now() > deadline {
+            break result;
+        }
+        sleep(Duration::from_millis(10));
+    };
+    assert!(matches!(result, Err(PoolError::ShuttingDown(_))));
+    assert!(matches!(pool.submit(|| ()), Err(PoolError::ShuttingDown(_))));
+
+    // Dropping the pool propagates the panic of the worker.
+}
@@ -0,0 +1,173 @@
+//! The same tests for `TreiberStack` on each reclamation scheme.
+
+#![cfg(not(feature = "check-loom"))]
+
+use crossbeam_utils::thread::scope;
+use cs431_homework::reclaim::EpochReclaim;
+#[cfg(feature = "global-hazards")]
+use cs431_homework::reclaim::HpReclaim;
+use cs431_homework::test_util::drop_counter::DropCounter;
+use cs431_homework::TreiberStack;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+macro_rules! stack_tests {
+    ($name:ident, $reclaim:ty) => {
+        mod $name {
+            use super::*;
+
+            type Stack<T> = TreiberStack<T, $reclaim>;
+
+            #[test]
+            fn smoke() {
+                let stack = Stack::new();
+                assert!(stack.is_empty());
+                stack.push(1);
+                stack.push(2);
+                assert!(!stack.is_empty());
+                assert_eq!(stack.pop(), Some(2));
+                stack.push(3);
+                assert_eq!(stack.pop(), Some(3));
+                assert_eq!(stack.pop(), Some(1));
+                assert_eq!(stack.pop(), None);
+                assert!(stack.is_empty());
+            }
+
+            #[test]
+            fn push_pop_concurrent() {
+                const THREADS: usize = 8;
+                const STEPS: usize = 1024 * 16;
+
+                let stack = Stack::new();
+                scope(|s| {
+                    for _ in 0..THREADS {
+                        s.spawn(|_| {
+                            for i in 0..STEPS {
+                                stack.push(i);
+                                assert!(stack.pop().is_some());
+                            }
+                        });
+                    }
+                })
+                .unwrap();
+                assert!(stack.is_empty());
+            }
+
+            // Every pushed value is popped exactly once.
+            #[test]
+            fn pop_exactly_once() {
+                const THREADS: usize = 8;
+                const STEPS: usize = 1024 * 4;
+
+                let stack = Stack::new();
+                let popped = scope(|s| {
+                    let handles = (0..THREADS)
+                        .map(|t| {
+                            let stack = &stack;
+                            s.spawn(move |_| {
+                                let mut popped = Vec::new();
+                                for i in 0..STEPS {
+                                    stack.push(t * STEPS + i);
+                                    if i % 2 == 1 {
+                                        popped.extend(stack.pop());
+                                        popped.extend(stack.pop());
+                                    }
+                                }
+                                popped
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    handles
+                        .into_iter()
+                        .flat_map(|h| h.join().unwrap())
+                        .collect::<Vec<_>>()
+                })
+                .unwrap();
+
+                let mut all = popped.iter().cloned().collect::<HashSet<_>>();
+                assert_eq!(all.len(), popped.len());
+                while let Some(v) = stack.pop() {
+                    assert!(all.insert(v));
+                }
+                assert_eq!(all, (0..THREADS * STEPS).collect());
+            }
+
+            // Consumers pop the multiset of values pushed by producers.
+            #[test]
+            fn producers_consumers() {
+                const PRODUCERS: usize = 4;
+                const CONSUMERS: usize = 4;
+                const STEPS: usize = 1024 * 64;
+
+                let stack = Stack::new();
+                let remaining = AtomicUsize::new(PRODUCERS * STEPS);
+                let mut popped = Vec::new();
+                scope(|s| {
+                    for _ in 0..PRODUCERS {
+                        s.spawn(|_| {
+                            for i in 0..STEPS {
+                                stack.push(i % 1024);
+                            }
+                        });
+                    }
+                    let consumers = (0..CONSUMERS)
+                        .map(|_| {
+                            s.spawn(|_| {
+                                let mut popped = Vec::new();
+                                while remaining.load(Ordering::Relaxed) > 0 {
+                                    if let Some(v) = stack.pop() {
+                                        let _ = remaining.fetch_sub(1, Ordering::Relaxed);
+                                        popped.push(v);
+                                    }
+                                }
+                                popped
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    for consumer in consumers {
+                        popped.extend(consumer.join().unwrap());
+                    }
+                })
+                .unwrap();
+                assert!(stack.is_empty());
+
+                let mut pushed = (0..PRODUCERS)
+                    .flat_map(|_| (0..STEPS).map(|i| i % 1024))
+                    .collect::<Vec<_>>();
+                pushed.sort_unstable();
+                popped.sort_unstable();
+                assert_eq!(popped, pushed);
+            }
+
+            // Popped and remaining values are dropped exactly once.
+            #[test]
+            fn drop_values() {
+                const THREADS: usize = 4;
+                const STEPS: usize = 1024;
+
+                let counter = DropCounter::new();
+                let stack = Stack::new();
+                scope(|s| {
+                    for _ in 0..THREADS {
+                        s.spawn(|_| {
+                            for i in 0..STEPS {
+                                stack.push(counter.track(i));
+                                if i % 2 == 0 {
+                                    drop(stack.pop());
+                                }
+                            }
+                        });
+                    }
+                })
+                .unwrap();
+                assert_eq!(counter.live(), THREADS * STEPS / 2);
+                drop(stack);
+                assert_eq!(counter.live(), 0);
+            }
+        }
+    };
+}
+
+stack_tests!(epoch, EpochReclaim);
+#[cfg(feature = "global-hazards")]
+stack_tests!(hazard_pointer, HpReclaim);
@@ -0,0 +1,109 @@
+#[path = "../examples/word_index.rs"]
+#[allow(dead_code)]
+mod word_index;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use word_index::WordIndex;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/word_index");
+
+/// Creates an empty directory for the test.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("word_index-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn words(top: &[(&str, usize)]) -> Vec<(String, usize)> {
+    top.iter().map(|(w, c)| (w.to_string(), *c)).collect()
+}
+
+#[test]
+fn word_index_fixtures() {
+    let index = WordIndex::new(4);
+    let paths = index.index_dir(Path::new(FIXTURES)).unwrap();
+    assert_eq!(paths.len(), 2);
+
+    let fox = Path::new(FIXTURES).join("fox.txt");
+    assert_eq!(
+        index.top_words(&fox, 4).unwrap(),
+        words(&[("the", 5), ("dog", 3), ("fox", 3), ("a", 2)])
+    );
+    let lorem = Path::new(FIXTURES).join("lorem.txt");
+    assert_eq!(
+        index.top_words(&lorem, 10).unwrap(),
+        words(&[
+            ("sit", 4),
+            ("dolor", 3),
+            ("ipsum", 2),
+            ("amet", 1),
+            ("lorem", 1)
+        ])
+    );
+    assert_eq!(index.top_words(&Path::new(FIXTURES).join("none"), 1), None);
+}
+
+#[test]
+fn word_index_cache() {
+    let index = WordIndex::new(4);
+    let fox = Path::new(FIXTURES).join("fox.txt");
+    index.index_files(&[fox.clone()]).unwrap();
+
+    let top = index.top_words(&fox, 3).unwrap();
+    assert_eq!(index.computations(), 1);
+    for n in 0..8 {
+        assert_eq!(index.top_words(&fox, 3).unwrap(), top);
+        assert_eq!(index.top_words(&fox, n).unwrap().len(), n);
+    }
+    assert_eq!(index.computations(), 1);
+}
+
+#[test]
+fn word_index_reindex() {
+    let dir = temp_dir("reindex");
+    let path = dir.join("file.txt");
+    fs::write(&path, "a b b c c c\n").unwrap();
+
+    let index = WordIndex::new(4);
+    index.index_dir(&dir).unwrap();
+    assert_eq!(
+        index.top_words(&path, 3).unwrap(),
+        words(&[("c", 3), ("b", 2), ("a", 1)])
+    );
+
+    fs::write(&path, "a a a a\nb\n").unwrap();
+    index.index_files(&[path.clone()]).unwrap();
+    assert_eq!(
+        index.top_words(&path, 3).unwrap(),
+        words(&[("a", 4), ("b", 1)])
+    );
+    assert_eq!(index.computations(), 2);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn word_index_many_chunks() {
+    const LINES: usize = 1000;
+
+    let dir = temp_dir("many_chunks");
+    let path = dir.join("file.txt");
+    let text = (0..LINES)
+        .map(|i| format!("shared w{} line{}\n", i % 7, i))
+        .collect::<String>();
+    fs::write(&path, text).unwrap();
+
+    let index = WordIndex::new(8);
+    index.index_dir(&dir).unwrap();
+    let top = index.top_words(&path, 8).unwrap();
+    assert_eq!(top[0], ("shared".to_string(), LINES));
+    for (i, (word, count)) in top[1..].iter().enumerate() {
+        assert_eq!(word, &format!("w{}", i));
+        assert_eq!(*count, (LINES + 6 - i) / 7);
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -73,6 +73,16 @@ impl<K, V> Node<K, V> {
     pub fn into_value(self) -> V {
         self.value
     }
+
+    /// Extracts the inner key.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Returns the key mutably.
+    pub fn key_mut(&mut self) -> &mut K {
+        &mut self.key
+    }
 }
 
 impl<'g, K, V> Cursor<'g, K, V>
@@ -214,6 +224,12 @@ where
         unsafe { self.curr.as_ref().map(|n| &n.value) }
     }
 
+    /// Lookups the key.
+    #[inline]
+    pub fn key(&self) -> Option<&'g K> {
+        unsafe { self.curr.as_ref().map(|n| &n.key) }
+    }
+
     /// Inserts a value.
     #[inline]
     pub fn insert(
@@ -237,6 +253,52 @@ where
         }
     }
 
+    /// Replaces the current node with `node`, which should have the same key. Returns `node` back if
+    /// the current node is deleted.
+    ///
+    /// The current node is marked with `node` as its next node in one CAS, so a search finds either
+    /// the current node or `node`. Then the current node is unlinked, and the cursor moves to
+    /// `node`.
+    #[inline]
+    pub fn replace(
+        &mut self,
+        mut node: Owned<Node<K, V>>,
+        guard: &'g Guard,
+    ) -> Result<(), Owned<Node<K, V>>> {
+        let curr_node = unsafe { self.curr.as_ref() }.unwrap();
+
+        let mut next = curr_node.next.load(Ordering::Acquire, guard);
+        let node = loop {
+            if next.tag() == 1 {
+                return Err(node);
+            }
+            node.next.store(next, Ordering::Relaxed);
+            match curr_node.next.compare_exchange(
+                next,
+                node.with_tag(1),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(node) => break node.with_tag(0),
+                Err(e) => {
+                    next = e.current;
+                    node = e.new.with_tag(0);
+                }
+            }
+        };
+
+        if self
+            .prev
+            .compare_exchange(self.curr, node, Ordering::Release, Ordering::Relaxed, guard)
+            .is_ok()
+        {
+            unsafe { guard.defer_destroy(self.curr) };
+        }
+        self.curr = node;
+        Ok(())
+    }
+
     /// Deletes the current node.
     #[inline]
     pub fn delete(self, guard: &'g Guard) -> Result<&'g V, ()> {